@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use model::{BlockHash, ConsensusAddress, Height};
+
+/// Row of `proposer_to_height`: a block as read from the RPC endpoint through a parser profile.
+#[derive(Debug)]
+pub struct Block {
+    pub proposer: ConsensusAddress,
+    pub height: Height,
+    pub hash: BlockHash,
+    /// Missing for the first block of a chain and for networks whose profile does not find it.
+    pub last_block_hash: Option<BlockHash>,
+    pub block_time: DateTime<Utc>,
+    pub num_txs: i32,
+    /// Missing for the first block of a chain and for networks whose profile does not find it.
+    pub last_commit: Option<LastCommit>,
+}
+
+/// Commit to the previous block which a block carries: whether each validator of the set which
+/// produced the previous block signed it, in the order of the set.
+#[derive(Debug)]
+pub struct LastCommit {
+    pub height: Height,
+    pub signatures: Vec<CommitSignature>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CommitSignature {
+    /// Missing for absent validators, whose entries carry no address, until it is looked up
+    /// in the validator set.
+    pub validator: Option<ConsensusAddress>,
+    /// The validator voted for the block, rather than being absent or voting nil.
+    pub signed: bool,
+}
+
+impl LastCommit {
+    /// Addresses of the whole set, if none is missing.
+    pub fn validators(&self) -> Option<Vec<&ConsensusAddress>> {
+        self.signatures.iter().map(|s| s.validator.as_ref()).collect()
+    }
+}
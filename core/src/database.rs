@@ -0,0 +1,112 @@
+use std::{env, fs};
+
+use rustls::{ClientConfig, RootCertStore};
+use tokio_postgres::config::SslMode;
+use tokio_postgres_rustls::MakeRustlsConnect;
+use tracing::info;
+
+use crate::{Error, secrets};
+
+/// Connection settings of the database together with the TLS connector used for them.
+#[derive(Clone)]
+pub struct DatabaseConfig {
+    pub config: tokio_postgres::Config,
+    pub tls: MakeRustlsConnect,
+}
+
+/// Connection settings from `DATABASE_URL`, a connection string like
+/// `postgresql://osmosis@db:5432/osmosis?sslmode=require`, with its parts overridden by
+/// `DATABASE_HOST`, `DATABASE_PORT`, `DATABASE_USER` and `DATABASE_NAME` and the defaults for
+/// what neither gives. `DATABASE_PASSWORD` is resolved as a secret, so it may be read from the
+/// file in `DATABASE_PASSWORD_FILE` as mounted by Docker and Kubernetes.
+pub async fn from_env(default_host: &str, default_user: &str, default_password: Option<&str>)
+                      -> Result<DatabaseConfig, Error> {
+    let url = env::var("DATABASE_URL").ok();
+    let mut config = match &url {
+        Some(url) => url.parse().map_err(|e| Error::CouldNotParseDatabaseConfig(Box::new(e)))?,
+        None => tokio_postgres::Config::new(),
+    };
+
+    match env::var("DATABASE_HOST") {
+        Ok(host) => config.host(&host),
+        Err(_) if config.get_hosts().is_empty() => config.host(default_host),
+        Err(_) => &mut config,
+    };
+    if let Ok(port) = env::var("DATABASE_PORT") {
+        config.port(port.parse().map_err(|e| Error::CouldNotParseDatabaseConfig(Box::new(e)))?);
+    }
+    match env::var("DATABASE_USER") {
+        Ok(user) => config.user(&user),
+        Err(_) if config.get_user().is_none() => config.user(default_user),
+        Err(_) => &mut config,
+    };
+    if let Ok(name) = env::var("DATABASE_NAME") {
+        config.dbname(&name);
+    }
+    match secrets::resolve("DATABASE_PASSWORD").await? {
+        Some(password) => config.password(password),
+        None if config.get_password().is_none() => match default_password {
+            Some(password) => config.password(password),
+            None => &mut config,
+        },
+        None => &mut config,
+    };
+
+    let url_sets_ssl_mode = url.is_some_and(|u| u.contains("sslmode"));
+    with_tls(config, url_sets_ssl_mode)
+}
+
+/// Secures connections as `DATABASE_SSLMODE` asks: `disable` (the default unless `DATABASE_URL`
+/// sets `sslmode`), `prefer`, `require` or `verify-full`. Unlike libpq, certificates are always
+/// verified when TLS is used, against the PEM certificates in `DATABASE_SSLROOTCERT` if it is set
+/// or else the Mozilla root store, so `require` is as strict as `verify-full`.
+fn with_tls(mut config: tokio_postgres::Config, url_sets_ssl_mode: bool) -> Result<DatabaseConfig, Error> {
+    let ssl_mode = match env::var("DATABASE_SSLMODE").as_deref() {
+        Err(_) if url_sets_ssl_mode => config.get_ssl_mode(),
+        Err(_) | Ok("disable") => SslMode::Disable,
+        Ok("prefer") => SslMode::Prefer,
+        Ok("require") | Ok("verify-full") => SslMode::Require,
+        Ok(other) => return Err(Error::CouldNotConfigureTls(format!("unknown DATABASE_SSLMODE {other}").into())),
+    };
+    if ssl_mode != SslMode::Disable {
+        info!(ssl_mode = ?ssl_mode, "Connecting to the database over TLS");
+    }
+    config.ssl_mode(ssl_mode);
+
+    let roots = match env::var("DATABASE_SSLROOTCERT") {
+        Ok(path) => read_root_certificates(&path)?,
+        Err(_) => RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() },
+    };
+    let tls_config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Ok(DatabaseConfig { config, tls: MakeRustlsConnect::new(tls_config) })
+}
+
+fn read_root_certificates(path: &str) -> Result<RootCertStore, Error> {
+    let pem = fs::read(path).map_err(|e| Error::CouldNotConfigureTls(Box::new(e)))?;
+
+    let mut roots = RootCertStore::empty();
+    for certificate in rustls_pemfile::certs(&mut pem.as_slice()) {
+        roots.add(certificate.map_err(|e| Error::CouldNotConfigureTls(Box::new(e)))?)
+            .map_err(|e| Error::CouldNotConfigureTls(Box::new(e)))?;
+    }
+
+    if roots.is_empty() {
+        return Err(Error::CouldNotConfigureTls(format!("no certificates in {path}").into()));
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connector_builds_with_the_bundled_root_store() {
+        let database_config = with_tls(tokio_postgres::Config::new(), false).unwrap();
+
+        assert_eq!(database_config.config.get_ssl_mode(), SslMode::Disable);
+    }
+}
@@ -0,0 +1,37 @@
+mod blocks;
+pub mod database;
+pub mod profiles;
+pub mod repository;
+pub mod secrets;
+
+pub use blocks::{Block, CommitSignature, LastCommit};
+
+/// Boxed source of an error, for failures which come from several kinds of errors.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors of the code shared by the indexer and the statistics service, which each convert
+/// them into their own errors. They keep the error they come from, so logs say what failed.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("block response has no valid field at {pointer}")]
+    CouldNotParseResponseForBlockAtHeight { pointer: String },
+    #[error("blockchain response has no valid field at {pointer}")]
+    CouldNotParseResponseForBlockchain { pointer: String },
+
+    #[error("could not resolve secret {name}")]
+    CouldNotResolveSecret { name: String, #[source] source: BoxError },
+    #[error("could not configure TLS for the database")]
+    CouldNotConfigureTls(#[source] BoxError),
+    #[error("could not parse the database settings")]
+    CouldNotParseDatabaseConfig(#[source] BoxError),
+    #[error("batch has a height which is indexed already")]
+    CouldNotIndexDuplicateHeight(#[source] tokio_postgres::Error),
+    #[error("could not insert batch")]
+    CouldNotInsertBatch(#[source] tokio_postgres::Error),
+    #[error("inserted {inserted} rows of a batch of {expected}")]
+    InsertedIncorrectNumberOfRows { expected: u64, inserted: u64 },
+    #[error("could not insert signatures")]
+    CouldNotInsertSignatures(#[source] tokio_postgres::Error),
+    #[error("database is unavailable")]
+    DatabaseUnavailable(#[source] tokio_postgres::Error),
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use model::{ChainId, Height};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::{Block, CommitSignature, Error, LastCommit};
+
+/// `block_id_flag` of a validator which voted for the block; absent validators have 1 and nil votes 3.
+const BLOCK_ID_FLAG_COMMIT: u64 = 2;
+
+/// JSON pointers to the fields which the indexer reads from RPC responses. The defaults follow
+/// CometBFT as served by Osmosis mainnet; forks, testnets and devnets which move or rename a field
+/// override only that field for their chain id in `[profiles.<chain id>]` of the config.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Profile {
+    /// Fields of the `/block` response.
+    pub height: String,
+    pub time: String,
+    pub proposer_address: String,
+    pub hash: String,
+    /// Hash of the previous block; empty in the first block of a chain.
+    pub last_block_hash: String,
+    /// Array of transactions, which are only counted; a missing or null array means no transactions.
+    pub txs: String,
+    /// Height and signatures of the commit to the previous block; the commit of the first block
+    /// of a chain has height 0 and no signatures.
+    pub last_commit_height: String,
+    pub last_commit_signatures: String,
+    /// Field of the `/blockchain` response.
+    pub last_height: String,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            height: "/result/block/header/height".to_string(),
+            time: "/result/block/header/time".to_string(),
+            proposer_address: "/result/block/header/proposer_address".to_string(),
+            hash: "/result/block_id/hash".to_string(),
+            last_block_hash: "/result/block/header/last_block_id/hash".to_string(),
+            txs: "/result/block/data/txs".to_string(),
+            last_commit_height: "/result/block/last_commit/height".to_string(),
+            last_commit_signatures: "/result/block/last_commit/signatures".to_string(),
+            last_height: "/result/last_height".to_string(),
+        }
+    }
+}
+
+impl Profile {
+    /// Chooses the profile configured for the chain id, or the default one.
+    pub fn select(profiles: &HashMap<ChainId, Profile>, chain_id: &ChainId) -> Profile {
+        match profiles.get(chain_id) {
+            Some(p) => {
+                info!(%chain_id, "Using the parser profile of the chain");
+                p.clone()
+            }
+            None => Profile::default(),
+        }
+    }
+
+    pub fn parse_block(&self, response: &Value) -> Result<Block, Error> {
+        let num_txs = match response.pointer(&self.txs) {
+            None | Some(Value::Null) => 0,
+            Some(Value::Array(txs)) => txs.len() as i32,
+            Some(_) => return Err(Error::CouldNotParseResponseForBlockAtHeight { pointer: self.txs.clone() }),
+        };
+        let last_block_hash = match response.pointer(&self.last_block_hash) {
+            None | Some(Value::Null) => None,
+            Some(Value::String(h)) if h.is_empty() => None,
+            Some(_) => Some(field(response, &self.last_block_hash)?),
+        };
+
+        Ok(Block {
+            proposer: field(response, &self.proposer_address)?,
+            height: field(response, &self.height)?,
+            hash: field(response, &self.hash)?,
+            last_block_hash,
+            block_time: field(response, &self.time)?,
+            num_txs,
+            last_commit: self.parse_last_commit(response)?,
+        })
+    }
+
+    /// Absent validators are left without an address, which only the validator set has.
+    fn parse_last_commit(&self, response: &Value) -> Result<Option<LastCommit>, Error> {
+        let (Some(height), Some(Value::Array(signatures))) =
+            (response.pointer(&self.last_commit_height), response.pointer(&self.last_commit_signatures)) else {
+            return Ok(None);
+        };
+        let height: Height = field(height, "")?;
+        if height == Height::new(0) || signatures.is_empty() {
+            return Ok(None);
+        }
+
+        let signatures = signatures.iter()
+            .map(|s| {
+                let signed = s.get("block_id_flag").and_then(Value::as_u64) == Some(BLOCK_ID_FLAG_COMMIT);
+                let validator = match s.get("validator_address") {
+                    None | Some(Value::Null) => None,
+                    Some(Value::String(a)) if a.is_empty() => None,
+                    Some(_) => Some(field(s, "/validator_address")?),
+                };
+                Ok(CommitSignature { validator, signed })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Some(LastCommit { height, signatures }))
+    }
+
+    pub fn parse_last_height(&self, response: &Value) -> Result<Height, Error> {
+        field(response, &self.last_height)
+            .map_err(|_| Error::CouldNotParseResponseForBlockchain { pointer: self.last_height.clone() })
+    }
+}
+
+fn field<T: DeserializeOwned>(response: &Value, pointer: &str) -> Result<T, Error> {
+    response.pointer(pointer)
+        .and_then(|v| T::deserialize(v).ok())
+        .ok_or_else(|| Error::CouldNotParseResponseForBlockAtHeight { pointer: pointer.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const PROPOSER: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+    const HASH: &str = "0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF";
+
+    #[test]
+    fn default_profile_reads_cometbft_blocks() {
+        let response = json!({"result": {
+            "block_id": {"hash": HASH},
+            "block": {
+                "header": {
+                    "height": "10",
+                    "time": "2023-06-01T00:00:00Z",
+                    "proposer_address": PROPOSER,
+                    "last_block_id": {"hash": HASH},
+                },
+                "data": {"txs": ["a", "b"]},
+            },
+        }});
+
+        let block = Profile::default().parse_block(&response).unwrap();
+
+        assert_eq!(block.height, Height::new(10));
+        assert_eq!(block.proposer.as_str(), PROPOSER);
+        assert_eq!(block.hash.as_str(), HASH);
+        assert_eq!(block.last_block_hash.unwrap().as_str(), HASH);
+        assert_eq!(block.num_txs, 2);
+        assert!(block.last_commit.is_none());
+    }
+
+    #[test]
+    fn absent_validators_of_the_last_commit_have_no_address() {
+        let response = json!({"result": {
+            "block_id": {"hash": HASH},
+            "block": {
+                "header": {"height": "10", "time": "2023-06-01T00:00:00Z", "proposer_address": PROPOSER},
+                "last_commit": {
+                    "height": "9",
+                    "signatures": [
+                        {"block_id_flag": 2, "validator_address": PROPOSER},
+                        {"block_id_flag": 1, "validator_address": ""},
+                        {"block_id_flag": 3, "validator_address": PROPOSER},
+                    ],
+                },
+            },
+        }});
+
+        let commit = Profile::default().parse_block(&response).unwrap().last_commit.unwrap();
+
+        assert_eq!(commit.height, Height::new(9));
+        assert_eq!(commit.signatures, vec![
+            CommitSignature { validator: Some(PROPOSER.parse().unwrap()), signed: true },
+            CommitSignature { validator: None, signed: false },
+            CommitSignature { validator: Some(PROPOSER.parse().unwrap()), signed: false },
+        ]);
+        assert!(commit.validators().is_none());
+    }
+
+    #[test]
+    fn overridden_fields_are_read_from_their_pointers() {
+        let profiles: HashMap<ChainId, Profile> = toml::from_str(r#"
+            [localnet]
+            hash = "/result/block/header/last_block_id/hash"
+            txs = "/result/block/data/transactions"
+        "#).unwrap();
+        let response = json!({"result": {
+            "block": {
+                "header": {
+                    "height": 3,
+                    "time": "2023-06-01T00:00:00Z",
+                    "proposer_address": PROPOSER,
+                    "last_block_id": {"hash": HASH},
+                },
+                "data": {"transactions": null},
+            },
+        }});
+
+        let profile = Profile::select(&profiles, &"localnet".parse().unwrap());
+        let block = profile.parse_block(&response).unwrap();
+
+        assert_eq!(profile.height, Profile::default().height);
+        assert_eq!(block.hash.as_str(), HASH);
+        assert_eq!(block.num_txs, 0);
+    }
+}
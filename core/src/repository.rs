@@ -0,0 +1,226 @@
+use chrono::{DateTime, Utc};
+use model::{BlockHash, ConsensusAddress, Height};
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::GenericClient;
+use tokio_postgres::types::{ToSql, Type};
+use tracing::{debug, info};
+
+use crate::{Block, CommitSignature, Error, LastCommit};
+
+/// Inserts the batch in a single statement with the columns bound as arrays, so no value
+/// from the RPC response ever becomes part of the SQL text. Heights which are indexed already,
+/// by another replica or by an earlier attempt of the same batch, are left as they are, and the
+/// rest of the batch is written. Returns the number of rows written.
+pub async fn insert_batch(database_client: &tokio_postgres::Client, proposers_to_height: &[Block])
+                      -> Result<u64, Error> {
+    let columns = BlockColumns::new(proposers_to_height);
+    debug!(rows = proposers_to_height.len(), "Inserting");
+
+    upsert_signatures(database_client, proposers_to_height).await?;
+
+    let count_rows_inserted = database_client
+        .execute("INSERT INTO proposer_to_height(proposer, height, hash, last_block_hash, block_time, num_txs) \
+                  SELECT * FROM UNNEST($1::varchar[], $2::bigint[], $3::varchar[], $4::varchar[], \
+                                       $5::timestamptz[], $6::integer[]) \
+                  ON CONFLICT (height) DO NOTHING",
+                 &columns.params())
+        .await
+        .map_err(insert_error)?;
+
+    let rows = proposers_to_height.len() as u64;
+    if count_rows_inserted > rows {
+        return Err(Error::InsertedIncorrectNumberOfRows { expected: rows, inserted: count_rows_inserted });
+    }
+    if count_rows_inserted < rows {
+        info!(rows, skipped = rows - count_rows_inserted, "Skipped heights which are indexed already");
+    }
+
+    Ok(count_rows_inserted)
+}
+
+/// Loads the batch with the binary COPY protocol, which is much faster than INSERT for large
+/// batches, such as those written while catching up from OSMOSIS_LOWEST_HEIGHT. COPY cannot skip
+/// conflicting rows, so a batch with a height which is indexed already is inserted instead.
+/// Returns the number of rows written.
+pub async fn copy_batch(database_client: &tokio_postgres::Client, proposers_to_height: &[Block])
+                    -> Result<u64, Error> {
+    match copy(database_client, proposers_to_height).await {
+        Err(Error::CouldNotIndexDuplicateHeight(_)) => {
+            debug!(rows = proposers_to_height.len(), "Batch has indexed heights, inserting instead of copying");
+            insert_batch(database_client, proposers_to_height).await
+        }
+        copied => copied,
+    }
+}
+
+async fn copy(database_client: &tokio_postgres::Client, proposers_to_height: &[Block]) -> Result<u64, Error> {
+    debug!(rows = proposers_to_height.len(), "Copying");
+
+    upsert_signatures(database_client, proposers_to_height).await?;
+
+    let sink = database_client
+        .copy_in("COPY proposer_to_height(proposer, height, hash, last_block_hash, block_time, num_txs) \
+                  FROM STDIN BINARY")
+        .await
+        .map_err(insert_error)?;
+    let types = [Type::VARCHAR, Type::INT8, Type::VARCHAR, Type::VARCHAR, Type::TIMESTAMPTZ, Type::INT4];
+    let writer = BinaryCopyInWriter::new(sink, &types);
+    tokio::pin!(writer);
+
+    for proposer_to_height in proposers_to_height {
+        writer.as_mut()
+            .write(&[&proposer_to_height.proposer,
+                     &proposer_to_height.height,
+                     &proposer_to_height.hash,
+                     &proposer_to_height.last_block_hash,
+                     &proposer_to_height.block_time,
+                     &proposer_to_height.num_txs])
+            .await
+            .map_err(insert_error)?;
+    }
+
+    let count_rows_copied = writer.finish().await.map_err(insert_error)?;
+
+    let rows = proposers_to_height.len() as u64;
+    if count_rows_copied != rows {
+        return Err(Error::InsertedIncorrectNumberOfRows { expected: rows, inserted: count_rows_copied });
+    }
+
+    Ok(count_rows_copied)
+}
+
+/// Writes the batch over rows which are indexed already, for re-indexing ranges of heights.
+pub async fn upsert_batch(database_client: &impl GenericClient, proposers_to_height: &[Block])
+                          -> Result<(), Error> {
+    let columns = BlockColumns::new(proposers_to_height);
+    debug!(rows = proposers_to_height.len(), "Upserting");
+
+    upsert_signatures(database_client, proposers_to_height).await?;
+
+    database_client
+        .execute("INSERT INTO proposer_to_height(proposer, height, hash, last_block_hash, block_time, num_txs) \
+                  SELECT * FROM UNNEST($1::varchar[], $2::bigint[], $3::varchar[], $4::varchar[], \
+                                       $5::timestamptz[], $6::integer[]) \
+                  ON CONFLICT (height) DO UPDATE SET proposer = excluded.proposer, hash = excluded.hash, \
+                  last_block_hash = excluded.last_block_hash, block_time = excluded.block_time, \
+                  num_txs = excluded.num_txs",
+                 &columns.params())
+        .await
+        .map_err(insert_error)?;
+
+    Ok(())
+}
+
+/// Values of the columns of `proposer_to_height` for a batch, one array per column, which the
+/// statements bind as parameters and `UNNEST` into rows.
+struct BlockColumns<'a> {
+    proposers: Vec<&'a ConsensusAddress>,
+    heights: Vec<Height>,
+    hashes: Vec<&'a BlockHash>,
+    last_block_hashes: Vec<Option<&'a BlockHash>>,
+    block_times: Vec<DateTime<Utc>>,
+    nums_txs: Vec<i32>,
+}
+
+impl<'a> BlockColumns<'a> {
+    fn new(blocks: &'a [Block]) -> Self {
+        BlockColumns {
+            proposers: blocks.iter().map(|b| &b.proposer).collect(),
+            heights: blocks.iter().map(|b| b.height).collect(),
+            hashes: blocks.iter().map(|b| &b.hash).collect(),
+            last_block_hashes: blocks.iter().map(|b| b.last_block_hash.as_ref()).collect(),
+            block_times: blocks.iter().map(|b| b.block_time).collect(),
+            nums_txs: blocks.iter().map(|b| b.num_txs).collect(),
+        }
+    }
+
+    /// In the order of `proposer, height, hash, last_block_hash, block_time, num_txs`.
+    fn params(&self) -> [&(dyn ToSql + Sync); 6] {
+        [&self.proposers, &self.heights, &self.hashes, &self.last_block_hashes, &self.block_times, &self.nums_txs]
+    }
+}
+
+/// Records which validators signed the blocks whose commits the batch carries, before the blocks
+/// themselves, so a batch retried after a failure writes them again. Validator sets are stored
+/// once in `signer_sets`, keyed by a digest of their addresses in order, and the signatures of
+/// each block as a bitmap over its set. Commits missing the address of an absent validator are
+/// left out.
+pub async fn upsert_signatures(database_client: &impl GenericClient, blocks: &[Block]) -> Result<(), Error> {
+    let commits: Vec<(&LastCommit, String)> = blocks.iter()
+        .filter_map(|b| b.last_commit.as_ref())
+        .filter_map(|c| {
+            let addresses: Vec<&str> = c.validators()?.into_iter().map(ConsensusAddress::as_str).collect();
+            Some((c, addresses.join(",")))
+        })
+        .collect();
+    if commits.is_empty() {
+        return Ok(());
+    }
+    let heights: Vec<Height> = commits.iter().map(|(c, _)| c.height).collect();
+    let signer_sets: Vec<&str> = commits.iter().map(|(_, s)| s.as_str()).collect();
+    let signed: Vec<Vec<u8>> = commits.iter().map(|(c, _)| signed_bitmap(&c.signatures)).collect();
+
+    database_client
+        .execute("INSERT INTO signer_sets(digest, addresses) \
+                  SELECT DISTINCT md5(a), string_to_array(a, ',') FROM UNNEST($1::text[]) AS a \
+                  ON CONFLICT (digest) DO NOTHING",
+                 &[&signer_sets])
+        .await
+        .map_err(signatures_error)?;
+
+    database_client
+        .execute("INSERT INTO block_signatures(height, signer_set_id, signed) \
+                  SELECT u.height, s.id, u.signed \
+                  FROM UNNEST($1::bigint[], $2::text[], $3::bytea[]) AS u(height, addresses, signed) \
+                  JOIN signer_sets s ON s.digest = md5(u.addresses) \
+                  ON CONFLICT (height) DO UPDATE SET signer_set_id = excluded.signer_set_id, \
+                  signed = excluded.signed",
+                 &[&heights, &signer_sets, &signed])
+        .await
+        .map_err(signatures_error)?;
+
+    Ok(())
+}
+
+/// Bit `i % 8` of byte `i / 8`, counting from the least significant bit, is set if the `i`th
+/// validator signed, which is the bit Postgres' `get_bit(signed, i)` reads.
+fn signed_bitmap(signatures: &[CommitSignature]) -> Vec<u8> {
+    let mut bitmap = vec![0; signatures.len().div_ceil(8)];
+    for (i, _) in signatures.iter().enumerate().filter(|(_, s)| s.signed) {
+        bitmap[i / 8] |= 1 << (i % 8);
+    }
+    bitmap
+}
+
+fn signatures_error(e: tokio_postgres::Error) -> Error {
+    match e.code() {
+        Some(_) => Error::CouldNotInsertSignatures(e),
+        None => Error::DatabaseUnavailable(e),
+    }
+}
+
+/// Errors without an SQL state come from the connection rather than from the statement.
+fn insert_error(e: tokio_postgres::Error) -> Error {
+    match e.code() {
+        Some(&SqlState::UNIQUE_VIOLATION) => Error::CouldNotIndexDuplicateHeight(e),
+        Some(_) => Error::CouldNotInsertBatch(e),
+        None => Error::DatabaseUnavailable(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_are_bits_from_the_least_significant() {
+        let signatures: Vec<CommitSignature> = [true, false, true, false, false, false, false, false, false, true]
+            .into_iter()
+            .map(|signed| CommitSignature { validator: None, signed })
+            .collect();
+
+        assert_eq!(signed_bitmap(&signatures), vec![0b0000_0101, 0b0000_0010]);
+        assert_eq!(signed_bitmap(&[]), Vec::<u8>::new());
+    }
+}
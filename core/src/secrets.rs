@@ -0,0 +1,63 @@
+use std::{env, fs};
+
+use crate::{BoxError, Error};
+
+const FILE_SUFFIX: &str = "_FILE";
+const ENV_PREFIX: &str = "env:";
+const VAULT_PREFIX: &str = "vault:";
+
+/// Resolves a secret setting at startup, so passwords and keys do not have to sit in plain
+/// configuration:
+/// - `<NAME>_FILE` names a file holding the secret, e.g. a Docker or Kubernetes secret;
+/// - `<NAME>=env:OTHER` takes the secret from another environment variable;
+/// - `<NAME>=vault:<path>#<field>` reads the field of a KV v2 secret from Vault at
+///   `VAULT_ADDR` using `VAULT_TOKEN`;
+/// - any other value of `<NAME>` is the secret itself.
+pub async fn resolve(name: &str) -> Result<Option<String>, Error> {
+    if let Ok(path) = env::var(format!("{name}{FILE_SUFFIX}")) {
+        let secret = fs::read_to_string(path)
+            .map_err(|e| Error::CouldNotResolveSecret { name: name.to_string(), source: Box::new(e) })?;
+        return Ok(Some(secret.trim_end_matches(['\r', '\n']).to_string()));
+    }
+
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    if let Some(other) = value.strip_prefix(ENV_PREFIX) {
+        return env::var(other)
+            .map(Some)
+            .map_err(|e| Error::CouldNotResolveSecret { name: name.to_string(), source: Box::new(e) });
+    }
+
+    if let Some(reference) = value.strip_prefix(VAULT_PREFIX) {
+        return read_from_vault(reference)
+            .await
+            .map(Some)
+            .map_err(|source| Error::CouldNotResolveSecret { name: name.to_string(), source });
+    }
+
+    Ok(Some(value))
+}
+
+async fn read_from_vault(reference: &str) -> Result<String, BoxError> {
+    let (path, field) = reference.split_once('#')
+        .ok_or("Vault reference has no #field")?;
+    let address = env::var("VAULT_ADDR")?;
+    let token = env::var("VAULT_TOKEN")?;
+
+    let response: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}/v1/{}", address.trim_end_matches('/'), path.trim_start_matches('/')))
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())?
+        .json()
+        .await?;
+
+    response
+        .pointer(&format!("/data/data/{field}"))
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string())
+        .ok_or_else(|| format!("Vault secret has no field {field}").into())
+}
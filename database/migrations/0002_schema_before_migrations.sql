@@ -0,0 +1,85 @@
+-- Tables and columns added to the initial script before migrations were tracked. Databases which
+-- Docker created from some version of that script adopt 0001 and have any part of this already,
+-- so every statement is skipped for what exists.
+alter table proposer_to_height add column if not exists hash varchar(64) UNIQUE;
+alter table proposer_to_height add column if not exists block_time timestamptz;
+alter table proposer_to_height add column if not exists num_txs integer;
+alter table proposer_to_height add column if not exists indexed_at timestamptz DEFAULT now();
+
+create index if not exists proposer_to_height_block_time_idx on proposer_to_height (block_time);
+
+do $$
+begin
+    if not exists (select from pg_roles where rolname = 'statistics_readonly') then
+        create role statistics_readonly nologin;
+    end if;
+end
+$$;
+grant select on proposer_to_height to statistics_readonly;
+
+create table if not exists reports (
+    name varchar(255) PRIMARY KEY,
+    sql text NOT NULL,
+    parameters varchar(255)[] NOT NULL
+);
+
+create table if not exists genesis_validators (
+    chain_id varchar(255),
+    address varchar(64),
+    moniker varchar(255),
+    operator_address varchar(255),
+    voting_power bigint,
+    PRIMARY KEY (chain_id, address)
+);
+
+create table if not exists validators (
+    address varchar(64) PRIMARY KEY,
+    operator_address varchar(255) NOT NULL,
+    moniker varchar(255) NOT NULL
+);
+alter table validators add column if not exists identity varchar(255);
+alter table validators add column if not exists avatar_url text;
+alter table validators add column if not exists avatar_updated_at timestamp;
+alter table validators add column if not exists commission_rate numeric;
+alter table validators add column if not exists updated_at timestamp NOT NULL DEFAULT now();
+create index if not exists validators_identity_idx on validators (identity);
+grant select on validators to statistics_readonly;
+
+create table if not exists commission_changes (
+    id bigserial PRIMARY KEY,
+    address varchar(64) NOT NULL,
+    old_rate numeric NOT NULL,
+    new_rate numeric NOT NULL,
+    height bigint,
+    changed_at timestamptz NOT NULL,
+    detected_at timestamptz NOT NULL DEFAULT now()
+);
+create index if not exists commission_changes_address_idx on commission_changes (address);
+grant select on commission_changes to statistics_readonly;
+
+create table if not exists fetch_telemetry (
+    height bigint NOT NULL,
+    endpoint varchar(255) NOT NULL,
+    duration_in_milliseconds integer NOT NULL,
+    response_size_in_bytes integer NOT NULL,
+    fetched_at timestamptz NOT NULL DEFAULT now()
+);
+create index if not exists fetch_telemetry_height_idx on fetch_telemetry (height);
+
+create table if not exists indexer_state (
+    name varchar(255) PRIMARY KEY,
+    height bigint NOT NULL
+);
+
+create table if not exists indexer_checkpoints (
+    name varchar(255) PRIMARY KEY,
+    first_height bigint NOT NULL,
+    last_height bigint NOT NULL,
+    cursor bigint NOT NULL,
+    updated_at timestamp NOT NULL DEFAULT now()
+);
+
+create table if not exists height_requests (
+    height bigint PRIMARY KEY,
+    requested_at timestamp NOT NULL DEFAULT now()
+);
@@ -0,0 +1,7 @@
+create table subsystem_backfills (
+    subsystem varchar(255) PRIMARY KEY,
+    first_height bigint NOT NULL,
+    last_height bigint NOT NULL,
+    cursor bigint NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,9 @@
+create table custom_records (
+    plugin varchar(255) NOT NULL,
+    height bigint NOT NULL,
+    key varchar(255) NOT NULL,
+    value jsonb NOT NULL,
+    PRIMARY KEY (plugin, height, key)
+);
+
+grant select on custom_records to statistics_readonly;
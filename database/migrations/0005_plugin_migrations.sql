@@ -0,0 +1,6 @@
+create table plugin_migrations (
+    plugin varchar(255) NOT NULL,
+    version bigint NOT NULL,
+    applied_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (plugin, version)
+);
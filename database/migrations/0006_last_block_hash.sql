@@ -0,0 +1 @@
+alter table proposer_to_height add column last_block_hash varchar(64);
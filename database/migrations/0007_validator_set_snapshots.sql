@@ -0,0 +1,10 @@
+create table validator_set_snapshots (
+    height bigint NOT NULL,
+    address varchar(64) NOT NULL,
+    voting_power bigint NOT NULL,
+    rank integer NOT NULL,
+    taken_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (height, address)
+);
+create index on validator_set_snapshots (address, height);
+grant select on validator_set_snapshots to statistics_readonly;
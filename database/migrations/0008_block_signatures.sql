@@ -0,0 +1,13 @@
+create table signer_sets (
+    id bigserial PRIMARY KEY,
+    digest varchar(32) NOT NULL UNIQUE,
+    addresses varchar(64)[] NOT NULL
+);
+grant select on signer_sets to statistics_readonly;
+
+create table block_signatures (
+    height bigint PRIMARY KEY,
+    signer_set_id bigint NOT NULL REFERENCES signer_sets (id),
+    signed bytea NOT NULL
+);
+grant select on block_signatures to statistics_readonly;
@@ -0,0 +1,7 @@
+create table validator_tags (
+    address varchar(64),
+    tag varchar(64),
+    PRIMARY KEY (address, tag)
+);
+create index on validator_tags (tag);
+grant select on validator_tags to statistics_readonly;
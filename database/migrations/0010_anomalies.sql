@@ -0,0 +1,11 @@
+create table anomalies (
+    id bigserial PRIMARY KEY,
+    kind varchar(32) NOT NULL,
+    address varchar(64) NOT NULL,
+    first_height bigint NOT NULL,
+    last_height bigint NOT NULL,
+    score double precision NOT NULL,
+    detected_at timestamptz NOT NULL DEFAULT now()
+);
+create index on anomalies (address);
+grant select on anomalies to statistics_readonly;
@@ -0,0 +1,88 @@
+-- Blocks proposed per validator and UTC day, kept up to date by triggers in the transaction
+-- which writes the blocks, so aggregates over whole days need not scan proposer_to_height.
+-- Blocks without a time are not counted.
+create table proposer_daily_counts (
+    day date NOT NULL,
+    proposer varchar(255) NOT NULL,
+    blocks bigint NOT NULL,
+    first_height bigint NOT NULL,
+    last_height bigint NOT NULL,
+    PRIMARY KEY (day, proposer)
+);
+grant select on proposer_daily_counts to statistics_readonly;
+
+insert into proposer_daily_counts (day, proposer, blocks, first_height, last_height)
+select (block_time at time zone 'UTC')::date, proposer, count(*), min(height), max(height)
+from proposer_to_height
+where block_time is not null and proposer is not null
+group by 1, 2;
+
+-- Counts the blocks of the validators on the days again from proposer_to_height.
+create function recount_proposer_days(days date[], proposers varchar[]) returns void language sql as $$
+    delete from proposer_daily_counts d
+    using unnest(days, proposers) as k (day, proposer)
+    where d.day = k.day and d.proposer = k.proposer;
+
+    insert into proposer_daily_counts (day, proposer, blocks, first_height, last_height)
+    select k.day, k.proposer, count(*), min(p.height), max(p.height)
+    from unnest(days, proposers) as k (day, proposer)
+    join proposer_to_height p on p.proposer = k.proposer
+        and p.block_time >= k.day::timestamp at time zone 'UTC'
+        and p.block_time < (k.day + 1)::timestamp at time zone 'UTC'
+    group by k.day, k.proposer;
+$$;
+
+create function count_inserted_blocks() returns trigger language plpgsql as $$
+begin
+    insert into proposer_daily_counts as d (day, proposer, blocks, first_height, last_height)
+    select (block_time at time zone 'UTC')::date, proposer, count(*), min(height), max(height)
+    from inserted_rows
+    where block_time is not null and proposer is not null
+    group by 1, 2
+    on conflict (day, proposer) do update set
+        blocks = d.blocks + excluded.blocks,
+        first_height = least(d.first_height, excluded.first_height),
+        last_height = greatest(d.last_height, excluded.last_height);
+    return null;
+end
+$$;
+
+-- Rows are rarely updated or deleted, by reorgs and repairs, so their days are counted again.
+create function recount_changed_blocks() returns trigger language plpgsql as $$
+begin
+    if tg_op = 'UPDATE' then
+        perform recount_proposer_days(array_agg(k.day), array_agg(k.proposer))
+        from (select (block_time at time zone 'UTC')::date as day, proposer from old_rows
+              union
+              select (block_time at time zone 'UTC')::date, proposer from new_rows) k
+        where k.day is not null and k.proposer is not null;
+    else
+        perform recount_proposer_days(array_agg(k.day), array_agg(k.proposer))
+        from (select distinct (block_time at time zone 'UTC')::date as day, proposer from old_rows) k
+        where k.day is not null and k.proposer is not null;
+    end if;
+    return null;
+end
+$$;
+
+create trigger proposer_daily_counts_insert after insert on proposer_to_height
+    referencing new table as inserted_rows
+    for each statement execute function count_inserted_blocks();
+
+create trigger proposer_daily_counts_update after update on proposer_to_height
+    referencing old table as old_rows new table as new_rows
+    for each statement execute function recount_changed_blocks();
+
+create trigger proposer_daily_counts_delete after delete on proposer_to_height
+    referencing old table as old_rows
+    for each statement execute function recount_changed_blocks();
+
+create function truncate_proposer_daily_counts() returns trigger language plpgsql as $$
+begin
+    truncate proposer_daily_counts;
+    return null;
+end
+$$;
+
+create trigger proposer_daily_counts_truncate after truncate on proposer_to_height
+    for each statement execute function truncate_proposer_daily_counts();
@@ -0,0 +1,14 @@
+-- Plugin values of old blocks, moved out of custom_records in chunks of heights. Each chunk is
+-- one JSON array of {"height", "key", "value"} objects, which Postgres compresses when stored.
+create table custom_records_archive (
+    id bigserial PRIMARY KEY,
+    plugin varchar(255) NOT NULL,
+    first_height bigint NOT NULL,
+    last_height bigint NOT NULL,
+    records jsonb NOT NULL,
+    archived_at timestamptz NOT NULL DEFAULT now()
+);
+create index on custom_records_archive (plugin, last_height);
+grant select on custom_records_archive to statistics_readonly;
+
+create index on custom_records (height);
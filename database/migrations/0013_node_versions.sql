@@ -0,0 +1,14 @@
+-- Software versions of the nodes seen by the RPC node, per UTC day. Peers only announce their
+-- CometBFT version, while the osmosisd version is known for the RPC node itself.
+create table node_versions (
+    day date NOT NULL,
+    node_id varchar(64) NOT NULL,
+    moniker varchar(255) NOT NULL,
+    version varchar(64) NOT NULL,
+    app_version varchar(64),
+    first_seen_at timestamptz NOT NULL DEFAULT now(),
+    last_seen_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (day, node_id, version)
+);
+create index on node_versions (day, moniker);
+grant select on node_versions to statistics_readonly;
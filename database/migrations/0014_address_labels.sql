@@ -0,0 +1,12 @@
+-- Names of known addresses, such as exchanges, bridges and team wallets, keyed by any address:
+-- account, operator or consensus. Labels of the community source are refreshed by the indexer,
+-- while labels set through the admin API of the statistics service take precedence over them.
+create table address_labels (
+    address varchar(255) PRIMARY KEY,
+    label varchar(255) NOT NULL,
+    category varchar(64),
+    source varchar(16) NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+create index on address_labels (category);
+grant select on address_labels to statistics_readonly;
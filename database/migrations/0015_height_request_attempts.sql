@@ -0,0 +1,3 @@
+-- Requested heights stay queued until they are indexed; a height which fails is retried later
+-- and dropped after a few attempts.
+alter table height_requests add column attempts integer NOT NULL DEFAULT 0;
@@ -0,0 +1,93 @@
+-- Runs of consecutive indexed heights, kept up to date by triggers in the transaction which
+-- writes the blocks, so telling whether a range of heights is completely indexed need not scan
+-- proposer_to_height.
+create table indexed_height_ranges (
+    first_height bigint PRIMARY KEY,
+    last_height bigint NOT NULL
+);
+grant select on indexed_height_ranges to statistics_readonly;
+
+insert into indexed_height_ranges (first_height, last_height)
+select min(height), max(height)
+from (select height, height - row_number() over (order by height) as island from proposer_to_height) h
+group by island;
+
+-- Merges the runs of inserted heights with the ranges they extend or join. Writers take turns,
+-- so each merges with the ranges the previous one left.
+create function merge_inserted_heights() returns trigger language plpgsql as $$
+begin
+    lock table indexed_height_ranges in share row exclusive mode;
+
+    with inserted as (
+        select min(height) as first_height, max(height) as last_height
+        from (select height, height - row_number() over (order by height) as island from inserted_rows) h
+        group by island
+    ), joined as (
+        delete from indexed_height_ranges r
+        using inserted i
+        where r.first_height <= i.last_height + 1 and r.last_height >= i.first_height - 1
+        returning r.first_height, r.last_height
+    ), ranges as (
+        select first_height, last_height,
+               coalesce(first_height > max(last_height) over (order by first_height
+                   rows between unbounded preceding and 1 preceding) + 1, true) as starts_run
+        from (select * from inserted union all select * from joined) r
+    )
+    insert into indexed_height_ranges (first_height, last_height)
+    select min(first_height), max(last_height)
+    from (select *, count(*) filter (where starts_run) over (order by first_height) as run from ranges) r
+    group by run;
+
+    return null;
+end
+$$;
+
+-- Splits the ranges around the runs of deleted heights, which reorgs delete from the top.
+create function split_deleted_heights() returns trigger language plpgsql as $$
+begin
+    lock table indexed_height_ranges in share row exclusive mode;
+
+    with deleted as (
+        select min(height) as first_height, max(height) as last_height
+        from (select height, height - row_number() over (order by height) as island from deleted_rows) h
+        group by island
+    ), split as (
+        delete from indexed_height_ranges r
+        using deleted d
+        where r.first_height <= d.first_height and r.last_height >= d.last_height
+        returning r.first_height, r.last_height
+    )
+    insert into indexed_height_ranges (first_height, last_height)
+    select p.first_height, p.last_height
+    from (select distinct * from split) s,
+    lateral (
+        select lag(b.last_height, 1, s.first_height - 1) over (order by b.first_height) + 1 as first_height,
+               b.first_height - 1 as last_height
+        from (select d.first_height, d.last_height from deleted d
+              where d.first_height >= s.first_height and d.last_height <= s.last_height
+              union all
+              select s.last_height + 1, s.last_height + 1) b
+    ) p
+    where p.first_height <= p.last_height;
+
+    return null;
+end
+$$;
+
+create trigger indexed_height_ranges_insert after insert on proposer_to_height
+    referencing new table as inserted_rows
+    for each statement execute function merge_inserted_heights();
+
+create trigger indexed_height_ranges_delete after delete on proposer_to_height
+    referencing old table as deleted_rows
+    for each statement execute function split_deleted_heights();
+
+create function truncate_indexed_height_ranges() returns trigger language plpgsql as $$
+begin
+    truncate indexed_height_ranges;
+    return null;
+end
+$$;
+
+create trigger indexed_height_ranges_truncate after truncate on proposer_to_height
+    for each statement execute function truncate_indexed_height_ranges();
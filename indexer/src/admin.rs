@@ -0,0 +1,83 @@
+use std::net::SocketAddr;
+
+use axum::{Json, Router};
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::get;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::rpc::RpcClient;
+use crate::settings::Settings;
+
+const ADMIN_KEY_HEADER: &str = "x-admin-key";
+
+#[derive(Clone)]
+struct AdminState {
+    settings: Settings,
+    rpc_client: RpcClient,
+    admin_key: String,
+}
+
+/// Settings in effect; a change leaves out those which stay the same.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SettingsBody {
+    interval_in_seconds: Option<u64>,
+    rpc_requests_per_second: Option<f64>,
+    rpc_maximum_concurrency: Option<u64>,
+}
+
+/// Serves `GET` and `PUT /admin/settings`, which read and change the polling interval and the
+/// RPC rate and concurrency while the indexer runs. Requests need the key in `X-Admin-Key`.
+pub async fn serve(address: SocketAddr, settings: Settings, rpc_client: RpcClient, admin_key: String) {
+    let app = Router::new()
+        .route("/admin/settings", get(settings_handler).put(change_handler))
+        .with_state(AdminState { settings, rpc_client, admin_key });
+
+    info!(%address, "Serving admin endpoint");
+    if let Err(e) = axum::Server::bind(&address).serve(app.into_make_service()).await {
+        warn!(error = %e, "Admin endpoint stopped");
+    }
+}
+
+async fn settings_handler(headers: HeaderMap, State(state): State<AdminState>)
+                          -> Result<Json<SettingsBody>, StatusCode> {
+    authorize(&headers, &state)?;
+
+    Ok(Json(current(&state)))
+}
+
+async fn change_handler(headers: HeaderMap, State(state): State<AdminState>, Json(change): Json<SettingsBody>)
+                        -> Result<Json<SettingsBody>, StatusCode> {
+    authorize(&headers, &state)?;
+
+    if change.interval_in_seconds == Some(0)
+        || change.rpc_maximum_concurrency == Some(0)
+        || change.rpc_requests_per_second.is_some_and(|r| !(r > 0.0 && r.is_finite())) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Some(interval_in_seconds) = change.interval_in_seconds {
+        state.settings.set_interval_in_seconds(interval_in_seconds);
+    }
+    state.rpc_client.limiter().reconfigure(change.rpc_requests_per_second, change.rpc_maximum_concurrency);
+
+    let settings = current(&state);
+    info!(?settings, "Changed settings");
+    Ok(Json(settings))
+}
+
+fn current(state: &AdminState) -> SettingsBody {
+    SettingsBody {
+        interval_in_seconds: Some(state.settings.interval_in_seconds()),
+        rpc_requests_per_second: Some(state.rpc_client.limiter().requests_per_second()),
+        rpc_maximum_concurrency: Some(state.rpc_client.limiter().maximum_concurrency()),
+    }
+}
+
+fn authorize(headers: &HeaderMap, state: &AdminState) -> Result<(), StatusCode> {
+    match headers.get(ADMIN_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        Some(key) if key == state.admin_key => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
@@ -0,0 +1,120 @@
+use model::{ConsensusAddress, Height};
+use tracing::{info, warn};
+
+use crate::{Error, repository};
+
+pub const ANOMALY_DETECTION_INTERVAL_IN_SECONDS: u64 = 600;
+/// Latest blocks every detection looks at, about three hours.
+const ANOMALY_WINDOW_IN_BLOCKS: i64 = 2000;
+/// Standard deviations below its expected proposals at which a validator counts as falling short.
+const PROPOSAL_SHORTFALL_IN_STANDARD_DEVIATIONS: f64 = 4.0;
+/// Consecutive blocks a validator did not sign which count as a burst of misses.
+const MISSED_SIGNATURES_BURST_IN_BLOCKS: i64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnomalyKind {
+    /// Far fewer proposals than the share of the voting power predicts.
+    ProposalShortfall,
+    /// A run of consecutive blocks the validator did not sign.
+    MissedSignaturesBurst,
+}
+
+impl AnomalyKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnomalyKind::ProposalShortfall => "proposal_shortfall",
+            AnomalyKind::MissedSignaturesBurst => "missed_signatures_burst",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub address: ConsensusAddress,
+    pub first_height: Height,
+    pub last_height: Height,
+    /// Standard deviations below the expected proposals, or the number of blocks missed in a row.
+    pub score: f64,
+}
+
+/// Proposals of a validator over the blocks of the window which a validator set snapshot covers,
+/// and the proposals its share of the voting power predicts.
+#[derive(Debug)]
+pub struct ProposalExpectation {
+    pub address: ConsensusAddress,
+    pub first_height: Height,
+    pub last_height: Height,
+    pub proposals: i64,
+    pub expected_proposals: f64,
+    pub variance: f64,
+}
+
+/// Looks for anomalies in the latest ANOMALY_WINDOW_IN_BLOCKS blocks and records them in
+/// `anomalies`, where the statistics service serves them to clients and alerting. An anomaly
+/// which overlaps one recorded before extends it instead, so it is reported once.
+pub async fn detect(database_client: &tokio_postgres::Client) -> Result<(), Error> {
+    let expectations = repository::proposal_expectations(database_client, ANOMALY_WINDOW_IN_BLOCKS).await?;
+    let bursts = repository::missed_signature_bursts(database_client,
+                                                     ANOMALY_WINDOW_IN_BLOCKS,
+                                                     MISSED_SIGNATURES_BURST_IN_BLOCKS).await?;
+
+    let anomalies: Vec<Anomaly> = expectations.iter().filter_map(shortfall).chain(bursts).collect();
+    info!(validators = expectations.len(), anomalies = anomalies.len(), "Detected anomalies");
+
+    for anomaly in anomalies {
+        warn!(kind = anomaly.kind.name(),
+              address = %anomaly.address,
+              first_height = %anomaly.first_height,
+              last_height = %anomaly.last_height,
+              score = anomaly.score,
+              "Anomaly");
+        repository::record_anomaly(database_client, &anomaly).await?;
+    }
+
+    Ok(())
+}
+
+/// Proposals are a sum of independent draws per block, so they fall this far short by chance
+/// about once in 30000 validators and windows.
+fn shortfall(expectation: &ProposalExpectation) -> Option<Anomaly> {
+    if expectation.variance <= 0.0 {
+        return None;
+    }
+
+    let deviation = (expectation.expected_proposals - expectation.proposals as f64) / expectation.variance.sqrt();
+    (deviation >= PROPOSAL_SHORTFALL_IN_STANDARD_DEVIATIONS).then(|| Anomaly {
+        kind: AnomalyKind::ProposalShortfall,
+        address: expectation.address.clone(),
+        first_height: expectation.first_height,
+        last_height: expectation.last_height,
+        score: deviation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expectation(proposals: i64) -> ProposalExpectation {
+        // 2000 blocks at a share of 0.05 make 100 expected proposals with a variance of 95.
+        ProposalExpectation {
+            address: "A".repeat(40).parse().unwrap(),
+            first_height: Height::new(1),
+            last_height: Height::new(2000),
+            proposals,
+            expected_proposals: 100.0,
+            variance: 95.0,
+        }
+    }
+
+    #[test]
+    fn only_far_fewer_proposals_than_expected_are_a_shortfall() {
+        assert_eq!(shortfall(&expectation(90)), None);
+        assert_eq!(shortfall(&expectation(150)), None);
+
+        let anomaly = shortfall(&expectation(50)).unwrap();
+        assert_eq!(anomaly.kind, AnomalyKind::ProposalShortfall);
+        assert!((anomaly.score - 50.0 / 95f64.sqrt()).abs() < 1e-9);
+    }
+}
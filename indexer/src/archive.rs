@@ -0,0 +1,31 @@
+use tracing::{debug, info};
+
+use crate::{Error, repository};
+
+pub const ARCHIVE_INTERVAL_IN_SECONDS: u64 = 3600;
+/// Heights whose values go into one chunk of the archive per plugin.
+const ARCHIVE_HEIGHTS_PER_CHUNK: i64 = 10_000;
+/// Chunks written per run at most, so a large backlog is archived over several runs.
+const ARCHIVE_CHUNKS_PER_RUN: usize = 100;
+
+/// Moves the values plugins stored for blocks older than the months from `custom_records` into
+/// compressed chunks of `custom_records_archive`, lowest heights first. Blocks and everything
+/// derived from them stay where they are, and the API reads archived values more slowly.
+pub async fn archive(database_client: &tokio_postgres::Client, after_months: u32) -> Result<(), Error> {
+    let months = i32::try_from(after_months).unwrap_or(i32::MAX);
+    let Some(last_height) = repository::highest_height_older_than(database_client, months).await? else {
+        debug!(after_months, "No blocks old enough to archive");
+        return Ok(());
+    };
+
+    for _ in 0..ARCHIVE_CHUNKS_PER_RUN {
+        let chunks = repository::archive_custom_records(database_client, last_height, ARCHIVE_HEIGHTS_PER_CHUNK)
+            .await?;
+        if chunks == 0 {
+            break;
+        }
+        info!(chunks, %last_height, "Archived plugin values");
+    }
+
+    Ok(())
+}
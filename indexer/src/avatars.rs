@@ -0,0 +1,77 @@
+use reqwest::Url;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{Error, repository};
+use crate::rpc::RpcClient;
+
+const KEYBASE_LOOKUP_URL: &str = "https://keybase.io/_/api/1.0/user/lookup.json";
+/// Avatars are resolved again after a day, since validators change their pictures.
+const AVATAR_MAXIMUM_AGE_IN_HOURS: i32 = 24;
+/// Keybase rate limits lookups, so only some identities are resolved per refresh.
+const AVATARS_PER_REFRESH: i64 = 50;
+
+#[derive(Deserialize, Debug)]
+struct LookupResponse {
+    #[serde(default)]
+    them: Vec<Option<KeybaseUser>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct KeybaseUser {
+    pictures: Option<Pictures>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Pictures {
+    primary: Option<Picture>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Picture {
+    url: String,
+}
+
+/// Resolves the Keybase identities in validator descriptions to avatar URLs and caches them
+/// in `validators`, starting with identities never resolved and then the oldest ones.
+/// An identity without a Keybase picture is cached as having no avatar, while one whose lookup
+/// fails is only logged and retried on the next refresh.
+pub async fn refresh(rpc_client: &RpcClient, database_client: &tokio_postgres::Client) -> Result<(), Error> {
+    let identities = repository::identities_to_resolve(database_client,
+                                                       AVATAR_MAXIMUM_AGE_IN_HOURS,
+                                                       AVATARS_PER_REFRESH).await?;
+    if !identities.is_empty() {
+        info!(identities = identities.len(), "Resolving avatars");
+    }
+
+    for identity in identities {
+        match request_avatar_url(rpc_client, &identity).await {
+            Ok(avatar_url) => repository::update_avatar(database_client, &identity, avatar_url.as_deref()).await?,
+            Err(e) => warn!(identity, error = ?e, "Could not resolve avatar"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn request_avatar_url(rpc_client: &RpcClient, identity: &str) -> Result<Option<String>, Error> {
+    let url = Url::parse_with_params(KEYBASE_LOOKUP_URL, &[("key_suffix", identity), ("fields", "pictures")])
+        .map_err(|e| Error::CouldNotBuildHttpRequest {
+            url: KEYBASE_LOOKUP_URL.to_string(),
+            source: Box::new(e),
+        })?;
+
+    let response: LookupResponse = rpc_client.request(url.to_string())
+        .await?
+        .error_for_status()
+        .map_err(|e| Error::CouldNotGetResponseFromServer { url: url.to_string(), source: Box::new(e) })?
+        .json()
+        .await
+        .map_err(Error::CouldNotParseResponseForKeybase)?;
+
+    Ok(response.them
+        .into_iter()
+        .flatten()
+        .find_map(|u| u.pictures?.primary)
+        .map(|p| p.url))
+}
@@ -0,0 +1,51 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::time;
+use tracing::{error, info, warn};
+
+const INITIAL_DELAY_IN_MILLISECONDS: u64 = 500;
+const MAXIMUM_DELAY_IN_MILLISECONDS: u64 = 30_000;
+/// Failed attempts are logged at least this often, even when they keep failing quietly.
+const LOG_INTERVAL_IN_ATTEMPTS: u32 = 10;
+
+/// Retries an operation on a dependency which is not ready yet (database or RPC at startup),
+/// doubling the delay after every failed attempt up to MAXIMUM_DELAY_IN_MILLISECONDS.
+/// Every delay is jittered between half and the full delay, so several indexers restarted
+/// together do not hit the dependency in lockstep. Without `max_attempts` it retries forever.
+pub async fn retry<T, E, F, Fut>(dependency: &str, max_attempts: Option<u32>, mut operation: F) -> Result<T, E>
+    where E: Debug,
+          F: FnMut() -> Fut,
+          Fut: Future<Output=Result<T, E>> {
+    let mut delay = INITIAL_DELAY_IN_MILLISECONDS;
+    let mut attempt: u32 = 1;
+
+    loop {
+        let error = match operation().await {
+            Ok(value) => {
+                if attempt > 1 {
+                    info!(dependency, attempt, "Dependency is available");
+                }
+                return Ok(value);
+            }
+            Err(e) => e,
+        };
+
+        if max_attempts.is_some_and(|m| attempt >= m) {
+            error!(dependency, attempt, ?error, "Dependency is unavailable, giving up");
+            return Err(error);
+        }
+
+        let jittered_delay = rand::thread_rng().gen_range(delay / 2..=delay);
+        if attempt <= 3 || attempt.is_multiple_of(LOG_INTERVAL_IN_ATTEMPTS) {
+            warn!(dependency, attempt, retry_in_milliseconds = jittered_delay, ?error, "Dependency is unavailable");
+        }
+
+        time::sleep(Duration::from_millis(jittered_delay)).await;
+
+        delay = (delay * 2).min(MAXIMUM_DELAY_IN_MILLISECONDS);
+        attempt += 1;
+    }
+}
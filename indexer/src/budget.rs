@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{NaiveDate, Utc};
+
+/// Share of a daily quota after which an endpoint is only used when no other one is available.
+const NEARLY_EXHAUSTED_RATIO: f64 = 0.9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Allowance {
+    Available,
+    NearlyExhausted,
+    Exhausted,
+}
+
+/// Counts requests per RPC endpoint per UTC day against the daily quotas of metered providers.
+/// Endpoints without a quota are never limited.
+#[derive(Debug, Default)]
+pub struct Budget {
+    quotas: HashMap<String, u64>,
+    usage: Mutex<Usage>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    day: NaiveDate,
+    requests: HashMap<String, u64>,
+}
+
+impl Budget {
+    pub fn new(quotas: HashMap<String, u64>) -> Self {
+        Budget { quotas, usage: Mutex::default() }
+    }
+
+    pub fn allowance(&self, endpoint: &str) -> Allowance {
+        self.allowance_on(Utc::now().date_naive(), endpoint)
+    }
+
+    pub fn spend(&self, endpoint: &str) {
+        self.spend_on(Utc::now().date_naive(), endpoint)
+    }
+
+    fn allowance_on(&self, day: NaiveDate, endpoint: &str) -> Allowance {
+        let Some(&quota) = self.quotas.get(endpoint) else {
+            return Allowance::Available;
+        };
+
+        let spent = self.usage_on(day, |u| u.requests.get(endpoint).copied().unwrap_or_default());
+
+        if spent >= quota {
+            Allowance::Exhausted
+        } else if spent as f64 >= quota as f64 * NEARLY_EXHAUSTED_RATIO {
+            Allowance::NearlyExhausted
+        } else {
+            Allowance::Available
+        }
+    }
+
+    fn spend_on(&self, day: NaiveDate, endpoint: &str) {
+        if self.quotas.contains_key(endpoint) {
+            self.usage_on(day, |u| *u.requests.entry(endpoint.to_string()).or_default() += 1);
+        }
+    }
+
+    /// Usage starts from zero every day.
+    fn usage_on<T>(&self, day: NaiveDate, f: impl FnOnce(&mut Usage) -> T) -> T {
+        let mut usage = self.usage.lock().unwrap();
+        if usage.day != day {
+            *usage = Usage { day, requests: HashMap::new() };
+        }
+        f(&mut usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const ENDPOINT: &str = "https://metered.example";
+
+    proptest! {
+        #[test]
+        fn requests_within_allowance_never_exceed_the_quota(
+            quota in 0..200u64,
+            days in prop::collection::vec(0..3u32, 0..1000),
+        ) {
+            let budget = Budget::new(HashMap::from([(ENDPOINT.to_string(), quota)]));
+            let mut spent: HashMap<u32, u64> = HashMap::new();
+
+            // Days only move forward, as the clock does.
+            let mut days = days;
+            days.sort();
+
+            for day in days {
+                let date = NaiveDate::from_yo_opt(2023, day + 1).unwrap();
+                if budget.allowance_on(date, ENDPOINT) != Allowance::Exhausted {
+                    budget.spend_on(date, ENDPOINT);
+                    *spent.entry(day).or_default() += 1;
+                }
+                prop_assert!(spent.get(&day).copied().unwrap_or_default() <= quota);
+            }
+        }
+
+        #[test]
+        fn endpoints_without_quota_are_always_available(requests in 0..1000usize) {
+            let budget = Budget::new(HashMap::from([(ENDPOINT.to_string(), 1)]));
+            let day = NaiveDate::from_yo_opt(2023, 1).unwrap();
+
+            for _ in 0..requests {
+                budget.spend_on(day, "https://free.example");
+            }
+            prop_assert_eq!(budget.allowance_on(day, "https://free.example"), Allowance::Available);
+        }
+    }
+}
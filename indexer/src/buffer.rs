@@ -0,0 +1,47 @@
+use std::collections::VecDeque;
+
+use model::Height;
+use osmosis_core::Block;
+use tracing::info;
+
+use crate::Error;
+use crate::health::Progress;
+use crate::store::Store;
+
+const INSERT_BUFFER_MAXIMUM_BATCHES: usize = 100;
+
+/// Batches fetched while the database is unavailable. Fetching goes on until the buffer holds
+/// INSERT_BUFFER_MAXIMUM_BATCHES, and the batches are replayed in order once the database is back.
+#[derive(Default)]
+pub struct InsertBuffer {
+    batches: VecDeque<Vec<Block>>,
+}
+
+impl InsertBuffer {
+    pub fn push(&mut self, batch: Vec<Block>) -> Result<(), Error> {
+        if self.batches.len() >= INSERT_BUFFER_MAXIMUM_BATCHES {
+            return Err(Error::InsertBufferIsFull);
+        }
+
+        self.batches.push_back(batch);
+        Ok(())
+    }
+
+    pub fn highest_height(&self) -> Option<Height> {
+        self.batches.iter().flatten().map(|p| p.height).max()
+    }
+
+    /// Inserts buffered batches in order, stopping at the first batch the database is
+    /// still unavailable for. Heights which got indexed meanwhile are skipped.
+    pub async fn replay(&mut self, store: &impl Store, progress: &Progress) -> Result<(), Error> {
+        while let Some(batch) = self.batches.front() {
+            let written = store.insert_batch(batch).await?;
+            progress.record_written(batch.len(), written);
+            info!(rows = batch.len(), written, "Replayed buffered batch");
+
+            self.batches.pop_front();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use std::{env, fs};
+use std::path::PathBuf;
+
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use osmosis_core::BoxError;
+
+use crate::Error;
+
+#[derive(Debug, PartialEq)]
+enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// Records RPC responses to a directory or replays them from it, so parser bugs observed in
+/// production can be reproduced offline. Every request is kept in one file named after its path
+/// and query; a later response to the same request replaces the earlier one.
+#[derive(Debug)]
+pub struct Cassette {
+    mode: CassetteMode,
+    directory: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Recording {
+    request: String,
+    status: u16,
+    body: String,
+}
+
+impl Cassette {
+    /// Reads the directory from `RPC_CASSETTE_REPLAY` or, if not set, `RPC_CASSETTE_RECORD`.
+    pub fn from_env() -> Option<Self> {
+        let (mode, directory) = match (env::var("RPC_CASSETTE_REPLAY"), env::var("RPC_CASSETTE_RECORD")) {
+            (Ok(d), _) => (CassetteMode::Replay, d),
+            (_, Ok(d)) => (CassetteMode::Record, d),
+            _ => return None,
+        };
+
+        info!(?mode, %directory, "RPC cassette");
+        Some(Cassette { mode, directory: directory.into() })
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.mode == CassetteMode::Replay
+    }
+
+    /// Stores the response and hands back an equal one, since reading the body consumes it.
+    /// A response which cannot be stored is only logged.
+    pub async fn record(&self, request: &str, response: Response) -> Result<Response, Error> {
+        let status = response.status();
+        let body = response.bytes()
+            .await
+            .map_err(|e| Error::CouldNotGetResponseFromServer { url: request.to_string(), source: Box::new(e) })?;
+
+        let recording = Recording {
+            request: request.to_string(),
+            status: status.as_u16(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        };
+
+        let written = fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(self.path(request), serde_json::to_vec(&recording)?));
+        if let Err(e) = written {
+            warn!(request, error = %e, "Could not record RPC response");
+        }
+
+        Ok(to_response(status, body.to_vec()))
+    }
+
+    pub fn replay(&self, request: &str) -> Result<Response, Error> {
+        let recording: Recording = fs::read(self.path(request))
+            .map_err(BoxError::from)
+            .and_then(|c| Ok(serde_json::from_slice(&c)?))
+            .map_err(|e| {
+                warn!(request, error = %e, "Cassette has no response");
+                Error::CouldNotGetResponseFromServer { url: request.to_string(), source: e }
+            })?;
+
+        let status = StatusCode::from_u16(recording.status)
+            .map_err(|e| Error::CouldNotGetResponseFromServer { url: request.to_string(), source: Box::new(e) })?;
+
+        Ok(to_response(status, recording.body.into_bytes()))
+    }
+
+    fn path(&self, request: &str) -> PathBuf {
+        let name: String = request
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+
+        self.directory.join(format!("{name}.json"))
+    }
+}
+
+fn to_response(status: StatusCode, body: Vec<u8>) -> Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    Response::from(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_recorded_responses() {
+        let directory = env::temp_dir().join(format!("cassette-{}", std::process::id()));
+        let recorder = Cassette { mode: CassetteMode::Record, directory: directory.clone() };
+        let player = Cassette { mode: CassetteMode::Replay, directory: directory.clone() };
+
+        let recorded = recorder.record("/block?height=7", to_response(StatusCode::OK, b"{\"a\":1}".to_vec()))
+            .await
+            .unwrap();
+        let replayed = player.replay("/block?height=7").unwrap();
+
+        assert_eq!(recorded.text().await.unwrap(), "{\"a\":1}");
+        assert_eq!(replayed.status(), StatusCode::OK);
+        assert_eq!(replayed.text().await.unwrap(), "{\"a\":1}");
+        assert!(player.replay("/block?height=8").is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
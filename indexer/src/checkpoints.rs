@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use model::Height;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{Error, ranges, repository, request_proposers};
+use crate::rpc::RpcClient;
+
+const CHECKPOINT_HEIGHTS_PER_INTERVAL: u64 = 500;
+
+/// Range of heights in the `[checkpoints.<name>]` section of the config, both ends inclusive.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CheckpointRange {
+    pub first_height: Height,
+    pub last_height: Height,
+}
+
+/// Named range of heights indexed independently of live indexing, such as an older range to
+/// backfill or a range to re-index. `cursor` is the next height to index; everything in the
+/// range below it is indexed.
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub name: String,
+    pub first_height: Height,
+    pub last_height: Height,
+    pub cursor: Height,
+}
+
+impl Checkpoint {
+    pub fn is_complete(&self) -> bool {
+        self.cursor > self.last_height
+    }
+
+    /// Heights to index next, up to `limit` of them, as a half-open range like `Height::range`.
+    pub fn next_heights(&self, limit: u64) -> (Height, Height) {
+        let end = self.last_height.saturating_add(1);
+        (self.cursor.min(end), end.min(self.cursor.saturating_add(limit)))
+    }
+}
+
+/// Records the configured ranges in `indexer_checkpoints`. A range which is new starts at its
+/// first height, while a range which was recorded before keeps its cursor, so indexing resumes
+/// where it stopped after a restart.
+pub async fn define(database_client: &tokio_postgres::Client, ranges: &HashMap<String, CheckpointRange>)
+                    -> Result<(), Error> {
+    for (name, range) in ranges {
+        repository::define_checkpoint(database_client, name, range).await?;
+    }
+
+    Ok(())
+}
+
+/// Indexes up to CHECKPOINT_HEIGHTS_PER_INTERVAL heights of each incomplete checkpoint with one of
+/// the names and moves its cursor after every batch. Heights which are indexed already are
+/// overwritten, so ranges may overlap each other and what live indexing has done.
+pub async fn advance(rpc_client: &RpcClient, database_client: &tokio_postgres::Client, names: &[String])
+                     -> Result<(), Error> {
+    for checkpoint in repository::incomplete_checkpoints(database_client, names).await? {
+        advance_checkpoint(rpc_client, database_client, checkpoint).await?;
+    }
+
+    Ok(())
+}
+
+/// Indexes the checkpoint without pausing between intervals until it is complete.
+pub async fn complete(rpc_client: &RpcClient, database_client: &tokio_postgres::Client, name: &str)
+                      -> Result<(), Error> {
+    let names = [name.to_string()];
+
+    while let Some(checkpoint) = repository::incomplete_checkpoints(database_client, &names).await?.pop() {
+        advance_checkpoint(rpc_client, database_client, checkpoint).await?;
+    }
+
+    Ok(())
+}
+
+/// A historical backfill is named after its range, so running the same range again resumes it.
+pub fn historical_backfill_name(range: &CheckpointRange) -> String {
+    format!("backfill-{}-{}", range.first_height, range.last_height)
+}
+
+async fn advance_checkpoint(rpc_client: &RpcClient,
+                            database_client: &tokio_postgres::Client,
+                            checkpoint: Checkpoint)
+                            -> Result<(), Error> {
+    let (first_height, last_height) = checkpoint.next_heights(CHECKPOINT_HEIGHTS_PER_INTERVAL);
+    info!(name = checkpoint.name, cursor = %checkpoint.cursor, last_height = %checkpoint.last_height,
+          "Indexing checkpoint");
+
+    for (first_height_to_index, last_height_to_index) in
+        ranges::ascending(first_height, last_height, rpc_client.heights_per_batch()) {
+        let proposers_to_height =
+            request_proposers(rpc_client, Height::range(first_height_to_index, last_height_to_index)).await?;
+        osmosis_core::repository::upsert_batch(database_client, &proposers_to_height).await?;
+        repository::move_checkpoint(database_client, &checkpoint.name, last_height_to_index).await?;
+    }
+
+    let checkpoint = Checkpoint { cursor: last_height, ..checkpoint };
+    if checkpoint.is_complete() {
+        info!(name = checkpoint.name, "Checkpoint is complete");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn advancing_covers_the_range_once(
+            first_height in 0..1_000_000u64,
+            length in 0..5000u64,
+            limit in 1..1000u64,
+        ) {
+            let mut checkpoint = Checkpoint {
+                name: "backfill".to_string(),
+                first_height: Height::new(first_height),
+                last_height: Height::new(first_height + length),
+                cursor: Height::new(first_height),
+            };
+            let mut indexed = Vec::new();
+
+            while !checkpoint.is_complete() {
+                let (first, last) = checkpoint.next_heights(limit);
+                prop_assert!(first < last);
+                prop_assert!(last.value() - first.value() <= limit);
+                indexed.extend(Height::range(first, last).map(Height::value));
+                checkpoint.cursor = last;
+            }
+
+            prop_assert_eq!(indexed, (first_height..=first_height + length).collect::<Vec<u64>>());
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::net::SocketAddr;
+
+use clap::{Parser, Subcommand, value_parser};
+use model::Height;
+
+use crate::compare::MAXIMUM_SAMPLED_HEIGHTS;
+use crate::config::HEALTH_ADDRESS_VARIABLE;
+use crate::release::DEFAULT_RELEASES_DIRECTORY;
+
+/// Indexes the blocks of Osmosis and their proposers into Postgres. Without a subcommand it runs
+/// as `run` does.
+#[derive(Parser, Debug)]
+#[command(name = "indexer")]
+pub struct Cli {
+    /// Runs against a locally running osmosisd node and a database published on localhost.
+    #[arg(long, global = true)]
+    pub devnet: bool,
+    /// First height of a historical backfill indexed alongside new blocks, as fast as the RPC
+    /// endpoints allow; `BACKFILL_FROM_HEIGHT` if not given.
+    #[arg(long, global = true, requires = "to_height")]
+    pub from_height: Option<Height>,
+    /// Last height of the historical backfill; `BACKFILL_TO_HEIGHT` if not given.
+    #[arg(long, global = true, requires = "from_height")]
+    pub to_height: Option<Height>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Indexes new blocks and runs the recurring tasks until stopped.
+    Run,
+    /// Indexes the heights of the inclusive range, overwriting what is indexed, and exits.
+    /// Running the same range again resumes it.
+    Backfill {
+        #[arg(long)]
+        from: Height,
+        #[arg(long)]
+        to: Height,
+    },
+    /// Reports the heights missing below the highest indexed one and fails if there are any.
+    VerifyGaps {
+        /// Lowest height expected to be indexed; the lowest indexed height if not given.
+        #[arg(long)]
+        from: Option<Height>,
+    },
+    /// Fetches the block at the height again and overwrites what is indexed for it.
+    Reindex {
+        #[arg(long)]
+        height: Height,
+    },
+    /// Applies the migrations of the database and of the enabled plugins and exits.
+    Migrate,
+    /// Writes a release of the dataset to the directory and exits.
+    PublishDataset {
+        #[arg(default_value = DEFAULT_RELEASES_DIRECTORY)]
+        directory: String,
+    },
+    /// Compares two deployments through their statistics services, how far apart they are and
+    /// the blocks they indexed at sampled heights, and fails if the blocks differ.
+    Compare {
+        /// Base URL of the statistics service of one deployment, e.g. `https://eu.example.com`.
+        left_url: String,
+        /// Base URL of the statistics service of the other deployment.
+        right_url: String,
+        /// Heights sampled from the range both deployments indexed.
+        #[arg(long, default_value_t = 100, value_parser = value_parser!(u64).range(1..=MAXIMUM_SAMPLED_HEIGHTS))]
+        samples: u64,
+        /// Also fails if the highest indexed heights are further apart.
+        #[arg(long)]
+        maximum_height_difference: Option<u64>,
+    },
+    /// Asks the health endpoint of the indexer running alongside whether it is healthy, for
+    /// Docker and Kubernetes health checks, and fails if it is not.
+    Healthcheck {
+        /// Address of the health endpoint; `INDEXER_HEALTH_ADDRESS` if not given.
+        #[arg(long, env = HEALTH_ADDRESS_VARIABLE)]
+        address: SocketAddr,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommands_take_their_arguments() {
+        let cli = Cli::try_parse_from(["indexer", "backfill", "--from", "10", "--to", "20"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Backfill { from: Height::new(10), to: Height::new(20) }));
+
+        let cli = Cli::try_parse_from(["indexer", "publish-dataset"]).unwrap();
+        let directory = DEFAULT_RELEASES_DIRECTORY.to_string();
+        assert_eq!(cli.command, Some(Command::PublishDataset { directory }));
+
+        assert!(Cli::try_parse_from(["indexer", "reindex"]).is_err());
+        let compare = ["indexer", "compare", "http://a", "http://b", "--samples", "1001"];
+        assert!(Cli::try_parse_from(compare).is_err());
+    }
+
+    #[test]
+    fn historical_backfill_needs_both_heights() {
+        let cli = Cli::try_parse_from(["indexer", "--devnet", "--from-height", "1", "--to-height", "2"]).unwrap();
+        assert!(cli.devnet);
+        assert_eq!(cli.command, None);
+        assert_eq!((cli.from_height, cli.to_height), (Some(Height::new(1)), Some(Height::new(2))));
+
+        assert!(Cli::try_parse_from(["indexer", "--from-height", "1"]).is_err());
+    }
+}
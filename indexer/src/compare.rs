@@ -0,0 +1,140 @@
+use std::collections::BTreeSet;
+
+use model::Height;
+use rand::Rng;
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::Error;
+
+/// Most heights the statistics service looks up in one request to `/blocks/proposers`.
+pub const MAXIMUM_SAMPLED_HEIGHTS: u64 = 1000;
+
+#[derive(Deserialize, Debug)]
+struct Status {
+    lowest_indexed_height: Option<Height>,
+    indexed_height: Option<Height>,
+    lag_in_seconds: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ProposersResponse {
+    proposers: Vec<Value>,
+}
+
+/// Compares two deployments through the statistics services in front of them: how far one is
+/// behind the other, and whether they indexed the same blocks at heights sampled from the range
+/// both indexed. Fails if any sampled block differs, or if the deployments are more than
+/// `maximum_height_difference` heights apart, so it can gate failing traffic over.
+pub async fn compare(http_client: &Client,
+                     left_url: &str,
+                     right_url: &str,
+                     samples: u64,
+                     maximum_height_difference: Option<u64>)
+                     -> Result<(), Error> {
+    let left: Status = request(http_client.get(format!("{left_url}/status")), left_url).await?;
+    let right: Status = request(http_client.get(format!("{right_url}/status")), right_url).await?;
+
+    let (Some(left_height), Some(right_height)) = (left.indexed_height, right.indexed_height) else {
+        return Err(Error::NothingIndexed);
+    };
+    let height_difference = left_height.value().abs_diff(right_height.value());
+    info!(left = left_url,
+          %left_height,
+          left_lag_in_seconds = left.lag_in_seconds,
+          right = right_url,
+          %right_height,
+          right_lag_in_seconds = right.lag_in_seconds,
+          height_difference,
+          "Compared status");
+
+    let first_height = left.lowest_indexed_height.max(right.lowest_indexed_height).unwrap_or_default();
+    let last_height = left_height.min(right_height);
+    let heights = sample_heights(&mut rand::thread_rng(), first_height, last_height, samples);
+    let body = json!({ "heights": heights });
+    let left_blocks: ProposersResponse =
+        request(http_client.post(format!("{left_url}/blocks/proposers")).json(&body), left_url).await?;
+    let right_blocks: ProposersResponse =
+        request(http_client.post(format!("{right_url}/blocks/proposers")).json(&body), right_url).await?;
+
+    let divergent = divergent_heights(&heights, &left_blocks.proposers, &right_blocks.proposers);
+    for height in &divergent {
+        warn!(%height, "Deployments indexed different blocks");
+    }
+    info!(%first_height, %last_height, sampled = heights.len(), divergent = divergent.len(), "Compared blocks");
+
+    if !divergent.is_empty() {
+        return Err(Error::DeploymentsDiverge { heights: divergent.len(), sampled: heights.len() });
+    }
+    match maximum_height_difference {
+        Some(maximum) if height_difference > maximum =>
+            Err(Error::DeploymentsAreApart { heights: height_difference }),
+        _ => Ok(()),
+    }
+}
+
+async fn request<T: DeserializeOwned>(request: reqwest::RequestBuilder, url: &str) -> Result<T, Error> {
+    request.send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| Error::CouldNotGetResponseFromServer { url: url.to_string(), source: Box::new(e) })?
+        .json()
+        .await
+        .map_err(|e| Error::CouldNotParseResponseOfDeployment { url: url.to_string(), source: e })
+}
+
+/// Distinct heights of the inclusive range in ascending order, every height if there are no
+/// more than `samples`.
+fn sample_heights(rng: &mut impl Rng, first_height: Height, last_height: Height, samples: u64) -> Vec<Height> {
+    if first_height.count_to(last_height) <= samples {
+        return Height::range(first_height, last_height.saturating_add(1)).collect();
+    }
+
+    let mut heights = BTreeSet::new();
+    while (heights.len() as u64) < samples {
+        heights.insert(Height::new(rng.gen_range(first_height.value()..=last_height.value())));
+    }
+    heights.into_iter().collect()
+}
+
+/// Blocks are compared as the services return them, so any field they disagree on counts.
+fn divergent_heights(heights: &[Height], left: &[Value], right: &[Value]) -> Vec<Height> {
+    heights.iter()
+        .enumerate()
+        .filter(|(i, _)| left.get(*i) != right.get(*i))
+        .map(|(_, height)| *height)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn samples_are_distinct_heights_of_the_range() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let heights = sample_heights(&mut rng, Height::new(100), Height::new(199), 10);
+        assert_eq!(heights.len(), 10);
+        assert!(heights.windows(2).all(|w| w[0] < w[1]));
+        assert!(heights.iter().all(|h| (100..=199).contains(&h.value())));
+
+        let heights = sample_heights(&mut rng, Height::new(1), Height::new(3), 10);
+        assert_eq!(heights, vec![Height::new(1), Height::new(2), Height::new(3)]);
+    }
+
+    #[test]
+    fn blocks_differing_in_any_field_diverge() {
+        let heights = [Height::new(1), Height::new(2), Height::new(3)];
+        let left = [json!({"height": 1, "hash": "AA"}), json!({"height": 2, "hash": "BB"}), json!({"height": 3})];
+        let right = [json!({"height": 1, "hash": "AA"}), json!({"height": 2, "hash": "CC"})];
+
+        assert_eq!(divergent_heights(&heights, &left, &right), vec![Height::new(2), Height::new(3)]);
+    }
+}
@@ -0,0 +1,279 @@
+use std::{env, fs};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use model::{ChainId, Height};
+use osmosis_core::profiles::Profile;
+use serde::Deserialize;
+
+use crate::Error;
+use crate::checkpoints::CheckpointRange;
+use crate::cli::Cli;
+use crate::shards::ShardsConfig;
+
+const DEFAULT_RPC_URL: &str = "https://rpc.osmosis.zone";
+const DEFAULT_LCD_URL: &str = "https://lcd.osmosis.zone";
+const DEFAULT_DATABASE_HOST: &str = "db";
+const DEVNET_RPC_URL: &str = "http://localhost:26657";
+const DEVNET_LCD_URL: &str = "http://localhost:1317";
+const DEVNET_DATABASE_HOST: &str = "localhost";
+/// Read by `indexer healthcheck` as well, to find the endpoint.
+pub const HEALTH_ADDRESS_VARIABLE: &str = "INDEXER_HEALTH_ADDRESS";
+
+/// Where the indexer starts when nothing is indexed yet.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StartMode {
+    /// Index the full history from the lowest height of the chain.
+    #[default]
+    Lowest,
+    /// Start at the current tip and record it as `start_height` in `indexer_state`.
+    Latest,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackfillMode {
+    /// Index from the lowest indexed height down to the lowest height of the chain.
+    Reverse,
+}
+
+/// Indexer configuration read from the TOML file named by `INDEXER_CONFIG`, if any,
+/// with environment variables taking precedence over the file.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Defaults for a local chain, where the lowest height is the earliest one the node has.
+    pub devnet: bool,
+    pub database_host: String,
+    /// RPC endpoints tried in order until one of them answers.
+    pub rpc_urls: Vec<String>,
+    /// Requests per UTC day allowed by metered RPC endpoints, by URL.
+    pub rpc_quotas: HashMap<String, u64>,
+    /// Requests per second made to the RPC endpoints at most.
+    pub rpc_requests_per_second: Option<f64>,
+    /// Most RPC requests in parallel, which the indexer works up to while responses are fast.
+    pub rpc_maximum_concurrency: Option<u64>,
+    /// Seconds between rounds of indexing and the other recurring work, 30 if not set.
+    pub interval_in_seconds: Option<u64>,
+    /// Address the admin endpoint listens on, which changes settings while the indexer runs;
+    /// it is not served if not set.
+    pub admin_address: Option<SocketAddr>,
+    /// Address the health endpoint listens on, which `indexer healthcheck` queries; it is not
+    /// served if not set.
+    pub health_address: Option<SocketAddr>,
+    /// Seconds live indexing may go without progress before the indexer exits with
+    /// `EXIT_STALLED`; it never does if not set.
+    pub watchdog_timeout_in_seconds: Option<u64>,
+    /// Blocks requested per JSON-RPC batch; each block is requested on its own if not set.
+    pub rpc_batch_size: Option<u64>,
+    /// LCD (REST) endpoint from which validator monikers are resolved.
+    pub lcd_url: String,
+    /// Lowest height of the chain to index; OSMOSIS_LOWEST_HEIGHT on mainnet if not set.
+    pub lowest_height: Option<Height>,
+    pub start: StartMode,
+    pub backfill: Option<BackfillMode>,
+    pub genesis_file: Option<String>,
+    /// URL of a JSON list of labelled addresses maintained by the community, refreshed daily
+    /// into `address_labels`; no labels are fetched if not set.
+    pub labels_url: Option<String>,
+    /// Attempts to reach the database and RPC at startup; unlimited if not set.
+    pub startup_attempts: Option<u32>,
+    /// Batches of at least this many rows are written with COPY instead of INSERT.
+    pub copy_threshold: Option<usize>,
+    /// Stores duration and size of every block fetch in `fetch_telemetry`.
+    pub fetch_telemetry: bool,
+    /// Name of the publication for logical replication of the dataset; not created if not set.
+    pub cdc_publication: Option<String>,
+    /// Values plugins stored for blocks older than this many months are moved to the archive;
+    /// nothing is archived if not set.
+    pub archive_after_months: Option<u32>,
+    /// Named ranges of heights indexed alongside live indexing, each resuming from its own cursor.
+    pub checkpoints: HashMap<String, CheckpointRange>,
+    /// Range given with `--from-height` and `--to-height`, which is indexed as fast as the RPC
+    /// rate limits allow while live indexing goes on.
+    #[serde(skip)]
+    pub historical_backfill: Option<CheckpointRange>,
+    /// Range split into shards which workers of any number of replicas index side by side.
+    pub shards: Option<ShardsConfig>,
+    /// Parser profiles by chain id for networks whose RPC payloads differ from mainnet.
+    pub profiles: HashMap<ChainId, Profile>,
+}
+
+impl Config {
+    pub fn load(cli: &Cli) -> Result<Self, Error> {
+        let mut config: Config = match env::var("INDEXER_CONFIG") {
+            Ok(path) => {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| Error::CouldNotReadConfig { path: path.clone(), source: Box::new(e) })?;
+                toml::from_str(&content).map_err(|e| Error::CouldNotReadConfig { path, source: Box::new(e) })?
+            }
+            Err(_) => Config::default(),
+        };
+
+        if let Ok(urls) = env::var("RPC_URLS") {
+            config.rpc_urls = urls.split(',')
+                .map(|u| u.trim().to_string())
+                .filter(|u| !u.is_empty())
+                .collect();
+        }
+        if cli.devnet {
+            config.devnet = true;
+        }
+        if let Ok(database_host) = env::var("DATABASE_HOST") {
+            config.database_host = database_host;
+        }
+        if let Ok(rate) = env::var("RPC_REQUESTS_PER_SECOND") {
+            config.rpc_requests_per_second = Some(parse_setting("RPC_REQUESTS_PER_SECOND", &rate)?);
+        }
+        if let Ok(concurrency) = env::var("RPC_MAXIMUM_CONCURRENCY") {
+            config.rpc_maximum_concurrency = Some(parse_setting("RPC_MAXIMUM_CONCURRENCY", &concurrency)?);
+        }
+        if let Ok(interval) = env::var("INDEXER_INTERVAL_IN_SECONDS") {
+            config.interval_in_seconds = Some(parse_setting("INDEXER_INTERVAL_IN_SECONDS", &interval)?);
+        }
+        if let Ok(address) = env::var("INDEXER_ADMIN_ADDRESS") {
+            config.admin_address = Some(parse_setting("INDEXER_ADMIN_ADDRESS", &address)?);
+        }
+        if let Ok(address) = env::var(HEALTH_ADDRESS_VARIABLE) {
+            config.health_address = Some(parse_setting(HEALTH_ADDRESS_VARIABLE, &address)?);
+        }
+        if let Ok(timeout) = env::var("INDEXER_WATCHDOG_TIMEOUT_IN_SECONDS") {
+            config.watchdog_timeout_in_seconds =
+                Some(parse_setting("INDEXER_WATCHDOG_TIMEOUT_IN_SECONDS", &timeout)?);
+        }
+        if let Ok(batch_size) = env::var("RPC_BATCH_SIZE") {
+            config.rpc_batch_size = Some(parse_setting("RPC_BATCH_SIZE", &batch_size)?);
+        }
+        if let Ok(lcd_url) = env::var("LCD_URL") {
+            config.lcd_url = lcd_url;
+        }
+        if let Ok(fetch_telemetry) = env::var("FETCH_TELEMETRY") {
+            config.fetch_telemetry = parse_setting("FETCH_TELEMETRY", &fetch_telemetry)?;
+        }
+        if let Ok(publication) = env::var("CDC_PUBLICATION") {
+            config.cdc_publication = Some(publication);
+        }
+        if let Ok(months) = env::var("ARCHIVE_AFTER_MONTHS") {
+            config.archive_after_months = Some(parse_setting("ARCHIVE_AFTER_MONTHS", &months)?);
+        }
+        if let Ok(start) = env::var("INDEXER_START") {
+            config.start = parse_mode("INDEXER_START", &start)?;
+        }
+        if let Ok(backfill) = env::var("INDEXER_BACKFILL") {
+            config.backfill = Some(parse_mode("INDEXER_BACKFILL", &backfill)?);
+        }
+        if let Ok(lowest_height) = env::var("LOWEST_HEIGHT") {
+            config.lowest_height = Some(parse_setting("LOWEST_HEIGHT", &lowest_height)?);
+        }
+        let from_height = match (cli.from_height, env::var("BACKFILL_FROM_HEIGHT")) {
+            (Some(height), _) => Some(height),
+            (None, Ok(height)) => Some(parse_setting("BACKFILL_FROM_HEIGHT", &height)?),
+            (None, Err(_)) => None,
+        };
+        let to_height = match (cli.to_height, env::var("BACKFILL_TO_HEIGHT")) {
+            (Some(height), _) => Some(height),
+            (None, Ok(height)) => Some(parse_setting("BACKFILL_TO_HEIGHT", &height)?),
+            (None, Err(_)) => None,
+        };
+        config.historical_backfill = match (from_height, to_height) {
+            (Some(first_height), Some(last_height)) => Some(CheckpointRange { first_height, last_height }),
+            (None, None) => None,
+            (Some(_), None) => return Err(Error::CouldNotParseConfig("BACKFILL_TO_HEIGHT")),
+            (None, Some(_)) => return Err(Error::CouldNotParseConfig("BACKFILL_FROM_HEIGHT")),
+        };
+        if let Ok(genesis_file) = env::var("GENESIS_FILE") {
+            config.genesis_file = Some(genesis_file);
+        }
+        if let Ok(labels_url) = env::var("LABELS_URL") {
+            config.labels_url = Some(labels_url);
+        }
+        if let Ok(attempts) = env::var("STARTUP_ATTEMPTS") {
+            config.startup_attempts = Some(parse_setting("STARTUP_ATTEMPTS", &attempts)?);
+        }
+        if let Ok(workers) = env::var("SHARD_WORKERS") {
+            let workers = parse_setting("SHARD_WORKERS", &workers)?;
+            config.shards = config.shards.map(|s| ShardsConfig { workers: Some(workers), ..s });
+        }
+        if let Ok(threshold) = env::var("COPY_THRESHOLD") {
+            config.copy_threshold = Some(parse_setting("COPY_THRESHOLD", &threshold)?);
+        }
+
+        let (rpc_url, lcd_url, database_host) = if config.devnet {
+            (DEVNET_RPC_URL, DEVNET_LCD_URL, DEVNET_DATABASE_HOST)
+        } else {
+            (DEFAULT_RPC_URL, DEFAULT_LCD_URL, DEFAULT_DATABASE_HOST)
+        };
+
+        if config.rpc_urls.is_empty() {
+            config.rpc_urls.push(rpc_url.to_string());
+        }
+        for url in config.rpc_urls.iter_mut() {
+            *url = url.trim_end_matches('/').to_string();
+        }
+        config.rpc_quotas = config.rpc_quotas
+            .into_iter()
+            .map(|(u, q)| (u.trim_end_matches('/').to_string(), q))
+            .collect();
+        if config.lcd_url.is_empty() {
+            config.lcd_url = lcd_url.to_string();
+        }
+        if config.database_host.is_empty() {
+            config.database_host = database_host.to_string();
+        }
+        config.lcd_url = config.lcd_url.trim_end_matches('/').to_string();
+
+        if config.rpc_requests_per_second.is_some_and(|r| !(r > 0.0 && r.is_finite())) {
+            return Err(Error::CouldNotParseConfig("rpc_requests_per_second"));
+        }
+        if config.rpc_maximum_concurrency == Some(0) {
+            return Err(Error::CouldNotParseConfig("rpc_maximum_concurrency"));
+        }
+        if config.rpc_batch_size == Some(0) {
+            return Err(Error::CouldNotParseConfig("rpc_batch_size"));
+        }
+        if config.interval_in_seconds == Some(0) {
+            return Err(Error::CouldNotParseConfig("interval_in_seconds"));
+        }
+        if config.archive_after_months == Some(0) {
+            return Err(Error::CouldNotParseConfig("archive_after_months"));
+        }
+        if config.watchdog_timeout_in_seconds == Some(0) {
+            return Err(Error::CouldNotParseConfig("watchdog_timeout_in_seconds"));
+        }
+
+        if config.checkpoints.values().any(|r| r.first_height > r.last_height) {
+            return Err(Error::CouldNotParseConfig("checkpoints"));
+        }
+        if config.historical_backfill.is_some_and(|r| r.first_height > r.last_height) {
+            return Err(Error::CouldNotParseConfig("BACKFILL_FROM_HEIGHT"));
+        }
+        if config.shards.is_some_and(|s| s.first_height > s.last_height || s.count == 0 || s.workers == Some(0)) {
+            return Err(Error::CouldNotParseConfig("shards"));
+        }
+
+        if let Some(publication) = &config.cdc_publication {
+            if !is_identifier(publication) {
+                return Err(Error::CouldNotParseConfig("cdc_publication"));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Lowercase SQL identifier which needs no quoting.
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn parse_setting<T: FromStr>(name: &'static str, value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::CouldNotParseConfig(name))
+}
+
+fn parse_mode<'a, T: Deserialize<'a>>(name: &'static str, value: &'a str) -> Result<T, Error> {
+    T::deserialize(serde::de::value::StrDeserializer::<serde::de::value::Error>::new(value))
+        .map_err(|_| Error::CouldNotParseConfig(name))
+}
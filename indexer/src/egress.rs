@@ -0,0 +1,61 @@
+use std::env;
+
+use reqwest::Url;
+use tracing::warn;
+
+use crate::Error;
+
+/// Restricts which hosts and RPC methods the indexer may call. Tendermint RPC methods are
+/// the first path segment of a request URL, e.g. `block` in `/block?height=1`.
+/// Every violation is logged before the request is refused.
+#[derive(Debug, Default)]
+pub struct EgressPolicy {
+    /// If not empty, only these hosts may be called.
+    allowed_hosts: Vec<String>,
+    denied_hosts: Vec<String>,
+    denied_methods: Vec<String>,
+}
+
+impl EgressPolicy {
+    /// Reads comma separated lists from `EGRESS_ALLOWED_HOSTS`, `EGRESS_DENIED_HOSTS` and
+    /// `EGRESS_DENIED_METHODS`. Without them every request is allowed.
+    pub fn from_env() -> Self {
+        EgressPolicy {
+            allowed_hosts: read_list("EGRESS_ALLOWED_HOSTS"),
+            denied_hosts: read_list("EGRESS_DENIED_HOSTS"),
+            denied_methods: read_list("EGRESS_DENIED_METHODS"),
+        }
+    }
+
+    pub fn check(&self, url: &Url) -> Result<(), Error> {
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        let method = url.path_segments().and_then(|mut s| s.next()).unwrap_or_default();
+
+        let violation = if !self.allowed_hosts.is_empty() && !self.allowed_hosts.contains(&host) {
+            Some("host is not allowed")
+        } else if self.denied_hosts.contains(&host) {
+            Some("host is denied")
+        } else if self.denied_methods.iter().any(|m| m == method) {
+            Some("method is denied")
+        } else {
+            None
+        };
+
+        match violation {
+            Some(reason) => {
+                warn!(%url, reason, "Egress violation");
+                Err(Error::EgressDenied { url: url.to_string(), reason })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn read_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|v| v.split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect())
+        .unwrap_or_default()
+}
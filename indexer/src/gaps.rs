@@ -0,0 +1,116 @@
+use model::Height;
+use tracing::info;
+
+use crate::{Error, index_batch, repository};
+use crate::health::Progress;
+use crate::store::{BlockSource, Store};
+
+pub const GAP_SCAN_INTERVAL_IN_SECONDS: u64 = 300;
+pub const GAP_REPAIR_HEIGHTS_PER_SCAN: usize = 500;
+const GAP_REPORT_MAXIMUM_RANGES: usize = 20;
+
+/// A crash in the middle of a cycle can leave holes below the highest indexed height, which
+/// forward indexing never revisits, and so does a height indexed on request ahead of forward
+/// indexing. The scan finds missing heights from `lowest_height` (or the lowest indexed height
+/// if not given) up to the highest indexed height, logs them as a `/gaps` report and re-indexes
+/// up to GAP_REPAIR_HEIGHTS_PER_SCAN of them, lowest first.
+/// Without `lowest_height`, heights below the lowest indexed height are left to the backfill.
+pub async fn repair(source: &impl BlockSource,
+                    store: &impl Store,
+                    progress: &Progress,
+                    lowest_height: Option<Height>)
+                    -> Result<(), Error> {
+    let gaps = store.find_gaps(lowest_height).await?;
+    report(&gaps);
+
+    let heights_to_index = heights_to_repair(&gaps, GAP_REPAIR_HEIGHTS_PER_SCAN);
+
+    for heights in heights_to_index.chunks(source.heights_per_batch() as usize) {
+        index_batch(source, store, progress, heights.iter().copied()).await?;
+    }
+
+    Ok(())
+}
+
+/// Reports the gaps like `repair` does without repairing them, and fails if there are any.
+pub async fn verify(database_client: &tokio_postgres::Client, lowest_height: Option<Height>)
+                    -> Result<(), Error> {
+    let gaps = repository::find_gaps(database_client, lowest_height).await?;
+    report(&gaps);
+
+    if gaps.is_empty() {
+        return Ok(());
+    }
+    Err(Error::IndexHasGaps { missing_heights: count_missing(&gaps), ranges: gaps.len() })
+}
+
+/// Expands inclusive gap ranges into at most `limit` heights, lowest first.
+pub fn heights_to_repair(gaps: &[(Height, Height)], limit: usize) -> Vec<Height> {
+    gaps.iter()
+        .flat_map(|&(first, last)| Height::range(first, last.saturating_add(1)))
+        .take(limit)
+        .collect()
+}
+
+fn report(gaps: &[(Height, Height)]) {
+    info!(count_missing = count_missing(gaps), ranges = gaps.len(), "/gaps");
+
+    for (first, last) in gaps.iter().take(GAP_REPORT_MAXIMUM_RANGES) {
+        info!(%first, %last, "/gaps");
+    }
+    if gaps.len() > GAP_REPORT_MAXIMUM_RANGES {
+        info!(more_ranges = gaps.len() - GAP_REPORT_MAXIMUM_RANGES, "/gaps");
+    }
+}
+
+fn count_missing(gaps: &[(Height, Height)]) -> u64 {
+    gaps.iter().map(|(first, last)| first.count_to(*last)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// What `repository::find_gaps` computes with `lead()` over the indexed heights.
+    fn gaps_between(indexed: &BTreeSet<u64>) -> Vec<(Height, Height)> {
+        let indexed: Vec<u64> = indexed.iter().copied().collect();
+        indexed.windows(2)
+            .filter(|w| w[1] > w[0] + 1)
+            .map(|w| (Height::new(w[0] + 1), Height::new(w[1] - 1)))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn repairing_every_gap_leaves_no_gaps(indexed in prop::collection::btree_set(0..5000u64, 1..200)) {
+            let gaps = gaps_between(&indexed);
+            let repaired = heights_to_repair(&gaps, usize::MAX);
+
+            let lowest = *indexed.first().unwrap();
+            let highest = *indexed.last().unwrap();
+            let mut all: Vec<u64> = indexed.iter().copied().chain(repaired.iter().map(|h| h.value())).collect();
+            all.sort_unstable();
+
+            prop_assert_eq!(all, (lowest..=highest).collect::<Vec<u64>>());
+        }
+
+        #[test]
+        fn repair_takes_the_lowest_missing_heights_up_to_the_limit(
+            indexed in prop::collection::btree_set(0..5000u64, 1..200),
+            limit in 0..1000usize,
+        ) {
+            let gaps = gaps_between(&indexed);
+            let everything = heights_to_repair(&gaps, usize::MAX);
+            let repaired = heights_to_repair(&gaps, limit);
+
+            prop_assert_eq!(repaired.len(), limit.min(everything.len()));
+            prop_assert_eq!(&repaired[..], &everything[..repaired.len()]);
+            prop_assert!(repaired.windows(2).all(|w| w[0] < w[1]));
+            prop_assert!(repaired.iter().all(|h| !indexed.contains(&h.value())));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use model::{ChainId, ConsensusAddress};
+use osmosis_core::BoxError;
+use serde::Deserialize;
+use serde_aux::prelude::*;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{Error, repository};
+use crate::rpc::RpcClient;
+
+/// Validators bond 1 unit of voting power per 10^6 base denom tokens.
+const POWER_REDUCTION: i64 = 1_000_000;
+const CONSENSUS_ADDRESS_LENGTH: usize = 20;
+const CREATE_VALIDATOR_MESSAGE: &str = "/cosmos.staking.v1beta1.MsgCreateValidator";
+
+#[derive(Deserialize, Debug)]
+struct Genesis {
+    chain_id: ChainId,
+    #[serde(default)]
+    validators: Vec<GenesisValidator>,
+    app_state: Option<AppState>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GenesisValidator {
+    address: ConsensusAddress,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    power: i64,
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AppState {
+    genutil: Option<GenUtil>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GenUtil {
+    #[serde(default)]
+    gen_txs: Vec<GenTx>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GenTx {
+    body: GenTxBody,
+}
+
+#[derive(Deserialize, Debug)]
+struct GenTxBody {
+    messages: Vec<GenTxMessage>,
+}
+
+/// Only MsgCreateValidator is of interest, other messages deserialize with empty fields.
+#[derive(Deserialize, Debug)]
+struct GenTxMessage {
+    #[serde(rename = "@type")]
+    message_type: String,
+    description: Option<Description>,
+    validator_address: Option<String>,
+    pubkey: Option<PubKey>,
+    value: Option<Coin>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Description {
+    moniker: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PubKey {
+    key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Coin {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    amount: i64,
+}
+
+pub struct ValidatorMetadata {
+    pub address: ConsensusAddress,
+    pub moniker: String,
+    pub operator_address: Option<String>,
+    pub voting_power: i64,
+}
+
+/// Seeds validator metadata of a chain from its genesis file, given as a path or an http(s) URL,
+/// so blocks at low heights can be attributed before any live snapshot exists.
+/// Validators come from the CometBFT `validators` list and from MsgCreateValidator gentxs, where
+/// the consensus address is derived from the validator's public key.
+/// The import runs only once per chain id.
+pub async fn import(rpc_client: &RpcClient, database_client: &tokio_postgres::Client, source: &str)
+                    -> Result<(), Error> {
+    let genesis = read(rpc_client, source).await?;
+
+    let count_imported = repository::count_genesis_validators(database_client, &genesis.chain_id).await?;
+
+    if count_imported > 0 {
+        info!(chain_id = %genesis.chain_id, "Genesis is already imported");
+        return Ok(());
+    }
+
+    let validators = collect_validators(&genesis);
+    info!(chain_id = %genesis.chain_id, validators = validators.len(), "Importing genesis validators");
+
+    repository::insert_genesis_validators(database_client, &genesis.chain_id, &validators).await?;
+
+    Ok(())
+}
+
+async fn read(rpc_client: &RpcClient, source: &str) -> Result<Genesis, Error> {
+    let bytes = if source.starts_with("http://") || source.starts_with("https://") {
+        rpc_client.request(source.to_string())
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| read_error(source, Box::new(e)))?
+            .to_vec()
+    } else {
+        fs::read(source).map_err(|e| read_error(source, Box::new(e)))?
+    };
+
+    serde_json::from_slice(&bytes).map_err(Error::CouldNotParseGenesis)
+}
+
+fn read_error(location: &str, source: BoxError) -> Error {
+    Error::CouldNotReadGenesis { location: location.to_string(), source }
+}
+
+fn collect_validators(genesis: &Genesis) -> Vec<ValidatorMetadata> {
+    let mut validators: Vec<ValidatorMetadata> = genesis.validators
+        .iter()
+        .map(|v| ValidatorMetadata {
+            address: v.address.clone(),
+            moniker: v.name.clone(),
+            operator_address: None,
+            voting_power: v.power,
+        })
+        .collect();
+
+    let gen_txs = genesis.app_state
+        .as_ref()
+        .and_then(|a| a.genutil.as_ref())
+        .map_or(&[][..], |g| &g.gen_txs[..]);
+
+    for message in gen_txs.iter().flat_map(|t| &t.body.messages) {
+        if message.message_type != CREATE_VALIDATOR_MESSAGE {
+            continue;
+        }
+
+        let Some(address) = message.pubkey.as_ref().and_then(|p| consensus_address(&p.key)) else {
+            continue;
+        };
+
+        validators.push(ValidatorMetadata {
+            address,
+            moniker: message.description.as_ref().map_or_else(String::new, |d| d.moniker.clone()),
+            operator_address: message.validator_address.clone(),
+            voting_power: message.value.as_ref().map_or(0, |c| c.amount / POWER_REDUCTION),
+        });
+    }
+
+    validators
+}
+
+/// The consensus address which blocks report as proposer_address is the first 20 bytes of
+/// SHA-256 of the validator's ed25519 public key, hex encoded.
+pub fn consensus_address(public_key: &str) -> Option<ConsensusAddress> {
+    let public_key = STANDARD.decode(public_key).ok()?;
+    let digest = Sha256::digest(public_key);
+
+    digest[..CONSENSUS_ADDRESS_LENGTH]
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<String>()
+        .parse()
+        .ok()
+}
@@ -0,0 +1,162 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use axum::{Json, Router};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::Error;
+
+/// Exit codes follow sysexits.h, so restart policies can tell the classes of failure apart.
+pub const EXIT_FAILURE: u8 = 1;
+/// `EX_DATAERR`: the database schema is newer than the indexer, which an older image cannot fix.
+pub const EXIT_SCHEMA_MISMATCH: u8 = 65;
+/// `EX_TEMPFAIL`: live indexing stalled, which a restart may fix.
+pub const EXIT_STALLED: u8 = 75;
+/// `EX_CONFIG`: a setting or secret is invalid, which no restart fixes.
+pub const EXIT_CONFIG_ERROR: u8 = 78;
+
+/// Seconds without progress after which `/health` reports live indexing as stalled, unless the
+/// watchdog timeout says otherwise.
+pub const DEFAULT_STALL_TIMEOUT_IN_SECONDS: u64 = 600;
+const WATCHDOG_CHECK_INTERVAL_IN_SECONDS: u64 = 10;
+
+/// When live indexing last completed a cycle, fetched batches which the writer holds on to, or
+/// found another replica indexing the tip of the chain, shared by the indexing loop, the health
+/// endpoint and the watchdog. It also counts the rows every task wrote since the start.
+#[derive(Clone, Debug)]
+pub struct Progress {
+    last_cycle_at: Arc<AtomicI64>,
+    rows_written: Arc<AtomicU64>,
+    heights_skipped: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Starts counting from now, so the first cycle has the whole timeout.
+    pub fn new() -> Self {
+        Progress {
+            last_cycle_at: Arc::new(AtomicI64::new(Utc::now().timestamp())),
+            rows_written: Arc::default(),
+            heights_skipped: Arc::default(),
+        }
+    }
+
+    pub fn record(&self) {
+        self.last_cycle_at.store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Counts the rows of a batch which were written, and the heights of the batch which were
+    /// skipped because another replica or an earlier attempt indexed them already.
+    pub fn record_written(&self, rows: usize, written: u64) {
+        self.rows_written.fetch_add(written, Ordering::Relaxed);
+        self.heights_skipped.fetch_add((rows as u64).saturating_sub(written), Ordering::Relaxed);
+    }
+
+    pub fn rows_written(&self) -> u64 {
+        self.rows_written.load(Ordering::Relaxed)
+    }
+
+    pub fn heights_skipped(&self) -> u64 {
+        self.heights_skipped.load(Ordering::Relaxed)
+    }
+
+    fn seconds_since_progress(&self) -> u64 {
+        Utc::now().timestamp().saturating_sub(self.last_cycle_at.load(Ordering::Relaxed)).max(0) as u64
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HealthResponse {
+    healthy: bool,
+    seconds_since_progress: u64,
+    rows_written: u64,
+    heights_skipped: u64,
+}
+
+/// Serves `GET /health`, which answers 503 once live indexing made no progress for the timeout.
+pub async fn serve(address: SocketAddr, progress: Progress, stall_timeout: Duration) {
+    let app = Router::new()
+        .route("/health", get(health_handler))
+        .with_state((progress, stall_timeout));
+
+    info!(%address, "Serving health endpoint");
+    if let Err(e) = axum::Server::bind(&address).serve(app.into_make_service()).await {
+        warn!(error = %e, "Health endpoint stopped");
+    }
+}
+
+async fn health_handler(State((progress, stall_timeout)): State<(Progress, Duration)>)
+                        -> (StatusCode, Json<HealthResponse>) {
+    let seconds_since_progress = progress.seconds_since_progress();
+    let healthy = seconds_since_progress < stall_timeout.as_secs();
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    let rows_written = progress.rows_written();
+    let heights_skipped = progress.heights_skipped();
+
+    (status, Json(HealthResponse { healthy, seconds_since_progress, rows_written, heights_skipped }))
+}
+
+/// Returns the seconds live indexing went without progress once they reach the timeout.
+pub async fn watch(progress: Progress, timeout: Duration) -> u64 {
+    let mut interval = time::interval(Duration::from_secs(WATCHDOG_CHECK_INTERVAL_IN_SECONDS));
+
+    loop {
+        interval.tick().await;
+        let seconds_since_progress = progress.seconds_since_progress();
+        if seconds_since_progress >= timeout.as_secs() {
+            return seconds_since_progress;
+        }
+    }
+}
+
+/// Asks the health endpoint of an indexer running at the address whether it is healthy.
+pub async fn check(http_client: &reqwest::Client, address: SocketAddr) -> Result<(), Error> {
+    let url = format!("http://{address}/health");
+    let response = http_client.get(&url)
+        .send()
+        .await
+        .map_err(|e| Error::CouldNotGetResponseFromServer { url: url.clone(), source: Box::new(e) })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::UnsuccessfulResponse { url, status });
+    }
+    if let Ok(health) = response.json::<HealthResponse>().await {
+        info!(seconds_since_progress = health.seconds_since_progress,
+              rows_written = health.rows_written,
+              heights_skipped = health.heights_skipped,
+              "Indexer is healthy");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn health_turns_unavailable_once_progress_is_older_than_the_timeout() {
+        let progress = Progress::new();
+        let stall_timeout = Duration::from_secs(60);
+
+        let (status, _) = health_handler(State((progress.clone(), stall_timeout))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        progress.last_cycle_at.fetch_sub(60, Ordering::Relaxed);
+        let (status, Json(health)) = health_handler(State((progress.clone(), stall_timeout))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!health.healthy);
+
+        progress.record();
+        let (status, _) = health_handler(State((progress, stall_timeout))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
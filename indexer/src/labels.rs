@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{Error, repository};
+use crate::rpc::RpcClient;
+
+/// Community lists change rarely, so they are fetched once a day.
+pub const LABEL_REFRESH_INTERVAL_IN_SECONDS: u64 = 24 * 3600;
+/// Longest label and category the columns hold; longer entries of the source are skipped.
+const MAXIMUM_LABEL_LENGTH: usize = 255;
+const MAXIMUM_CATEGORY_LENGTH: usize = 64;
+
+/// Entry of the community source, a JSON list such as
+/// `[{"address": "osmo1...", "label": "Binance deposit", "category": "exchange"}]`.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct AddressLabel {
+    pub address: String,
+    pub label: String,
+    pub category: Option<String>,
+}
+
+/// Replaces the labels of the community source in `address_labels` with those at the URL.
+/// Labels set through the admin API of the statistics service are kept.
+pub async fn refresh(rpc_client: &RpcClient, database_client: &tokio_postgres::Client, url: &str)
+                     -> Result<(), Error> {
+    let labels: Vec<AddressLabel> = rpc_client.request(url.to_string())
+        .await?
+        .error_for_status()
+        .map_err(|e| Error::CouldNotGetResponseFromServer { url: url.to_string(), source: Box::new(e) })?
+        .json()
+        .await
+        .map_err(Error::CouldNotParseResponseForLabels)?;
+
+    let labels = valid(labels);
+    info!(labels = labels.len(), "Refreshing address labels");
+    repository::replace_community_labels(database_client, &labels).await
+}
+
+/// Leaves out entries which are empty or too long for the columns, and all but the first entry
+/// of an address.
+fn valid(labels: Vec<AddressLabel>) -> Vec<AddressLabel> {
+    let mut addresses = HashSet::new();
+
+    labels.into_iter()
+        .filter(|l| {
+            !l.address.is_empty()
+                && l.address.len() <= MAXIMUM_LABEL_LENGTH
+                && !l.label.is_empty()
+                && l.label.len() <= MAXIMUM_LABEL_LENGTH
+                && l.category.as_ref().is_none_or(|c| c.len() <= MAXIMUM_CATEGORY_LENGTH)
+        })
+        .filter(|l| addresses.insert(l.address.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(address: &str, label: &str) -> AddressLabel {
+        AddressLabel { address: address.to_string(), label: label.to_string(), category: None }
+    }
+
+    #[test]
+    fn entries_which_do_not_fit_are_left_out() {
+        let labels = vec![
+            label("osmo1a", "Binance deposit"),
+            label("osmo1a", "Duplicate"),
+            label("", "No address"),
+            label("osmo1b", ""),
+            label("osmo1c", &"x".repeat(MAXIMUM_LABEL_LENGTH + 1)),
+            label("osmo1d", "Bridge"),
+        ];
+
+        assert_eq!(valid(labels), vec![label("osmo1a", "Binance deposit"), label("osmo1d", "Bridge")]);
+    }
+}
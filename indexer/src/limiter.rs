@@ -0,0 +1,267 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+use tokio::time;
+use tracing::{debug, info};
+
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 20.0;
+pub const DEFAULT_MAXIMUM_CONCURRENCY: u64 = 20;
+/// Concurrency the limiter starts with before responses show how much the endpoints can take.
+const INITIAL_CONCURRENCY: usize = 5;
+/// Responses at least this fast count towards raising the concurrency.
+const FAST_RESPONSE_IN_MILLISECONDS: u128 = 1000;
+
+/// How a request which was let through by the limiter went.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Fast,
+    Slow,
+    /// The endpoint answered with 429 Too Many Requests or did not answer in time.
+    Throttled,
+}
+
+/// Limits requests to the RPC endpoints by rate, with a token bucket holding up to one second
+/// of requests, and by concurrency, which is raised by one after as many fast responses as
+/// requests are allowed in parallel and halved whenever an endpoint throttles.
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+    released: Notify,
+}
+
+#[derive(Debug)]
+struct State {
+    requests_per_second: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    maximum_concurrency: usize,
+    concurrency: usize,
+    in_flight: usize,
+    fast_responses: usize,
+}
+
+/// Holds one of the parallel requests allowed by the limiter until dropped.
+pub struct Permit<'a> {
+    limiter: &'a RateLimiter,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new(DEFAULT_REQUESTS_PER_SECOND, DEFAULT_MAXIMUM_CONCURRENCY)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, maximum_concurrency: u64) -> Self {
+        let requests_per_second = requests_per_second.max(f64::MIN_POSITIVE);
+        let maximum_concurrency = (maximum_concurrency as usize).max(1);
+
+        RateLimiter {
+            state: Mutex::new(State {
+                requests_per_second,
+                tokens: burst(requests_per_second),
+                refilled_at: Instant::now(),
+                maximum_concurrency,
+                concurrency: INITIAL_CONCURRENCY.min(maximum_concurrency),
+                in_flight: 0,
+                fast_responses: 0,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Most requests the limiter ever lets through in parallel.
+    pub fn maximum_concurrency(&self) -> u64 {
+        self.state.lock().unwrap().maximum_concurrency as u64
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        self.state.lock().unwrap().requests_per_second
+    }
+
+    /// Changes the rate and the most parallel requests while requests are made. A lower maximum
+    /// lowers the concurrency right away; requests in flight above it finish first.
+    pub fn reconfigure(&self, requests_per_second: Option<f64>, maximum_concurrency: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(requests_per_second) = requests_per_second {
+            state.requests_per_second = requests_per_second.max(f64::MIN_POSITIVE);
+            state.tokens = state.tokens.min(burst(state.requests_per_second));
+        }
+        if let Some(maximum_concurrency) = maximum_concurrency {
+            state.maximum_concurrency = (maximum_concurrency as usize).max(1);
+            state.concurrency = state.concurrency.min(state.maximum_concurrency);
+        }
+        drop(state);
+        self.released.notify_waiters();
+    }
+
+    /// Waits until both a token and a parallel request are available.
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            // Registered before the lock is released so that no release is missed.
+            let released = self.released.notified();
+
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                match state.try_acquire(Instant::now()) {
+                    Ok(()) => return Permit { limiter: self },
+                    Err(wait) => wait,
+                }
+            };
+
+            match wait {
+                Some(duration) => time::sleep(duration).await,
+                None => released.await,
+            }
+        }
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().in_flight -= 1;
+        self.released.notify_waiters();
+    }
+}
+
+impl Permit<'_> {
+    pub fn record(&self, outcome: Outcome) {
+        let mut state = self.limiter.state.lock().unwrap();
+        let concurrency = state.concurrency;
+        state.record(outcome);
+
+        if state.concurrency < concurrency {
+            info!(concurrency = state.concurrency, "RPC endpoint throttles, lowering concurrency");
+        } else if state.concurrency > concurrency {
+            debug!(concurrency = state.concurrency, "Raising concurrency");
+            drop(state);
+            self.limiter.released.notify_waiters();
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+impl State {
+    /// Takes a token and a parallel request, or tells how long to wait for a token.
+    /// No duration means waiting for a parallel request to be released.
+    fn try_acquire(&mut self, now: Instant) -> Result<(), Option<Duration>> {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.requests_per_second).min(burst(self.requests_per_second));
+        self.refilled_at = now;
+
+        if self.in_flight >= self.concurrency {
+            return Err(None);
+        }
+        if self.tokens < 1.0 {
+            return Err(Some(Duration::from_secs_f64((1.0 - self.tokens) / self.requests_per_second)));
+        }
+
+        self.tokens -= 1.0;
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Fast => {
+                self.fast_responses += 1;
+                if self.fast_responses >= self.concurrency {
+                    self.concurrency = (self.concurrency + 1).min(self.maximum_concurrency);
+                    self.fast_responses = 0;
+                }
+            }
+            Outcome::Slow => self.fast_responses = 0,
+            Outcome::Throttled => {
+                self.concurrency = (self.concurrency / 2).max(1);
+                self.fast_responses = 0;
+            }
+        }
+    }
+}
+
+impl Outcome {
+    pub fn of_response(duration: Duration) -> Self {
+        if duration.as_millis() <= FAST_RESPONSE_IN_MILLISECONDS {
+            Outcome::Fast
+        } else {
+            Outcome::Slow
+        }
+    }
+}
+
+/// Tokens the bucket holds at most: one second of requests, but always at least one request.
+fn burst(requests_per_second: f64) -> f64 {
+    requests_per_second.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn outcome() -> impl Strategy<Value=Outcome> {
+        prop_oneof![Just(Outcome::Fast), Just(Outcome::Slow), Just(Outcome::Throttled)]
+    }
+
+    #[test]
+    fn lower_maximum_lowers_the_concurrency() {
+        let limiter = RateLimiter::new(DEFAULT_REQUESTS_PER_SECOND, 20);
+        limiter.reconfigure(Some(4.0), Some(3));
+
+        let state = limiter.state.lock().unwrap();
+        assert_eq!((state.maximum_concurrency, state.concurrency), (3, 3));
+        assert_eq!(state.requests_per_second, 4.0);
+        assert!(state.tokens <= 4.0);
+    }
+
+    proptest! {
+        #[test]
+        fn concurrency_stays_between_one_and_maximum(
+            maximum_concurrency in 1..50u64,
+            outcomes in prop::collection::vec(outcome(), 0..500),
+        ) {
+            let limiter = RateLimiter::new(DEFAULT_REQUESTS_PER_SECOND, maximum_concurrency);
+            let mut state = limiter.state.lock().unwrap();
+
+            for outcome in outcomes {
+                let concurrency = state.concurrency;
+                state.record(outcome);
+
+                prop_assert!(state.concurrency >= 1);
+                prop_assert!(state.concurrency <= maximum_concurrency as usize);
+                if outcome == Outcome::Throttled {
+                    prop_assert!(state.concurrency <= concurrency.div_ceil(2));
+                }
+            }
+        }
+
+        #[test]
+        fn requests_never_exceed_the_rate(
+            requests_per_second in 1..100u32,
+            steps_in_milliseconds in prop::collection::vec(0..200u64, 1..300),
+        ) {
+            let limiter = RateLimiter::new(requests_per_second as f64, 1);
+            let mut state = limiter.state.lock().unwrap();
+            let started = state.refilled_at;
+            let mut now = started;
+            let mut granted = 0u32;
+
+            for step in steps_in_milliseconds {
+                now += Duration::from_millis(step);
+                if state.try_acquire(now).is_ok() {
+                    granted += 1;
+                    state.in_flight -= 1;
+                }
+            }
+
+            let allowed = burst(requests_per_second as f64)
+                + now.duration_since(started).as_secs_f64() * requests_per_second as f64;
+            prop_assert!(granted as f64 <= allowed + f64::EPSILON);
+        }
+    }
+}
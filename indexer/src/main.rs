@@ -1,239 +1,1052 @@
-use std::thread;
-use std::time::Duration;
-use reqwest::Client;
+use std::collections::HashMap;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use bb8::{Pool, PooledConnection};
+use bb8_postgres::PostgresConnectionManager;
+use clap::Parser;
+use reqwest::{Client, StatusCode};
 
 use serde::Deserialize;
-use serde_aux::prelude::*;
 use tokio::{task, time};
 use tokio::task::JoinSet;
+use tokio_postgres_rustls::MakeRustlsConnect;
+use tracing::{debug, error, info, info_span, Instrument, Span, warn};
 
-#[derive(Deserialize, Debug)]
-struct BlockResponse {
-    result: BlockResult,
-}
+mod admin;
+mod anomalies;
+mod archive;
+mod avatars;
+mod backoff;
+mod budget;
+mod buffer;
+mod cassette;
+mod checkpoints;
+mod cli;
+mod compare;
+mod config;
+mod egress;
+mod gaps;
+mod genesis;
+mod health;
+mod labels;
+mod limiter;
+mod migrations;
+mod node_versions;
+mod plugins;
+mod ranges;
+mod release;
+mod reorgs;
+mod repository;
+mod rpc;
+mod settings;
+mod shards;
+mod signatures;
+#[cfg(test)]
+mod simulation;
+mod store;
+mod subsystems;
+mod telemetry;
+#[cfg(feature = "plugin-transaction-sizes")]
+mod transaction_sizes;
+mod tuner;
+mod validator_sets;
+mod validators;
+#[cfg(feature = "wasm-plugins")]
+mod wasm_plugins;
+mod writer;
 
-#[derive(Deserialize, Debug)]
-struct BlockResult {
-    block: Block,
-}
+use checkpoints::CheckpointRange;
+use cli::{Cli, Command};
+use config::{BackfillMode, Config, StartMode};
+use health::Progress;
+use model::{ChainId, Height};
+use osmosis_core::{Block, BoxError, database, Error as CoreError, secrets};
+use osmosis_core::profiles::Profile;
+use rpc::RpcClient;
+use store::{BlockSource, Store};
+use telemetry::{FetchTelemetry, TelemetryCollector};
+use writer::BatchWriter;
 
+/// Fields of `/block` and `/blockchain` responses vary between networks and are read through
+/// a parser profile, while `/status` is needed to choose the profile in the first place.
 #[derive(Deserialize, Debug)]
-struct Block {
-    header: Header,
+struct StatusResponse {
+    result: StatusResult,
 }
 
 #[derive(Deserialize, Debug)]
-struct Header {
-    #[serde(deserialize_with = "deserialize_number_from_string")]
-    height: i64,
-    proposer_address: String,
+struct StatusResult {
+    node_info: NodeInfo,
+    sync_info: SyncInfo,
 }
 
 #[derive(Deserialize, Debug)]
-struct BlockchainResponse {
-    result: BlockchainResult,
+struct NodeInfo {
+    network: ChainId,
 }
 
 #[derive(Deserialize, Debug)]
-struct BlockchainResult {
-    #[serde(deserialize_with = "deserialize_number_from_string")]
-    last_height: i64,
+struct SyncInfo {
+    earliest_block_height: Height,
 }
 
-struct ProposerToHeight {
-    proposer: String,
-    height: i64,
-}
+const OSMOSIS_LOWEST_HEIGHT: Height = Height::new(9558628);
+const DEFAULT_DATABASE_USER: &str = "osmosis";
+const DEFAULT_DATABASE_PASSWORD: &str = "osmosis";
+const BACKFILL_HEIGHTS_PER_INTERVAL: u64 = 100;
+const REQUESTED_HEIGHTS_PER_INTERVAL: i64 = 50;
+/// Cycles in which a requested height may fail before it is dropped from the queue.
+const MAXIMUM_REQUESTED_HEIGHT_ATTEMPTS: i32 = 5;
+/// Requests which the RPC endpoint has not answered by then count as throttled.
+const RPC_REQUEST_TIMEOUT_IN_SECONDS: u64 = 30;
+/// Connections shared by the recurring tasks, which check one out for each cycle.
+const DATABASE_POOL_SIZE: u32 = 8;
+const DATABASE_CONNECTION_TIMEOUT_IN_SECONDS: u64 = 5;
 
-const OSMOSIS_LOWEST_HEIGHT: i64 = 9558628;
-const INDEXER_INTERVAL_IN_SECONDS: u64 = 30;
-const MAXIMUM_NUMBER_OF_PARALLEL_REQUESTS: i64 = 5;
+type ConnectionPool = Pool<PostgresConnectionManager<MakeRustlsConnect>>;
 
-#[derive(Debug)]
+/// Errors keep the error they come from and what failed, e.g. the URL or the height, so that
+/// logging them with `?` tells why.
+#[derive(Debug, thiserror::Error)]
 enum Error {
-    CouldNotCreateHttpClient,
-    CouldNotBuildHttpRequest,
-    CouldNotGetResponseFromServer,
-    CouldNotParseResponseForBlockAtHeight,
-    CouldNotParseResponseForBlockchain,
-    CouldNotProcessResponsesInParallel,
+    #[error("could not create HTTP client")]
+    CouldNotCreateHttpClient(#[source] reqwest::Error),
+    #[error("could not build HTTP request for {url}")]
+    CouldNotBuildHttpRequest { url: String, #[source] source: BoxError },
+    #[error("could not get response from {url}")]
+    CouldNotGetResponseFromServer { url: String, #[source] source: BoxError },
+    #[error("{url} answered with HTTP status {status}")]
+    UnsuccessfulResponse { url: String, status: StatusCode },
+    /// The height of the block, or of the first block of a batch.
+    #[error("could not parse the response for the block at height {height}")]
+    CouldNotParseResponseForBlockAtHeight { height: Height, #[source] source: BoxError },
+    #[error("could not parse the response for the blockchain")]
+    CouldNotParseResponseForBlockchain(#[source] BoxError),
+    #[error("could not parse the response for the status")]
+    CouldNotParseResponseForStatus(#[source] reqwest::Error),
+    #[error("could not parse the response for the validators")]
+    CouldNotParseResponseForValidators(#[source] reqwest::Error),
+    #[error("could not parse the response for the validator set")]
+    CouldNotParseResponseForValidatorSet(#[source] BoxError),
+    #[error("could not parse the response for the node versions")]
+    CouldNotParseResponseForNodeVersions(#[source] reqwest::Error),
+    #[error("could not parse the response of the deployment at {url}")]
+    CouldNotParseResponseOfDeployment { url: String, #[source] source: reqwest::Error },
+    #[error("could not parse the address labels")]
+    CouldNotParseResponseForLabels(#[source] reqwest::Error),
+    #[error("could not parse the response of Keybase")]
+    CouldNotParseResponseForKeybase(#[source] reqwest::Error),
+    #[error("batch response does not answer every call of the batch")]
+    CouldNotParseBatchResponse,
+    #[error("could not process responses in parallel")]
+    CouldNotProcessResponsesInParallel(#[source] task::JoinError),
+    #[error("egress to {url} is denied: {reason}")]
+    EgressDenied { url: String, reason: &'static str },
+    #[error("RPC budgets of all endpoints are exhausted")]
+    RpcBudgetExhausted,
+    #[error("request to {url} timed out")]
+    RpcRequestTimedOut { url: String },
+
+    #[error("could not read config file {path}")]
+    CouldNotReadConfig { path: String, #[source] source: BoxError },
+    #[error("invalid setting {0}")]
+    CouldNotParseConfig(&'static str),
+    #[error("could not resolve secret {name}")]
+    CouldNotResolveSecret { name: String, #[source] source: BoxError },
+    #[error("could not configure TLS for the database")]
+    CouldNotConfigureTls(#[source] BoxError),
+    #[error("could not parse the database settings")]
+    CouldNotParseDatabaseConfig(#[source] BoxError),
+    #[error("could not connect to the database")]
+    CouldNotCreateDatabaseClient(#[source] tokio_postgres::Error),
+    #[error("could not migrate the database")]
+    CouldNotMigrateDatabase(#[source] tokio_postgres::Error),
+    #[error("database schema version {version} is newer than {known_version}, the latest this indexer knows")]
+    SchemaIsNewer { version: i64, known_version: i64 },
+    #[error("live indexing made no progress for {seconds} seconds")]
+    IndexingStalled { seconds: u64 },
+    #[error("could not find indexed height")]
+    CouldNotFindIndexedHeight(#[source] tokio_postgres::Error),
+    #[error("no blocks are indexed")]
+    NothingIndexed,
+    #[error("could not convert height")]
+    CouldNotConvertHeight(#[source] tokio_postgres::Error),
+    #[error("could not record start height")]
+    CouldNotRecordStartHeight(#[source] tokio_postgres::Error),
+    #[error("could not update requested heights")]
+    CouldNotTakeRequestedHeights(#[source] tokio_postgres::Error),
+    #[error("could not find gaps")]
+    CouldNotFindGaps(#[source] tokio_postgres::Error),
+    #[error("could not find where the chain diverged below height {height}")]
+    CouldNotFindReorgDivergence { height: Height },
+    #[error("could not roll back reorganized blocks")]
+    CouldNotRollBackReorg(#[source] tokio_postgres::Error),
+    #[error("batch has a height which is indexed already")]
+    CouldNotIndexDuplicateHeight(#[source] tokio_postgres::Error),
+    #[error("could not insert batch")]
+    CouldNotInsertBatch(#[source] tokio_postgres::Error),
+    #[error("inserted {inserted} rows of a batch of {expected}")]
+    InsertedIncorrectNumberOfRows { expected: u64, inserted: u64 },
+    #[error("could not insert signatures")]
+    CouldNotInsertSignatures(#[source] tokio_postgres::Error),
+    #[error("database is unavailable")]
+    DatabaseUnavailable(#[source] BoxError),
+    #[error("insert buffer is full")]
+    InsertBufferIsFull,
+    #[error("{missing_heights} heights are missing in {ranges} ranges")]
+    IndexHasGaps { missing_heights: u64, ranges: usize },
+    #[error("deployments indexed different blocks at {heights} of {sampled} sampled heights")]
+    DeploymentsDiverge { heights: usize, sampled: usize },
+    #[error("deployments are {heights} heights apart")]
+    DeploymentsAreApart { heights: u64 },
+
+    #[error("could not read genesis from {location}")]
+    CouldNotReadGenesis { location: String, #[source] source: BoxError },
+    #[error("could not parse genesis")]
+    CouldNotParseGenesis(#[source] serde_json::Error),
+    #[error("could not import genesis")]
+    CouldNotImportGenesis(#[source] tokio_postgres::Error),
+
+    #[error("could not refresh validators")]
+    CouldNotRefreshValidators(#[source] tokio_postgres::Error),
+    #[error("could not store validator set snapshot")]
+    CouldNotStoreValidatorSetSnapshot(#[source] tokio_postgres::Error),
+    #[error("could not store node versions")]
+    CouldNotStoreNodeVersions(#[source] tokio_postgres::Error),
+    #[error("could not store address labels")]
+    CouldNotStoreLabels(#[source] tokio_postgres::Error),
+    #[error("could not detect anomalies")]
+    CouldNotDetectAnomalies(#[source] tokio_postgres::Error),
+    #[error("could not archive plugin values")]
+    CouldNotArchiveRecords(#[source] tokio_postgres::Error),
+    #[error("could not store telemetry")]
+    CouldNotStoreTelemetry(#[source] tokio_postgres::Error),
+    #[error("could not store plugin values")]
+    CouldNotStorePluginRecords(#[source] tokio_postgres::Error),
+    #[error("could not migrate plugin tables")]
+    CouldNotMigratePlugin(#[source] tokio_postgres::Error),
+    #[error("plugin name {0} is not a valid schema name")]
+    InvalidPluginName(String),
+    #[cfg(feature = "wasm-plugins")]
+    #[error("could not load WASM plugin {path}")]
+    CouldNotLoadWasmPlugin { path: String, #[source] source: BoxError },
+    #[error("could not enable change data capture")]
+    CouldNotEnableChangeDataCapture(#[source] tokio_postgres::Error),
+    #[error("could not copy table")]
+    CouldNotCopyTable(#[source] tokio_postgres::Error),
+    #[error("could not write release")]
+    CouldNotWriteRelease(#[source] std::io::Error),
+    #[error("could not update checkpoint")]
+    CouldNotUpdateCheckpoint(#[source] tokio_postgres::Error),
+    #[error("could not lock live indexing")]
+    CouldNotLockLiveIndexing(#[source] tokio_postgres::Error),
+    #[error("could not update block details")]
+    CouldNotUpdateBlockDetails(#[source] tokio_postgres::Error),
+}
 
-    CouldNotCreateDatabaseClient,
-    CouldNotFindIndexedHeight,
-    CouldNotIndexDuplicateHeight,
-    InsertedIncorrectNumberOfRows,
+impl Error {
+    /// Exit code of the indexer when it stops with the error.
+    fn exit_code(&self) -> u8 {
+        match self {
+            Error::CouldNotReadConfig { .. }
+            | Error::CouldNotParseConfig(_)
+            | Error::CouldNotResolveSecret { .. }
+            | Error::CouldNotConfigureTls(_)
+            | Error::CouldNotParseDatabaseConfig(_)
+            | Error::InvalidPluginName(_) => health::EXIT_CONFIG_ERROR,
+            #[cfg(feature = "wasm-plugins")]
+            Error::CouldNotLoadWasmPlugin { .. } => health::EXIT_CONFIG_ERROR,
+            Error::SchemaIsNewer { .. } => health::EXIT_SCHEMA_MISMATCH,
+            Error::IndexingStalled { .. } => health::EXIT_STALLED,
+            _ => health::EXIT_FAILURE,
+        }
+    }
+}
+
+impl From<CoreError> for Error {
+    fn from(e: CoreError) -> Self {
+        match e {
+            CoreError::CouldNotParseResponseForBlockAtHeight { .. }
+            | CoreError::CouldNotParseResponseForBlockchain { .. } => {
+                Error::CouldNotParseResponseForBlockchain(Box::new(e))
+            }
+            CoreError::CouldNotResolveSecret { name, source } => Error::CouldNotResolveSecret { name, source },
+            CoreError::CouldNotConfigureTls(source) => Error::CouldNotConfigureTls(source),
+            CoreError::CouldNotParseDatabaseConfig(source) => Error::CouldNotParseDatabaseConfig(source),
+            CoreError::CouldNotIndexDuplicateHeight(source) => Error::CouldNotIndexDuplicateHeight(source),
+            CoreError::CouldNotInsertBatch(source) => Error::CouldNotInsertBatch(source),
+            CoreError::InsertedIncorrectNumberOfRows { expected, inserted } => {
+                Error::InsertedIncorrectNumberOfRows { expected, inserted }
+            }
+            CoreError::CouldNotInsertSignatures(source) => Error::CouldNotInsertSignatures(source),
+            CoreError::DatabaseUnavailable(source) => Error::DatabaseUnavailable(Box::new(source)),
+        }
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() -> ExitCode {
+    logging::init();
+
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!(error = ?e, "Indexer stopped");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Error> {
     let http_client = Client::builder()
+        .timeout(Duration::from_secs(RPC_REQUEST_TIMEOUT_IN_SECONDS))
         .build()
-        .map_err(|_| Error::CouldNotCreateHttpClient)?;
+        .map_err(Error::CouldNotCreateHttpClient)?;
+    if let Some(Command::Compare { left_url, right_url, samples, maximum_height_difference }) = &cli.command {
+        let (left_url, right_url) = (left_url.trim_end_matches('/'), right_url.trim_end_matches('/'));
+        return compare::compare(&http_client, left_url, right_url, *samples, *maximum_height_difference).await;
+    }
+    if let Some(Command::Healthcheck { address }) = &cli.command {
+        return health::check(&http_client, *address).await;
+    }
+
+    let config = Config::load(&cli)?;
+    let rpc_client = RpcClient::new(http_client, egress::EgressPolicy::from_env(), config.rpc_urls)
+        .with_budget(budget::Budget::new(config.rpc_quotas))
+        .with_limiter(limiter::RateLimiter::new(
+            config.rpc_requests_per_second.unwrap_or(limiter::DEFAULT_REQUESTS_PER_SECOND),
+            config.rpc_maximum_concurrency.unwrap_or(limiter::DEFAULT_MAXIMUM_CONCURRENCY)))
+        .with_batch_size(config.rpc_batch_size.unwrap_or(1))
+        .with_cassette(cassette::Cassette::from_env())
+        .with_telemetry(TelemetryCollector::new(config.fetch_telemetry))
+        .with_plugins(plugins::Plugins::new(plugins::enabled()?));
+
+    let database_config =
+        database::from_env(&config.database_host, DEFAULT_DATABASE_USER, Some(DEFAULT_DATABASE_PASSWORD)).await?;
+
+    // Live indexing keeps a connection of its own, because the lock it takes is held by the session.
+    let mut database_client = connect_to_database(&database_config, config.startup_attempts).await?;
+    migrations::run(&mut database_client, config.cdc_publication.as_deref()).await?;
+    rpc_client.plugins().migrate(&mut database_client).await?;
+
+    let command = cli.command.unwrap_or(Command::Run);
+    match &command {
+        Command::Migrate => {
+            info!("Database is migrated");
+            return Ok(());
+        }
+        Command::PublishDataset { directory } => return release::publish(&mut database_client, directory).await,
+        Command::VerifyGaps { from } => return gaps::verify(&database_client, *from).await,
+        Command::Backfill { from, to } if from > to => return Err(Error::CouldNotParseConfig("--from")),
+        Command::Compare { .. }
+        | Command::Healthcheck { .. }
+        | Command::Run
+        | Command::Backfill { .. }
+        | Command::Reindex { .. } => {}
+    }
+
+    let status = backoff::retry("RPC", config.startup_attempts, || request_status(&rpc_client)).await?;
+    let chain_id = status.node_info.network;
+    info!(%chain_id, "Connected to RPC");
+    let rpc_client = rpc_client.with_profile(Profile::select(&config.profiles, &chain_id));
+
+    match command {
+        Command::Backfill { from, to } => {
+            let range = CheckpointRange { first_height: from, last_height: to };
+            let name = checkpoints::historical_backfill_name(&range);
+            checkpoints::define(&database_client, &HashMap::from([(name.clone(), range)])).await?;
+            checkpoints::complete(&rpc_client, &database_client, &name).await?;
+            info!(%from, %to, "Backfill is complete");
+            return Ok(());
+        }
+        Command::Reindex { height } => return reindex(&rpc_client, &database_client, height).await,
+        _ => {}
+    }
+
+    // A local chain starts far below OSMOSIS_LOWEST_HEIGHT, and its node may have pruned early blocks.
+    let lowest_height = match config.lowest_height {
+        Some(h) => h,
+        None if config.devnet => status.sync_info.earliest_block_height,
+        None => OSMOSIS_LOWEST_HEIGHT,
+    };
+    info!(%lowest_height, "Lowest height of the chain");
+
+    let start_mode = config.start;
+
+    // The pool is built without connecting, so a task whose cycle finds the database unavailable
+    // skips it and checks out a connection again on the next one.
+    let manager = PostgresConnectionManager::new(database_config.config.clone(), database_config.tls.clone());
+    let pool = Pool::builder()
+        .max_size(DATABASE_POOL_SIZE)
+        .connection_timeout(Duration::from_secs(DATABASE_CONNECTION_TIMEOUT_IN_SECONDS))
+        .build_unchecked(manager);
+
+    let settings = settings::Settings::new(
+        config.interval_in_seconds.unwrap_or(settings::DEFAULT_INDEXER_INTERVAL_IN_SECONDS));
+    if let Some(address) = config.admin_address {
+        match secrets::resolve("INDEXER_ADMIN_KEY").await? {
+            Some(admin_key) => {
+                task::spawn(admin::serve(address, settings.clone(), rpc_client.clone(), admin_key));
+            }
+            None => warn!(%address, "Admin endpoint is disabled without INDEXER_ADMIN_KEY"),
+        }
+    }
+
+    let progress = Progress::new();
+    let watchdog_timeout = config.watchdog_timeout_in_seconds.map(Duration::from_secs);
+    if let Some(address) = config.health_address {
+        let stall_timeout =
+            watchdog_timeout.unwrap_or(Duration::from_secs(health::DEFAULT_STALL_TIMEOUT_IN_SECONDS));
+        task::spawn(health::serve(address, progress.clone(), stall_timeout));
+    }
+
+    if let Some(source) = config.genesis_file {
+        genesis::import(&rpc_client, &database_client, &source)
+            .await
+            .unwrap_or_else(|e| error!(error = ?e, "Genesis import error"));
+    }
+
+    if !config.checkpoints.is_empty() {
+        checkpoints::define(&database_client, &config.checkpoints).await?;
+
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+        let names: Vec<String> = config.checkpoints.into_keys().collect();
+
+        let settings = settings.clone();
+        task::spawn(async move {
+            let mut interval = settings.interval();
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                checkpoints::advance(&rpc_client, &database_client, &names)
+                    .instrument(info_span!("checkpoints"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Checkpoint error"));
+            }
+        });
+    }
+
+    if let Some(range) = config.historical_backfill {
+        let name = checkpoints::historical_backfill_name(&range);
+        checkpoints::define(&database_client, &HashMap::from([(name.clone(), range)])).await?;
+
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+
+        let settings = settings.clone();
+        task::spawn(async move {
+            loop {
+                let Some(database_client) = check_out(&pool).await else {
+                    time::sleep(settings.period()).await;
+                    continue;
+                };
+                match checkpoints::complete(&rpc_client, &database_client, &name)
+                    .instrument(info_span!("historical_backfill", name))
+                    .await {
+                    Ok(()) => break,
+                    Err(e) => {
+                        error!(error = ?e, "Historical backfill error");
+                        time::sleep(settings.period()).await;
+                    }
+                }
+            }
+        });
+    }
+
+    if let Some(shards) = config.shards {
+        shards::define(&database_client, &shards).await?;
+        let names = shards.names();
+
+        for worker in 0..shards.workers.unwrap_or(shards::DEFAULT_SHARD_WORKERS) {
+            let rpc_client = rpc_client.clone();
+            let pool = pool.clone();
+            let names = names.clone();
+
+            let settings = settings.clone();
+            task::spawn(async move {
+                let mut interval = settings.interval();
+
+                loop {
+                    interval.tick().await;
+                    let Some(mut database_client) = check_out(&pool).await else {
+                        continue;
+                    };
+                    shards::work(&rpc_client, &mut database_client, &names)
+                        .instrument(info_span!("shards", worker))
+                        .await
+                        .unwrap_or_else(|e| error!(error = ?e, "Shard error"));
+                }
+            });
+        }
+    }
+
+    {
+        subsystems::define(&database_client).await?;
+
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+
+        let settings = settings.clone();
+        task::spawn(async move {
+            let mut interval = settings.interval();
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                subsystems::advance(&rpc_client, &database_client)
+                    .instrument(info_span!("subsystems"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Subsystem backfill error"));
+            }
+        });
+    }
+
+    if config.backfill == Some(BackfillMode::Reverse) {
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+
+        let settings = settings.clone();
+        let progress = progress.clone();
+        task::spawn(async move {
+            let mut interval = settings.interval();
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                backfill(&rpc_client, &database_client, &progress, lowest_height)
+                    .instrument(info_span!("backfill"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Backfill error"));
+            }
+        });
+    }
+
+    {
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+        let lcd_url = config.lcd_url;
+
+        task::spawn(async move {
+            let mut interval =
+                time::interval(Duration::from_secs(validators::VALIDATOR_REFRESH_INTERVAL_IN_SECONDS));
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                validators::refresh(&rpc_client, &database_client, &lcd_url)
+                    .instrument(info_span!("validators"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Validator refresh error"));
+                avatars::refresh(&rpc_client, &database_client)
+                    .instrument(info_span!("avatars"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Avatar refresh error"));
+            }
+        });
+    }
+
+    {
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+
+        task::spawn(async move {
+            let mut interval =
+                time::interval(Duration::from_secs(validator_sets::VALIDATOR_SET_SNAPSHOT_INTERVAL_IN_SECONDS));
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                validator_sets::snapshot(&rpc_client, &database_client)
+                    .instrument(info_span!("validator_sets"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Validator set snapshot error"));
+            }
+        });
+    }
+
+    {
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
 
-    let database_client = connect_to_database().await?;
+        task::spawn(async move {
+            let mut interval =
+                time::interval(Duration::from_secs(node_versions::NODE_VERSION_SNAPSHOT_INTERVAL_IN_SECONDS));
 
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                node_versions::snapshot(&rpc_client, &database_client)
+                    .instrument(info_span!("node_versions"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Node version snapshot error"));
+            }
+        });
+    }
+
+    if let Some(labels_url) = config.labels_url {
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+
+        task::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(labels::LABEL_REFRESH_INTERVAL_IN_SECONDS));
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                labels::refresh(&rpc_client, &database_client, &labels_url)
+                    .instrument(info_span!("labels"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Address label refresh error"));
+            }
+        });
+    }
+
+    {
+        let pool = pool.clone();
+
+        task::spawn(async move {
+            let mut interval =
+                time::interval(Duration::from_secs(anomalies::ANOMALY_DETECTION_INTERVAL_IN_SECONDS));
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                anomalies::detect(&database_client)
+                    .instrument(info_span!("anomalies"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Anomaly detection error"));
+            }
+        });
+    }
+
+    if let Some(after_months) = config.archive_after_months {
+        let pool = pool.clone();
+
+        task::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(archive::ARCHIVE_INTERVAL_IN_SECONDS));
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                archive::archive(&database_client, after_months)
+                    .instrument(info_span!("archive"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Archive error"));
+            }
+        });
+    }
+
+    {
+        let rpc_client = rpc_client.clone();
+        let pool = pool.clone();
+        let progress = progress.clone();
+
+        task::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(gaps::GAP_SCAN_INTERVAL_IN_SECONDS));
+            // When indexing the full history, anything missing above the lowest height is a gap.
+            let lowest_height_to_repair = (start_mode == StartMode::Lowest).then_some(lowest_height);
+
+            loop {
+                interval.tick().await;
+                let Some(database_client) = check_out(&pool).await else {
+                    continue;
+                };
+                gaps::repair(&rpc_client, &*database_client, &progress, lowest_height_to_repair)
+                    .instrument(info_span!("gaps"))
+                    .await
+                    .unwrap_or_else(|e| error!(error = ?e, "Gap repair error"));
+            }
+        });
+    }
+
+    let watchdog_progress = progress.clone();
     let forever = task::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(INDEXER_INTERVAL_IN_SECONDS));
+        let mut interval = settings.interval();
+        let mut writer = BatchWriter::new(config.copy_threshold, progress.clone());
+        let mut indexes_live = false;
 
         loop {
             interval.tick().await;
-            index(&http_client, &database_client)
+            let was_closed = database_client.is_closed();
+            reconnect_if_closed(&database_config, &mut database_client).await;
+
+            // The lock is held by the session, so a new connection takes it again. While the
+            // database is unavailable, the replica which held it goes on buffering batches.
+            if !indexes_live || (was_closed && !database_client.is_closed()) {
+                indexes_live = repository::try_lock_live_indexing(&database_client)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(error = ?e, "Could not take the live indexing lock");
+                        false
+                    });
+                if !indexes_live {
+                    info!("Another replica indexes the tip of the chain");
+                }
+            }
+
+            if indexes_live {
+                let highest_height = writer.highest_height();
+                match index(&rpc_client, &database_client, &mut writer, start_mode, lowest_height)
+                    .instrument(info_span!("index"))
+                    .await {
+                    Ok(()) => progress.record(),
+                    Err(e) => {
+                        // Batches fetched before the error are held by the writer, so the cycle
+                        // made progress even if they could not be written yet.
+                        if writer.highest_height() > highest_height {
+                            progress.record();
+                        }
+                        error!(error = ?e, "Indexing error");
+                    }
+                }
+            } else {
+                progress.record();
+            }
+            rpc_client.telemetry().store(&database_client)
                 .await
-                .unwrap_or_else(|e| println!("Indexing error {e:?}"));
+                .unwrap_or_else(|e| warn!(error = ?e, "Telemetry error"));
+            rpc_client.plugins().store(&database_client)
+                .await
+                .unwrap_or_else(|e| error!(error = ?e, "Plugin records error"));
         }
     });
 
-    forever.await.expect("Recurring task failed");
-    Ok(())
+    let Some(watchdog_timeout) = watchdog_timeout else {
+        forever.await.expect("Recurring task failed");
+        return Ok(());
+    };
+    tokio::select! {
+        result = forever => {
+            result.expect("Recurring task failed");
+            Ok(())
+        }
+        seconds = health::watch(watchdog_progress, watchdog_timeout) => Err(Error::IndexingStalled { seconds }),
+    }
 }
 
 /// When we start database and indexer in docker compose, database is not ready and indexer
-/// cannot connect to it. We shall do several attempts to connect to database before failing.
-async fn connect_to_database() -> Result<tokio_postgres::Client, Error> {
-    for _ in 0..10 {
-        thread::sleep(Duration::from_secs(2));
-        if let Ok(c) = connect_to_database_unsafe().await { return Ok(c) }
-    }
-
-    Err(Error::CouldNotCreateDatabaseClient)
+/// cannot connect to it. We keep connecting with backoff until it is ready.
+async fn connect_to_database(database_config: &database::DatabaseConfig, max_attempts: Option<u32>)
+                             -> Result<tokio_postgres::Client, Error> {
+    backoff::retry("Database", max_attempts, || connect_to_database_unsafe(database_config)).await
 }
 
-async fn connect_to_database_unsafe() -> Result<tokio_postgres::Client, Error> {
+async fn connect_to_database_unsafe(database_config: &database::DatabaseConfig)
+                                    -> Result<tokio_postgres::Client, Error> {
     let (database_client, database_connection) =
-        tokio_postgres::connect("host=db port=5432 user=osmosis password=osmosis", tokio_postgres::NoTls)
-            .await.map_err(|_| Error::CouldNotCreateDatabaseClient)?;
+        database_config.config.connect(database_config.tls.clone())
+            .await.map_err(Error::CouldNotCreateDatabaseClient)?;
 
     tokio::spawn(async move {
         if let Err(e) = database_connection.await {
-            println!("connection error: {e}");
+            error!(error = %e, "Database connection error");
         }
     });
 
     Ok(database_client)
 }
 
-async fn index(http_client: &Client, database_client: &tokio_postgres::Client)
+/// tokio_postgres never reestablishes a dropped connection, so live indexing checks its client
+/// before each cycle and reconnects once the database is back.
+async fn reconnect_if_closed(database_config: &database::DatabaseConfig,
+                             database_client: &mut tokio_postgres::Client) {
+    if !database_client.is_closed() {
+        return;
+    }
+
+    match connect_to_database_unsafe(database_config).await {
+        Ok(c) => {
+            info!("Reconnected to database");
+            *database_client = c;
+        }
+        Err(e) => warn!(error = ?e, "Database is unavailable"),
+    }
+}
+
+/// Connection for one cycle of a recurring task, which is skipped while the database is unavailable.
+async fn check_out(pool: &ConnectionPool)
+                   -> Option<PooledConnection<'static, PostgresConnectionManager<MakeRustlsConnect>>> {
+    match pool.get_owned().await {
+        Ok(c) => Some(c),
+        Err(e) => {
+            warn!(error = ?e, "Database is unavailable");
+            None
+        }
+    }
+}
+
+/// While the database is unavailable, indexing continues from the highest height written or
+/// buffered so far and fetched batches are kept in the buffer until they can be inserted.
+async fn index(source: &impl BlockSource,
+               store: &impl Store,
+               writer: &mut BatchWriter,
+               start_mode: StartMode,
+               lowest_height: Height)
                -> Result<(), Error> {
-    let height_to_index: i64 = database_client
-        .query("SELECT max(height) FROM proposer_to_height", &[])
-        .await
-        .map_err(|_| Error::CouldNotFindIndexedHeight)?
-        .get(0)
-        .map_or_else(|| OSMOSIS_LOWEST_HEIGHT - 1, |r| {
-            r.try_get(0).map_or_else(|_| OSMOSIS_LOWEST_HEIGHT - 1, |v| v)
-        }) + 1;
+    let indexed_height = match catch_up(source, store, writer).await {
+        Ok(indexed_height) => indexed_height,
+        Err(e) if store.is_closed() || matches!(e, Error::DatabaseUnavailable(_)) => {
+            warn!(error = ?e, "Database is unavailable, buffering batches");
+            Some(writer.highest_height().ok_or(e)?)
+        }
+        Err(e) => return Err(e),
+    };
 
-    println!("height_to_index: {height_to_index}");
+    let last_height = source.request_last_height().await?;
+    info!(%last_height, "Last height of the chain");
 
-    let last_height = request_last_height(http_client).await?;
-    println!("last_height: {last_height}");
+    let height_to_index = match (indexed_height, start_mode) {
+        (Some(h), _) => h.saturating_add(1),
+        (None, StartMode::Lowest) => lowest_height,
+        (None, StartMode::Latest) => store.record_start_height(last_height).await?,
+    };
+
+    info!(%height_to_index, "Indexing");
 
     if height_to_index > last_height {
-        println!("Nothing to index");
+        info!("Nothing to index");
         return Ok(());
     }
 
-    let mut first_height_to_index = height_to_index;
+    for (first_height_to_index, last_height_to_index) in
+        ranges::ascending(height_to_index, last_height, source.heights_per_batch()) {
+        let batch = batch_span(first_height_to_index, last_height_to_index);
+        async {
+            let heights_to_index = Height::range(first_height_to_index, last_height_to_index).collect();
+            let proposers_to_height = source.request_blocks(heights_to_index).await?;
 
-    while first_height_to_index < last_height {
-        let last_height_to_index = if first_height_to_index + MAXIMUM_NUMBER_OF_PARALLEL_REQUESTS > last_height {
-            last_height
-        } else {
-            first_height_to_index + MAXIMUM_NUMBER_OF_PARALLEL_REQUESTS
-        };
+            writer.write(store, proposers_to_height).await
+        }.instrument(batch).await?;
+    }
 
-        let proposers_to_height =
-            request_proposers(http_client, first_height_to_index, last_height_to_index).await?;
+    writer.flush(store).await
+}
 
-        let query = prepare_statement(&proposers_to_height);
-        println!("query: {}", query);
+/// Writes what is left over from previous cycles, follows a reorganization of the chain and
+/// indexes requested heights. Returns the highest indexed height, which the writer keeps for
+/// when the database becomes unavailable.
+async fn catch_up(source: &impl BlockSource, store: &impl Store, writer: &mut BatchWriter)
+                  -> Result<Option<Height>, Error> {
+    writer.replay(store).await?;
+    reorgs::check(source, store).await?;
+    index_requested(source, store, writer.progress()).await?;
 
-        let count_rows_inserted = database_client
-            .execute(&query, &[])
-            .await
-            .map_err(|_| Error::CouldNotIndexDuplicateHeight)? as usize;
+    let indexed_height = store.highest_indexed_height().await?;
+    writer.record_indexed_height(indexed_height);
+    Ok(indexed_height)
+}
+
+/// Heights which API users asked for but which are not indexed yet are queued in
+/// `height_requests`. They are indexed before any other work in the cycle, up to
+/// REQUESTED_HEIGHTS_PER_INTERVAL at a time, and removed from the queue once inserted.
+/// A batch which fails is retried height by height, and heights which still fail are skipped
+/// until a later cycle rather than failing the cycle.
+async fn index_requested(source: &impl BlockSource, store: &impl Store, progress: &Progress)
+                         -> Result<(), Error> {
+    let heights_to_index = store.requested_heights(REQUESTED_HEIGHTS_PER_INTERVAL).await?;
+
+    if !heights_to_index.is_empty() {
+        info!(heights = ?heights_to_index, "Indexing requested heights");
+    }
+
+    let mut failed_heights = Vec::new();
+    for heights in heights_to_index.chunks(source.heights_per_batch() as usize) {
+        if let Err(e) = index_batch(source, store, progress, heights.iter().copied()).await {
+            warn!(error = ?e, "Could not index requested heights, retrying them one by one");
 
-        if count_rows_inserted != proposers_to_height.len() {
-            return Err(Error::InsertedIncorrectNumberOfRows);
+            for &height in heights {
+                match index_batch(source, store, progress, [height]).await {
+                    Ok(()) => store.complete_requested_heights(&[height]).await?,
+                    Err(e) => {
+                        warn!(%height, error = ?e, "Could not index requested height");
+                        failed_heights.push(height);
+                    }
+                }
+            }
+        } else {
+            store.complete_requested_heights(heights).await?;
         }
+    }
 
-        first_height_to_index = last_height_to_index;
+    let dropped_heights =
+        store.retry_requested_heights(&failed_heights, MAXIMUM_REQUESTED_HEIGHT_ATTEMPTS).await?;
+    if !dropped_heights.is_empty() {
+        warn!(heights = ?dropped_heights, "Dropped requested heights which failed too often");
     }
 
     Ok(())
 }
 
-async fn request_last_height(http_client: &Client) -> Result<i64, Error> {
-    let raw_response =
-        request(http_client.clone(), "https://rpc.osmosis.zone/blockchain".to_string())
+/// Reverse (tip-down) backfill: indexes up to BACKFILL_HEIGHTS_PER_INTERVAL heights right below
+/// the lowest indexed height, going down to `lowest_height`. Combined with starting at the
+/// latest height, the most recent blocks are available first and the history fills in over time.
+async fn backfill(rpc_client: &RpcClient,
+                  database_client: &tokio_postgres::Client,
+                  progress: &Progress,
+                  lowest_height: Height)
+                  -> Result<(), Error> {
+    let Some(lowest_indexed_height) = repository::lowest_indexed_height(database_client).await? else {
+        info!("Nothing to backfill below");
+        return Ok(());
+    };
+
+    let lowest_height_to_index =
+        lowest_height.max(lowest_indexed_height.saturating_sub(BACKFILL_HEIGHTS_PER_INTERVAL));
+    info!(%lowest_height_to_index, %lowest_indexed_height, "Backfilling");
+
+    for (first_height_to_index, last_height_to_index) in
+        ranges::descending(lowest_height_to_index, lowest_indexed_height, rpc_client.heights_per_batch()) {
+        let heights_to_index = Height::range(first_height_to_index, last_height_to_index);
+        index_batch(rpc_client, database_client, progress, heights_to_index)
+            .instrument(batch_span(first_height_to_index, last_height_to_index))
             .await?;
-    let response: BlockchainResponse = raw_response.json()
-        .await
-        .map_err(|_| Error::CouldNotParseResponseForBlockchain)?;
-    Ok(response.result.last_height)
+    }
+
+    Ok(())
 }
 
-fn prepare_statement(proposers_to_height: &Vec<ProposerToHeight>) -> String {
-    let mut query = "INSERT INTO proposer_to_height(proposer, height) VALUES".to_string();
+fn batch_span(first_height: Height, last_height: Height) -> Span {
+    info_span!("batch", %first_height, %last_height)
+}
 
-    for proposer_to_height in proposers_to_height {
-        query.push_str(&format!("('{}',{}),", proposer_to_height.proposer, proposer_to_height.height));
-    }
+async fn index_batch(source: &impl BlockSource,
+                     store: &impl Store,
+                     progress: &Progress,
+                     heights_to_index: impl IntoIterator<Item=Height>)
+                     -> Result<(), Error> {
+    let proposers_to_height = source.request_blocks(heights_to_index.into_iter().collect()).await?;
+    let written = store.insert_batch(&proposers_to_height).await?;
+    progress.record_written(proposers_to_height.len(), written);
+    Ok(())
+}
 
-    query.remove(query.len() - 1);
+/// Fetches the block at the height again and overwrites what is indexed for it.
+async fn reindex(rpc_client: &RpcClient, database_client: &tokio_postgres::Client, height: Height)
+                 -> Result<(), Error> {
+    let proposers_to_height = request_proposers(rpc_client, [height]).await?;
+    osmosis_core::repository::upsert_batch(database_client, &proposers_to_height).await?;
+    info!(%height, "Re-indexed block");
+    Ok(())
+}
 
-    query
+async fn request_last_height(rpc_client: &RpcClient) -> Result<Height, Error> {
+    let raw_response =
+        rpc_client.request_rpc("/blockchain")
+            .await?;
+    let response: serde_json::Value = raw_response.json()
+        .await
+        .map_err(|e| Error::CouldNotParseResponseForBlockchain(Box::new(e)))?;
+    rpc_client.profile().parse_last_height(&response).map_err(Error::from)
 }
 
-/// Request information about block at MAXIMUM_NUMBER_OF_PARALLEL_REQUESTS heights in parallel
+async fn request_status(rpc_client: &RpcClient) -> Result<StatusResult, Error> {
+    let raw_response =
+        rpc_client.request_rpc("/status")
+            .await?;
+    let response: StatusResponse = raw_response.json()
+        .await
+        .map_err(Error::CouldNotParseResponseForStatus)?;
+    Ok(response.result)
+}
+
+/// Request information about block at many heights in parallel
 /// I have not found endpoint which would give block info in bulk
 /// https://rpc.osmosis.zone/blockchain?minHeight=9558628 gives only 20 heights back from the top
-/// Requests are made in parallel and the number of such requests is limited to avoid overloading
-/// the server.
-async fn request_proposers(http_client: &Client,
-                           first_height_to_index: i64,
-                           last_height_to_index: i64)
-                           -> Result<Vec<ProposerToHeight>, Error> {
+/// Requests are made in parallel and the rate limiter of the RPC client keeps their rate and
+/// number low enough not to overload the server. With a batch size above 1 each request is a
+/// JSON-RPC batch of that many blocks, which saves round trips while backfilling.
+async fn request_proposers(rpc_client: &RpcClient, heights_to_index: impl IntoIterator<Item=Height>)
+                           -> Result<Vec<Block>, Error> {
     let mut set = JoinSet::new();
+    let heights_to_index: Vec<Height> = heights_to_index.into_iter().collect();
 
-    for height in first_height_to_index..last_height_to_index {
-        let request_url = format!("https://rpc.osmosis.zone/block?height={height}");
-        println!("request_url: {}", request_url);
-
-        let future_response = request(http_client.clone(), request_url);
-        set.spawn(future_response);
+    for heights in heights_to_index.chunks(rpc_client.batch_size() as usize) {
+        let heights = heights.to_vec();
+        let rpc_client = rpc_client.clone();
+        set.spawn(async move { request_blocks(&rpc_client, heights).await });
     }
 
     let mut proposers_to_height = Vec::new();
 
     while let Some(res) = set.join_next().await {
-        let raw_response = res
-            .map_err(|_| Error::CouldNotProcessResponsesInParallel)??;
+        let fetched = res.map_err(Error::CouldNotProcessResponsesInParallel)??;
 
-        let response: BlockResponse = raw_response.json()
-            .await
-            .map_err(|_| Error::CouldNotParseResponseForBlockAtHeight)?;
-        println!("{:?}", response);
+        for (height, response, fetch) in fetched {
+            rpc_client.telemetry().record(fetch);
 
-        let proposer_to_height = ProposerToHeight {
-            proposer: response.result.block.header.proposer_address,
-            height: response.result.block.header.height,
-        };
+            let mut proposer_to_height = rpc_client.profile()
+                .parse_block(&response)
+                .map_err(|e| Error::CouldNotParseResponseForBlockAtHeight { height, source: Box::new(e) })?;
+            if let Some(last_commit) = proposer_to_height.last_commit.as_mut() {
+                rpc_client.signer_sets().resolve(rpc_client, last_commit).await;
+            }
+            debug!(block = ?proposer_to_height, "Fetched block");
+            rpc_client.plugins().process_blocking(height, response).await;
 
-        proposers_to_height.push(proposer_to_height);
+            proposers_to_height.push(proposer_to_height);
+        }
     }
 
     Ok(proposers_to_height)
 }
 
-async fn request(http_client: Client, request_url: String)
-                 -> Result<reqwest::Response, Error> {
-    let request = http_client.get(request_url).build()
-        .map_err(|_| Error::CouldNotBuildHttpRequest)?;
+/// Requests the blocks in one request, as a JSON-RPC batch if there is more than one. The blocks
+/// of a batch share its duration and size in the telemetry.
+async fn request_blocks(rpc_client: &RpcClient, heights: Vec<Height>)
+                        -> Result<Vec<(Height, serde_json::Value, FetchTelemetry)>, Error> {
+    let request_paths: Vec<String> = heights.iter().map(|h| format!("/block?height={h}")).collect();
+    debug!(first_request_path = request_paths[0], blocks = heights.len(), "Requesting blocks");
 
-    let raw_response = http_client.execute(request)
+    let started = Instant::now();
+    let raw_response = match request_paths.as_slice() {
+        [request_path] => rpc_client.request_rpc(request_path).await?,
+        request_paths => rpc_client.request_rpc_batch(request_paths).await?,
+    };
+    let endpoint = raw_response.url().host_str().unwrap_or_default().to_string();
+    let body = raw_response.bytes()
         .await
-        .map_err(|_| Error::CouldNotGetResponseFromServer)?;
+        .map_err(|e| Error::CouldNotParseResponseForBlockAtHeight { height: heights[0], source: Box::new(e) })?;
+    let duration = started.elapsed();
+
+    let response: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|e| Error::CouldNotParseResponseForBlockAtHeight { height: heights[0], source: Box::new(e) })?;
+    let responses = match heights.len() {
+        1 => vec![response],
+        count => rpc::split_batch_response(response, count)?,
+    };
 
-    Ok(raw_response)
+    let response_size_in_bytes = (body.len() / heights.len()) as i32;
+    Ok(heights.into_iter()
+        .zip(responses)
+        .map(|(height, response)| {
+            let fetch = FetchTelemetry {
+                height,
+                endpoint: endpoint.clone(),
+                duration_in_milliseconds: duration.as_millis() as i32,
+                response_size_in_bytes,
+            };
+            (height, response, fetch)
+        })
+        .collect())
 }
@@ -0,0 +1,143 @@
+use tracing::info;
+
+use crate::{Error, repository};
+
+/// Version adopted without running it by databases whose schema was created before migrations
+/// were tracked, when Docker ran the initial script on the first start of the database. It is
+/// that script as first released; the next migration adds what later versions of it added, so
+/// a database created from any of them is brought up to date by the migrations which follow.
+const BASELINE_VERSION: i64 = 1;
+
+/// Versioned schema changes, applied in ascending order. A released migration never changes;
+/// changing the schema takes a new migration with the next version.
+const MIGRATIONS: [Migration; 16] = [
+    Migration { version: 1, name: "init", sql: include_str!("../../database/migrations/0001_init.sql") },
+    Migration {
+        version: 2,
+        name: "schema_before_migrations",
+        sql: include_str!("../../database/migrations/0002_schema_before_migrations.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "subsystem_backfills",
+        sql: include_str!("../../database/migrations/0003_subsystem_backfills.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "custom_records",
+        sql: include_str!("../../database/migrations/0004_custom_records.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "plugin_migrations",
+        sql: include_str!("../../database/migrations/0005_plugin_migrations.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "last_block_hash",
+        sql: include_str!("../../database/migrations/0006_last_block_hash.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "validator_set_snapshots",
+        sql: include_str!("../../database/migrations/0007_validator_set_snapshots.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "block_signatures",
+        sql: include_str!("../../database/migrations/0008_block_signatures.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "validator_tags",
+        sql: include_str!("../../database/migrations/0009_validator_tags.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "anomalies",
+        sql: include_str!("../../database/migrations/0010_anomalies.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "proposer_daily_counts",
+        sql: include_str!("../../database/migrations/0011_proposer_daily_counts.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "custom_records_archive",
+        sql: include_str!("../../database/migrations/0012_custom_records_archive.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "node_versions",
+        sql: include_str!("../../database/migrations/0013_node_versions.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "address_labels",
+        sql: include_str!("../../database/migrations/0014_address_labels.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "height_request_attempts",
+        sql: include_str!("../../database/migrations/0015_height_request_attempts.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "indexed_height_ranges",
+        sql: include_str!("../../database/migrations/0016_indexed_height_ranges.sql"),
+    },
+];
+
+#[derive(Debug)]
+struct Migration {
+    version: i64,
+    name: &'static str,
+    sql: &'static str,
+}
+
+/// Applies the migrations missing from `schema_migrations`, each recorded with its version in the
+/// same transaction. Indexers starting together wait for each other on a lock, so every
+/// migration runs once. Fails if a newer indexer applied migrations this one does not know.
+/// The publication for change data capture, if configured, is prepared under the same lock.
+pub async fn run(database_client: &mut tokio_postgres::Client, cdc_publication: Option<&str>)
+                 -> Result<(), Error> {
+    let transaction = repository::begin_migrations(database_client).await?;
+    let mut applied = repository::applied_migrations(&transaction).await?;
+
+    if applied.is_empty() && repository::table_exists(&transaction, "proposer_to_height").await? {
+        info!(version = BASELINE_VERSION, "Adopting the existing schema as baseline");
+        repository::record_migration(&transaction, BASELINE_VERSION, "baseline").await?;
+        applied.push(BASELINE_VERSION);
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        info!(version = migration.version, name = migration.name, "Applying migration");
+        transaction.batch_execute(migration.sql).await.map_err(Error::CouldNotMigrateDatabase)?;
+        repository::record_migration(&transaction, migration.version, migration.name).await?;
+    }
+
+    let known_version = MIGRATIONS.iter().map(|m| m.version).max().unwrap_or_default();
+    if let Some(version) = applied.iter().copied().filter(|v| *v > known_version).max() {
+        return Err(Error::SchemaIsNewer { version, known_version });
+    }
+
+    if let Some(publication) = cdc_publication {
+        repository::enable_change_data_capture(&transaction, publication).await?;
+        info!(publication, "Change data capture is enabled");
+    }
+
+    transaction.commit().await.map_err(Error::CouldNotMigrateDatabase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_ascend_without_gaps() {
+        let versions: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+
+        assert_eq!(versions, (1..=MIGRATIONS.len() as i64).collect::<Vec<i64>>());
+    }
+}
@@ -0,0 +1,157 @@
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{Error, repository};
+use crate::rpc::RpcClient;
+
+/// Upgrades are adopted over hours to days, so an observation an hour is precise enough.
+pub const NODE_VERSION_SNAPSHOT_INTERVAL_IN_SECONDS: u64 = 3600;
+
+#[derive(Deserialize, Debug)]
+struct NetInfoResponse {
+    result: NetInfoResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetInfoResult {
+    peers: Vec<Peer>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Peer {
+    node_info: NodeInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusResponse {
+    result: StatusResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusResult {
+    node_info: NodeInfo,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct NodeInfo {
+    id: String,
+    moniker: String,
+    /// CometBFT version of the node.
+    version: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AbciInfoResponse {
+    result: AbciInfoResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct AbciInfoResult {
+    response: AbciInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct AbciInfo {
+    /// osmosisd version of the node, missing on some nodes.
+    version: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NodeVersion {
+    pub node_id: String,
+    pub moniker: String,
+    pub version: String,
+    pub app_version: Option<String>,
+}
+
+/// Stores the versions the RPC node and its peers announce in `node_versions`, so the statistics
+/// service can follow the adoption of upgrades, matching nodes to validators by moniker. Peers
+/// only announce their CometBFT version, while the osmosisd version of the RPC node itself is
+/// taken from `/abci_info` where it answers it.
+pub async fn snapshot(rpc_client: &RpcClient, database_client: &tokio_postgres::Client) -> Result<(), Error> {
+    let status: StatusResponse = rpc_client.request_rpc("/status")
+        .await?
+        .json()
+        .await
+        .map_err(Error::CouldNotParseResponseForStatus)?;
+    let net_info: NetInfoResponse = rpc_client.request_rpc("/net_info")
+        .await?
+        .json()
+        .await
+        .map_err(Error::CouldNotParseResponseForNodeVersions)?;
+    let app_version = request_app_version(rpc_client)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(error = ?e, "Could not request application version");
+            None
+        });
+
+    let versions = node_versions(status.result.node_info, app_version, net_info.result.peers);
+    info!(nodes = versions.len(), "Taking node version snapshot");
+    repository::upsert_node_versions(database_client, &versions).await
+}
+
+async fn request_app_version(rpc_client: &RpcClient) -> Result<Option<String>, Error> {
+    let response: AbciInfoResponse = rpc_client.request_rpc("/abci_info")
+        .await?
+        .json()
+        .await
+        .map_err(Error::CouldNotParseResponseForNodeVersions)?;
+
+    Ok(response.result.response.version.filter(|v| !v.is_empty()))
+}
+
+/// The RPC node first, then its peers, each node once.
+fn node_versions(own: NodeInfo, app_version: Option<String>, peers: Vec<Peer>) -> Vec<NodeVersion> {
+    let own = NodeVersion { node_id: own.id, moniker: own.moniker, version: own.version, app_version };
+    let mut versions = vec![own];
+
+    for peer in peers {
+        if versions.iter().any(|v| v.node_id == peer.node_info.id) {
+            continue;
+        }
+        versions.push(NodeVersion {
+            node_id: peer.node_info.id,
+            moniker: peer.node_info.moniker,
+            version: peer.node_info.version,
+            app_version: None,
+        });
+    }
+
+    versions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_info(id: &str, version: &str) -> NodeInfo {
+        NodeInfo { id: id.to_string(), moniker: format!("{id}-moniker"), version: version.to_string() }
+    }
+
+    #[test]
+    fn nodes_are_listed_once_with_the_application_version_of_the_rpc_node() {
+        let peers = vec![
+            Peer { node_info: node_info("b", "0.37.4") },
+            Peer { node_info: node_info("a", "0.37.4") },
+            Peer { node_info: node_info("b", "0.37.4") },
+        ];
+
+        let versions = node_versions(node_info("a", "0.38.1"), Some("25.0.0".to_string()), peers);
+
+        assert_eq!(versions, vec![
+            NodeVersion {
+                node_id: "a".to_string(),
+                moniker: "a-moniker".to_string(),
+                version: "0.38.1".to_string(),
+                app_version: Some("25.0.0".to_string()),
+            },
+            NodeVersion {
+                node_id: "b".to_string(),
+                moniker: "b-moniker".to_string(),
+                version: "0.37.4".to_string(),
+                app_version: None,
+            },
+        ]);
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::{env, mem};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use model::Height;
+use serde_json::Value;
+use tokio::task;
+use tracing::{error, info, warn};
+
+use crate::{Error, repository};
+
+/// Records are dropped rather than buffered without bound while the database is unavailable;
+/// indexing the heights again brings them back.
+const MAXIMUM_BUFFERED_RECORDS: usize = 100_000;
+/// Postgres truncates identifiers to 63 bytes, of which the schema prefix takes some.
+const MAXIMUM_NAME_LENGTH: usize = 48;
+
+/// Block as fetched from the RPC endpoint, for plugins to extract their own data from.
+pub struct BlockData<'a> {
+    pub height: Height,
+    /// Whole `/block` response, which only plugins read.
+    #[cfg_attr(not(any(feature = "plugin-transaction-sizes", feature = "wasm-plugins")), allow(dead_code))]
+    pub response: &'a Value,
+}
+
+/// Data a plugin extracts from a block.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(not(any(feature = "plugin-transaction-sizes", feature = "wasm-plugins")), allow(dead_code))]
+pub enum Record {
+    /// Stored in `custom_records` under the key; the same key for a height again replaces it.
+    Value { key: String, value: Value },
+    /// Row of one of the plugin's own tables, as a JSON object of its columns. Every such table
+    /// has a `height` column, and rows of a height are replaced when the height is indexed again.
+    Row { table: &'static str, columns: Value },
+}
+
+/// Custom extraction logic run on every fetched block. Plugins are compiled in behind a cargo
+/// feature and listed in `compiled_in`.
+pub trait BlockProcessor: Send + Sync {
+    /// Unique name of lowercase letters, digits and underscores, which values are stored under
+    /// and which names the plugin's schema `plugin_<name>`.
+    fn name(&self) -> &'static str;
+
+    /// SQL creating and later changing the plugin's tables, run once each, in order, with its
+    /// schema first on the search path. Released migrations never change; changes are appended.
+    fn migrations(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn process(&self, block: &BlockData) -> Vec<Record>;
+}
+
+/// Record of a plugin for a height, waiting to be stored.
+pub struct PluginRecord {
+    pub plugin: &'static str,
+    pub height: Height,
+    pub record: Record,
+}
+
+/// Runs the plugins on the blocks fetched by any task sharing the RPC client and collects their
+/// records until the indexing task stores them, like fetch telemetry.
+#[derive(Clone, Default)]
+pub struct Plugins {
+    processors: Arc<Vec<Box<dyn BlockProcessor>>>,
+    records: Arc<Mutex<Vec<PluginRecord>>>,
+}
+
+impl Plugins {
+    pub fn new(processors: Vec<Box<dyn BlockProcessor>>) -> Self {
+        for processor in &processors {
+            info!(plugin = processor.name(), "Plugin is enabled");
+        }
+
+        Plugins { processors: Arc::new(processors), records: Arc::default() }
+    }
+
+    /// Runs the plugins on a blocking thread, since a plugin such as a WASM module may compute for
+    /// as long as its fuel lasts, which would hold up the other tasks of the runtime thread.
+    pub async fn process_blocking(&self, height: Height, response: Value) {
+        if self.processors.is_empty() {
+            return;
+        }
+
+        let plugins = self.clone();
+        let processed = task::spawn_blocking(move || plugins.process(&BlockData { height, response: &response }));
+        if let Err(e) = processed.await {
+            error!(%height, error = %e, "Plugins did not finish");
+        }
+    }
+
+    /// A plugin which panics loses its records for the block, while the block is indexed anyway.
+    pub fn process(&self, block: &BlockData) {
+        for processor in self.processors.iter() {
+            let records = match panic::catch_unwind(AssertUnwindSafe(|| processor.process(block))) {
+                Ok(records) => records,
+                Err(_) => {
+                    error!(plugin = processor.name(), height = %block.height, "Plugin panicked");
+                    continue;
+                }
+            };
+
+            let mut buffered = self.records.lock().unwrap();
+            if buffered.len() + records.len() > MAXIMUM_BUFFERED_RECORDS {
+                warn!(plugin = processor.name(), height = %block.height, "Dropped plugin records");
+                continue;
+            }
+            buffered.extend(records.into_iter().map(|record| PluginRecord {
+                plugin: processor.name(),
+                height: block.height,
+                record,
+            }));
+        }
+    }
+
+    /// Creates the schema of every plugin declaring tables and applies its new migrations,
+    /// recorded in `plugin_migrations`. Tables of disabled plugins are left as they are.
+    pub async fn migrate(&self, database_client: &mut tokio_postgres::Client) -> Result<(), Error> {
+        for processor in self.processors.iter().filter(|p| !p.migrations().is_empty()) {
+            let plugin = processor.name();
+            let schema = schema_name(plugin).ok_or_else(|| Error::InvalidPluginName(plugin.to_string()))?;
+
+            let transaction = repository::begin_plugin_migrations(database_client, &schema).await?;
+            let applied = repository::applied_plugin_migrations(&transaction, plugin).await?;
+
+            for (version, sql) in (1..).zip(processor.migrations()).skip(applied as usize) {
+                info!(plugin, version, "Applying plugin migration");
+                transaction.batch_execute(sql).await.map_err(Error::CouldNotMigratePlugin)?;
+                repository::record_plugin_migration(&transaction, plugin, version).await?;
+            }
+
+            transaction.commit().await.map_err(Error::CouldNotMigratePlugin)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn store(&self, database_client: &tokio_postgres::Client) -> Result<(), Error> {
+        let records = mem::take(&mut *self.records.lock().unwrap());
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let (values, rows): (Vec<PluginRecord>, Vec<PluginRecord>) =
+            records.into_iter().partition(|r| matches!(r.record, Record::Value { .. }));
+        if !values.is_empty() {
+            repository::upsert_custom_records(database_client, &values).await?;
+        }
+
+        let mut rows_per_table: BTreeMap<(&str, &str), Vec<&PluginRecord>> = BTreeMap::new();
+        for row in &rows {
+            if let Record::Row { table, .. } = row.record {
+                rows_per_table.entry((row.plugin, table)).or_default().push(row);
+            }
+        }
+
+        for ((plugin, table), rows) in rows_per_table {
+            let Some(schema) = schema_name(plugin).filter(|_| is_identifier(table)) else {
+                warn!(plugin, table, "Dropped rows of a table with an invalid name");
+                continue;
+            };
+
+            repository::replace_plugin_rows(database_client, &schema, table, &rows).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Schema holding the tables of a plugin, so plugins cannot touch each other's data or that of
+/// the indexer, and dropping the schema removes a plugin completely.
+fn schema_name(plugin: &str) -> Option<String> {
+    is_identifier(plugin).then(|| format!("plugin_{plugin}"))
+}
+
+/// Names become part of SQL, since identifiers cannot be bound parameters.
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAXIMUM_NAME_LENGTH
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Plugins compiled in, followed by the WASM plugins if they are enabled, except those named
+/// in the comma separated `PLUGINS_DISABLED`.
+pub fn enabled() -> Result<Vec<Box<dyn BlockProcessor>>, Error> {
+    #[allow(unused_mut)]
+    let mut processors = compiled_in();
+
+    #[cfg(feature = "wasm-plugins")]
+    processors.extend(crate::wasm_plugins::load_from_env()?);
+
+    let disabled = env::var("PLUGINS_DISABLED").unwrap_or_default();
+    let disabled: Vec<&str> = disabled.split(',').map(str::trim).collect();
+
+    Ok(processors
+        .into_iter()
+        .filter(|p| {
+            let enabled = !disabled.contains(&p.name());
+            if !enabled {
+                info!(plugin = p.name(), "Plugin is disabled");
+            }
+            enabled
+        })
+        .collect())
+}
+
+/// Plugins enabled by the cargo features the indexer was built with.
+pub fn compiled_in() -> Vec<Box<dyn BlockProcessor>> {
+    vec![
+        #[cfg(feature = "plugin-transaction-sizes")]
+        Box::new(crate::transaction_sizes::TransactionSizes),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct Panicking;
+
+    impl BlockProcessor for Panicking {
+        fn name(&self) -> &'static str {
+            "panicking"
+        }
+
+        fn process(&self, _: &BlockData) -> Vec<Record> {
+            panic!("plugin failure")
+        }
+    }
+
+    struct Echo;
+
+    impl BlockProcessor for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn process(&self, block: &BlockData) -> Vec<Record> {
+            vec![Record::Value { key: "height".to_string(), value: json!(block.height.value()) }]
+        }
+    }
+
+    #[test]
+    fn panicking_plugin_does_not_stop_others() {
+        let plugins = Plugins::new(vec![Box::new(Panicking), Box::new(Echo)]);
+
+        plugins.process(&BlockData { height: Height::new(7), response: &json!({}) });
+
+        let records = plugins.records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].plugin, "echo");
+        assert_eq!(records[0].record, Record::Value { key: "height".to_string(), value: json!(7) });
+    }
+
+    #[test]
+    fn only_plain_lowercase_names_become_schemas() {
+        assert_eq!(schema_name("transaction_sizes").as_deref(), Some("plugin_transaction_sizes"));
+        assert_eq!(schema_name("Sizes"), None);
+        assert_eq!(schema_name("sizes; DROP TABLE validators"), None);
+        assert_eq!(schema_name("1sizes"), None);
+        assert_eq!(schema_name(""), None);
+    }
+}
@@ -0,0 +1,87 @@
+use model::Height;
+
+/// Splits the heights from `first` up to but excluding `last` into consecutive ranges of at
+/// most `size` heights each, lowest first. Ranges are half-open like `Height::range`.
+pub fn ascending(first: Height, last: Height, size: u64) -> Vec<(Height, Height)> {
+    let mut ranges = Vec::new();
+    let mut first_of_range = first;
+
+    while first_of_range < last {
+        let last_of_range = last.min(first_of_range.saturating_add(size.max(1)));
+        ranges.push((first_of_range, last_of_range));
+        first_of_range = last_of_range;
+    }
+
+    ranges
+}
+
+/// The same split as `ascending`, but highest first, as backfill walks down the chain.
+pub fn descending(first: Height, last: Height, size: u64) -> Vec<(Height, Height)> {
+    let mut ranges = Vec::new();
+    let mut last_of_range = last;
+
+    while last_of_range > first {
+        let first_of_range = first.max(last_of_range.saturating_sub(size.max(1)));
+        ranges.push((first_of_range, last_of_range));
+        last_of_range = first_of_range;
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn covered_heights(ranges: &[(Height, Height)]) -> Vec<u64> {
+        ranges.iter().flat_map(|&(first, last)| Height::range(first, last)).map(Height::value).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn ascending_covers_every_height_once_in_order(
+            first in 0..u64::MAX / 2,
+            length in 0..2000u64,
+            size in 0..200u64,
+        ) {
+            let ranges = ascending(Height::new(first), Height::new(first + length), size);
+
+            prop_assert_eq!(covered_heights(&ranges), (first..first + length).collect::<Vec<u64>>());
+            for &(first_of_range, last_of_range) in &ranges {
+                prop_assert!(first_of_range < last_of_range);
+                prop_assert!(last_of_range.value() - first_of_range.value() <= size.max(1));
+            }
+        }
+
+        #[test]
+        fn descending_covers_every_height_once_top_down(
+            first in 0..u64::MAX / 2,
+            length in 0..2000u64,
+            size in 0..200u64,
+        ) {
+            let ranges = descending(Height::new(first), Height::new(first + length), size);
+
+            let mut heights = covered_heights(&ranges);
+            prop_assert!(ranges.windows(2).all(|w| w[1].1 == w[0].0));
+            heights.sort_unstable();
+            prop_assert_eq!(heights, (first..first + length).collect::<Vec<u64>>());
+            for &(first_of_range, last_of_range) in &ranges {
+                prop_assert!(first_of_range < last_of_range);
+                prop_assert!(last_of_range.value() - first_of_range.value() <= size.max(1));
+            }
+        }
+
+        #[test]
+        fn empty_or_inverted_bounds_give_no_ranges(
+            first in any::<u64>(),
+            below in 0..1000u64,
+            size in 0..200u64,
+        ) {
+            let last = Height::new(first.saturating_sub(below));
+            prop_assert!(ascending(Height::new(first), last, size).is_empty());
+            prop_assert!(descending(Height::new(first), last, size).is_empty());
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Utc;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use futures::{pin_mut, TryStreamExt};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{Error, repository};
+
+pub const DEFAULT_RELEASES_DIRECTORY: &str = "releases";
+/// Raised whenever files or columns of a release change incompatibly.
+const FORMAT_VERSION: u32 = 1;
+const CHECKSUMS_FILE: &str = "SHA256SUMS";
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Tables of the dataset in a release, with the columns their rows are ordered by, so that
+/// releases of the same data are identical.
+const RELEASED_TABLES: [(&str, &str); 3] = [
+    ("proposer_to_height", "height"),
+    ("validators", "address"),
+    ("genesis_validators", "chain_id, address"),
+];
+
+#[derive(Serialize, Debug)]
+struct Manifest {
+    format_version: u32,
+    /// Highest indexed height when the release was made, which names the release.
+    version: String,
+    created_at: String,
+    tables: Vec<TableFile>,
+}
+
+#[derive(Serialize, Debug)]
+struct TableFile {
+    table: String,
+    file: String,
+    rows: u64,
+    size_in_bytes: u64,
+    sha256: String,
+}
+
+/// Writes a release of the dataset to `osmosis-dataset-<version>` in the directory: one gzipped
+/// CSV file with a header per table, a `manifest.json` describing them and `SHA256SUMS` in the
+/// format `sha256sum --check` reads. All tables are read in one snapshot, so they are consistent.
+pub async fn publish(database_client: &mut tokio_postgres::Client, directory: &str) -> Result<(), Error> {
+    let transaction = repository::begin_snapshot(database_client).await?;
+
+    let version = repository::highest_indexed_height(&transaction)
+        .await?
+        .map(|h| h.to_string())
+        .ok_or(Error::NothingIndexed)?;
+    let release_directory = Path::new(directory).join(format!("osmosis-dataset-{version}"));
+    fs::create_dir_all(&release_directory).map_err(Error::CouldNotWriteRelease)?;
+
+    let mut tables = Vec::new();
+    for (table, order_by) in RELEASED_TABLES {
+        let file = format!("{table}.csv.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let rows = repository::count_rows(&transaction, table).await?;
+
+        let stream = repository::copy_table_as_csv(&transaction, table, order_by).await?;
+        pin_mut!(stream);
+        while let Some(chunk) = stream.try_next().await.map_err(Error::CouldNotCopyTable)? {
+            encoder.write_all(&chunk).map_err(Error::CouldNotWriteRelease)?;
+        }
+
+        let compressed = encoder.finish().map_err(Error::CouldNotWriteRelease)?;
+        fs::write(release_directory.join(&file), &compressed).map_err(Error::CouldNotWriteRelease)?;
+
+        info!(table, rows, "Released table");
+        tables.push(TableFile {
+            table: table.to_string(),
+            file,
+            rows,
+            size_in_bytes: compressed.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(&compressed)),
+        });
+    }
+
+    transaction.commit().await.map_err(Error::CouldNotCopyTable)?;
+
+    let checksums: String = tables
+        .iter()
+        .map(|t| format!("{}  {}\n", t.sha256, t.file))
+        .collect();
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        version,
+        created_at: Utc::now().to_rfc3339(),
+        tables,
+    };
+
+    fs::write(release_directory.join(CHECKSUMS_FILE), checksums)
+        .and_then(|_| fs::write(release_directory.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?))
+        .map_err(Error::CouldNotWriteRelease)?;
+
+    info!(directory = %release_directory.display(), version = manifest.version, "Published dataset");
+    Ok(())
+}
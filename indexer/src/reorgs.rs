@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use model::{BlockHash, Height};
+use osmosis_core::Block;
+use tracing::{debug, warn};
+
+use crate::Error;
+use crate::store::{BlockSource, Store};
+
+/// Heights below the highest indexed one searched at most for the point where the chain diverged.
+const MAXIMUM_REORG_DEPTH: u64 = 1000;
+
+/// Compares the hash of the highest indexed block with the hash the chain reports at its height.
+/// When they differ, the chain was reorganized: the indexed blocks are compared with the chain
+/// going down until their hashes agree again, and every height above that point is deleted,
+/// so live indexing re-indexes the blocks of the new branch from there.
+pub async fn check(source: &impl BlockSource, store: &impl Store) -> Result<(), Error> {
+    let Some(indexed_height) = store.highest_indexed_height().await? else {
+        return Ok(());
+    };
+
+    let lowest_height = indexed_height.saturating_sub(MAXIMUM_REORG_DEPTH);
+    let mut last_height = indexed_height.saturating_add(1);
+    // Only the highest block is compared unless it differs.
+    let mut heights_per_request = 1;
+
+    while last_height > lowest_height {
+        let first_height = lowest_height.max(last_height.saturating_sub(heights_per_request));
+        let indexed = store.block_hashes(first_height, last_height).await?;
+        let fetched = source.request_blocks(Height::range(first_height, last_height).collect()).await?;
+
+        if let Some(divergence_height) = highest_agreeing_height(&indexed, &fetched) {
+            if divergence_height == indexed_height {
+                debug!(%indexed_height, "Highest indexed block is on the chain");
+                return Ok(());
+            }
+
+            warn!(%divergence_height, %indexed_height, "Chain was reorganized, re-indexing above the divergence");
+            return store.delete_blocks_above(divergence_height).await;
+        }
+
+        last_height = first_height;
+        heights_per_request = source.heights_per_batch();
+    }
+
+    Err(Error::CouldNotFindReorgDivergence { height: lowest_height })
+}
+
+/// Highest height whose indexed hash is the one on the chain. Blocks indexed before hashes were
+/// recorded cannot be compared and are taken to agree.
+fn highest_agreeing_height(indexed: &HashMap<Height, Option<BlockHash>>, fetched: &[Block]) -> Option<Height> {
+    fetched.iter()
+        .filter(|b| match indexed.get(&b.height) {
+            Some(Some(hash)) => *hash == b.hash,
+            Some(None) => true,
+            None => false,
+        })
+        .map(|b| b.height)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: char) -> BlockHash {
+        byte.to_string().repeat(64).parse().unwrap()
+    }
+
+    fn block(height: u64, hash: BlockHash) -> Block {
+        Block {
+            proposer: "0123456789ABCDEF0123456789ABCDEF01234567".parse().unwrap(),
+            height: Height::new(height),
+            hash,
+            last_block_hash: None,
+            block_time: Default::default(),
+            num_txs: 0,
+            last_commit: None,
+        }
+    }
+
+    #[test]
+    fn divergence_is_the_highest_height_with_the_same_hash() {
+        let indexed = HashMap::from([
+            (Height::new(10), Some(hash('A'))),
+            (Height::new(11), Some(hash('B'))),
+            (Height::new(12), Some(hash('C'))),
+        ]);
+        let fetched = [block(10, hash('A')), block(11, hash('D')), block(12, hash('E'))];
+
+        assert_eq!(highest_agreeing_height(&indexed, &fetched), Some(Height::new(10)));
+        assert_eq!(highest_agreeing_height(&indexed, &fetched[1..]), None);
+    }
+}
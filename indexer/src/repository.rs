@@ -0,0 +1,918 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use model::{BlockHash, ChainId, ConsensusAddress, Height};
+use serde_json::Value;
+use tokio_postgres::IsolationLevel;
+use tokio_postgres::{CopyOutStream, GenericClient, Row, Transaction};
+use tokio_postgres::types::FromSql;
+
+use osmosis_core::Block;
+
+use crate::Error;
+use crate::anomalies::{Anomaly, AnomalyKind, ProposalExpectation};
+use crate::checkpoints::{Checkpoint, CheckpointRange};
+use crate::genesis::ValidatorMetadata;
+use crate::labels::AddressLabel;
+use crate::node_versions::NodeVersion;
+use crate::plugins::{PluginRecord, Record};
+use crate::telemetry::FetchTelemetry;
+use crate::validator_sets::SnapshotValidator;
+use crate::validators::{CommissionChange, Validator, ValidatorCommission};
+
+pub async fn highest_indexed_height(database_client: &impl GenericClient) -> Result<Option<Height>, Error> {
+    let row = database_client
+        .query_one("SELECT max(height) FROM proposer_to_height", &[])
+        .await
+        .map_err(Error::CouldNotFindIndexedHeight)?;
+
+    get_height(&row, 0)
+}
+
+pub async fn lowest_indexed_height(database_client: &tokio_postgres::Client) -> Result<Option<Height>, Error> {
+    let row = database_client
+        .query_one("SELECT min(height) FROM proposer_to_height", &[])
+        .await
+        .map_err(Error::CouldNotFindIndexedHeight)?;
+
+    get_height(&row, 0)
+}
+
+/// Returns inclusive ranges of heights missing below the highest indexed height in ascending order,
+/// starting at `lowest_height` if given and at the lowest indexed height otherwise.
+pub async fn find_gaps(database_client: &tokio_postgres::Client, lowest_height: Option<Height>)
+                       -> Result<Vec<(Height, Height)>, Error> {
+    let rows = database_client
+        .query("SELECT height + 1, next_height - 1 FROM \
+                (SELECT height, lead(height) OVER (ORDER BY height) AS next_height FROM \
+                 (SELECT height FROM proposer_to_height WHERE $1::bigint IS NULL OR height >= $1 \
+                  UNION ALL SELECT $1 - 1 WHERE $1::bigint IS NOT NULL) AS i) AS h \
+                WHERE next_height > height + 1 ORDER BY height",
+               &[&lowest_height])
+        .await
+        .map_err(Error::CouldNotFindGaps)?;
+
+    rows.iter().map(|r| Ok((get_height(r, 0)?, get_height(r, 1)?))).collect()
+}
+
+/// Returns up to `limit` of the longest waiting heights in `height_requests` which are not indexed
+/// yet, in ascending order, after removing requests for heights indexed meanwhile. Heights above
+/// the highest indexed height are left to live indexing, which indexes them in order.
+/// Requests stay queued until they are completed or dropped, so none is lost when indexing fails.
+pub async fn requested_heights(database_client: &tokio_postgres::Client, limit: i64)
+                               -> Result<Vec<Height>, Error> {
+    database_client
+        .execute("DELETE FROM height_requests r USING proposer_to_height p WHERE p.height = r.height", &[])
+        .await
+        .map_err(Error::CouldNotTakeRequestedHeights)?;
+
+    let rows = database_client
+        .query("SELECT height FROM \
+                (SELECT height FROM height_requests \
+                 WHERE height < (SELECT max(height) FROM proposer_to_height) \
+                 ORDER BY requested_at LIMIT $1) AS r \
+                ORDER BY height",
+               &[&limit])
+        .await
+        .map_err(Error::CouldNotTakeRequestedHeights)?;
+
+    rows.iter().map(|r| get_height(r, 0)).collect()
+}
+
+pub async fn complete_requested_heights(database_client: &tokio_postgres::Client, heights: &[Height])
+                                        -> Result<(), Error> {
+    database_client
+        .execute("DELETE FROM height_requests WHERE height = ANY($1)", &[&heights])
+        .await
+        .map_err(Error::CouldNotTakeRequestedHeights)?;
+
+    Ok(())
+}
+
+/// Moves heights which could not be indexed to the back of the queue, and drops those which
+/// failed `maximum_attempts` times. Returns the dropped heights.
+pub async fn retry_requested_heights(database_client: &tokio_postgres::Client,
+                                     heights: &[Height],
+                                     maximum_attempts: i32)
+                                     -> Result<Vec<Height>, Error> {
+    let rows = database_client
+        .query("DELETE FROM height_requests WHERE height = ANY($1) AND attempts + 1 >= $2 RETURNING height",
+               &[&heights, &maximum_attempts])
+        .await
+        .map_err(Error::CouldNotTakeRequestedHeights)?;
+
+    database_client
+        .execute("UPDATE height_requests SET attempts = attempts + 1, requested_at = now() \
+                  WHERE height = ANY($1)",
+                 &[&heights])
+        .await
+        .map_err(Error::CouldNotTakeRequestedHeights)?;
+
+    rows.iter().map(|r| get_height(r, 0)).collect()
+}
+
+/// Takes the session lock which the replica indexing the tip of the chain holds, unless another
+/// session holds it. It is released when the connection closes, so another replica takes over.
+pub async fn try_lock_live_indexing(database_client: &tokio_postgres::Client) -> Result<bool, Error> {
+    let row = database_client
+        .query_one("SELECT pg_try_advisory_lock(hashtext('live_indexing'))", &[])
+        .await
+        .map_err(Error::CouldNotLockLiveIndexing)?;
+
+    Ok(row.get(0))
+}
+
+/// Hashes of the indexed blocks in `[first_height, last_height)`, which are missing for blocks
+/// indexed before they were recorded.
+pub async fn block_hashes(database_client: &tokio_postgres::Client, first_height: Height, last_height: Height)
+                          -> Result<HashMap<Height, Option<BlockHash>>, Error> {
+    let rows = database_client
+        .query("SELECT height, hash FROM proposer_to_height WHERE height >= $1 AND height < $2",
+               &[&first_height, &last_height])
+        .await
+        .map_err(Error::CouldNotFindIndexedHeight)?;
+
+    rows.iter().map(|r| Ok((get_height(r, 0)?, r.get(1)))).collect()
+}
+
+/// Deletes the blocks above the height together with the records plugins made of them and the
+/// signatures their commits carried.
+pub async fn delete_blocks_above(database_client: &tokio_postgres::Client, height: Height) -> Result<(), Error> {
+    database_client
+        .execute("WITH records AS (DELETE FROM custom_records WHERE height > $1), \
+                  signatures AS (DELETE FROM block_signatures WHERE height >= $1) \
+                  DELETE FROM proposer_to_height WHERE height > $1",
+                 &[&height])
+        .await
+        .map_err(Error::CouldNotRollBackReorg)?;
+
+    Ok(())
+}
+
+/// Records the height at which indexing started, unless it has been recorded before,
+/// and returns the recorded height.
+pub async fn record_start_height(database_client: &tokio_postgres::Client, height: Height)
+                                 -> Result<Height, Error> {
+    database_client
+        .execute("INSERT INTO indexer_state(name, height) VALUES ('start_height', $1) \
+                  ON CONFLICT (name) DO NOTHING", &[&height])
+        .await
+        .map_err(Error::CouldNotRecordStartHeight)?;
+
+    let row = database_client
+        .query_one("SELECT height FROM indexer_state WHERE name = 'start_height'", &[])
+        .await
+        .map_err(Error::CouldNotRecordStartHeight)?;
+
+    get_height(&row, 0)
+}
+
+pub async fn count_genesis_validators(database_client: &tokio_postgres::Client, chain_id: &ChainId)
+                                      -> Result<i64, Error> {
+    let row = database_client
+        .query_one("SELECT count(*) FROM genesis_validators WHERE chain_id = $1", &[&chain_id])
+        .await
+        .map_err(Error::CouldNotImportGenesis)?;
+
+    Ok(row.get(0))
+}
+
+pub async fn insert_genesis_validators(database_client: &tokio_postgres::Client,
+                                       chain_id: &ChainId,
+                                       validators: &[ValidatorMetadata])
+                                       -> Result<(), Error> {
+    let addresses: Vec<&ConsensusAddress> = validators.iter().map(|v| &v.address).collect();
+    let monikers: Vec<&str> = validators.iter().map(|v| v.moniker.as_str()).collect();
+    let operator_addresses: Vec<Option<&str>> =
+        validators.iter().map(|v| v.operator_address.as_deref()).collect();
+    let voting_powers: Vec<i64> = validators.iter().map(|v| v.voting_power).collect();
+
+    database_client
+        .execute("INSERT INTO genesis_validators(chain_id, address, moniker, operator_address, voting_power) \
+                  SELECT $1, * FROM UNNEST($2::varchar[], $3::varchar[], $4::varchar[], $5::bigint[]) \
+                  ON CONFLICT DO NOTHING",
+                 &[&chain_id, &addresses, &monikers, &operator_addresses, &voting_powers])
+        .await
+        .map_err(Error::CouldNotImportGenesis)?;
+
+    Ok(())
+}
+
+pub async fn upsert_validators(database_client: &tokio_postgres::Client, validators: &[Validator])
+                               -> Result<(), Error> {
+    let addresses: Vec<&ConsensusAddress> = validators.iter().map(|v| &v.address).collect();
+    let operator_addresses: Vec<&str> = validators.iter().map(|v| v.operator_address.as_str()).collect();
+    let monikers: Vec<&str> = validators.iter().map(|v| v.moniker.as_str()).collect();
+    let identities: Vec<Option<&str>> = validators.iter().map(|v| v.identity.as_deref()).collect();
+    let commission_rates: Vec<Option<&str>> =
+        validators.iter().map(|v| v.commission.as_ref().map(|c| c.rate.as_str())).collect();
+
+    // The cached avatar belongs to the previous identity when it changes.
+    database_client
+        .execute("INSERT INTO validators(address, operator_address, moniker, identity, commission_rate) \
+                  SELECT * FROM UNNEST($1::varchar[], $2::varchar[], $3::varchar[], $4::varchar[], \
+                                       $5::varchar[]::numeric[]) \
+                  ON CONFLICT (address) DO UPDATE SET operator_address = excluded.operator_address, \
+                  moniker = excluded.moniker, identity = excluded.identity, \
+                  commission_rate = excluded.commission_rate, updated_at = now(), \
+                  avatar_updated_at = CASE WHEN validators.identity IS DISTINCT FROM excluded.identity \
+                                      THEN NULL ELSE validators.avatar_updated_at END",
+                 &[&addresses, &operator_addresses, &monikers, &identities, &commission_rates])
+        .await
+        .map_err(Error::CouldNotRefreshValidators)?;
+
+    Ok(())
+}
+
+/// A snapshot already taken at the same height, by another replica or before a restart, is kept.
+pub async fn insert_validator_set_snapshot(database_client: &tokio_postgres::Client,
+                                           height: Height,
+                                           validators: &[SnapshotValidator])
+                                           -> Result<(), Error> {
+    let addresses: Vec<&ConsensusAddress> = validators.iter().map(|v| &v.address).collect();
+    let voting_powers: Vec<i64> = validators.iter().map(|v| v.voting_power).collect();
+    let ranks: Vec<i32> = validators.iter().map(|v| v.rank).collect();
+
+    database_client
+        .execute("INSERT INTO validator_set_snapshots(height, address, voting_power, rank) \
+                  SELECT $1, * FROM UNNEST($2::varchar[], $3::bigint[], $4::integer[]) \
+                  ON CONFLICT DO NOTHING",
+                 &[&height, &addresses, &voting_powers, &ranks])
+        .await
+        .map_err(Error::CouldNotStoreValidatorSetSnapshot)?;
+
+    Ok(())
+}
+
+/// Records the nodes as seen today, keeping when each was first seen with a version that day.
+pub async fn upsert_node_versions(database_client: &tokio_postgres::Client, versions: &[NodeVersion])
+                                  -> Result<(), Error> {
+    let node_ids: Vec<&str> = versions.iter().map(|v| v.node_id.as_str()).collect();
+    let monikers: Vec<&str> = versions.iter().map(|v| v.moniker.as_str()).collect();
+    let node_versions: Vec<&str> = versions.iter().map(|v| v.version.as_str()).collect();
+    let app_versions: Vec<Option<&str>> = versions.iter().map(|v| v.app_version.as_deref()).collect();
+
+    database_client
+        .execute("INSERT INTO node_versions(day, node_id, moniker, version, app_version) \
+                  SELECT (now() AT TIME ZONE 'UTC')::date, * \
+                  FROM UNNEST($1::varchar[], $2::varchar[], $3::varchar[], $4::varchar[]) \
+                  ON CONFLICT (day, node_id, version) DO UPDATE SET \
+                  moniker = excluded.moniker, \
+                  app_version = COALESCE(excluded.app_version, node_versions.app_version), \
+                  last_seen_at = now()",
+                 &[&node_ids, &monikers, &node_versions, &app_versions])
+        .await
+        .map_err(Error::CouldNotStoreNodeVersions)?;
+
+    Ok(())
+}
+
+/// Labels of the community source which are no longer in it are removed, while labels set by
+/// an admin are neither overwritten nor removed.
+pub async fn replace_community_labels(database_client: &tokio_postgres::Client, labels: &[AddressLabel])
+                                      -> Result<(), Error> {
+    let addresses: Vec<&str> = labels.iter().map(|l| l.address.as_str()).collect();
+    let names: Vec<&str> = labels.iter().map(|l| l.label.as_str()).collect();
+    let categories: Vec<Option<&str>> = labels.iter().map(|l| l.category.as_deref()).collect();
+
+    database_client
+        .execute("WITH removed AS ( \
+                      DELETE FROM address_labels WHERE source = 'community' AND NOT address = ANY($1)) \
+                  INSERT INTO address_labels(address, label, category, source) \
+                  SELECT *, 'community' FROM UNNEST($1::varchar[], $2::varchar[], $3::varchar[]) \
+                  ON CONFLICT (address) DO UPDATE SET \
+                  label = excluded.label, category = excluded.category, updated_at = now() \
+                  WHERE address_labels.source = 'community' \
+                  AND (address_labels.label, address_labels.category) \
+                      IS DISTINCT FROM (excluded.label, excluded.category)",
+                 &[&addresses, &names, &categories])
+        .await
+        .map_err(Error::CouldNotStoreLabels)?;
+
+    Ok(())
+}
+
+/// Proposals and expected proposals of every validator of a snapshot over the latest `window`
+/// blocks, each block weighted by the shares in the latest snapshot at or below it.
+pub async fn proposal_expectations(database_client: &tokio_postgres::Client, window: i64)
+                                   -> Result<Vec<ProposalExpectation>, Error> {
+    let rows = database_client
+        .query("WITH recent AS ( \
+                    SELECT height, proposer FROM proposer_to_height \
+                    WHERE height > (SELECT max(height) FROM proposer_to_height) - $1), \
+                snapshots AS ( \
+                    SELECT height, lead(height) OVER (ORDER BY height) AS next_height \
+                    FROM (SELECT DISTINCT height FROM validator_set_snapshots) AS s), \
+                covered AS ( \
+                    SELECT r.height, r.proposer, s.height AS snapshot_height \
+                    FROM recent r JOIN snapshots s ON r.height >= s.height \
+                        AND (s.next_height IS NULL OR r.height < s.next_height)), \
+                per_snapshot AS ( \
+                    SELECT snapshot_height, count(*) AS blocks, min(height) AS first_height, \
+                        max(height) AS last_height \
+                    FROM covered GROUP BY snapshot_height), \
+                shares AS ( \
+                    SELECT height, address, \
+                        voting_power::float8 / sum(voting_power) OVER (PARTITION BY height) AS share \
+                    FROM validator_set_snapshots WHERE height IN (SELECT snapshot_height FROM per_snapshot)) \
+                SELECT s.address, min(p.first_height), max(p.last_height), \
+                    (SELECT count(*) FROM covered c WHERE c.proposer = s.address), \
+                    sum(p.blocks * s.share), sum(p.blocks * s.share * (1 - s.share)) \
+                FROM shares s JOIN per_snapshot p ON p.snapshot_height = s.height \
+                GROUP BY s.address",
+               &[&window])
+        .await
+        .map_err(Error::CouldNotDetectAnomalies)?;
+
+    rows.iter()
+        .map(|r| Ok(ProposalExpectation {
+            address: r.get(0),
+            first_height: get_height(r, 1)?,
+            last_height: get_height(r, 2)?,
+            proposals: r.get(3),
+            expected_proposals: r.get(4),
+            variance: r.get(5),
+        }))
+        .collect()
+}
+
+/// Runs of at least `minimum_blocks` consecutive stored heights among the latest `window` which
+/// a validator of the signer set did not sign.
+pub async fn missed_signature_bursts(database_client: &tokio_postgres::Client, window: i64, minimum_blocks: i64)
+                                     -> Result<Vec<Anomaly>, Error> {
+    let rows = database_client
+        .query("WITH votes AS ( \
+                    SELECT b.height, a.address, get_bit(b.signed, (a.position - 1)::integer) = 1 AS signed \
+                    FROM block_signatures b JOIN signer_sets s ON s.id = b.signer_set_id \
+                    CROSS JOIN LATERAL unnest(s.addresses) WITH ORDINALITY AS a(address, position) \
+                    WHERE b.height > (SELECT max(height) FROM block_signatures) - $1), \
+                runs AS ( \
+                    SELECT address, height, signed, \
+                        height - row_number() OVER (PARTITION BY address, signed ORDER BY height) AS run \
+                    FROM votes) \
+                SELECT address, min(height), max(height), count(*) FROM runs WHERE NOT signed \
+                GROUP BY address, run HAVING count(*) >= $2",
+               &[&window, &minimum_blocks])
+        .await
+        .map_err(Error::CouldNotDetectAnomalies)?;
+
+    rows.iter()
+        .map(|r| Ok(Anomaly {
+            kind: AnomalyKind::MissedSignaturesBurst,
+            address: r.get(0),
+            first_height: get_height(r, 1)?,
+            last_height: get_height(r, 2)?,
+            score: r.get::<_, i64>(3) as f64,
+        }))
+        .collect()
+}
+
+/// Extends the latest anomaly of the same kind and validator if the two overlap, so alerting
+/// sees it once, and records a new one otherwise.
+pub async fn record_anomaly(database_client: &tokio_postgres::Client, anomaly: &Anomaly) -> Result<(), Error> {
+    database_client
+        .execute("WITH extended AS ( \
+                      UPDATE anomalies SET last_height = greatest(last_height, $4), score = $5 \
+                      WHERE id = (SELECT id FROM anomalies \
+                                  WHERE kind = $1 AND address = $2 AND last_height >= $3 \
+                                  ORDER BY id DESC LIMIT 1) \
+                      RETURNING id) \
+                  INSERT INTO anomalies(kind, address, first_height, last_height, score) \
+                  SELECT $1, $2, $3, $4, $5 WHERE NOT EXISTS (SELECT 1 FROM extended)",
+                 &[&anomaly.kind.name(),
+                   &anomaly.address,
+                   &anomaly.first_height,
+                   &anomaly.last_height,
+                   &anomaly.score])
+        .await
+        .map_err(Error::CouldNotDetectAnomalies)?;
+
+    Ok(())
+}
+
+/// Records every commission rate which differs from the stored one before the validators are
+/// updated. Validators seen for the first time have no previous rate to compare with.
+pub async fn record_commission_changes(database_client: &tokio_postgres::Client, validators: &[Validator])
+                                       -> Result<Vec<CommissionChange>, Error> {
+    let (addresses, commissions): (Vec<&ConsensusAddress>, Vec<&ValidatorCommission>) = validators
+        .iter()
+        .filter_map(|v| Some((&v.address, v.commission.as_ref()?)))
+        .unzip();
+    let rates: Vec<&str> = commissions.iter().map(|c| c.rate.as_str()).collect();
+    let updated_at: Vec<DateTime<Utc>> = commissions.iter().map(|c| c.updated_at).collect();
+
+    let rows = database_client
+        .query("INSERT INTO commission_changes(address, old_rate, new_rate, height, changed_at) \
+                SELECT v.address, v.commission_rate, c.rate, \
+                       COALESCE((SELECT min(height) FROM proposer_to_height WHERE block_time >= c.changed_at), \
+                                (SELECT max(height) FROM proposer_to_height)), \
+                       c.changed_at \
+                FROM UNNEST($1::varchar[], $2::varchar[]::numeric[], $3::timestamptz[]) \
+                     AS c(address, rate, changed_at) \
+                JOIN validators v ON v.address = c.address \
+                WHERE v.commission_rate <> c.rate \
+                RETURNING address, old_rate::text, new_rate::text, height",
+               &[&addresses, &rates, &updated_at])
+        .await
+        .map_err(Error::CouldNotRefreshValidators)?;
+
+    rows.iter()
+        .map(|r| Ok(CommissionChange {
+            address: r.try_get(0).map_err(Error::CouldNotRefreshValidators)?,
+            old_rate: r.get(1),
+            new_rate: r.get(2),
+            height: r.get(3),
+        }))
+        .collect()
+}
+
+/// Returns identities whose avatar was never resolved or is older than the maximum age,
+/// never resolved first.
+pub async fn identities_to_resolve(database_client: &tokio_postgres::Client,
+                                   maximum_age_in_hours: i32,
+                                   limit: i64)
+                                   -> Result<Vec<String>, Error> {
+    let rows = database_client
+        .query("SELECT identity FROM validators WHERE identity IS NOT NULL \
+                GROUP BY identity \
+                HAVING bool_or(avatar_updated_at IS NULL) \
+                    OR min(avatar_updated_at) < now() - $1 * interval '1 hour' \
+                ORDER BY min(avatar_updated_at) NULLS FIRST LIMIT $2",
+               &[&maximum_age_in_hours, &limit])
+        .await
+        .map_err(Error::CouldNotRefreshValidators)?;
+
+    Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+/// Caches the avatar of every validator with the identity; `None` if Keybase has no picture.
+pub async fn update_avatar(database_client: &tokio_postgres::Client, identity: &str, avatar_url: Option<&str>)
+                           -> Result<(), Error> {
+    database_client
+        .execute("UPDATE validators SET avatar_url = $2, avatar_updated_at = now(), \
+                  updated_at = CASE WHEN avatar_url IS DISTINCT FROM $2 THEN now() ELSE updated_at END \
+                  WHERE identity = $1",
+                 &[&identity, &avatar_url])
+        .await
+        .map_err(Error::CouldNotRefreshValidators)?;
+
+    Ok(())
+}
+
+pub async fn insert_fetch_telemetry(database_client: &tokio_postgres::Client, fetches: &[FetchTelemetry])
+                                   -> Result<(), Error> {
+    let heights: Vec<Height> = fetches.iter().map(|f| f.height).collect();
+    let endpoints: Vec<&str> = fetches.iter().map(|f| f.endpoint.as_str()).collect();
+    let durations: Vec<i32> = fetches.iter().map(|f| f.duration_in_milliseconds).collect();
+    let sizes: Vec<i32> = fetches.iter().map(|f| f.response_size_in_bytes).collect();
+
+    database_client
+        .execute("INSERT INTO fetch_telemetry \
+                  (height, endpoint, duration_in_milliseconds, response_size_in_bytes) \
+                  SELECT * FROM UNNEST($1::bigint[], $2::varchar[], $3::integer[], $4::integer[])",
+                 &[&heights, &endpoints, &durations, &sizes])
+        .await
+        .map_err(Error::CouldNotStoreTelemetry)?;
+
+    Ok(())
+}
+
+/// Highest height whose block is older than the months, or none if no such block is indexed.
+pub async fn highest_height_older_than(database_client: &tokio_postgres::Client, months: i32)
+                                       -> Result<Option<Height>, Error> {
+    let row = database_client
+        .query_one("SELECT max(height) FROM proposer_to_height \
+                    WHERE block_time < now() - make_interval(months => $1)",
+                   &[&months])
+        .await
+        .map_err(Error::CouldNotFindIndexedHeight)?;
+
+    get_height(&row, 0)
+}
+
+/// Moves the values of the `heights` heights from the lowest one in `custom_records`, up to
+/// `last_height`, into one chunk of `custom_records_archive` per plugin in a single statement.
+/// Returns the number of chunks written, which is 0 once nothing is left to archive.
+pub async fn archive_custom_records(database_client: &tokio_postgres::Client, last_height: Height, heights: i64)
+                                    -> Result<u64, Error> {
+    database_client
+        .execute("WITH moved AS ( \
+                      DELETE FROM custom_records \
+                      WHERE height <= $1 AND height < (SELECT min(height) FROM custom_records) + $2 \
+                      RETURNING plugin, height, key, value) \
+                  INSERT INTO custom_records_archive(plugin, first_height, last_height, records) \
+                  SELECT plugin, min(height), max(height), \
+                      jsonb_agg(jsonb_build_object('height', height, 'key', key, 'value', value) \
+                                ORDER BY height, key) \
+                  FROM moved GROUP BY plugin",
+                 &[&last_height, &heights])
+        .await
+        .map_err(Error::CouldNotArchiveRecords)
+}
+
+/// Values are bound as text and cast, since they are JSON of any shape.
+pub async fn upsert_custom_records(database_client: &tokio_postgres::Client, records: &[PluginRecord])
+                                   -> Result<(), Error> {
+    let values: Vec<(&PluginRecord, &str, String)> = records
+        .iter()
+        .filter_map(|r| match &r.record {
+            Record::Value { key, value } => Some((r, key.as_str(), value.to_string())),
+            Record::Row { .. } => None,
+        })
+        .collect();
+    let plugins: Vec<&str> = values.iter().map(|(r, _, _)| r.plugin).collect();
+    let heights: Vec<Height> = values.iter().map(|(r, _, _)| r.height).collect();
+    let keys: Vec<&str> = values.iter().map(|(_, k, _)| *k).collect();
+    let values: Vec<&str> = values.iter().map(|(_, _, v)| v.as_str()).collect();
+
+    database_client
+        .execute("INSERT INTO custom_records(plugin, height, key, value) \
+                  SELECT * FROM UNNEST($1::varchar[], $2::bigint[], $3::varchar[], $4::text[]::jsonb[]) \
+                  ON CONFLICT (plugin, height, key) DO UPDATE SET value = excluded.value",
+                 &[&plugins, &heights, &keys, &values])
+        .await
+        .map_err(Error::CouldNotStorePluginRecords)?;
+
+    Ok(())
+}
+
+/// Replaces the rows of the heights in a plugin's table with the new ones, turning JSON objects
+/// into rows by the names of the columns. Schema and table are validated identifiers.
+pub async fn replace_plugin_rows(database_client: &tokio_postgres::Client,
+                                 schema: &str,
+                                 table: &str,
+                                 rows: &[&PluginRecord])
+                                 -> Result<(), Error> {
+    let heights: Vec<Height> = rows.iter().map(|r| r.height).collect();
+    let columns: Vec<Value> = rows
+        .iter()
+        .filter_map(|r| match &r.record {
+            Record::Row { columns, .. } => Some(columns.clone()),
+            Record::Value { .. } => None,
+        })
+        .collect();
+    let columns = Value::Array(columns).to_string();
+
+    database_client
+        .execute(&format!("WITH replaced AS (DELETE FROM {schema}.{table} WHERE height = ANY($1)) \
+                           INSERT INTO {schema}.{table} \
+                           SELECT * FROM jsonb_populate_recordset(NULL::{schema}.{table}, $2::text::jsonb)"),
+                 &[&heights, &columns])
+        .await
+        .map_err(Error::CouldNotStorePluginRecords)?;
+
+    Ok(())
+}
+
+/// Creates the schema of a plugin, readable like the rest of the dataset, and puts it first on
+/// the search path of the transaction, so migrations create their tables in it. The lock keeps
+/// indexers starting together from migrating at the same time.
+pub async fn begin_plugin_migrations<'a>(database_client: &'a mut tokio_postgres::Client, schema: &str)
+                                         -> Result<Transaction<'a>, Error> {
+    let transaction = database_client.transaction().await.map_err(Error::CouldNotMigratePlugin)?;
+
+    transaction
+        .batch_execute(&format!("SELECT pg_advisory_xact_lock(hashtext('plugin_migrations')); \
+                                 CREATE SCHEMA IF NOT EXISTS {schema}; \
+                                 GRANT USAGE ON SCHEMA {schema} TO statistics_readonly; \
+                                 ALTER DEFAULT PRIVILEGES IN SCHEMA {schema} \
+                                     GRANT SELECT ON TABLES TO statistics_readonly; \
+                                 SET LOCAL search_path TO {schema}, public"))
+        .await
+        .map_err(Error::CouldNotMigratePlugin)?;
+
+    Ok(transaction)
+}
+
+pub async fn applied_plugin_migrations(transaction: &Transaction<'_>, plugin: &str) -> Result<i64, Error> {
+    let row = transaction
+        .query_one("SELECT COALESCE(max(version), 0) FROM plugin_migrations WHERE plugin = $1", &[&plugin])
+        .await
+        .map_err(Error::CouldNotMigratePlugin)?;
+
+    Ok(row.get(0))
+}
+
+pub async fn record_plugin_migration(transaction: &Transaction<'_>, plugin: &str, version: i64)
+                                     -> Result<(), Error> {
+    transaction
+        .execute("INSERT INTO plugin_migrations(plugin, version) VALUES ($1, $2)", &[&plugin, &version])
+        .await
+        .map_err(Error::CouldNotMigratePlugin)?;
+
+    Ok(())
+}
+
+/// Inserts the checkpoint at the start of its range, or updates the range of an existing one
+/// while keeping its cursor.
+pub async fn define_checkpoint(database_client: &tokio_postgres::Client, name: &str, range: &CheckpointRange)
+                               -> Result<(), Error> {
+    database_client
+        .execute("INSERT INTO indexer_checkpoints(name, first_height, last_height, cursor) \
+                  VALUES ($1, $2, $3, $2) \
+                  ON CONFLICT (name) DO UPDATE SET first_height = excluded.first_height, \
+                  last_height = excluded.last_height, cursor = greatest(indexer_checkpoints.cursor, \
+                  excluded.first_height), updated_at = now()",
+                 &[&name, &range.first_height, &range.last_height])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    Ok(())
+}
+
+pub async fn incomplete_checkpoints(database_client: &tokio_postgres::Client, names: &[String])
+                                   -> Result<Vec<Checkpoint>, Error> {
+    let rows = database_client
+        .query("SELECT name, first_height, last_height, cursor FROM indexer_checkpoints \
+                WHERE cursor <= last_height AND name = ANY($1) ORDER BY name",
+               &[&names])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    rows.iter()
+        .map(|r| Ok(Checkpoint {
+            name: r.get(0),
+            first_height: get_height(r, 1)?,
+            last_height: get_height(r, 2)?,
+            cursor: get_height(r, 3)?,
+        }))
+        .collect()
+}
+
+/// Locks an incomplete checkpoint with one of the names for the rest of the transaction,
+/// skipping those locked by other transactions.
+pub async fn claim_checkpoint(transaction: &Transaction<'_>, names: &[String])
+                              -> Result<Option<Checkpoint>, Error> {
+    let row = transaction
+        .query_opt("SELECT name, first_height, last_height, cursor FROM indexer_checkpoints \
+                    WHERE cursor <= last_height AND name = ANY($1) ORDER BY name \
+                    LIMIT 1 FOR UPDATE SKIP LOCKED",
+                   &[&names])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    Ok(Some(Checkpoint {
+        name: row.get(0),
+        first_height: get_height(&row, 1)?,
+        last_height: get_height(&row, 2)?,
+        cursor: get_height(&row, 3)?,
+    }))
+}
+
+pub async fn move_checkpoint(database_client: &impl GenericClient, name: &str, cursor: Height)
+                             -> Result<(), Error> {
+    database_client
+        .execute("UPDATE indexer_checkpoints SET cursor = $2, updated_at = now() WHERE name = $1",
+                 &[&name, &cursor])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    Ok(())
+}
+
+/// The range is empty, and so complete, when nothing was indexed yet.
+pub async fn define_subsystem_backfill(database_client: &tokio_postgres::Client, subsystem: &str)
+                                       -> Result<(), Error> {
+    database_client
+        .execute("INSERT INTO subsystem_backfills(subsystem, first_height, last_height, cursor) \
+                  SELECT $1, COALESCE(min(height), 1), COALESCE(max(height), 0), COALESCE(min(height), 1) \
+                  FROM proposer_to_height \
+                  ON CONFLICT (subsystem) DO NOTHING",
+                 &[&subsystem])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    Ok(())
+}
+
+pub async fn incomplete_subsystem_backfills(database_client: &tokio_postgres::Client)
+                                            -> Result<Vec<Checkpoint>, Error> {
+    let rows = database_client
+        .query("SELECT subsystem, first_height, last_height, cursor FROM subsystem_backfills \
+                WHERE cursor <= last_height ORDER BY subsystem",
+               &[])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    rows.iter()
+        .map(|r| Ok(Checkpoint {
+            name: r.get(0),
+            first_height: get_height(r, 1)?,
+            last_height: get_height(r, 2)?,
+            cursor: get_height(r, 3)?,
+        }))
+        .collect()
+}
+
+pub async fn move_subsystem_backfill(database_client: &tokio_postgres::Client, subsystem: &str, cursor: Height)
+                                     -> Result<(), Error> {
+    database_client
+        .execute("UPDATE subsystem_backfills SET cursor = $2, updated_at = now() WHERE subsystem = $1",
+                 &[&subsystem, &cursor])
+        .await
+        .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+    Ok(())
+}
+
+/// Indexed heights in `[first_height, last_height)` missing the hash, time or number of transactions.
+pub async fn heights_without_block_details(database_client: &tokio_postgres::Client,
+                                           first_height: Height,
+                                           last_height: Height)
+                                           -> Result<Vec<Height>, Error> {
+    let rows = database_client
+        .query("SELECT height FROM proposer_to_height WHERE height >= $1 AND height < $2 \
+                AND (hash IS NULL OR block_time IS NULL OR num_txs IS NULL) ORDER BY height",
+               &[&first_height, &last_height])
+        .await
+        .map_err(Error::CouldNotFindGaps)?;
+
+    rows.iter().map(|r| get_height(r, 0)).collect()
+}
+
+/// Indexed heights in `[first_height, last_height)` missing the hash of the previous block.
+pub async fn heights_without_last_block_hash(database_client: &tokio_postgres::Client,
+                                             first_height: Height,
+                                             last_height: Height)
+                                             -> Result<Vec<Height>, Error> {
+    let rows = database_client
+        .query("SELECT height FROM proposer_to_height WHERE height >= $1 AND height < $2 \
+                AND last_block_hash IS NULL ORDER BY height",
+               &[&first_height, &last_height])
+        .await
+        .map_err(Error::CouldNotFindGaps)?;
+
+    rows.iter().map(|r| get_height(r, 0)).collect()
+}
+
+/// Indexed heights in `[first_height, last_height)` whose commit of the block below is not stored.
+pub async fn heights_without_last_commit(database_client: &tokio_postgres::Client,
+                                         first_height: Height,
+                                         last_height: Height)
+                                         -> Result<Vec<Height>, Error> {
+    let rows = database_client
+        .query("SELECT p.height FROM proposer_to_height p \
+                WHERE p.height >= $1 AND p.height < $2 \
+                AND NOT EXISTS (SELECT 1 FROM block_signatures s WHERE s.height = p.height - 1) \
+                ORDER BY p.height",
+               &[&first_height, &last_height])
+        .await
+        .map_err(Error::CouldNotFindGaps)?;
+
+    rows.iter().map(|r| get_height(r, 0)).collect()
+}
+
+/// Sets the hashes, time and number of transactions of indexed blocks, leaving their proposer as is.
+pub async fn update_block_details(database_client: &tokio_postgres::Client, blocks: &[Block])
+                                  -> Result<(), Error> {
+    let heights: Vec<Height> = blocks.iter().map(|b| b.height).collect();
+    let hashes: Vec<&BlockHash> = blocks.iter().map(|b| &b.hash).collect();
+    let last_block_hashes: Vec<Option<&BlockHash>> = blocks.iter().map(|b| b.last_block_hash.as_ref()).collect();
+    let block_times: Vec<DateTime<Utc>> = blocks.iter().map(|b| b.block_time).collect();
+    let nums_txs: Vec<i32> = blocks.iter().map(|b| b.num_txs).collect();
+
+    database_client
+        .execute("UPDATE proposer_to_height AS p \
+                  SET hash = d.hash, last_block_hash = d.last_block_hash, block_time = d.block_time, \
+                      num_txs = d.num_txs \
+                  FROM UNNEST($1::bigint[], $2::varchar[], $3::varchar[], $4::timestamptz[], $5::integer[]) \
+                      AS d(height, hash, last_block_hash, block_time, num_txs) \
+                  WHERE p.height = d.height",
+                 &[&heights, &hashes, &last_block_hashes, &block_times, &nums_txs])
+        .await
+        .map_err(Error::CouldNotUpdateBlockDetails)?;
+
+    Ok(())
+}
+
+/// Tables of the indexed dataset published for change data capture: everything the indexer reads
+/// from the chain and its endpoints or derives from it. Left out is the bookkeeping of the services,
+/// which means nothing to subscribers: schema_migrations, plugin_migrations, indexer_state,
+/// indexer_checkpoints, indexed_height_ranges, subsystem_backfills, height_requests, fetch_telemetry
+/// and the saved reports.
+const PUBLISHED_TABLES: [&str; 14] = [
+    "proposer_to_height",
+    "block_signatures",
+    "signer_sets",
+    "validators",
+    "genesis_validators",
+    "commission_changes",
+    "validator_set_snapshots",
+    "validator_tags",
+    "address_labels",
+    "node_versions",
+    "anomalies",
+    "proposer_daily_counts",
+    "custom_records",
+    "custom_records_archive",
+];
+
+/// Sets REPLICA IDENTITY FULL on the dataset tables, so updates and deletes carry the whole old row
+/// (proposer_to_height has no primary key), and creates the publication for them, or sets its
+/// tables if it exists, so tables added by later migrations join it. Runs in the transaction of the
+/// migrations, under their lock. The publication name is a validated identifier, since DDL cannot
+/// take bound parameters.
+pub async fn enable_change_data_capture(transaction: &Transaction<'_>, publication: &str) -> Result<(), Error> {
+    let tables = PUBLISHED_TABLES.join(", ");
+
+    let mut statements: Vec<String> = PUBLISHED_TABLES
+        .iter()
+        .map(|t| format!("ALTER TABLE {t} REPLICA IDENTITY FULL;"))
+        .collect();
+    statements.push(format!("DO $$ BEGIN \
+                             IF NOT EXISTS (SELECT 1 FROM pg_publication WHERE pubname = '{publication}') THEN \
+                             CREATE PUBLICATION {publication} FOR TABLE {tables}; \
+                             ELSE \
+                             ALTER PUBLICATION {publication} SET TABLE {tables}; \
+                             END IF; END $$;"));
+
+    transaction
+        .batch_execute(&statements.join(" "))
+        .await
+        .map_err(Error::CouldNotEnableChangeDataCapture)
+}
+
+/// Creates `schema_migrations` if it is missing and takes a lock held until the transaction
+/// ends, for indexers starting at the same time to migrate one after the other.
+pub async fn begin_migrations(database_client: &mut tokio_postgres::Client) -> Result<Transaction<'_>, Error> {
+    let transaction = database_client.transaction().await.map_err(Error::CouldNotMigrateDatabase)?;
+
+    transaction
+        .batch_execute("SELECT pg_advisory_xact_lock(hashtext('schema_migrations')); \
+                        CREATE TABLE IF NOT EXISTS schema_migrations ( \
+                            version bigint PRIMARY KEY, \
+                            name varchar(255) NOT NULL, \
+                            applied_at timestamptz NOT NULL DEFAULT now())")
+        .await
+        .map_err(Error::CouldNotMigrateDatabase)?;
+
+    Ok(transaction)
+}
+
+pub async fn applied_migrations(transaction: &Transaction<'_>) -> Result<Vec<i64>, Error> {
+    let rows = transaction
+        .query("SELECT version FROM schema_migrations ORDER BY version", &[])
+        .await
+        .map_err(Error::CouldNotMigrateDatabase)?;
+
+    Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+pub async fn record_migration(transaction: &Transaction<'_>, version: i64, name: &str) -> Result<(), Error> {
+    transaction
+        .execute("INSERT INTO schema_migrations(version, name) VALUES ($1, $2)", &[&version, &name])
+        .await
+        .map_err(Error::CouldNotMigrateDatabase)?;
+
+    Ok(())
+}
+
+pub async fn table_exists(transaction: &Transaction<'_>, table: &str) -> Result<bool, Error> {
+    let row = transaction
+        .query_one("SELECT to_regclass($1) IS NOT NULL", &[&table])
+        .await
+        .map_err(Error::CouldNotMigrateDatabase)?;
+
+    Ok(row.get(0))
+}
+
+/// Starts a read-only transaction in which every query sees the same snapshot of the database.
+pub async fn begin_snapshot(database_client: &mut tokio_postgres::Client) -> Result<Transaction<'_>, Error> {
+    database_client
+        .build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .read_only(true)
+        .start()
+        .await
+        .map_err(Error::CouldNotCopyTable)
+}
+
+/// The table name and ordering are constants of the caller, since they cannot be bound parameters.
+pub async fn count_rows(transaction: &Transaction<'_>, table: &str) -> Result<u64, Error> {
+    let row = transaction
+        .query_one(&format!("SELECT count(*) FROM {table}"), &[])
+        .await
+        .map_err(Error::CouldNotCopyTable)?;
+
+    Ok(row.get::<_, i64>(0) as u64)
+}
+
+/// Streams the rows of the table as CSV with a header. See [`count_rows`] for the arguments.
+pub async fn copy_table_as_csv(transaction: &Transaction<'_>, table: &str, order_by: &str)
+                               -> Result<CopyOutStream, Error> {
+    let query = format!("COPY (SELECT * FROM {table} ORDER BY {order_by}) TO STDOUT WITH (FORMAT csv, HEADER)");
+
+    transaction
+        .copy_out(&query)
+        .await
+        .map_err(Error::CouldNotCopyTable)
+}
+
+/// Heights are stored as `bigint`, so reading one can fail where a negative height was stored.
+fn get_height<'a, T: FromSql<'a>>(row: &'a Row, index: usize) -> Result<T, Error> {
+    row.try_get(index).map_err(Error::CouldNotConvertHeight)
+}
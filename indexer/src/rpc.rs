@@ -0,0 +1,322 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use osmosis_core::profiles::Profile;
+use reqwest::{Client, StatusCode, Url};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Map, Value};
+use tokio::time;
+use tracing::{debug, warn};
+
+use crate::Error;
+use crate::budget::{Allowance, Budget};
+use crate::cassette::Cassette;
+use crate::egress::EgressPolicy;
+use crate::limiter::{Outcome, RateLimiter};
+use crate::plugins::Plugins;
+use crate::signatures::SignerSets;
+use crate::telemetry::TelemetryCollector;
+
+/// Delay before each request to an endpoint whose daily budget is nearly exhausted.
+const NEARLY_EXHAUSTED_BUDGET_DELAY_IN_MILLISECONDS: u64 = 1000;
+
+/// HTTP client for every outbound request of the indexer, which enforces the egress policy.
+#[derive(Clone)]
+pub struct RpcClient {
+    http_client: Client,
+    egress_policy: Arc<EgressPolicy>,
+    rpc_urls: Arc<Vec<String>>,
+    profile: Arc<Profile>,
+    cassette: Option<Arc<Cassette>>,
+    telemetry: TelemetryCollector,
+    plugins: Plugins,
+    signer_sets: SignerSets,
+    budget: Arc<Budget>,
+    limiter: Arc<RateLimiter>,
+    batch_size: u64,
+}
+
+impl RpcClient {
+    pub fn new(http_client: Client, egress_policy: EgressPolicy, rpc_urls: Vec<String>) -> Self {
+        RpcClient {
+            http_client,
+            egress_policy: Arc::new(egress_policy),
+            rpc_urls: Arc::new(rpc_urls),
+            profile: Arc::new(Profile::default()),
+            cassette: None,
+            telemetry: TelemetryCollector::default(),
+            plugins: Plugins::default(),
+            signer_sets: SignerSets::default(),
+            budget: Arc::new(Budget::default()),
+            limiter: Arc::new(RateLimiter::default()),
+            batch_size: 1,
+        }
+    }
+
+    /// Records RPC responses to the cassette or replays them from it instead of calling the endpoints.
+    pub fn with_cassette(self, cassette: Option<Cassette>) -> Self {
+        RpcClient { cassette: cassette.map(Arc::new), ..self }
+    }
+
+    /// Replaces the default profile once the chain id of the endpoints is known.
+    pub fn with_profile(self, profile: Profile) -> Self {
+        RpcClient { profile: Arc::new(profile), ..self }
+    }
+
+    pub fn with_budget(self, budget: Budget) -> Self {
+        RpcClient { budget: Arc::new(budget), ..self }
+    }
+
+    pub fn with_limiter(self, limiter: RateLimiter) -> Self {
+        RpcClient { limiter: Arc::new(limiter), ..self }
+    }
+
+    /// Blocks requested per JSON-RPC batch; 1 requests every block on its own.
+    pub fn with_batch_size(self, batch_size: u64) -> Self {
+        RpcClient { batch_size, ..self }
+    }
+
+    pub fn batch_size(&self) -> u64 {
+        self.batch_size
+    }
+
+    pub fn with_telemetry(self, telemetry: TelemetryCollector) -> Self {
+        RpcClient { telemetry, ..self }
+    }
+
+    /// Collects telemetry of block fetches made through any clone of this client.
+    pub fn telemetry(&self) -> &TelemetryCollector {
+        &self.telemetry
+    }
+
+    pub fn with_plugins(self, plugins: Plugins) -> Self {
+        RpcClient { plugins, ..self }
+    }
+
+    /// Plugins run on the blocks fetched through any clone of this client.
+    pub fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+
+    /// Validator sets of recent commits, which fill in the addresses of absent validators.
+    pub fn signer_sets(&self) -> &SignerSets {
+        &self.signer_sets
+    }
+
+    /// Limits the requests of every clone, and can be reconfigured while they are made.
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// Heights fetched per batch: as many requests as are made in parallel at most, times the
+    /// blocks per request.
+    pub fn heights_per_batch(&self) -> u64 {
+        self.limiter.maximum_concurrency() * self.batch_size
+    }
+
+    /// Parser profile for the responses of the chain behind the endpoints.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Requests an RPC method, e.g. `/block?height=1`, from the configured endpoints in order.
+    /// The next endpoint is tried when one cannot be reached, is rate limiting or answers
+    /// with a server error.
+    /// Endpoints whose daily budget is nearly exhausted are tried last and slowed down, and
+    /// endpoints which exhausted it are skipped until the next day.
+    /// Every attempt waits for the rate limiter, which backs off when endpoints throttle.
+    pub async fn request_rpc(&self, path_and_query: &str) -> Result<reqwest::Response, Error> {
+        match self.cassette.as_deref() {
+            Some(c) if c.is_replaying() => c.replay(path_and_query),
+            Some(c) => c.record(path_and_query, self.request_endpoints(path_and_query, None).await?).await,
+            None => self.request_endpoints(path_and_query, None).await,
+        }
+    }
+
+    /// Requests several RPC methods, given as for `request_rpc`, in one JSON-RPC batch POSTed to
+    /// the endpoints in the same way. The response is an array with one object per method, in any
+    /// order, which `split_batch_response` puts in the order of the request.
+    pub async fn request_rpc_batch(&self, paths_and_queries: &[String]) -> Result<reqwest::Response, Error> {
+        let description = format!("{} and {} more", paths_and_queries[0], paths_and_queries.len() - 1);
+        let body = serde_json::to_vec(&batch_request(paths_and_queries)?)
+            .map_err(|e| Error::CouldNotBuildHttpRequest { url: description.clone(), source: Box::new(e) })?;
+
+        match self.cassette.as_deref() {
+            Some(c) if c.is_replaying() => c.replay(&description),
+            Some(c) => c.record(&description, self.request_endpoints(&description, Some(&body)).await?).await,
+            None => self.request_endpoints(&description, Some(&body)).await,
+        }
+    }
+
+    /// Sends a GET request for the path and query, or POSTs the batch to the root of each endpoint.
+    async fn request_endpoints(&self, path_and_query: &str, batch: Option<&[u8]>)
+                               -> Result<reqwest::Response, Error> {
+        let mut last_error = None;
+
+        let mut rpc_urls: Vec<(&String, Allowance)> = self.rpc_urls
+            .iter()
+            .map(|u| (u, self.budget.allowance(u)))
+            .filter(|(_, a)| *a != Allowance::Exhausted)
+            .collect();
+        if rpc_urls.is_empty() {
+            return Err(Error::RpcBudgetExhausted);
+        }
+        rpc_urls.sort_by_key(|(_, a)| *a == Allowance::NearlyExhausted);
+
+        for (rpc_url, allowance) in rpc_urls {
+            if allowance == Allowance::NearlyExhausted {
+                time::sleep(Duration::from_millis(NEARLY_EXHAUSTED_BUDGET_DELAY_IN_MILLISECONDS)).await;
+            }
+            self.budget.spend(rpc_url);
+
+            let permit = self.limiter.acquire().await;
+            let started = Instant::now();
+            let response = match batch {
+                Some(body) => self.post(rpc_url, body.to_vec()).await,
+                None => self.request(format!("{rpc_url}{path_and_query}")).await,
+            };
+            let duration = started.elapsed();
+            let duration_in_milliseconds = duration.as_millis() as u64;
+
+            permit.record(match &response {
+                Ok(r) if r.status() == StatusCode::TOO_MANY_REQUESTS => Outcome::Throttled,
+                Err(Error::RpcRequestTimedOut { .. }) => Outcome::Throttled,
+                Ok(r) if r.status().is_success() => Outcome::of_response(duration),
+                _ => Outcome::Slow,
+            });
+            drop(permit);
+
+            match response {
+                Ok(r) if r.status().is_server_error() || r.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    warn!(endpoint = %rpc_url,
+                          status = %r.status(),
+                          duration_in_milliseconds,
+                          "RPC endpoint answered");
+                    last_error = Some(Error::UnsuccessfulResponse { url: rpc_url.clone(), status: r.status() });
+                }
+                Ok(r) => {
+                    debug!(endpoint = %rpc_url,
+                           request = path_and_query,
+                           status = %r.status(),
+                           duration_in_milliseconds,
+                           "RPC request");
+                    return Ok(r);
+                }
+                Err(e) => {
+                    warn!(endpoint = %rpc_url, error = ?e, duration_in_milliseconds, "RPC endpoint failed");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        // Every endpoint was tried, so there is an error of the last one.
+        Err(last_error.unwrap_or(Error::RpcBudgetExhausted))
+    }
+
+    pub async fn request(&self, request_url: String) -> Result<reqwest::Response, Error> {
+        let url = Url::parse(&request_url)
+            .map_err(|e| Error::CouldNotBuildHttpRequest { url: request_url.clone(), source: Box::new(e) })?;
+
+        self.egress_policy.check(&url)?;
+
+        let request = self.http_client.get(url).build()
+            .map_err(|e| Error::CouldNotBuildHttpRequest { url: request_url.clone(), source: Box::new(e) })?;
+
+        let raw_response = self.http_client.execute(request)
+            .await
+            .map_err(|e| execute_error(request_url, e))?;
+
+        Ok(raw_response)
+    }
+
+    async fn post(&self, request_url: &str, body: Vec<u8>) -> Result<reqwest::Response, Error> {
+        let url = Url::parse(request_url)
+            .map_err(|e| Error::CouldNotBuildHttpRequest { url: request_url.to_string(), source: Box::new(e) })?;
+
+        self.egress_policy.check(&url)?;
+
+        let request = self.http_client.post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .build()
+            .map_err(|e| Error::CouldNotBuildHttpRequest { url: request_url.to_string(), source: Box::new(e) })?;
+
+        self.http_client.execute(request)
+            .await
+            .map_err(|e| execute_error(request_url.to_string(), e))
+    }
+}
+
+fn execute_error(url: String, e: reqwest::Error) -> Error {
+    if e.is_timeout() {
+        Error::RpcRequestTimedOut { url }
+    } else {
+        Error::CouldNotGetResponseFromServer { url, source: Box::new(e) }
+    }
+}
+
+/// JSON-RPC calls of the methods, e.g. `/block?height=1` as `block` with `{"height": "1"}`,
+/// identified by their position.
+fn batch_request(paths_and_queries: &[String]) -> Result<Value, Error> {
+    let calls = paths_and_queries.iter()
+        .enumerate()
+        .map(|(id, path_and_query)| {
+            let url = Url::parse(&format!("http://rpc{path_and_query}"))
+                .map_err(|e| Error::CouldNotBuildHttpRequest {
+                    url: path_and_query.clone(),
+                    source: Box::new(e),
+                })?;
+            let params: Map<String, Value> = url.query_pairs()
+                .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+                .collect();
+
+            let method = url.path().trim_start_matches('/');
+
+            Ok(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+        })
+        .collect::<Result<Vec<Value>, Error>>()?;
+
+    Ok(Value::Array(calls))
+}
+
+/// Responses of a batch of `count` calls in the order they were requested, each shaped like the
+/// response to the call on its own.
+pub fn split_batch_response(response: Value, count: usize) -> Result<Vec<Value>, Error> {
+    let Value::Array(responses) = response else {
+        return Err(Error::CouldNotParseBatchResponse);
+    };
+
+    let mut ordered = vec![None; count];
+    for response in responses {
+        let id = response.get("id").and_then(Value::as_u64).ok_or(Error::CouldNotParseBatchResponse)?;
+        let slot = ordered.get_mut(id as usize).ok_or(Error::CouldNotParseBatchResponse)?;
+        *slot = Some(response);
+    }
+
+    ordered.into_iter().map(|r| r.ok_or(Error::CouldNotParseBatchResponse)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_call_methods_with_the_query_as_params() {
+        let request = batch_request(&["/block?height=7".to_string(), "/status".to_string()]).unwrap();
+
+        assert_eq!(request, json!([
+            {"jsonrpc": "2.0", "id": 0, "method": "block", "params": {"height": "7"}},
+            {"jsonrpc": "2.0", "id": 1, "method": "status", "params": {}},
+        ]));
+    }
+
+    #[test]
+    fn batch_responses_are_put_in_the_order_of_the_request() {
+        let response = json!([{"id": 1, "result": "b"}, {"id": 0, "result": "a"}]);
+
+        assert_eq!(split_batch_response(response, 2).unwrap(),
+                   vec![json!({"id": 0, "result": "a"}), json!({"id": 1, "result": "b"})]);
+        assert!(split_batch_response(json!([{"id": 0, "result": "a"}]), 2).is_err());
+        assert!(split_batch_response(json!({"error": "batches are disabled"}), 1).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::time::{self, Instant};
+
+pub const DEFAULT_INDEXER_INTERVAL_IN_SECONDS: u64 = 30;
+
+/// Settings which can be changed while the indexer runs, through the admin endpoint, shared by
+/// every task holding a clone.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    interval_in_seconds: Arc<AtomicU64>,
+}
+
+impl Settings {
+    pub fn new(interval_in_seconds: u64) -> Self {
+        Settings { interval_in_seconds: Arc::new(AtomicU64::new(interval_in_seconds)) }
+    }
+
+    /// Time between two rounds of indexing, backfilling and the other recurring work.
+    pub fn interval_in_seconds(&self) -> u64 {
+        self.interval_in_seconds.load(Ordering::Relaxed)
+    }
+
+    pub fn set_interval_in_seconds(&self, interval_in_seconds: u64) {
+        self.interval_in_seconds.store(interval_in_seconds, Ordering::Relaxed);
+    }
+
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.interval_in_seconds())
+    }
+
+    /// Ticks right away and then every interval, as it is set at the time of the tick.
+    pub fn interval(&self) -> Interval {
+        let period = self.period();
+        Interval { settings: self.clone(), period, interval: time::interval(period) }
+    }
+}
+
+pub struct Interval {
+    settings: Settings,
+    period: Duration,
+    interval: time::Interval,
+}
+
+impl Interval {
+    /// Waits for the next tick; a changed interval applies from the one after it.
+    pub async fn tick(&mut self) {
+        self.interval.tick().await;
+
+        let period = self.settings.period();
+        if period != self.period {
+            self.period = period;
+            self.interval = time::interval_at(Instant::now() + period, period);
+        }
+    }
+}
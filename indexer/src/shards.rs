@@ -0,0 +1,127 @@
+use model::Height;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{Error, repository, request_proposers};
+use crate::checkpoints::CheckpointRange;
+use crate::rpc::RpcClient;
+
+pub const DEFAULT_SHARD_WORKERS: u64 = 4;
+
+/// Range of heights in the `[shards]` section of the config, both ends inclusive, split into
+/// `count` disjoint shards which `workers` workers of every replica index in parallel.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ShardsConfig {
+    pub first_height: Height,
+    pub last_height: Height,
+    pub count: u64,
+    pub workers: Option<u64>,
+}
+
+impl ShardsConfig {
+    pub fn range(&self) -> CheckpointRange {
+        CheckpointRange { first_height: self.first_height, last_height: self.last_height }
+    }
+
+    /// Shards are named after the range and the count, so every replica given the same config
+    /// works on the same rows of `indexer_checkpoints`.
+    pub fn names(&self) -> Vec<String> {
+        split(&self.range(), self.count).into_iter().map(|(name, _)| name).collect()
+    }
+}
+
+/// Splits the range into at most `count` shards of consecutive heights whose sizes differ by
+/// one at most, lowest first. There are fewer shards than `count` if the range is shorter.
+pub fn split(range: &CheckpointRange, count: u64) -> Vec<(String, CheckpointRange)> {
+    let first = range.first_height.value() as u128;
+    let length = range.last_height.value() as u128 - first + 1;
+    let count = count.max(1);
+
+    (0..count)
+        .filter_map(|index| {
+            let first_height = first + length * index as u128 / count as u128;
+            let next_height = first + length * (index as u128 + 1) / count as u128;
+            (first_height < next_height).then(|| {
+                let name = format!("shard-{}-{}-{index}-of-{count}", range.first_height, range.last_height);
+                (name, CheckpointRange {
+                    first_height: Height::new(first_height as u64),
+                    last_height: Height::new(next_height as u64 - 1),
+                })
+            })
+        })
+        .collect()
+}
+
+/// Records the shards in `indexer_checkpoints`, keeping the cursors of those recorded before.
+pub async fn define(database_client: &tokio_postgres::Client, config: &ShardsConfig) -> Result<(), Error> {
+    for (name, range) in split(&config.range(), config.count) {
+        repository::define_checkpoint(database_client, &name, &range).await?;
+    }
+
+    Ok(())
+}
+
+/// Claims an incomplete shard which no other worker holds, indexes the next heights of it and
+/// moves its cursor in the same transaction, whose row lock keeps other workers, of this and
+/// of other replicas, off the shard meanwhile. Repeats until every shard is complete or held.
+pub async fn work(rpc_client: &RpcClient, database_client: &mut tokio_postgres::Client, names: &[String])
+                  -> Result<(), Error> {
+    loop {
+        let transaction = database_client.transaction()
+            .await
+            .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+        let Some(shard) = repository::claim_checkpoint(&transaction, names).await? else {
+            return Ok(());
+        };
+
+        let (first_height, last_height) = shard.next_heights(rpc_client.heights_per_batch());
+        info!(name = shard.name, %first_height, %last_height, "Indexing shard");
+
+        let proposers_to_height = request_proposers(rpc_client, Height::range(first_height, last_height)).await?;
+        osmosis_core::repository::upsert_batch(&transaction, &proposers_to_height).await?;
+        repository::move_checkpoint(&transaction, &shard.name, last_height).await?;
+
+        transaction.commit()
+            .await
+            .map_err(Error::CouldNotUpdateCheckpoint)?;
+
+        if last_height > shard.last_height {
+            info!(name = shard.name, "Shard is complete");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn shards_cover_the_range_once(
+            first_height in 0..1_000_000u64,
+            length in 0..5000u64,
+            count in 1..100u64,
+        ) {
+            let range = CheckpointRange {
+                first_height: Height::new(first_height),
+                last_height: Height::new(first_height + length),
+            };
+
+            let shards = split(&range, count);
+
+            prop_assert!(shards.len() as u64 <= count);
+            let heights: Vec<u64> = shards.iter()
+                .flat_map(|(_, r)| r.first_height.value()..=r.last_height.value())
+                .collect();
+            prop_assert_eq!(heights, (first_height..=first_height + length).collect::<Vec<u64>>());
+
+            let sizes: Vec<u64> = shards.iter()
+                .map(|(_, r)| r.last_height.value() - r.first_height.value())
+                .collect();
+            prop_assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1);
+        }
+    }
+}
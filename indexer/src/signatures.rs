@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use model::ConsensusAddress;
+use osmosis_core::LastCommit;
+use tracing::{debug, warn};
+
+use crate::Error;
+use crate::rpc::RpcClient;
+use crate::validator_sets;
+
+/// The validator set rarely changes between blocks, so a few sets cover every recent commit.
+const RECENT_SIGNER_SETS: usize = 8;
+
+/// Validator sets of recent commits, in the order of their signatures, shared by every clone.
+/// Commits leave out the address of a validator which did not vote, so it is taken from the set.
+#[derive(Clone, Default)]
+pub struct SignerSets {
+    recent: Arc<Mutex<VecDeque<Vec<ConsensusAddress>>>>,
+}
+
+impl SignerSets {
+    /// Fills in the addresses of the validators absent from the commit, from a recent set which
+    /// agrees with every address present, or else from the validator set at the commit's height.
+    /// A commit whose set cannot be found keeps its gaps, and its signatures are not stored.
+    pub async fn resolve(&self, rpc_client: &RpcClient, commit: &mut LastCommit) {
+        if let Some(validators) = commit.validators() {
+            self.remember(validators.into_iter().cloned().collect());
+            return;
+        }
+
+        let signer_set = match self.find(commit) {
+            Some(signer_set) => signer_set,
+            None => match request_signer_set(rpc_client, commit).await {
+                Ok(Some(signer_set)) => {
+                    self.remember(signer_set.clone());
+                    signer_set
+                }
+                Ok(None) => {
+                    warn!(height = %commit.height, "Validator set does not match the signatures of the commit");
+                    return;
+                }
+                Err(e) => {
+                    warn!(height = %commit.height, error = ?e, "Could not request validator set of the commit");
+                    return;
+                }
+            },
+        };
+
+        for (signature, address) in commit.signatures.iter_mut().zip(signer_set) {
+            signature.validator.get_or_insert(address);
+        }
+    }
+
+    fn find(&self, commit: &LastCommit) -> Option<Vec<ConsensusAddress>> {
+        self.recent.lock().unwrap().iter().find(|s| agrees(s, commit)).cloned()
+    }
+
+    fn remember(&self, signer_set: Vec<ConsensusAddress>) {
+        let mut recent = self.recent.lock().unwrap();
+        if let Some(position) = recent.iter().position(|s| *s == signer_set) {
+            recent.remove(position);
+        }
+        recent.push_front(signer_set);
+        recent.truncate(RECENT_SIGNER_SETS);
+    }
+}
+
+/// CometBFT orders both the signatures of a commit and `/validators` by voting power, so the set
+/// at the commit's height lines up with its signatures.
+async fn request_signer_set(rpc_client: &RpcClient, commit: &LastCommit)
+                            -> Result<Option<Vec<ConsensusAddress>>, Error> {
+    debug!(height = %commit.height, "Requesting validator set of commit");
+    let (_, validators) = validator_sets::request_validator_set(rpc_client, Some(commit.height)).await?;
+    let signer_set: Vec<ConsensusAddress> = validators.into_iter().map(|(address, _)| address).collect();
+
+    Ok(agrees(&signer_set, commit).then_some(signer_set))
+}
+
+fn agrees(signer_set: &[ConsensusAddress], commit: &LastCommit) -> bool {
+    signer_set.len() == commit.signatures.len()
+        && signer_set.iter()
+            .zip(&commit.signatures)
+            .all(|(address, signature)| signature.validator.as_ref().is_none_or(|v| v == address))
+}
+
+#[cfg(test)]
+mod tests {
+    use model::Height;
+    use osmosis_core::CommitSignature;
+
+    use super::*;
+
+    fn address(byte: char) -> ConsensusAddress {
+        byte.to_string().repeat(40).parse().unwrap()
+    }
+
+    fn commit(validators: &[Option<char>]) -> LastCommit {
+        LastCommit {
+            height: Height::new(100),
+            signatures: validators.iter()
+                .map(|v| CommitSignature { validator: v.map(address), signed: v.is_some() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn absent_validators_are_found_in_an_agreeing_set() {
+        let signer_sets = SignerSets::default();
+        signer_sets.remember(vec![address('A'), address('C')]);
+        signer_sets.remember(vec![address('A'), address('B'), address('D')]);
+        signer_sets.remember(vec![address('A'), address('B'), address('C')]);
+
+        assert_eq!(signer_sets.find(&commit(&[Some('A'), None, Some('D')])),
+                   Some(vec![address('A'), address('B'), address('D')]));
+        assert_eq!(signer_sets.find(&commit(&[None, Some('C')])), Some(vec![address('A'), address('C')]));
+        assert_eq!(signer_sets.find(&commit(&[Some('B'), None, None])), None);
+    }
+}
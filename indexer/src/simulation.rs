@@ -0,0 +1,378 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::time::Duration;
+
+use model::{BlockHash, Height};
+use osmosis_core::Block;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use reqwest::StatusCode;
+use tokio::time;
+
+use crate::{Error, gaps, index};
+use crate::config::StartMode;
+use crate::health::Progress;
+use crate::limiter::DEFAULT_MAXIMUM_CONCURRENCY;
+use crate::store::{BlockSource, Store};
+use crate::writer::BatchWriter;
+
+const SIMULATED_LOWEST_HEIGHT: Height = Height::new(1000);
+const SIMULATED_CHAIN_GROWTH_PER_CYCLE: u64 = 40;
+const SIMULATED_CYCLES_WITH_CRASHES: usize = 60;
+const SIMULATED_MAXIMUM_INSERT_LATENCY_IN_MILLISECONDS: u64 = 2000;
+const CRASH_PROBABILITY: f64 = 0.05;
+const RPC_FAILURE_PROBABILITY: f64 = 0.02;
+const REQUESTED_HEIGHT_PROBABILITY: f64 = 0.2;
+const OUTAGE_PROBABILITY: f64 = 0.05;
+const MAXIMUM_OUTAGE_CYCLES: usize = 3;
+const RECOVERY_CYCLES: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Crash {
+    AfterFetch,
+    MidInsert,
+    AtQuery,
+}
+
+/// Chain and database of a simulated deployment: `proposer_to_height` with its guarantees
+/// (indexed heights are skipped, batches are inserted all or nothing) and the queue of
+/// requested heights.
+struct Deployment {
+    random: StdRng,
+    heights: BTreeSet<Height>,
+    /// Requested heights with their failed attempts, longest waiting first.
+    requested: VecDeque<(Height, i32)>,
+    last_height: Height,
+    crashes: bool,
+    /// Cycles left in which the database is unavailable while the process keeps running.
+    outage_cycles: usize,
+    /// Set when the process crashed; every call fails from then on until it is restarted.
+    crashed_at: Option<Crash>,
+}
+
+impl Deployment {
+    fn crash_at(&mut self, crash: Crash) -> Result<(), Error> {
+        if self.crashed_at.is_none() && self.crashes && self.random.gen_bool(CRASH_PROBABILITY) {
+            self.crashed_at = Some(crash);
+        }
+        match self.crashed_at {
+            Some(_) => Err(Error::DatabaseUnavailable("process crashed".into())),
+            None => Ok(()),
+        }
+    }
+
+    /// A statement of the database, which fails while it is unavailable.
+    fn query(&mut self, crash: Crash) -> Result<(), Error> {
+        self.crash_at(crash)?;
+        if self.outage_cycles > 0 {
+            return Err(Error::DatabaseUnavailable("database is down".into()));
+        }
+        Ok(())
+    }
+
+    fn block(height: Height) -> Block {
+        Block {
+            proposer: "0123456789ABCDEF0123456789ABCDEF01234567".parse().unwrap(),
+            height,
+            hash: format!("{:064X}", height.value()).parse().unwrap(),
+            last_block_hash: None,
+            block_time: Default::default(),
+            num_txs: 0,
+            last_commit: None,
+        }
+    }
+}
+
+/// Drives the pipeline of the indexer against a simulated deployment, which it takes by shared
+/// reference both as the RPC endpoint and as the database.
+struct Simulation {
+    deployment: RefCell<Deployment>,
+    progress: Progress,
+}
+
+impl Simulation {
+    fn new(seed: u64) -> Self {
+        Simulation {
+            deployment: RefCell::new(Deployment {
+                random: StdRng::seed_from_u64(seed),
+                heights: BTreeSet::new(),
+                requested: VecDeque::new(),
+                last_height: SIMULATED_LOWEST_HEIGHT,
+                crashes: true,
+                outage_cycles: 0,
+                crashed_at: None,
+            }),
+            progress: Progress::new(),
+        }
+    }
+
+    /// The chain grows, API users sometimes ask for a height which is not indexed yet and the
+    /// database sometimes becomes unavailable for a few cycles.
+    fn advance(&self) {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.outage_cycles = deployment.outage_cycles.saturating_sub(1);
+        if deployment.crashes && deployment.random.gen_bool(OUTAGE_PROBABILITY) {
+            deployment.outage_cycles = deployment.random.gen_range(1..=MAXIMUM_OUTAGE_CYCLES);
+        }
+
+        let growth = deployment.random.gen_range(0..=SIMULATED_CHAIN_GROWTH_PER_CYCLE);
+        deployment.last_height = deployment.last_height.saturating_add(growth);
+
+        let last_height = deployment.last_height.value();
+        let requests = deployment.random.gen_bool(REQUESTED_HEIGHT_PROBABILITY);
+        if requests && last_height > SIMULATED_LOWEST_HEIGHT.value() {
+            let height = Height::new(deployment.random.gen_range(SIMULATED_LOWEST_HEIGHT.value()..last_height));
+            if !deployment.heights.contains(&height) && !deployment.requested.iter().any(|(h, _)| *h == height) {
+                deployment.requested.push_back((height, 0));
+            }
+        }
+    }
+
+    /// Returns the crash which killed the process, if any, and starts it again.
+    fn restart(&self) -> Option<Crash> {
+        self.deployment.borrow_mut().crashed_at.take()
+    }
+
+    /// One interval of the indexing task and one scan of the gap repair task. A crash loses the
+    /// writer with what it has not written yet, as a restart of the process does, while an
+    /// outage of the database leaves it to buffer batches.
+    async fn cycle(&self, writer: &mut BatchWriter, crashed_at: &mut Vec<Crash>) {
+        self.advance();
+
+        for repairs_gaps in [false, true] {
+            let result = if repairs_gaps {
+                gaps::repair(self, self, &self.progress, Some(SIMULATED_LOWEST_HEIGHT)).await
+            } else {
+                index(self, self, writer, StartMode::Lowest, SIMULATED_LOWEST_HEIGHT).await
+            };
+
+            if let Some(crash) = self.restart() {
+                *writer = BatchWriter::new(None, self.progress.clone());
+                crashed_at.push(crash);
+                continue;
+            }
+            let outage = self.deployment.borrow().outage_cycles > 0;
+            match result {
+                Ok(()) | Err(Error::UnsuccessfulResponse { .. }) => {}
+                Err(Error::DatabaseUnavailable(_)) if outage => {}
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+        }
+    }
+
+    fn has_converged(&self) -> bool {
+        let deployment = self.deployment.borrow();
+        deployment.heights.iter().copied().eq(Height::range(SIMULATED_LOWEST_HEIGHT, deployment.last_height))
+    }
+}
+
+impl Store for Simulation {
+    fn is_closed(&self) -> bool {
+        self.deployment.borrow().outage_cycles > 0
+    }
+
+    async fn highest_indexed_height(&self) -> Result<Option<Height>, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.query(Crash::AtQuery)?;
+        Ok(deployment.heights.last().copied())
+    }
+
+    async fn record_start_height(&self, height: Height) -> Result<Height, Error> {
+        Ok(height)
+    }
+
+    /// Waits as long as a write takes, which the batch size tuner measures. A crash in the
+    /// middle of the statement either commits the batch or not.
+    async fn insert_batch(&self, blocks: &[Block]) -> Result<u64, Error> {
+        let latency = {
+            let mut deployment = self.deployment.borrow_mut();
+            deployment.random.gen_range(1..SIMULATED_MAXIMUM_INSERT_LATENCY_IN_MILLISECONDS)
+        };
+        time::sleep(Duration::from_millis(latency)).await;
+
+        let mut deployment = self.deployment.borrow_mut();
+        let was_running = deployment.crashed_at.is_none();
+        let commits = deployment.random.gen_bool(0.5);
+        if let Err(e) = deployment.query(Crash::MidInsert) {
+            if was_running && deployment.crashed_at.is_some() && commits {
+                deployment.heights.extend(blocks.iter().map(|b| b.height));
+            }
+            return Err(e);
+        }
+
+        Ok(blocks.iter().filter(|b| deployment.heights.insert(b.height)).count() as u64)
+    }
+
+    async fn copy_batch(&self, blocks: &[Block]) -> Result<u64, Error> {
+        self.insert_batch(blocks).await
+    }
+
+    async fn block_hashes(&self, first_height: Height, last_height: Height)
+                          -> Result<HashMap<Height, Option<BlockHash>>, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.query(Crash::AtQuery)?;
+        Ok(deployment.heights
+            .range(first_height..last_height)
+            .map(|&h| (h, Some(Deployment::block(h).hash)))
+            .collect())
+    }
+
+    async fn delete_blocks_above(&self, height: Height) -> Result<(), Error> {
+        self.deployment.borrow_mut().heights.retain(|h| *h <= height);
+        Ok(())
+    }
+
+    /// The same ranges as `repository::find_gaps`.
+    async fn find_gaps(&self, lowest_height: Option<Height>) -> Result<Vec<(Height, Height)>, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.query(Crash::AtQuery)?;
+
+        let Some(lowest_height) = lowest_height.or(deployment.heights.first().copied()) else {
+            return Ok(Vec::new());
+        };
+        let heights: Vec<Height> = [lowest_height.saturating_sub(1)]
+            .into_iter()
+            .chain(deployment.heights.range(lowest_height..).copied())
+            .collect();
+        Ok(heights.windows(2)
+            .filter(|w| w[1] > w[0].saturating_add(1))
+            .map(|w| (w[0].saturating_add(1), w[1].saturating_sub(1)))
+            .collect())
+    }
+
+    /// Like `repository::requested_heights`.
+    async fn requested_heights(&self, limit: i64) -> Result<Vec<Height>, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.query(Crash::AtQuery)?;
+
+        let Deployment { heights, requested, .. } = &mut *deployment;
+        requested.retain(|(h, _)| !heights.contains(h));
+        let highest_indexed_height = heights.last().copied().unwrap_or(Height::new(0));
+
+        let mut taken: Vec<Height> = requested.iter()
+            .map(|(h, _)| *h)
+            .filter(|h| *h < highest_indexed_height)
+            .take(limit as usize)
+            .collect();
+        taken.sort();
+        Ok(taken)
+    }
+
+    async fn complete_requested_heights(&self, heights: &[Height]) -> Result<(), Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.query(Crash::AtQuery)?;
+        deployment.requested.retain(|(h, _)| !heights.contains(h));
+        Ok(())
+    }
+
+    async fn retry_requested_heights(&self, heights: &[Height], maximum_attempts: i32)
+                                     -> Result<Vec<Height>, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.query(Crash::AtQuery)?;
+
+        let (failed, mut requested): (VecDeque<_>, VecDeque<_>) =
+            deployment.requested.drain(..).partition(|(h, _)| heights.contains(h));
+        let mut dropped = Vec::new();
+        for (height, attempts) in failed {
+            if attempts + 1 >= maximum_attempts {
+                dropped.push(height);
+            } else {
+                requested.push_back((height, attempts + 1));
+            }
+        }
+        deployment.requested = requested;
+        Ok(dropped)
+    }
+}
+
+impl BlockSource for Simulation {
+    fn heights_per_batch(&self) -> u64 {
+        DEFAULT_MAXIMUM_CONCURRENCY
+    }
+
+    async fn request_last_height(&self) -> Result<Height, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        deployment.crash_at(Crash::AtQuery)?;
+        Ok(deployment.last_height)
+    }
+
+    /// Fails now and then like an overloaded endpoint, and a crash loses what was fetched.
+    async fn request_blocks(&self, heights: Vec<Height>) -> Result<Vec<Block>, Error> {
+        let mut deployment = self.deployment.borrow_mut();
+        if deployment.crashes && deployment.random.gen_bool(RPC_FAILURE_PROBABILITY) {
+            return Err(Error::UnsuccessfulResponse {
+                url: format!("/block?height={}", heights[0]),
+                status: StatusCode::SERVICE_UNAVAILABLE,
+            });
+        }
+
+        let blocks = heights.into_iter().map(Deployment::block).collect();
+        deployment.crash_at(Crash::AfterFetch)?;
+        Ok(blocks)
+    }
+}
+
+/// The database going down between cycles leaves nothing in the buffer, and indexing goes on
+/// from the highest height written before all the same.
+#[tokio::test(start_paused = true)]
+async fn batches_are_buffered_when_the_database_goes_down_with_an_empty_buffer() {
+    let simulation = Simulation::new(0);
+    let mut writer = BatchWriter::new(None, simulation.progress.clone());
+    let grow_chain = |blocks| {
+        let mut deployment = simulation.deployment.borrow_mut();
+        deployment.crashes = false;
+        deployment.last_height = deployment.last_height.saturating_add(blocks);
+    };
+
+    grow_chain(100);
+    index(&simulation, &simulation, &mut writer, StartMode::Lowest, SIMULATED_LOWEST_HEIGHT).await.unwrap();
+    let indexed_height = simulation.deployment.borrow().heights.last().copied();
+    assert_eq!(indexed_height, Some(Height::new(1099)));
+
+    grow_chain(100);
+    simulation.deployment.borrow_mut().outage_cycles = 1;
+    index(&simulation, &simulation, &mut writer, StartMode::Lowest, SIMULATED_LOWEST_HEIGHT).await.unwrap();
+    assert_eq!(simulation.deployment.borrow().heights.last().copied(), indexed_height);
+    assert_eq!(writer.highest_height(), Some(Height::new(1199)));
+
+    simulation.deployment.borrow_mut().outage_cycles = 0;
+    index(&simulation, &simulation, &mut writer, StartMode::Lowest, SIMULATED_LOWEST_HEIGHT).await.unwrap();
+    assert!(simulation.has_converged());
+    assert_eq!(simulation.progress.rows_written(), 200);
+    assert_eq!(simulation.progress.heights_skipped(), 0);
+}
+
+/// Deterministic simulation of crashes and restarts of the indexing pipeline. Each cycle runs
+/// `index` and `gaps::repair` of the indexer, with its batch writer and insert buffer, against a
+/// simulated deployment which stands in for both the RPC endpoint and the database. A seeded
+/// random source kills the process after a fetch, in the middle of an insert or at any query,
+/// or takes the database down for a few cycles, and the state left behind has to converge to
+/// every height exactly once after recovery.
+/// Time is paused, so the latencies the batch size tuner measures are simulated too.
+#[tokio::test(start_paused = true)]
+async fn pipeline_converges_after_crashes_and_restarts() {
+    let mut crashed_at = Vec::new();
+
+    for seed in 0..200 {
+        let simulation = Simulation::new(seed);
+        let mut writer = BatchWriter::new(None, simulation.progress.clone());
+
+        for _ in 0..SIMULATED_CYCLES_WITH_CRASHES {
+            simulation.cycle(&mut writer, &mut crashed_at).await;
+        }
+
+        simulation.deployment.borrow_mut().crashes = false;
+        for _ in 0..RECOVERY_CYCLES {
+            simulation.cycle(&mut writer, &mut crashed_at).await;
+            if simulation.has_converged() {
+                break;
+            }
+        }
+
+        assert!(simulation.has_converged(), "seed {seed} did not converge");
+    }
+
+    for crash in [Crash::AfterFetch, Crash::MidInsert, Crash::AtQuery] {
+        assert!(crashed_at.contains(&crash), "no simulated crash {crash:?}");
+    }
+}
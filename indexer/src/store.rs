@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use model::{BlockHash, Height};
+use osmosis_core::Block;
+use osmosis_core::repository::{copy_batch, insert_batch};
+
+use crate::{Error, repository, request_last_height, request_proposers};
+use crate::rpc::RpcClient;
+
+/// Tables which the indexing pipeline reads and writes: `proposer_to_height` and the queue of
+/// requested heights. The indexer keeps them in Postgres and the simulation in memory, so the
+/// simulation drives the same pipeline.
+pub trait Store {
+    /// Whether the connection is lost, in which case the pipeline buffers what it fetches.
+    fn is_closed(&self) -> bool;
+
+    async fn highest_indexed_height(&self) -> Result<Option<Height>, Error>;
+
+    /// See `repository::record_start_height`.
+    async fn record_start_height(&self, height: Height) -> Result<Height, Error>;
+
+    /// Returns the number of rows written; heights which are indexed already are skipped.
+    async fn insert_batch(&self, blocks: &[Block]) -> Result<u64, Error>;
+
+    /// Like `insert_batch`, with COPY for large batches.
+    async fn copy_batch(&self, blocks: &[Block]) -> Result<u64, Error>;
+
+    async fn block_hashes(&self, first_height: Height, last_height: Height)
+                          -> Result<HashMap<Height, Option<BlockHash>>, Error>;
+
+    async fn delete_blocks_above(&self, height: Height) -> Result<(), Error>;
+
+    async fn find_gaps(&self, lowest_height: Option<Height>) -> Result<Vec<(Height, Height)>, Error>;
+
+    async fn requested_heights(&self, limit: i64) -> Result<Vec<Height>, Error>;
+
+    async fn complete_requested_heights(&self, heights: &[Height]) -> Result<(), Error>;
+
+    async fn retry_requested_heights(&self, heights: &[Height], maximum_attempts: i32)
+                                     -> Result<Vec<Height>, Error>;
+}
+
+impl Store for tokio_postgres::Client {
+    fn is_closed(&self) -> bool {
+        tokio_postgres::Client::is_closed(self)
+    }
+
+    async fn highest_indexed_height(&self) -> Result<Option<Height>, Error> {
+        repository::highest_indexed_height(self).await
+    }
+
+    async fn record_start_height(&self, height: Height) -> Result<Height, Error> {
+        repository::record_start_height(self, height).await
+    }
+
+    async fn insert_batch(&self, blocks: &[Block]) -> Result<u64, Error> {
+        Ok(insert_batch(self, blocks).await?)
+    }
+
+    async fn copy_batch(&self, blocks: &[Block]) -> Result<u64, Error> {
+        Ok(copy_batch(self, blocks).await?)
+    }
+
+    async fn block_hashes(&self, first_height: Height, last_height: Height)
+                          -> Result<HashMap<Height, Option<BlockHash>>, Error> {
+        repository::block_hashes(self, first_height, last_height).await
+    }
+
+    async fn delete_blocks_above(&self, height: Height) -> Result<(), Error> {
+        repository::delete_blocks_above(self, height).await
+    }
+
+    async fn find_gaps(&self, lowest_height: Option<Height>) -> Result<Vec<(Height, Height)>, Error> {
+        repository::find_gaps(self, lowest_height).await
+    }
+
+    async fn requested_heights(&self, limit: i64) -> Result<Vec<Height>, Error> {
+        repository::requested_heights(self, limit).await
+    }
+
+    async fn complete_requested_heights(&self, heights: &[Height]) -> Result<(), Error> {
+        repository::complete_requested_heights(self, heights).await
+    }
+
+    async fn retry_requested_heights(&self, heights: &[Height], maximum_attempts: i32)
+                                     -> Result<Vec<Height>, Error> {
+        repository::retry_requested_heights(self, heights, maximum_attempts).await
+    }
+}
+
+/// Where the indexing pipeline fetches blocks from: the RPC endpoints, or a simulated chain.
+pub trait BlockSource {
+    /// Heights fetched per batch, as many as are requested in parallel.
+    fn heights_per_batch(&self) -> u64;
+
+    async fn request_last_height(&self) -> Result<Height, Error>;
+
+    /// Blocks in any order; fails if any of them cannot be fetched.
+    async fn request_blocks(&self, heights: Vec<Height>) -> Result<Vec<Block>, Error>;
+}
+
+impl BlockSource for RpcClient {
+    fn heights_per_batch(&self) -> u64 {
+        RpcClient::heights_per_batch(self)
+    }
+
+    async fn request_last_height(&self) -> Result<Height, Error> {
+        request_last_height(self).await
+    }
+
+    async fn request_blocks(&self, heights: Vec<Height>) -> Result<Vec<Block>, Error> {
+        request_proposers(self, heights).await
+    }
+}
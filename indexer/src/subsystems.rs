@@ -0,0 +1,103 @@
+use model::Height;
+use tracing::info;
+
+use crate::{Error, ranges, repository, request_proposers};
+use crate::rpc::RpcClient;
+
+const SUBSYSTEM_HEIGHTS_PER_INTERVAL: u64 = 500;
+
+/// Data the indexer records per height besides the proposer. When a subsystem is added to a
+/// deployment which indexed heights before, only its own data is backfilled for those heights.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Subsystem {
+    /// Hash, time and number of transactions of blocks, missing for heights indexed before
+    /// they were recorded.
+    BlockDetails,
+    /// Hash of the previous block, which reorgs are detected by, missing for heights indexed
+    /// before it was recorded.
+    LastBlockHash,
+    /// Which validators signed each block, read from the commit in the block above it.
+    BlockSignatures,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 3] =
+        [Subsystem::BlockDetails, Subsystem::LastBlockHash, Subsystem::BlockSignatures];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subsystem::BlockDetails => "block_details",
+            Subsystem::LastBlockHash => "last_block_hash",
+            Subsystem::BlockSignatures => "block_signatures",
+        }
+    }
+}
+
+/// Records, for every subsystem seen for the first time, the heights indexed so far as the range
+/// to backfill; live indexing records the subsystem for heights above it. A subsystem seen before
+/// keeps its range and cursor.
+pub async fn define(database_client: &tokio_postgres::Client) -> Result<(), Error> {
+    for subsystem in Subsystem::ALL {
+        repository::define_subsystem_backfill(database_client, subsystem.name()).await?;
+    }
+
+    Ok(())
+}
+
+/// Backfills up to SUBSYSTEM_HEIGHTS_PER_INTERVAL heights of every incomplete subsystem and moves
+/// its cursor after every batch. Heights which have the subsystem's data already are skipped.
+pub async fn advance(rpc_client: &RpcClient, database_client: &tokio_postgres::Client) -> Result<(), Error> {
+    for backfill in repository::incomplete_subsystem_backfills(database_client).await? {
+        let Some(subsystem) = Subsystem::ALL.into_iter().find(|s| s.name() == backfill.name) else {
+            continue;
+        };
+
+        let (first_height, last_height) = backfill.next_heights(SUBSYSTEM_HEIGHTS_PER_INTERVAL);
+        info!(subsystem = backfill.name, cursor = %backfill.cursor, last_height = %backfill.last_height,
+              "Backfilling subsystem");
+
+        for (first_height_to_backfill, last_height_to_backfill) in
+            ranges::ascending(first_height, last_height, rpc_client.heights_per_batch()) {
+            backfill_heights(rpc_client,
+                             database_client,
+                             subsystem,
+                             first_height_to_backfill,
+                             last_height_to_backfill).await?;
+            repository::move_subsystem_backfill(database_client, subsystem.name(), last_height_to_backfill)
+                .await?;
+        }
+
+        if last_height > backfill.last_height {
+            info!(subsystem = backfill.name, "Subsystem backfill is complete");
+        }
+    }
+
+    Ok(())
+}
+
+/// Backfills the heights in `[first_height, last_height)` which are indexed and lack the data.
+async fn backfill_heights(rpc_client: &RpcClient,
+                          database_client: &tokio_postgres::Client,
+                          subsystem: Subsystem,
+                          first_height: Height,
+                          last_height: Height)
+                          -> Result<(), Error> {
+    let heights = match subsystem {
+        Subsystem::BlockDetails =>
+            repository::heights_without_block_details(database_client, first_height, last_height).await?,
+        Subsystem::LastBlockHash =>
+            repository::heights_without_last_block_hash(database_client, first_height, last_height).await?,
+        Subsystem::BlockSignatures =>
+            repository::heights_without_last_commit(database_client, first_height, last_height).await?,
+    };
+    if heights.is_empty() {
+        return Ok(());
+    }
+
+    let blocks = request_proposers(rpc_client, heights).await?;
+    match subsystem {
+        Subsystem::BlockSignatures =>
+            Ok(osmosis_core::repository::upsert_signatures(database_client, &blocks).await?),
+        _ => repository::update_block_details(database_client, &blocks).await,
+    }
+}
@@ -0,0 +1,56 @@
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use model::Height;
+
+use crate::{Error, repository};
+
+/// Telemetry is dropped rather than buffered without bound while the database is unavailable.
+const MAXIMUM_BUFFERED_FETCHES: usize = 100_000;
+
+/// Duration and size of fetching the block at one height.
+pub struct FetchTelemetry {
+    pub height: Height,
+    /// Host of the RPC endpoint which answered.
+    pub endpoint: String,
+    pub duration_in_milliseconds: i32,
+    pub response_size_in_bytes: i32,
+}
+
+/// Collects fetch telemetry from every task sharing the RPC client until the indexing task
+/// stores it in `fetch_telemetry`. A disabled collector ignores everything.
+#[derive(Clone, Default)]
+pub struct TelemetryCollector {
+    fetches: Option<Arc<Mutex<Vec<FetchTelemetry>>>>,
+}
+
+impl TelemetryCollector {
+    pub fn new(enabled: bool) -> Self {
+        TelemetryCollector { fetches: enabled.then(Arc::default) }
+    }
+
+    pub fn record(&self, fetch: FetchTelemetry) {
+        let Some(fetches) = &self.fetches else {
+            return;
+        };
+
+        let mut fetches = fetches.lock().unwrap();
+        if fetches.len() < MAXIMUM_BUFFERED_FETCHES {
+            fetches.push(fetch);
+        }
+    }
+
+    /// Telemetry which cannot be stored is lost, so it never holds back indexing.
+    pub async fn store(&self, database_client: &tokio_postgres::Client) -> Result<(), Error> {
+        let Some(fetches) = &self.fetches else {
+            return Ok(());
+        };
+
+        let fetches = mem::take(&mut *fetches.lock().unwrap());
+        if fetches.is_empty() {
+            return Ok(());
+        }
+
+        repository::insert_fetch_telemetry(database_client, &fetches).await
+    }
+}
@@ -0,0 +1,43 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{json, Value};
+
+use crate::plugins::{BlockData, BlockProcessor, Record};
+
+/// Example plugin recording the number of transactions of every block and their total size in
+/// bytes in its own `transactions` table.
+pub struct TransactionSizes;
+
+impl BlockProcessor for TransactionSizes {
+    fn name(&self) -> &'static str {
+        "transaction_sizes"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &["CREATE TABLE transactions ( \
+               height bigint PRIMARY KEY, \
+               count integer NOT NULL, \
+               size_in_bytes bigint NOT NULL)"]
+    }
+
+    fn process(&self, block: &BlockData) -> Vec<Record> {
+        let Some(Value::Array(txs)) = block.response.pointer("/result/block/data/txs") else {
+            return Vec::new();
+        };
+
+        let size_in_bytes: usize = txs
+            .iter()
+            .filter_map(|tx| STANDARD.decode(tx.as_str()?).ok())
+            .map(|tx| tx.len())
+            .sum();
+
+        vec![Record::Row {
+            table: "transactions",
+            columns: json!({
+                "height": block.height.value(),
+                "count": txs.len(),
+                "size_in_bytes": size_in_bytes,
+            }),
+        }]
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+const MINIMUM_BATCH_SIZE: usize = 5;
+const MAXIMUM_BATCH_SIZE: usize = 5000;
+const INITIAL_BATCH_SIZE: usize = 50;
+/// Batches which take longer to write are too large, however fast they are per row.
+const MAXIMUM_BATCH_LATENCY_IN_MILLISECONDS: u128 = 1000;
+/// Throughput within this share of the previous measurement counts as unchanged.
+const THROUGHPUT_TOLERANCE: f64 = 0.95;
+
+/// Picks the number of rows written per statement at runtime. It measures the throughput of
+/// each full batch and keeps changing the batch size in the direction which improved it last
+/// time (hill climbing), halving it whenever a batch exceeds the latency bound.
+#[derive(Debug)]
+pub struct BatchSizeTuner {
+    batch_size: usize,
+    growing: bool,
+    previous_rows_per_second: f64,
+}
+
+impl Default for BatchSizeTuner {
+    fn default() -> Self {
+        BatchSizeTuner { batch_size: INITIAL_BATCH_SIZE, growing: true, previous_rows_per_second: 0.0 }
+    }
+}
+
+impl BatchSizeTuner {
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Records how long writing `rows` rows took. Batches smaller than the current batch
+    /// size, such as the last one near the chain tip, tell nothing about it and are ignored.
+    pub fn record(&mut self, rows: usize, elapsed: Duration) {
+        if rows < self.batch_size {
+            return;
+        }
+
+        if elapsed.as_millis() > MAXIMUM_BATCH_LATENCY_IN_MILLISECONDS {
+            self.batch_size = (self.batch_size / 2).max(MINIMUM_BATCH_SIZE);
+            self.growing = false;
+            self.previous_rows_per_second = 0.0;
+            return;
+        }
+
+        let rows_per_second = rows as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        if rows_per_second < self.previous_rows_per_second * THROUGHPUT_TOLERANCE {
+            self.growing = !self.growing;
+        }
+        self.previous_rows_per_second = rows_per_second;
+
+        self.batch_size = if self.growing {
+            (self.batch_size * 2).min(MAXIMUM_BATCH_SIZE)
+        } else {
+            (self.batch_size / 2).max(MINIMUM_BATCH_SIZE)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn batch_size_stays_within_bounds(
+            measurements in prop::collection::vec((0..10000usize, 0..5000u64), 0..200),
+        ) {
+            let mut tuner = BatchSizeTuner::default();
+
+            for (rows, elapsed_in_milliseconds) in measurements {
+                tuner.record(rows, Duration::from_millis(elapsed_in_milliseconds));
+                prop_assert!((MINIMUM_BATCH_SIZE..=MAXIMUM_BATCH_SIZE).contains(&tuner.batch_size()));
+            }
+        }
+
+        #[test]
+        fn partial_batches_do_not_change_the_batch_size(elapsed_in_milliseconds in 0..5000u64) {
+            let mut tuner = BatchSizeTuner::default();
+
+            tuner.record(INITIAL_BATCH_SIZE - 1, Duration::from_millis(elapsed_in_milliseconds));
+            prop_assert_eq!(tuner.batch_size(), INITIAL_BATCH_SIZE);
+        }
+
+        #[test]
+        fn slow_batches_shrink_the_batch_size(extra_in_milliseconds in 1..5000u64) {
+            let mut tuner = BatchSizeTuner::default();
+            let elapsed =
+                Duration::from_millis(MAXIMUM_BATCH_LATENCY_IN_MILLISECONDS as u64 + extra_in_milliseconds);
+
+            tuner.record(INITIAL_BATCH_SIZE, elapsed);
+            prop_assert!(tuner.batch_size() < INITIAL_BATCH_SIZE);
+        }
+    }
+}
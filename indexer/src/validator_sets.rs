@@ -0,0 +1,114 @@
+use model::{ConsensusAddress, Height};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{Error, repository};
+use crate::rpc::RpcClient;
+
+/// About a hundred blocks apart, so the power behind each proposal is known closely enough.
+pub const VALIDATOR_SET_SNAPSHOT_INTERVAL_IN_SECONDS: u64 = 600;
+/// Most validators CometBFT returns per page of `/validators`.
+const VALIDATORS_PER_PAGE: u32 = 100;
+
+#[derive(Deserialize, Debug)]
+struct ValidatorsResponse {
+    result: ValidatorsResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct ValidatorsResult {
+    block_height: Height,
+    validators: Vec<RpcValidator>,
+    /// Number of validators on all pages.
+    total: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcValidator {
+    address: ConsensusAddress,
+    voting_power: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SnapshotValidator {
+    pub address: ConsensusAddress,
+    pub voting_power: i64,
+    /// 1 for the validator with the most voting power.
+    pub rank: i32,
+}
+
+/// Stores the active validator set of the latest height, with the voting power and rank of each
+/// validator, in `validator_set_snapshots`, so the statistics service can compare the blocks
+/// a validator proposed with the share of them its power entitles it to.
+pub async fn snapshot(rpc_client: &RpcClient, database_client: &tokio_postgres::Client) -> Result<(), Error> {
+    let (height, validators) = request_validator_set(rpc_client, None).await?;
+
+    info!(%height, validators = validators.len(), "Taking validator set snapshot");
+    repository::insert_validator_set_snapshot(database_client, height, &rank(validators)).await
+}
+
+/// Requests the validator set at the height, or the latest one, ordered by voting power. Pages
+/// after the first are requested at the height of the first, so they belong to one set.
+pub async fn request_validator_set(rpc_client: &RpcClient, mut height: Option<Height>)
+                                   -> Result<(Height, Vec<(ConsensusAddress, i64)>), Error> {
+    let mut validators = Vec::new();
+    let mut page = 1;
+
+    loop {
+        let height_param = height.map(|h| format!("&height={h}")).unwrap_or_default();
+        let response: ValidatorsResponse = rpc_client
+            .request_rpc(&format!("/validators?page={page}&per_page={VALIDATORS_PER_PAGE}{height_param}"))
+            .await?
+            .json()
+            .await
+            .map_err(|e| Error::CouldNotParseResponseForValidatorSet(Box::new(e)))?;
+
+        let total: usize = response.result.total
+            .parse()
+            .map_err(|e| Error::CouldNotParseResponseForValidatorSet(Box::new(e)))?;
+        let count_on_page = response.result.validators.len();
+
+        for validator in response.result.validators {
+            let voting_power = validator.voting_power
+                .parse()
+                .map_err(|e| Error::CouldNotParseResponseForValidatorSet(Box::new(e)))?;
+            validators.push((validator.address, voting_power));
+        }
+
+        let height = *height.get_or_insert(response.result.block_height);
+        if validators.len() >= total || count_on_page == 0 {
+            return Ok((height, validators));
+        }
+        page += 1;
+    }
+}
+
+/// Ranks by voting power, most first. Validators with the same power are ranked by address.
+fn rank(mut validators: Vec<(ConsensusAddress, i64)>) -> Vec<SnapshotValidator> {
+    validators.sort_by(|(a, a_power), (b, b_power)| b_power.cmp(a_power).then_with(|| a.cmp(b)));
+
+    validators.into_iter()
+        .zip(1..)
+        .map(|((address, voting_power), rank)| SnapshotValidator { address, voting_power, rank })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(byte: char) -> ConsensusAddress {
+        byte.to_string().repeat(40).parse().unwrap()
+    }
+
+    #[test]
+    fn validators_are_ranked_by_voting_power() {
+        let ranked = rank(vec![(address('C'), 10), (address('A'), 30), (address('B'), 10)]);
+
+        assert_eq!(ranked, vec![
+            SnapshotValidator { address: address('A'), voting_power: 30, rank: 1 },
+            SnapshotValidator { address: address('B'), voting_power: 10, rank: 2 },
+            SnapshotValidator { address: address('C'), voting_power: 10, rank: 3 },
+        ]);
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use model::ConsensusAddress;
+use reqwest::Url;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{Error, genesis, repository};
+use crate::rpc::RpcClient;
+
+pub const VALIDATOR_REFRESH_INTERVAL_IN_SECONDS: u64 = 3600;
+const VALIDATORS_PER_PAGE: u32 = 200;
+const VALIDATORS_PATH: &str = "/cosmos/staking/v1beta1/validators";
+/// Consensus addresses of other key types are not derived the same way, so they are skipped.
+const ED25519_PUBLIC_KEY_TYPE: &str = "/cosmos.crypto.ed25519.PubKey";
+
+#[derive(Deserialize, Debug)]
+struct ValidatorsResponse {
+    validators: Vec<LcdValidator>,
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LcdValidator {
+    operator_address: String,
+    consensus_pubkey: ConsensusPubKey,
+    description: Description,
+    commission: Option<Commission>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConsensusPubKey {
+    #[serde(rename = "@type")]
+    key_type: String,
+    key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Description {
+    moniker: String,
+    /// Keybase key suffix, from which avatars are resolved.
+    #[serde(default)]
+    identity: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Commission {
+    commission_rates: CommissionRates,
+    update_time: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CommissionRates {
+    /// Decimal fraction with 18 digits, e.g. `0.050000000000000000`.
+    rate: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Pagination {
+    next_key: Option<String>,
+}
+
+pub struct Validator {
+    pub address: ConsensusAddress,
+    pub operator_address: String,
+    pub moniker: String,
+    pub identity: Option<String>,
+    pub commission: Option<ValidatorCommission>,
+}
+
+pub struct ValidatorCommission {
+    pub rate: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Commission rate of a validator which differs from the one stored at the previous refresh.
+#[derive(Debug)]
+pub struct CommissionChange {
+    pub address: ConsensusAddress,
+    pub old_rate: String,
+    pub new_rate: String,
+    /// Lowest indexed height at or after the time of the change, or the highest indexed height
+    /// if no block after it is indexed yet.
+    pub height: Option<i64>,
+}
+
+/// Stores the consensus address, operator address, moniker and commission rate of every validator
+/// known to the LCD endpoint in `validators`, so the statistics service can name the proposers of
+/// blocks. Validators of all bonding statuses are requested, page by page, and existing rows are
+/// updated because monikers change. Changed commission rates are recorded in `commission_changes`.
+pub async fn refresh(rpc_client: &RpcClient, database_client: &tokio_postgres::Client, lcd_url: &str)
+                     -> Result<(), Error> {
+    let mut validators = Vec::new();
+    let mut next_key = None;
+
+    loop {
+        let response = request_validators(rpc_client, lcd_url, next_key.as_deref()).await?;
+
+        validators.extend(response.validators.into_iter().filter_map(to_validator));
+
+        next_key = response.pagination.and_then(|p| p.next_key).filter(|k| !k.is_empty());
+        if next_key.is_none() {
+            break;
+        }
+    }
+
+    info!(validators = validators.len(), "Refreshing validators");
+    let changes = repository::record_commission_changes(database_client, &validators).await?;
+    for change in changes {
+        warn!(validator = %change.address,
+              old_rate = change.old_rate,
+              new_rate = change.new_rate,
+              height = change.height,
+              "Commission changed");
+    }
+
+    repository::upsert_validators(database_client, &validators).await
+}
+
+async fn request_validators(rpc_client: &RpcClient, lcd_url: &str, page_key: Option<&str>)
+                            -> Result<ValidatorsResponse, Error> {
+    let limit = VALIDATORS_PER_PAGE.to_string();
+    let mut params = vec![("pagination.limit", limit.as_str())];
+    if let Some(key) = page_key {
+        params.push(("pagination.key", key));
+    }
+
+    let url = Url::parse_with_params(&format!("{lcd_url}{VALIDATORS_PATH}"), &params)
+        .map_err(|e| Error::CouldNotBuildHttpRequest { url: lcd_url.to_string(), source: Box::new(e) })?;
+
+    rpc_client.request(url.to_string())
+        .await?
+        .json()
+        .await
+        .map_err(Error::CouldNotParseResponseForValidators)
+}
+
+fn to_validator(validator: LcdValidator) -> Option<Validator> {
+    if validator.consensus_pubkey.key_type != ED25519_PUBLIC_KEY_TYPE {
+        return None;
+    }
+
+    Some(Validator {
+        address: genesis::consensus_address(&validator.consensus_pubkey.key)?,
+        operator_address: validator.operator_address,
+        moniker: validator.description.moniker,
+        identity: validator.description.identity.filter(|i| !i.trim().is_empty()),
+        // Stored as numeric, so a rate which is not a number would fail the whole refresh.
+        commission: validator.commission
+            .filter(|c| c.commission_rates.rate.parse::<f64>().is_ok())
+            .map(|c| ValidatorCommission { rate: c.commission_rates.rate, updated_at: c.update_time }),
+    })
+}
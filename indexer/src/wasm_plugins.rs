@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+use osmosis_core::BoxError;
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::Error;
+use crate::plugins::{BlockData, BlockProcessor, Record};
+
+const DEFAULT_PLUGINS_DIRECTORY: &str = "plugins";
+/// Instructions a plugin may execute per block, roughly, before it is stopped.
+const FUEL_PER_BLOCK: u64 = 100_000_000;
+const MAXIMUM_MEMORY_IN_BYTES: usize = 64 * 1024 * 1024;
+/// Longest output read back from a plugin, checked before the indexer allocates room for it.
+const MAXIMUM_OUTPUT_IN_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Deserialize, Debug)]
+struct WasmRecord {
+    key: String,
+    value: Value,
+}
+
+/// Plugin loaded from a WebAssembly module, run in a fresh sandbox for every block: without any
+/// imports, so it cannot reach files, the network or the clock, and with bounded fuel and memory.
+///
+/// The module exports its `memory`, `alloc(len: i32) -> i32` returning where the indexer may
+/// write `len` bytes, and `process(ptr: i32, len: i32) -> i64`. `process` receives the JSON
+/// `{"height": <height>, "block": <the /block response>}` and returns the address of its output
+/// in the upper and its length in the lower 32 bits. The output is a JSON array of
+/// `{"key": <string>, "value": <any JSON>}` records for `custom_records`; WASM plugins have
+/// no tables of their own.
+pub struct WasmProcessor {
+    name: &'static str,
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmProcessor {
+    fn load(engine: &Engine, path: &Path) -> Result<Self, Error> {
+        let load_error = |source: BoxError| {
+            Error::CouldNotLoadWasmPlugin { path: path.display().to_string(), source }
+        };
+        let module = Module::from_file(engine, path).map_err(|e| load_error(e.into()))?;
+
+        let exports_abi = ["memory", "alloc", "process"].iter().all(|e| module.get_export(e).is_some());
+        if module.imports().len() > 0 || !exports_abi {
+            return Err(load_error("plugin imports functions or lacks the exports of the ABI".into()));
+        }
+
+        let name = path.file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| load_error("file name is not valid UTF-8".into()))?;
+        // Names are static for records of compiled in plugins; the few loaded once live as long.
+        let name = Box::leak(name.to_string().into_boxed_str());
+
+        Ok(WasmProcessor { name, engine: engine.clone(), module })
+    }
+
+    fn run(&self, input: &[u8]) -> wasmtime::Result<Vec<WasmRecord>> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAXIMUM_MEMORY_IN_BYTES).build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_PER_BLOCK)?;
+
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+        let memory = instance.get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("missing memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let process = instance.get_typed_func::<(i32, i32), i64>(&mut store, "process")?;
+
+        let length = i32::try_from(input.len())?;
+        let address = alloc.call(&mut store, length)?;
+        memory.write(&mut store, address as u32 as usize, input)?;
+
+        let output = process.call(&mut store, (address, length))? as u64;
+        let (output_address, output_length) = ((output >> 32) as usize, (output & u32::MAX as u64) as usize);
+        if output_length > MAXIMUM_OUTPUT_IN_BYTES
+            || output_address + output_length > memory.data_size(&store) {
+            return Err(wasmtime::Error::msg("output is out of bounds or too long"));
+        }
+        let mut records = vec![0; output_length];
+        memory.read(&store, output_address, &mut records)?;
+
+        Ok(serde_json::from_slice(&records)?)
+    }
+}
+
+impl BlockProcessor for WasmProcessor {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// A plugin which fails, runs out of fuel or returns invalid output loses its records for the block.
+    fn process(&self, block: &BlockData) -> Vec<Record> {
+        let input = json!({ "height": block.height.value(), "block": block.response }).to_string();
+
+        match self.run(input.as_bytes()) {
+            Ok(records) => records.into_iter().map(|r| Record::Value { key: r.key, value: r.value }).collect(),
+            Err(e) => {
+                warn!(plugin = self.name, height = %block.height, error = %e, "WASM plugin failed");
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Loads every `.wasm` or `.wat` module in `PLUGINS_DIRECTORY`, `plugins` by default. A missing
+/// directory means no plugins, while a module which cannot be loaded stops the indexer.
+pub fn load_from_env() -> Result<Vec<Box<dyn BlockProcessor>>, Error> {
+    let directory = std::env::var("PLUGINS_DIRECTORY").unwrap_or_else(|_| DEFAULT_PLUGINS_DIRECTORY.to_string());
+    let Ok(entries) = fs::read_dir(&directory) else {
+        return Ok(Vec::new());
+    };
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)
+        .map_err(|e| Error::CouldNotLoadWasmPlugin { path: directory.clone(), source: e.into() })?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "wasm" || e == "wat"))
+        .collect();
+    paths.sort();
+
+    paths.iter()
+        .map(|path| {
+            info!(path = %path.display(), "Loading WASM plugin");
+            WasmProcessor::load(&engine, path).map(|p| Box::new(p) as Box<dyn BlockProcessor>)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use model::Height;
+
+    use super::*;
+
+    const RECORDING_PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (data (i32.const 0) "[{\"key\":\"k\",\"value\":1}]")
+        (func (export "alloc") (param i32) (result i32) i32.const 1024)
+        (func (export "process") (param i32 i32) (result i64) i64.const 23))"#;
+
+    const OVERREACHING_PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) i32.const 1024)
+        (func (export "process") (param i32 i32) (result i64) i64.const 0xffffffff))"#;
+
+    const LOOPING_PLUGIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) i32.const 1024)
+        (func (export "process") (param i32 i32) (result i64) (loop (br 0)) i64.const 0))"#;
+
+    fn load(name: &str, wat: &str) -> WasmProcessor {
+        let path = env::temp_dir().join(format!("{name}.wat"));
+        fs::write(&path, wat).unwrap();
+
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        WasmProcessor::load(&Engine::new(&config).unwrap(), &path).unwrap()
+    }
+
+    #[test]
+    fn records_come_from_the_module_and_runaway_modules_are_stopped() {
+        let block = BlockData { height: Height::new(1), response: &json!({}) };
+
+        let records = load("recording", RECORDING_PLUGIN).process(&block);
+        assert_eq!(records, vec![Record::Value { key: "k".to_string(), value: json!(1) }]);
+
+        assert!(load("overreaching", OVERREACHING_PLUGIN).process(&block).is_empty());
+        assert!(load("looping", LOOPING_PLUGIN).process(&block).is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use std::mem;
+
+use model::Height;
+use osmosis_core::Block;
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::Error;
+use crate::buffer::InsertBuffer;
+use crate::health::Progress;
+use crate::store::Store;
+use crate::tuner::BatchSizeTuner;
+
+const DEFAULT_COPY_THRESHOLD_IN_ROWS: usize = 500;
+
+/// Collects fetched rows and writes them in batches of the size chosen by the tuner, with COPY
+/// once a batch reaches the copy threshold and with INSERT below it. Batches which cannot be
+/// written while the database is unavailable go to the insert buffer.
+pub struct BatchWriter {
+    pending: Vec<Block>,
+    tuner: BatchSizeTuner,
+    buffer: InsertBuffer,
+    copy_threshold: usize,
+    /// Highest height read from the database or written since, which indexing goes on from
+    /// while the database is unavailable and nothing is buffered yet.
+    indexed_height: Option<Height>,
+    progress: Progress,
+}
+
+impl BatchWriter {
+    pub fn new(copy_threshold: Option<usize>, progress: Progress) -> Self {
+        BatchWriter {
+            pending: Vec::new(),
+            tuner: BatchSizeTuner::default(),
+            buffer: InsertBuffer::default(),
+            copy_threshold: copy_threshold.unwrap_or(DEFAULT_COPY_THRESHOLD_IN_ROWS),
+            indexed_height: None,
+            progress,
+        }
+    }
+
+    /// Writes everything left over from previous cycles, buffered batches first.
+    pub async fn replay(&mut self, store: &impl Store) -> Result<(), Error> {
+        self.buffer.replay(store, &self.progress).await?;
+        self.flush(store).await
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn record_indexed_height(&mut self, height: Option<Height>) {
+        self.indexed_height = height;
+    }
+
+    /// Highest height which is indexed, pending or buffered.
+    pub fn highest_height(&self) -> Option<Height> {
+        self.pending.iter()
+            .map(|p| p.height)
+            .max()
+            .max(self.buffer.highest_height())
+            .max(self.indexed_height)
+    }
+
+    pub async fn write(&mut self, store: &impl Store, rows: Vec<Block>) -> Result<(), Error> {
+        self.pending.extend(rows);
+
+        if self.pending.len() >= self.tuner.batch_size() {
+            self.flush(store).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&mut self, store: &impl Store) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        let written = if self.pending.len() >= self.copy_threshold {
+            store.copy_batch(&self.pending).await
+        } else {
+            store.insert_batch(&self.pending).await
+        };
+
+        match written {
+            Ok(written) => {
+                // Skipping an indexed height costs next to nothing, so only written rows tell
+                // the throughput.
+                let batch_size = self.tuner.batch_size();
+                self.tuner.record(written as usize, started.elapsed());
+                self.progress.record_written(self.pending.len(), written);
+                if self.tuner.batch_size() != batch_size {
+                    info!(batch_size = self.tuner.batch_size(), "Batch size changed");
+                }
+                self.indexed_height = self.indexed_height.max(self.pending.iter().map(|p| p.height).max());
+                self.pending.clear();
+                Ok(())
+            }
+            Err(Error::DatabaseUnavailable(_)) => self.buffer.push(mem::take(&mut self.pending)),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Runs the queries of the indexer against PostgreSQL, each test in its own copy of the database
+//! in `DATABASE_URL`, which `indexer migrate` must have migrated. The indexer is run as a binary
+//! with RPC responses replayed from a cassette. Without `DATABASE_URL` the tests are skipped.
+
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use chrono::{TimeZone, Utc};
+use model::Height;
+use osmosis_core::database::{self, DatabaseConfig};
+use osmosis_core::{repository, Block};
+use serde_json::json;
+use tokio::process::Command;
+
+const PROPOSER_A: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+const PROPOSER_B: &str = "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB";
+/// 2024-01-01T00:00:00Z, the time of height 0; blocks follow every 6 seconds.
+const GENESIS_TIME_IN_SECONDS: i64 = 1704067200;
+
+struct TestDatabase {
+    name: String,
+    database_config: DatabaseConfig,
+    client: tokio_postgres::Client,
+}
+
+impl TestDatabase {
+    /// Copies the database without its blocks, validators and checkpoints.
+    async fn create(test: &str) -> Option<Self> {
+        let database = Self::create_from_template(test, None).await?;
+        database.client
+            .batch_execute("TRUNCATE proposer_to_height, block_signatures, signer_sets, validators, \
+                            indexer_checkpoints, height_requests")
+            .await
+            .unwrap();
+        Some(database)
+    }
+
+    /// Creates a database without any tables.
+    async fn create_empty(test: &str) -> Option<Self> {
+        Self::create_from_template(test, Some("template0")).await
+    }
+
+    /// Copies the template, by default the database in DATABASE_URL.
+    async fn create_from_template(test: &str, template: Option<&str>) -> Option<Self> {
+        if env::var("DATABASE_URL").is_err() {
+            eprintln!("Skipping {test}, DATABASE_URL is not set");
+            return None;
+        }
+        let database_config = database::from_env("localhost", "postgres", None).await.unwrap();
+        let database_name = database_config.config.get_dbname().unwrap().to_string();
+        let template = template.unwrap_or(&database_name);
+        let name = format!("{database_name}_{test}");
+
+        let maintenance = connect(&database_config, "postgres").await;
+        maintenance.batch_execute(&format!("DROP DATABASE IF EXISTS \"{name}\" WITH (FORCE)")).await.unwrap();
+        maintenance.batch_execute(&format!("CREATE DATABASE \"{name}\" TEMPLATE \"{template}\"")).await.unwrap();
+
+        let client = connect(&database_config, &name).await;
+        Some(TestDatabase { name, database_config, client })
+    }
+
+    async fn drop(self) {
+        drop(self.client);
+        let maintenance = connect(&self.database_config, "postgres").await;
+        maintenance.batch_execute(&format!("DROP DATABASE \"{}\" WITH (FORCE)", self.name)).await.unwrap();
+    }
+
+    async fn run_indexer(&self, args: &[&str], cassette: &Path) -> Output {
+        self.run_indexer_with(args, cassette, &[]).await
+    }
+
+    async fn run_indexer_with(&self, args: &[&str], cassette: &Path, variables: &[(&str, &str)]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_indexer"))
+            .args(args)
+            .envs(variables.iter().copied())
+            .env("DATABASE_NAME", &self.name)
+            .env("RPC_CASSETTE_REPLAY", cassette)
+            .output()
+            .await
+            .unwrap()
+    }
+
+    async fn proposers(&self) -> Vec<(i64, String)> {
+        self.client
+            .query("SELECT height, proposer FROM proposer_to_height ORDER BY height", &[])
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| (r.get(0), r.get(1)))
+            .collect()
+    }
+
+    async fn indexed_height_ranges(&self) -> Vec<(i64, i64)> {
+        self.client
+            .query("SELECT first_height, last_height FROM indexed_height_ranges ORDER BY first_height", &[])
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| (r.get(0), r.get(1)))
+            .collect()
+    }
+}
+
+async fn connect(database_config: &DatabaseConfig, name: &str) -> tokio_postgres::Client {
+    let mut config = database_config.config.clone();
+    config.dbname(name);
+    let (client, connection) = config.connect(database_config.tls.clone()).await.unwrap();
+    tokio::spawn(connection);
+    client
+}
+
+fn block(height: u64, proposer: &str) -> Block {
+    Block {
+        proposer: proposer.parse().unwrap(),
+        height: Height::new(height),
+        hash: format!("{height:064X}").parse().unwrap(),
+        last_block_hash: None,
+        block_time: Utc.timestamp_opt(GENESIS_TIME_IN_SECONDS + 6 * height as i64, 0).unwrap(),
+        num_txs: 0,
+        last_commit: None,
+    }
+}
+
+fn blocks(heights: impl IntoIterator<Item=u64>, proposer: &str) -> Vec<Block> {
+    heights.into_iter().map(|h| block(h, proposer)).collect()
+}
+
+/// Writes the responses of a chain whose blocks at the heights were proposed by PROPOSER_A, named
+/// the way the cassette of the indexer names its recordings.
+fn record_cassette(test: &str, heights: impl IntoIterator<Item=u64>) -> PathBuf {
+    let directory = env::temp_dir().join(format!("cassette-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    let record = |name: String, body: serde_json::Value| {
+        let recording = json!({"request": "", "status": 200, "body": body.to_string()});
+        fs::write(directory.join(format!("{name}.json")), recording.to_string()).unwrap();
+    };
+    record("_status".to_string(), json!({"result": {
+        "node_info": {"network": "osmosis-1"},
+        "sync_info": {"earliest_block_height": "1"},
+    }}));
+    for b in blocks(heights, PROPOSER_A) {
+        record(format!("_block_height_{}", b.height), json!({"result": {
+            "block_id": {"hash": b.hash},
+            "block": {
+                "header": {"height": b.height.to_string(), "time": b.block_time, "proposer_address": b.proposer},
+                "data": {"txs": []},
+            },
+        }}));
+    }
+
+    directory
+}
+
+#[tokio::test]
+async fn inserts_skip_indexed_heights_and_upserts_overwrite_them() {
+    let Some(database) = TestDatabase::create("inserts_and_upserts").await else {
+        return;
+    };
+
+    assert_eq!(repository::insert_batch(&database.client, &blocks(1..=3, PROPOSER_A)).await.unwrap(), 3);
+    assert_eq!(repository::insert_batch(&database.client, &blocks(2..=5, PROPOSER_B)).await.unwrap(), 2);
+    assert_eq!(repository::copy_batch(&database.client, &blocks(6..=7, PROPOSER_B)).await.unwrap(), 2);
+    assert_eq!(repository::copy_batch(&database.client, &blocks(7..=8, PROPOSER_A)).await.unwrap(), 1);
+    repository::upsert_batch(&database.client, &blocks([2], PROPOSER_B)).await.unwrap();
+
+    let proposers: Vec<(i64, String)> = [PROPOSER_A, PROPOSER_B, PROPOSER_A, PROPOSER_B,
+                                         PROPOSER_B, PROPOSER_B, PROPOSER_B, PROPOSER_A]
+        .iter()
+        .zip(1..)
+        .map(|(p, h)| (h, p.to_string()))
+        .collect();
+    assert_eq!(database.proposers().await, proposers);
+    assert_eq!(database.indexed_height_ranges().await, vec![(1, 8)]);
+
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn verify_gaps_fails_until_the_missing_heights_are_indexed() {
+    let Some(database) = TestDatabase::create("verify_gaps").await else {
+        return;
+    };
+    let cassette = record_cassette("verify_gaps", []);
+    repository::insert_batch(&database.client, &blocks((1..=3).chain(6..=7), PROPOSER_A)).await.unwrap();
+
+    let output = database.run_indexer(&["verify-gaps", "--from", "1"], &cassette).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("IndexHasGaps { missing_heights: 2, ranges: 1 }"));
+    assert_eq!(database.indexed_height_ranges().await, vec![(1, 3), (6, 7)]);
+
+    repository::insert_batch(&database.client, &blocks(4..=5, PROPOSER_A)).await.unwrap();
+
+    let output = database.run_indexer(&["verify-gaps", "--from", "1"], &cassette).await;
+    assert!(output.status.success());
+    assert_eq!(database.indexed_height_ranges().await, vec![(1, 7)]);
+
+    fs::remove_dir_all(cassette).unwrap();
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn backfill_completes_its_checkpoint_and_is_not_fetched_again() {
+    let Some(database) = TestDatabase::create("backfill").await else {
+        return;
+    };
+    repository::insert_batch(&database.client, &blocks([3], PROPOSER_B)).await.unwrap();
+
+    let cassette = record_cassette("backfill", 1..=5);
+    let output = database.run_indexer(&["backfill", "--from", "1", "--to", "5"], &cassette).await;
+    assert!(output.status.success());
+
+    let proposers: Vec<(i64, String)> = (1..=5).map(|h| (h, PROPOSER_A.to_string())).collect();
+    assert_eq!(database.proposers().await, proposers);
+    let checkpoint = database.client
+        .query_one("SELECT first_height, last_height, cursor FROM indexer_checkpoints WHERE name = $1",
+                   &[&"backfill-1-5"])
+        .await
+        .unwrap();
+    assert_eq!((checkpoint.get(0), checkpoint.get(1), checkpoint.get(2)), (1i64, 5i64, 6i64));
+
+    // Without the blocks in the cassette, running the completed backfill again must not fetch them.
+    fs::remove_dir_all(&cassette).unwrap();
+    let cassette = record_cassette("backfill", []);
+    let output = database.run_indexer(&["backfill", "--from", "1", "--to", "5"], &cassette).await;
+    assert!(output.status.success());
+    assert_eq!(database.proposers().await, proposers);
+
+    fs::remove_dir_all(cassette).unwrap();
+    database.drop().await;
+}
+
+/// `00_init.sql` as Docker ran it before migrations were tracked: as first released, and as it
+/// was once validators were added, leaving out the role which the cluster may have already.
+const INITIAL_SCRIPTS: [&str; 2] = [
+    "create table proposer_to_height (
+         proposer varchar(255),
+         height bigint UNIQUE
+     );",
+    "create table proposer_to_height (
+         proposer varchar(255),
+         height bigint UNIQUE,
+         hash varchar(64) UNIQUE,
+         block_time timestamptz,
+         num_txs integer
+     );
+     create table validators (
+         address varchar(64) PRIMARY KEY,
+         operator_address varchar(255) NOT NULL,
+         moniker varchar(255) NOT NULL,
+         updated_at timestamp NOT NULL DEFAULT now()
+     );
+     create table height_requests (
+         height bigint PRIMARY KEY,
+         requested_at timestamp NOT NULL DEFAULT now()
+     );",
+];
+
+async fn columns(client: &tokio_postgres::Client) -> Vec<(String, String, String, String)> {
+    client
+        .query("SELECT table_name::text, column_name::text, data_type::text, is_nullable::text \
+                FROM information_schema.columns WHERE table_schema = 'public' ORDER BY 1, 2", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2), r.get(3)))
+        .collect()
+}
+
+#[tokio::test]
+async fn databases_created_from_the_initial_script_are_migrated_to_the_current_schema() {
+    for (i, script) in INITIAL_SCRIPTS.iter().enumerate() {
+        let Some(database) = TestDatabase::create_empty(&format!("initial_script_{i}")).await else {
+            return;
+        };
+        database.client.batch_execute(script).await.unwrap();
+        database.client
+            .batch_execute(&format!("INSERT INTO proposer_to_height (proposer, height) \
+                                     VALUES ('{PROPOSER_A}', 1), ('{PROPOSER_A}', 2), ('{PROPOSER_B}', 4)"))
+            .await
+            .unwrap();
+        let cassette = record_cassette(&format!("initial_script_{i}"), []);
+
+        let output = database.run_indexer(&["migrate"], &cassette).await;
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+
+        let migrated_database_name = database.database_config.config.get_dbname().unwrap();
+        let migrated_database = connect(&database.database_config, migrated_database_name).await;
+        assert_eq!(columns(&database.client).await, columns(&migrated_database).await);
+        assert_eq!(database.indexed_height_ranges().await, vec![(1, 2), (4, 4)]);
+        let role = database.client
+            .query_one("SELECT count(*) FROM pg_roles WHERE rolname = 'statistics_readonly'", &[])
+            .await
+            .unwrap();
+        assert_eq!(role.get::<_, i64>(0), 1);
+
+        drop(migrated_database);
+        fs::remove_dir_all(cassette).unwrap();
+        database.drop().await;
+    }
+}
+
+#[tokio::test]
+async fn indexers_migrating_together_publish_every_table_but_the_bookkeeping() {
+    let Some(database) = TestDatabase::create("cdc_publication").await else {
+        return;
+    };
+    let cassette = record_cassette("cdc_publication", []);
+    let variables = [("CDC_PUBLICATION", "dataset_changes")];
+
+    let (first, second) = tokio::join!(
+        database.run_indexer_with(&["migrate"], &cassette, &variables),
+        database.run_indexer_with(&["migrate"], &cassette, &variables),
+    );
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stdout));
+    assert!(second.status.success(), "{}", String::from_utf8_lossy(&second.stdout));
+
+    let unpublished: Vec<String> = database.client
+        .query("SELECT tablename::text FROM pg_tables WHERE schemaname = 'public' \
+                EXCEPT SELECT tablename::text FROM pg_publication_tables WHERE pubname = 'dataset_changes' \
+                ORDER BY 1", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(unpublished, [
+        "fetch_telemetry", "height_requests", "indexed_height_ranges", "indexer_checkpoints", "indexer_state",
+        "plugin_migrations", "reports", "schema_migrations", "subsystem_backfills",
+    ]);
+
+    fs::remove_dir_all(cassette).unwrap();
+    database.drop().await;
+}
@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Debug;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+
+const DEFAULT_LOG_LEVEL: Level = Level::INFO;
+const JSON_LOG_FORMAT: &str = "json";
+
+thread_local! {
+    /// Spans entered on this thread, innermost last. Tasks enter their spans on every poll.
+    static ENTERED_SPANS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes events of the services to stdout, one line each, prefixed with the spans they
+/// happen in. `LOG_LEVEL` (error, warn, info, debug or trace) sets the most verbose level
+/// written, and `LOG_FORMAT=json` writes JSON objects for log aggregation instead of text.
+struct LineSubscriber {
+    max_level: Level,
+    json: bool,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+}
+
+struct SpanData {
+    name: &'static str,
+    fields: Map<String, Value>,
+    references: usize,
+}
+
+/// Installs the subscriber for the whole process. Unknown levels fall back to info.
+pub fn init() {
+    let max_level = env::var("LOG_LEVEL")
+        .ok()
+        .and_then(|l| Level::from_str(&l).ok())
+        .unwrap_or(DEFAULT_LOG_LEVEL);
+    let json = env::var("LOG_FORMAT").is_ok_and(|f| f == JSON_LOG_FORMAT);
+
+    let subscriber = LineSubscriber {
+        max_level,
+        json,
+        next_id: AtomicU64::new(1),
+        spans: Mutex::new(HashMap::new()),
+    };
+
+    tracing::subscriber::set_global_default(subscriber).expect("Logging is initialized twice");
+}
+
+impl Subscriber for LineSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut fields = FieldVisitor::default();
+        attributes.record(&mut fields);
+
+        let span = SpanData { name: attributes.metadata().name(), fields: fields.0, references: 1 };
+        self.spans.lock().unwrap().insert(id, span);
+
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            let mut fields = FieldVisitor(std::mem::take(&mut span.fields));
+            values.record(&mut fields);
+            span.fields = fields.0;
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+
+        let spans = self.entered_spans();
+        let metadata = event.metadata();
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+        let line = if self.json {
+            let spans: Vec<Value> = spans
+                .into_iter()
+                .map(|(name, mut fields)| {
+                    fields.insert("name".to_string(), Value::from(name));
+                    Value::Object(fields)
+                })
+                .collect();
+
+            let mut object = Map::new();
+            object.insert("timestamp".to_string(), Value::from(timestamp));
+            object.insert("level".to_string(), Value::from(metadata.level().as_str()));
+            object.insert("target".to_string(), Value::from(metadata.target()));
+            object.insert("spans".to_string(), Value::from(spans));
+            object.insert("fields".to_string(), Value::Object(fields.0));
+            Value::Object(object).to_string()
+        } else {
+            let mut line = format!("{timestamp} {:>5} ", metadata.level());
+            for (name, fields) in spans {
+                line.push_str(&format!("{name}{}: ", format_fields(&fields, true)));
+            }
+
+            let mut fields = fields.0;
+            if let Some(message) = fields.remove("message") {
+                line.push_str(message.as_str().unwrap_or_default());
+            }
+            line.push_str(&format_fields(&fields, false));
+            line
+        };
+
+        let _ = writeln!(std::io::stdout().lock(), "{line}");
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED_SPANS.with(|s| s.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED_SPANS.with(|s| {
+            let mut entered = s.borrow_mut();
+            if let Some(position) = entered.iter().rposition(|&id| id == span.into_u64()) {
+                entered.remove(position);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(data) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            data.references += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let Some(data) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+
+        data.references -= 1;
+        if data.references > 0 {
+            return false;
+        }
+
+        spans.remove(&span.into_u64());
+        true
+    }
+}
+
+impl LineSubscriber {
+    /// Names and fields of the spans entered on this thread, outermost first.
+    fn entered_spans(&self) -> Vec<(&'static str, Map<String, Value>)> {
+        let entered = ENTERED_SPANS.with(|s| s.borrow().clone());
+        let spans = self.spans.lock().unwrap();
+
+        entered
+            .iter()
+            .filter_map(|id| spans.get(id))
+            .map(|s| (s.name, s.fields.clone()))
+            .collect()
+    }
+}
+
+/// Text format of fields: `{a=1 b=x}` for spans and ` a=1 b=x` after the message of an event.
+fn format_fields(fields: &Map<String, Value>, braces: bool) -> String {
+    if fields.is_empty() {
+        return String::new();
+    }
+
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| match value {
+            Value::String(s) => format!("{name}={s}"),
+            v => format!("{name}={v}"),
+        })
+        .collect();
+
+    if braces {
+        format!("{{{}}}", fields.join(" "))
+    } else {
+        format!(" {}", fields.join(" "))
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor(Map<String, Value>);
+
+impl Visit for FieldVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{value:?}")));
+    }
+}
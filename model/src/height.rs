@@ -0,0 +1,219 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bytes::BytesMut;
+use postgres_types::{FromSql, IsNull, ToSql, to_sql_checked, Type};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, Visitor};
+
+use crate::Error;
+
+/// Height of a block. Heights are unsigned on chain but stored as `bigint` in Postgres,
+/// so conversions to and from `i64` are checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Height(u64);
+
+impl Height {
+    pub const fn new(height: u64) -> Self {
+        Height(height)
+    }
+
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+
+    pub const fn saturating_add(self, heights: u64) -> Self {
+        Height(self.0.saturating_add(heights))
+    }
+
+    pub const fn saturating_sub(self, heights: u64) -> Self {
+        Height(self.0.saturating_sub(heights))
+    }
+
+    /// Number of heights from `self` up to and including `last`, zero if `last` is lower.
+    pub const fn count_to(self, last: Height) -> u64 {
+        if last.0 < self.0 { 0 } else { last.0 - self.0 + 1 }
+    }
+
+    /// Heights from `first` up to but excluding `last`.
+    pub fn range(first: Height, last: Height) -> impl Iterator<Item=Height> {
+        (first.0..last.0).map(Height)
+    }
+}
+
+impl From<u64> for Height {
+    fn from(height: u64) -> Self {
+        Height(height)
+    }
+}
+
+impl TryFrom<i64> for Height {
+    type Error = Error;
+
+    fn try_from(height: i64) -> Result<Self, Error> {
+        u64::try_from(height).map(Height).map_err(|_| Error::InvalidHeight)
+    }
+}
+
+impl TryFrom<Height> for i64 {
+    type Error = Error;
+
+    fn try_from(height: Height) -> Result<Self, Error> {
+        i64::try_from(height.0).map_err(|_| Error::InvalidHeight)
+    }
+}
+
+impl FromStr for Height {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        s.parse().map(Height).map_err(|_| Error::InvalidHeight)
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for Height {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+/// CometBFT encodes heights as strings, the API as numbers, so both are accepted.
+impl<'de> Deserialize<'de> for Height {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(HeightVisitor)
+    }
+}
+
+struct HeightVisitor;
+
+impl Visitor<'_> for HeightVisitor {
+    type Value = Height;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative height as a number or a string")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Height, E> {
+        Ok(Height(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Height, E> {
+        Height::try_from(v).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Height, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+impl ToSql for Height {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        i64::try_from(*self)?.to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <i64 as ToSql>::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for Height {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(Height::try_from(i64::from_sql(ty, raw)?)?)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <i64 as FromSql>::accepts(ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const BOUNDARIES: [u64; 5] = [0, 1, i64::MAX as u64 - 1, i64::MAX as u64, i64::MAX as u64 + 1];
+
+    fn to_sql_and_back(height: Height) -> Result<Height, Box<dyn std::error::Error + Sync + Send>> {
+        let mut buffer = BytesMut::new();
+        height.to_sql(&Type::INT8, &mut buffer)?;
+        Height::from_sql(&Type::INT8, &buffer)
+    }
+
+    #[test]
+    fn boundary_values_convert_to_bigint_only_within_range() {
+        for value in BOUNDARIES {
+            let height = Height::new(value);
+            assert_eq!(i64::try_from(height).is_ok(), value <= i64::MAX as u64);
+            assert_eq!(to_sql_and_back(height).ok(), (value <= i64::MAX as u64).then_some(height));
+        }
+
+        assert_eq!(i64::try_from(Height::new(u64::MAX)), Err(Error::InvalidHeight));
+        assert_eq!(Height::try_from(-1i64), Err(Error::InvalidHeight));
+        assert_eq!(Height::try_from(i64::MIN), Err(Error::InvalidHeight));
+    }
+
+    #[test]
+    fn saturates_at_both_ends() {
+        assert_eq!(Height::new(0).saturating_sub(1), Height::new(0));
+        assert_eq!(Height::new(u64::MAX).saturating_add(1), Height::new(u64::MAX));
+        assert_eq!(Height::new(5).count_to(Height::new(4)), 0);
+        assert_eq!(Height::new(5).count_to(Height::new(5)), 1);
+    }
+
+    #[test]
+    fn deserializes_from_numbers_and_strings() {
+        assert_eq!(serde_json::from_str::<Height>("9558628").unwrap(), Height::new(9558628));
+        assert_eq!(serde_json::from_str::<Height>("\"9558628\"").unwrap(), Height::new(9558628));
+        assert!(serde_json::from_str::<Height>("-1").is_err());
+        assert!(serde_json::from_str::<Height>("\"-1\"").is_err());
+        assert!(serde_json::from_str::<Height>("\"18446744073709551616\"").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn non_negative_bigints_round_trip(value in 0..=i64::MAX) {
+            let height = Height::try_from(value).unwrap();
+            prop_assert_eq!(height.value(), value as u64);
+            prop_assert_eq!(i64::try_from(height), Ok(value));
+            prop_assert_eq!(to_sql_and_back(height).unwrap(), height);
+        }
+
+        #[test]
+        fn negative_bigints_are_rejected(value in i64::MIN..0) {
+            prop_assert_eq!(Height::try_from(value), Err(Error::InvalidHeight));
+        }
+
+        #[test]
+        fn heights_above_bigint_are_not_stored(value in (i64::MAX as u64 + 1)..=u64::MAX) {
+            prop_assert_eq!(i64::try_from(Height::new(value)), Err(Error::InvalidHeight));
+            prop_assert!(to_sql_and_back(Height::new(value)).is_err());
+        }
+
+        #[test]
+        fn strings_and_json_round_trip(value in any::<u64>()) {
+            let height = Height::new(value);
+            prop_assert_eq!(height.to_string().parse::<Height>(), Ok(height));
+            let json = serde_json::to_string(&height).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Height>(&json).unwrap(), height);
+        }
+
+        #[test]
+        fn ranges_hold_count_heights(first in 0..u64::MAX / 2, length in 0..1000u64) {
+            let first = Height::new(first);
+            let last = first.saturating_add(length);
+            prop_assert_eq!(Height::range(first, last).count() as u64, length);
+            if length > 0 {
+                prop_assert_eq!(first.count_to(last.saturating_sub(1)), length);
+            }
+        }
+    }
+}
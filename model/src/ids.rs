@@ -0,0 +1,113 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bytes::BytesMut;
+use postgres_types::{FromSql, IsNull, ToSql, to_sql_checked, Type};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+const CONSENSUS_ADDRESS_LENGTH_IN_BYTES: usize = 20;
+const BLOCK_HASH_LENGTH_IN_BYTES: usize = 32;
+/// CometBFT rejects longer chain ids.
+const MAXIMUM_CHAIN_ID_LENGTH: usize = 50;
+
+/// Defines a validated string newtype which (de)serializes and is stored as its string.
+macro_rules! string_type {
+    ($(#[$meta:meta])* $name:ident, $parse:expr) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Error> {
+                let parse: fn(&str) -> Result<String, Error> = $parse;
+                parse(s).map($name)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = Error;
+
+            fn try_from(s: String) -> Result<Self, Error> {
+                s.parse()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl ToSql for $name {
+            fn to_sql(&self, ty: &Type, out: &mut BytesMut)
+                      -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+                self.0.as_str().to_sql(ty, out)
+            }
+
+            fn accepts(ty: &Type) -> bool {
+                <&str as ToSql>::accepts(ty)
+            }
+
+            to_sql_checked!();
+        }
+
+        impl<'a> FromSql<'a> for $name {
+            fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+                Ok(<&str>::from_sql(ty, raw)?.parse()?)
+            }
+
+            fn accepts(ty: &Type) -> bool {
+                <&str as FromSql>::accepts(ty)
+            }
+        }
+    };
+}
+
+string_type!(
+    /// Address of a validator's consensus key, which blocks report as `proposer_address`:
+    /// 20 bytes as upper case hex.
+    ConsensusAddress,
+    |s| parse_hex(s, CONSENSUS_ADDRESS_LENGTH_IN_BYTES).ok_or(Error::InvalidConsensusAddress)
+);
+
+string_type!(
+    /// SHA-256 block hash as upper case hex. Lower case input is accepted.
+    BlockHash,
+    |s| parse_hex(s, BLOCK_HASH_LENGTH_IN_BYTES).ok_or(Error::InvalidBlockHash)
+);
+
+string_type!(
+    /// Identifier of a chain such as `osmosis-1`.
+    ChainId,
+    |s| {
+        if s.is_empty() || s.len() > MAXIMUM_CHAIN_ID_LENGTH || s.chars().any(char::is_whitespace) {
+            return Err(Error::InvalidChainId);
+        }
+        Ok(s.to_string())
+    }
+);
+
+fn parse_hex(s: &str, length_in_bytes: usize) -> Option<String> {
+    if s.len() != 2 * length_in_bytes || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some(s.to_ascii_uppercase())
+}
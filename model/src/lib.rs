@@ -0,0 +1,23 @@
+use std::fmt;
+
+mod ids;
+mod height;
+
+pub use ids::{BlockHash, ChainId, ConsensusAddress};
+pub use height::Height;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    InvalidHeight,
+    InvalidConsensusAddress,
+    InvalidBlockHash,
+    InvalidChainId,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,53 @@
+use axum::extract::State;
+use axum::Json;
+use model::{ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::completeness::{Completeness, request_completeness};
+use crate::params::{check_range, Limit, Params, Validate};
+use crate::repository::Absence;
+
+const DEFAULT_ABSENCE_LIMIT: i64 = 10;
+const MAXIMUM_ABSENCE_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct AbsenceParams {
+    validator: ConsensusAddress,
+    from_height: Option<Height>,
+    to_height: Option<Height>,
+    #[serde(default)]
+    limit: Limit<DEFAULT_ABSENCE_LIMIT, MAXIMUM_ABSENCE_LIMIT>,
+}
+
+impl Validate for AbsenceParams {
+    fn validate(&self) -> Result<(), String> {
+        check_range("from_height", self.from_height.as_ref(), "to_height", self.to_height.as_ref())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct AbsenceResponse {
+    absences: Vec<Absence>,
+    completeness: Completeness,
+}
+
+/// The `limit` longest stretches of consecutive blocks within `[from_height, to_height]` which
+/// the validator did not propose, longest first, which is where it likely went offline.
+/// Stretches before its first and after its last proposal in the range count as well.
+/// Missing bounds default to the lowest and the highest indexed height.
+pub async fn handler(Params(params): Params<AbsenceParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<AbsenceResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let absences = repository::longest_absences(&conn,
+                                                &params.validator,
+                                                params.from_height,
+                                                params.to_height,
+                                                params.limit.value()).await?;
+
+    let completeness = request_completeness(&conn, params.from_height, params.to_height).await?;
+
+    Ok(Json(AbsenceResponse { absences, completeness }))
+}
@@ -0,0 +1,48 @@
+use axum::extract::State;
+use axum::Json;
+use model::ConsensusAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Limit, Params, Validate};
+use crate::repository::Anomaly;
+
+const DEFAULT_ANOMALIES_LIMIT: i64 = 100;
+const MAXIMUM_ANOMALIES_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct AnomaliesParams {
+    validator: Option<ConsensusAddress>,
+    /// `proposal_shortfall` or `missed_signatures_burst`.
+    kind: Option<String>,
+    after_id: Option<i64>,
+    #[serde(default)]
+    limit: Limit<DEFAULT_ANOMALIES_LIMIT, MAXIMUM_ANOMALIES_LIMIT>,
+}
+
+impl Validate for AnomaliesParams {}
+
+#[derive(Serialize, Debug)]
+pub struct AnomaliesResponse {
+    anomalies: Vec<Anomaly>,
+    /// Pass as `after_id` to receive only anomalies detected since this response.
+    last_id: Option<i64>,
+}
+
+/// Anomalies the indexer detected, of all validators or of the watched `validator`, in the order
+/// they were first detected. An anomaly which goes on keeps its id while its `last_height` grows,
+/// so alerting which polls with the `last_id` of the previous response fires once per anomaly.
+pub async fn handler(Params(params): Params<AnomaliesParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<AnomaliesResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let anomalies = repository::anomalies(&conn,
+                                          params.validator.as_ref(),
+                                          params.kind.as_deref(),
+                                          params.after_id,
+                                          params.limit.value()).await?;
+    let last_id = anomalies.last().map(|a| a.id).or(params.after_id);
+
+    Ok(Json(AnomaliesResponse { anomalies, last_id }))
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::Json;
+use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+use model::{BlockHash, ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Params, Validate};
+
+const MAXIMUM_NUMBER_OF_HEIGHTS_PER_LOOKUP: usize = 1000;
+/// Matches the interval at which the indexer takes requested heights from the queue.
+const REQUESTED_HEIGHTS_RETRY_AFTER_IN_SECONDS: u64 = 30;
+
+#[derive(Deserialize, Debug)]
+pub struct ProposersParams {
+    #[serde(default)]
+    enqueue: bool,
+}
+
+impl Validate for ProposersParams {}
+
+#[derive(Deserialize, Debug)]
+pub struct ProposersRequest {
+    heights: Vec<Height>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProposersResponse {
+    proposers: Vec<HeightToProposer>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    queued: Vec<Height>,
+}
+
+#[derive(Serialize, Debug)]
+struct HeightToProposer {
+    height: Height,
+    hash: Option<BlockHash>,
+    proposer: Option<ConsensusAddress>,
+    block_time: Option<DateTime<Utc>>,
+    num_txs: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BlockResponse {
+    height: Height,
+    hash: BlockHash,
+    proposer: ConsensusAddress,
+    proposer_moniker: Option<String>,
+    /// Label of the proposer's consensus or operator address in the address directory.
+    proposer_label: Option<String>,
+    block_time: Option<DateTime<Utc>>,
+    num_txs: Option<i32>,
+}
+
+/// Looks up a block by its hash, which is how external systems usually reference blocks.
+/// The hash is hex encoded and compared case-insensitively.
+/// The proposer is named by its moniker and by its label in the address directory, if it has one.
+pub async fn handler(Path(hash): Path<BlockHash>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<BlockResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let block = repository::block_by_hash(&conn, &hash).await?
+        .ok_or(Error::BlockNotFound)?;
+
+    let proposer = repository::find_validator(&conn, &block.proposer).await?;
+    let operator_address = proposer.as_ref().and_then(|v| v.operator_address.as_deref());
+    let proposer_label = repository::find_label(&conn, &block.proposer, operator_address).await?;
+
+    Ok(Json(BlockResponse {
+        height: block.height,
+        hash,
+        proposer: block.proposer,
+        proposer_moniker: proposer.map(|v| v.moniker),
+        proposer_label,
+        block_time: block.block_time,
+        num_txs: block.num_txs,
+    }))
+}
+
+/// Looks up proposers of up to MAXIMUM_NUMBER_OF_HEIGHTS_PER_LOOKUP heights in one round trip.
+/// Proposers are returned in the order of the requested heights, and the proposer is null
+/// for a height which is not indexed yet.
+/// With `enqueue=true` heights which are not indexed yet are queued for the indexer at high
+/// priority, and the response is 202 Accepted listing them in `queued` with a Retry-After hint.
+/// Only heights below the highest indexed height are queued; live indexing reaches the others.
+pub async fn proposers_handler(Params(params): Params<ProposersParams>,
+                               State(pool): State<ConnectionPool>,
+                               Json(request): Json<ProposersRequest>)
+                               -> Result<impl IntoResponse, Error> {
+    if request.heights.len() > MAXIMUM_NUMBER_OF_HEIGHTS_PER_LOOKUP {
+        return Err(Error::TooManyHeights);
+    }
+
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let indexed: HashMap<Height, repository::Block> = repository::blocks_at_heights(&conn, &request.heights)
+        .await?
+        .into_iter()
+        .map(|b| (b.height, b))
+        .collect();
+
+    let proposers = request.heights
+        .iter()
+        .map(|&height| {
+            let block = indexed.get(&height);
+            HeightToProposer {
+                height,
+                hash: block.and_then(|b| b.hash.clone()),
+                proposer: block.map(|b| b.proposer.clone()),
+                block_time: block.and_then(|b| b.block_time),
+                num_txs: block.and_then(|b| b.num_txs),
+            }
+        })
+        .collect::<Vec<HeightToProposer>>();
+
+    let queued: Vec<Height> = if params.enqueue {
+        let (_, indexed_height) = repository::indexed_height_range(&conn).await?;
+        let missing: Vec<Height> = proposers
+            .iter()
+            .filter(|p| p.proposer.is_none() && p.height > Height::new(0))
+            .filter(|p| indexed_height.is_some_and(|h| p.height < h))
+            .map(|p| p.height)
+            .collect();
+
+        repository::request_heights(&conn, &missing).await?;
+
+        missing
+    } else {
+        Vec::new()
+    };
+
+    let mut headers = HeaderMap::new();
+    let status = if queued.is_empty() {
+        StatusCode::OK
+    } else {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(REQUESTED_HEIGHTS_RETRY_AFTER_IN_SECONDS));
+        StatusCode::ACCEPTED
+    };
+
+    Ok((status, headers, Json(ProposersResponse { proposers, queued })))
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::body::{Bytes, Full};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Method, Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use tokio::sync::watch;
+use tracing::debug;
+
+/// Requests being answered, by normalized request, which identical requests wait for instead
+/// of querying the database themselves.
+#[derive(Clone, Debug, Default)]
+pub struct Coalescer {
+    in_flight: Arc<Mutex<HashMap<String, watch::Receiver<Option<SharedResponse>>>>>,
+}
+
+#[derive(Clone, Debug)]
+struct SharedResponse {
+    status: StatusCode,
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+/// Takes the request out of `in_flight` when the first request is answered or cancelled.
+struct InFlight<'a> {
+    coalescer: &'a Coalescer,
+    key: String,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.coalescer.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Single flight: a GET request arriving while an identical one is answered waits for that
+/// answer and gets a copy of it, so a dashboard opened by many clients at once queries the
+/// database once. If the first request is cancelled, the waiting ones are answered on their own.
+pub async fn coalesce<B>(State(coalescer): State<Coalescer>, request: Request<B>, next: Next<B>)
+                         -> axum::response::Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+
+    let key = normalized_key(request.uri());
+    let (sender, mut receiver) = {
+        let mut in_flight = coalescer.in_flight.lock().unwrap();
+        match in_flight.get(&key) {
+            Some(receiver) => (None, receiver.clone()),
+            None => {
+                let (sender, receiver) = watch::channel(None);
+                in_flight.insert(key.clone(), receiver.clone());
+                (Some(sender), receiver)
+            }
+        }
+    };
+
+    let Some(sender) = sender else {
+        debug!(key, "Waiting for an identical request");
+        let shared = receiver.wait_for(Option::is_some).await.ok().and_then(|r| r.clone());
+        return match shared {
+            Some(shared) => into_response(shared),
+            None => next.run(request).await,
+        };
+    };
+
+    let _in_flight = InFlight { coalescer: &coalescer, key };
+    let (parts, body) = next.run(request).await.into_parts();
+    let Ok(body) = hyper::body::to_bytes(body).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let shared = SharedResponse {
+        status: parts.status,
+        content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+        body,
+    };
+    sender.send_replace(Some(shared.clone()));
+
+    into_response(shared)
+}
+
+fn into_response(shared: SharedResponse) -> axum::response::Response {
+    let mut response = (shared.status, Full::new(shared.body)).into_response();
+    if let Some(content_type) = shared.content_type {
+        response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// The path with the query parameters sorted, so their order does not tell requests apart.
+fn normalized_key(uri: &Uri) -> String {
+    let mut parameters: Vec<&str> = uri.query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .collect();
+    parameters.sort_unstable();
+
+    format!("{}?{}", uri.path(), parameters.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_of_parameters_does_not_matter() {
+        let uri: Uri = "/top?to=200&limit=10&from=100".parse().unwrap();
+        let reordered: Uri = "/top?from=100&to=200&limit=10".parse().unwrap();
+        let other: Uri = "/top?from=100&to=201&limit=10".parse().unwrap();
+
+        assert_eq!(normalized_key(&uri), normalized_key(&reordered));
+        assert_ne!(normalized_key(&uri), normalized_key(&other));
+    }
+}
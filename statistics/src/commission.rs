@@ -0,0 +1,45 @@
+use axum::extract::State;
+use axum::Json;
+use model::ConsensusAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Limit, Params, Validate};
+use crate::repository::CommissionChange;
+
+const DEFAULT_COMMISSION_CHANGES_LIMIT: i64 = 100;
+const MAXIMUM_COMMISSION_CHANGES_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct CommissionChangesParams {
+    validator: Option<ConsensusAddress>,
+    after_id: Option<i64>,
+    #[serde(default)]
+    limit: Limit<DEFAULT_COMMISSION_CHANGES_LIMIT, MAXIMUM_COMMISSION_CHANGES_LIMIT>,
+}
+
+impl Validate for CommissionChangesParams {}
+
+#[derive(Serialize, Debug)]
+pub struct CommissionChangesResponse {
+    changes: Vec<CommissionChange>,
+    /// Pass as `after_id` to receive only changes detected since this response.
+    last_id: Option<i64>,
+}
+
+/// Commission rate changes of all validators, or of the watched `validator`, in the order the
+/// indexer detected them. Alerting polls with the `last_id` of the previous response, so every
+/// change fires exactly once.
+pub async fn handler(Params(params): Params<CommissionChangesParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<CommissionChangesResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let changes = repository::commission_changes(&conn,
+                                                 params.validator.as_ref(),
+                                                 params.after_id,
+                                                 params.limit.value()).await?;
+    let last_id = changes.last().map(|c| c.id).or(params.after_id);
+
+    Ok(Json(CommissionChangesResponse { changes, last_id }))
+}
@@ -0,0 +1,29 @@
+use model::Height;
+use serde::Serialize;
+
+use crate::{Error, repository};
+
+/// Tells consumers of a range query whether the result covers the whole requested window
+/// or only the parts of it which are indexed.
+#[derive(Serialize, Debug)]
+pub struct Completeness {
+    /// Inclusive height ranges within the window without a single missing height.
+    indexed_ranges: Vec<[Height; 2]>,
+    complete: bool,
+}
+
+/// Finds indexed ranges within `[from_height, to_height]`. A missing bound defaults to the
+/// lowest or highest indexed height respectively.
+pub async fn request_completeness(conn: &tokio_postgres::Client,
+                                  from_height: Option<Height>,
+                                  to_height: Option<Height>)
+                                  -> Result<Completeness, Error> {
+    let indexed_ranges = repository::indexed_ranges(conn, from_height, to_height).await?;
+
+    let complete = match indexed_ranges[..] {
+        [[first, last]] => from_height.unwrap_or(first) >= first && to_height.unwrap_or(last) <= last,
+        _ => false,
+    };
+
+    Ok(Completeness { indexed_ranges, complete })
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{Bytes, Full};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::Router;
+use axum::routing::get;
+use tracing::info;
+
+use crate::{AppState, status};
+
+const DEMO_PROFILE: &str = "demo";
+/// Requests per minute allowed per client unless RATE_LIMIT_REQUESTS_PER_MINUTE is set.
+pub const DEFAULT_DEMO_REQUESTS_PER_MINUTE: f64 = 30.0;
+const DEFAULT_DEMO_CACHE_TTL_IN_SECONDS: u64 = 300;
+/// Queries taking longer are cancelled by the database, so no request can hold a connection for long.
+pub const DEMO_STATEMENT_TIMEOUT_IN_MILLISECONDS: u32 = 2000;
+/// Most responses cached; expired ones are dropped once it is reached.
+const MAXIMUM_CACHED_RESPONSES: usize = 10000;
+
+/// Public demo profile, enabled with `PROFILE=demo`: only aggregate endpoints are served,
+/// to anyone, from a shared response cache and rate limited per client address.
+#[derive(Clone, Debug)]
+pub struct Demo {
+    cache_ttl: Duration,
+    responses: Arc<Mutex<HashMap<String, CachedResponse>>>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedResponse {
+    cached_at: Instant,
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+impl Demo {
+    /// Reads DEMO_CACHE_TTL_IN_SECONDS if PROFILE is `demo`.
+    pub fn from_env() -> Option<Self> {
+        if env::var("PROFILE").ok()?.to_lowercase() != DEMO_PROFILE {
+            return None;
+        }
+
+        let cache_ttl_in_seconds = env::var("DEMO_CACHE_TTL_IN_SECONDS")
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or(DEFAULT_DEMO_CACHE_TTL_IN_SECONDS);
+
+        info!(cache_ttl_in_seconds, "Serving the public demo profile");
+        Some(Demo {
+            cache_ttl: Duration::from_secs(cache_ttl_in_seconds),
+            responses: Arc::default(),
+        })
+    }
+
+    fn cached(&self, key: &str, now: Instant) -> Option<CachedResponse> {
+        self.responses
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|r| now.saturating_duration_since(r.cached_at) < self.cache_ttl)
+            .cloned()
+    }
+
+    fn store(&self, key: String, response: CachedResponse) {
+        let mut responses = self.responses.lock().unwrap();
+        if responses.len() >= MAXIMUM_CACHED_RESPONSES {
+            let now = Instant::now();
+            responses.retain(|_, r| now.saturating_duration_since(r.cached_at) < self.cache_ttl);
+            if responses.len() >= MAXIMUM_CACHED_RESPONSES {
+                responses.clear();
+            }
+        }
+        responses.insert(key, response);
+    }
+
+    fn cache_control(&self) -> HeaderValue {
+        HeaderValue::from_str(&format!("public, max-age={}", self.cache_ttl.as_secs())).unwrap()
+    }
+}
+
+/// The aggregate endpoints, which is all the demo profile serves.
+pub fn routes() -> Router<AppState> {
+    crate::aggregate_routes()
+        .route("/status", get(status::handler))
+}
+
+/// Serves successful GET responses from the cache for DEMO_CACHE_TTL_IN_SECONDS, also telling
+/// browsers and CDNs to cache them as long.
+pub async fn cache<B>(State(demo): State<Demo>, request: Request<B>, next: Next<B>) -> axum::response::Response {
+    let now = Instant::now();
+
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+
+    let key = request.uri().to_string();
+    if let Some(cached) = demo.cached(&key, now) {
+        return demo_response(&demo, cached);
+    }
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(body) = hyper::body::to_bytes(body).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let cached = CachedResponse {
+        cached_at: now,
+        content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+        body,
+    };
+    demo.store(key, cached.clone());
+
+    demo_response(&demo, cached)
+}
+
+fn demo_response(demo: &Demo, cached: CachedResponse) -> axum::response::Response {
+    let mut response = Full::new(cached.body).into_response();
+    let headers = response.headers_mut();
+    if let Some(content_type) = cached.content_type {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(header::CACHE_CONTROL, demo.cache_control());
+    response
+}
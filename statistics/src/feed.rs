@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use model::{BlockHash, ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tracing::warn;
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Params, Validate};
+
+const FEED_POLL_INTERVAL_IN_SECONDS: u64 = 5;
+const FEED_MAXIMUM_EVENTS_PER_POLL: i64 = 1000;
+const FEED_DEDUP_WINDOW_IN_HEIGHTS: u64 = 1000;
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+const NEW_BLOCK_EVENT: &str = "block";
+const WATCHED_VALIDATOR_EVENT: &str = "validator";
+
+#[derive(Deserialize, Debug)]
+pub struct FeedParams {
+    validator: Option<ConsensusAddress>,
+}
+
+impl Validate for FeedParams {}
+
+#[derive(Serialize, Debug)]
+struct BlockEvent {
+    sequence: u64,
+    proposer: ConsensusAddress,
+    height: Height,
+    hash: Option<BlockHash>,
+    block_time: Option<DateTime<Utc>>,
+    num_txs: Option<i32>,
+}
+
+/// Heights already pushed to one client. Blocks which the indexer repairs or re-indexes below
+/// the highest pushed height are still pushed if they are within the dedup window, and each
+/// height is pushed at most once.
+struct DeliveryWindow {
+    floor: Height,
+    highest: Height,
+    delivered: BTreeSet<Height>,
+}
+
+impl DeliveryWindow {
+    /// Every height up to and including `floor` counts as delivered.
+    fn new(floor: Height) -> Self {
+        DeliveryWindow { floor, highest: floor, delivered: BTreeSet::new() }
+    }
+
+    fn lower_bound(&self) -> Height {
+        self.floor.max(self.highest.saturating_sub(FEED_DEDUP_WINDOW_IN_HEIGHTS))
+    }
+
+    /// Returns false if the height has already been delivered.
+    fn record(&mut self, height: Height) -> bool {
+        if height <= self.lower_bound() || !self.delivered.insert(height) {
+            return false;
+        }
+
+        self.highest = self.highest.max(height);
+        self.delivered = self.delivered.split_off(&self.lower_bound().saturating_add(1));
+        true
+    }
+}
+
+/// Server-sent events feed for clients which cannot keep a WebSocket open behind their proxies.
+/// Without parameters every newly indexed block is pushed as a `block` event. With `validator`
+/// only blocks proposed by the watched validator are pushed as `validator` events.
+///
+/// Ordering guarantees:
+/// - within one connection every height is pushed at most once, and `sequence` in the payload
+///   increases by one with every event, so a client can detect lost events;
+/// - blocks are pushed in ascending height, except blocks which appear late (gap repair,
+///   re-indexing) within `FEED_DEDUP_WINDOW_IN_HEIGHTS` below the highest pushed height, which
+///   are pushed once when they appear; older late blocks are not pushed;
+/// - the id of every event is the highest height pushed so far, so a client reconnecting with
+///   `Last-Event-ID` never receives a block at or below that height again.
+pub async fn handler(Params(params): Params<FeedParams>,
+                     headers: HeaderMap,
+                     State(pool): State<ConnectionPool>)
+                     -> Sse<impl Stream<Item=Result<Event, Infallible>>> {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Height>().ok());
+
+    let stream = async_stream::stream! {
+        let mut window = last_event_id.map(DeliveryWindow::new);
+        let mut sequence: u64 = 0;
+        let mut interval = time::interval(Duration::from_secs(FEED_POLL_INTERVAL_IN_SECONDS));
+
+        loop {
+            interval.tick().await;
+
+            let Some(window) = window.as_mut() else {
+                match request_indexed_height(&pool).await {
+                    Ok(h) => window = Some(DeliveryWindow::new(h)),
+                    Err(e) => warn!(error = ?e, "Feed error"),
+                }
+                continue;
+            };
+
+            let delivered: Vec<Height> = window.delivered.iter().copied().collect();
+            let blocks = match request_blocks_after(&pool,
+                                                    window.lower_bound(),
+                                                    &delivered,
+                                                    params.validator.as_ref()).await {
+                Ok(b) => b,
+                Err(e) => {
+                    warn!(error = ?e, "Feed error");
+                    continue;
+                }
+            };
+
+            for block in blocks {
+                if !window.record(block.height) {
+                    continue;
+                }
+
+                sequence += 1;
+                let block = BlockEvent {
+                    sequence,
+                    proposer: block.proposer,
+                    height: block.height,
+                    hash: block.hash,
+                    block_time: block.block_time,
+                    num_txs: block.num_txs,
+                };
+                yield Ok(to_event(&block, window.highest, params.validator.is_some()));
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn to_event(block: &BlockEvent, last_event_id: Height, watched: bool) -> Event {
+    let name = if watched { WATCHED_VALIDATOR_EVENT } else { NEW_BLOCK_EVENT };
+
+    Event::default()
+        .event(name)
+        .id(last_event_id.to_string())
+        .json_data(block)
+        .unwrap_or_else(|_| Event::default().event(name).id(last_event_id.to_string()))
+}
+
+async fn request_indexed_height(pool: &ConnectionPool) -> Result<Height, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let (_, indexed_height) = repository::indexed_height_range(&conn).await?;
+
+    Ok(indexed_height.unwrap_or_default())
+}
+
+async fn request_blocks_after(pool: &ConnectionPool,
+                              after_height: Height,
+                              delivered: &[Height],
+                              validator: Option<&ConsensusAddress>)
+                              -> Result<Vec<repository::Block>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    repository::blocks_after(&conn, after_height, delivered, validator, FEED_MAXIMUM_EVENTS_PER_POLL).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn every_height_is_delivered_at_most_once(
+            floor in 0..10000u64,
+            heights in prop::collection::vec(0..15000u64, 0..500),
+        ) {
+            let mut window = DeliveryWindow::new(Height::new(floor));
+            let mut delivered = HashSet::new();
+
+            for height in heights {
+                if window.record(Height::new(height)) {
+                    prop_assert!(delivered.insert(height), "{} delivered twice", height);
+                }
+            }
+        }
+
+        #[test]
+        fn nothing_at_or_below_the_cursor_is_delivered(
+            floor in 0..10000u64,
+            heights in prop::collection::vec(0..15000u64, 0..500),
+        ) {
+            let mut window = DeliveryWindow::new(Height::new(floor));
+
+            for height in heights {
+                let lower_bound = window.lower_bound();
+                let recorded = window.record(Height::new(height));
+                prop_assert!(!recorded || Height::new(height) > lower_bound);
+                prop_assert!(window.highest >= Height::new(floor));
+            }
+        }
+
+        #[test]
+        fn ascending_heights_are_all_delivered(floor in 0..10000u64, length in 0..2000u64) {
+            let mut window = DeliveryWindow::new(Height::new(floor));
+
+            for height in floor + 1..=floor + length {
+                prop_assert!(window.record(Height::new(height)));
+            }
+            prop_assert!(window.delivered.len() as u64 <= FEED_DEDUP_WINDOW_IN_HEIGHTS);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+
+use axum::body::StreamBody;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::response::IntoResponse;
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::Error;
+
+const CSV_CONTENT_TYPE: &str = "text/csv";
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Representation of a response, chosen with `?format=` or else with the `Accept` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    Ndjson,
+}
+
+impl Format {
+    pub fn negotiate(requested: Option<&str>, headers: &HeaderMap) -> Result<Self, Error> {
+        match requested {
+            Some("json") => return Ok(Format::Json),
+            Some("csv") => return Ok(Format::Csv),
+            Some("ndjson") => return Ok(Format::Ndjson),
+            Some(_) => return Err(Error::InvalidFormat),
+            None => {}
+        }
+
+        let accept = headers.get(header::ACCEPT).and_then(|a| a.to_str().ok()).unwrap_or_default();
+        Ok(if accept.contains(CSV_CONTENT_TYPE) {
+            Format::Csv
+        } else if accept.contains(NDJSON_CONTENT_TYPE) {
+            Format::Ndjson
+        } else {
+            Format::Json
+        })
+    }
+
+    /// Content type of rows streamed in the format, which are NDJSON for JSON.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => CSV_CONTENT_TYPE,
+            Format::Ndjson | Format::Json => NDJSON_CONTENT_TYPE,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Ndjson | Format::Json => "ndjson",
+        }
+    }
+}
+
+/// Writes rows as they come, as CSV with the given columns or as one JSON object per line, so
+/// large results are never held in memory. Rows are flat objects whose fields are the columns.
+/// A failing row ends the response early, which the client notices as a truncated body.
+pub fn stream_rows<T, S>(format: Format, columns: &'static [&'static str], rows: S) -> axum::response::Response
+    where T: Serialize,
+          S: Stream<Item=Result<T, Error>> + Send + 'static {
+    let body = StreamBody::new(lines(format, columns, rows));
+    ([(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()))], body).into_response()
+}
+
+/// Lines of the rows in the format as `stream_rows` sends them, with the CSV header first.
+pub fn lines<T, S>(format: Format, columns: &'static [&'static str], rows: S)
+                   -> impl Stream<Item=Result<String, io::Error>> + Send + 'static
+    where T: Serialize,
+          S: Stream<Item=Result<T, Error>> + Send + 'static {
+    let header = (format == Format::Csv).then(|| Ok(format!("{}\n", columns.join(","))));
+    let lines = rows.map(move |row| {
+        let row = row.map_err(|e| {
+            warn!(error = ?e, "Stopped streaming rows");
+            io::Error::other(format!("{e:?}"))
+        })?;
+
+        let value = serde_json::to_value(row).map_err(io::Error::other)?;
+        Ok::<_, io::Error>(match format {
+            Format::Csv => to_csv_line(columns, &value),
+            Format::Ndjson | Format::Json => format!("{value}\n"),
+        })
+    });
+
+    futures::stream::iter(header).chain(lines)
+}
+
+fn to_csv_line(columns: &[&str], row: &Value) -> String {
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|c| match row.get(c) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => escape_csv(s),
+            Some(v) => escape_csv(&v.to_string()),
+        })
+        .collect();
+
+    format!("{}\n", fields.join(","))
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn csv_lines_quote_fields_with_separators() {
+        let row = json!({"moniker": "Validator, \"the\" best", "count": 3, "missing": null});
+
+        assert_eq!(to_csv_line(&["moniker", "count", "missing"], &row), "\"Validator, \"\"the\"\" best\",3,\n");
+    }
+
+    #[test]
+    fn format_parameter_takes_precedence_over_accept_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/csv"));
+
+        assert_eq!(Format::negotiate(None, &headers).unwrap(), Format::Csv);
+        assert_eq!(Format::negotiate(Some("ndjson"), &headers).unwrap(), Format::Ndjson);
+        assert_eq!(Format::negotiate(None, &HeaderMap::new()).unwrap(), Format::Json);
+        assert!(Format::negotiate(Some("xml"), &headers).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use axum::extract::State;
+use axum::Json;
+use model::{ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{check_range, Params, TimeZone, Validate};
+use crate::repository::ProposalBucket;
+
+const DEFAULT_BUCKET_SIZE_IN_HEIGHTS: i64 = 1000;
+const DAY_BUCKET: &str = "day";
+
+#[derive(Deserialize, Debug)]
+pub struct FrequencyParams {
+    validator: ConsensusAddress,
+    /// Number of heights per bucket, or `day` for buckets of days in the time zone.
+    bucket: Option<String>,
+    #[serde(default)]
+    tz: TimeZone,
+    from_height: Option<Height>,
+    to_height: Option<Height>,
+}
+
+impl Validate for FrequencyParams {
+    fn validate(&self) -> Result<(), String> {
+        if !self.tz.is_utc() && self.bucket.as_deref() != Some(DAY_BUCKET) {
+            return Err(format!("tz: only applies to bucket={DAY_BUCKET}"));
+        }
+        check_range("from_height", self.from_height.as_ref(), "to_height", self.to_height.as_ref())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct FrequencyResponse {
+    /// Time zone the days are in.
+    tz: TimeZone,
+    buckets: Vec<ProposalBucket>,
+}
+
+/// Number of blocks the validator proposed per bucket of heights or per day, in ascending order,
+/// so dashboards can plot its activity without fetching every height. Buckets without a single
+/// proposal are left out. Days are UTC days unless `tz` names another zone, whose days may last
+/// 23 or 25 hours when daylight saving time begins or ends.
+pub async fn handler(Params(params): Params<FrequencyParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<FrequencyResponse>, Error> {
+    let bucket_size = match params.bucket.as_deref() {
+        Some(DAY_BUCKET) => None,
+        Some(size) => Some(size.parse::<i64>().ok().filter(|s| *s > 0).ok_or(Error::InvalidBucket)?),
+        None => Some(DEFAULT_BUCKET_SIZE_IN_HEIGHTS),
+    };
+
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    if !params.tz.is_utc() && !repository::time_zone_exists(&conn, params.tz.name()).await? {
+        return Err(Error::InvalidParameter(format!("tz: unknown time zone {}", params.tz)));
+    }
+
+    let buckets = repository::proposals_per_bucket(&conn,
+                                                   &params.validator,
+                                                   bucket_size,
+                                                   params.tz.name(),
+                                                   params.from_height,
+                                                   params.to_height).await?;
+
+    Ok(Json(FrequencyResponse { tz: params.tz, buckets }))
+}
@@ -0,0 +1,83 @@
+use axum::extract::State;
+use axum::Json;
+use model::Height;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{check_range, Params, Validate};
+use crate::repository::{HistogramBucket, HistogramMetric};
+
+const DEFAULT_HISTOGRAM_BUCKETS: i32 = 20;
+const MAXIMUM_HISTOGRAM_BUCKETS: i32 = 200;
+/// Blocks counted when no `from_height` is given.
+const DEFAULT_HISTOGRAM_BLOCKS: i64 = 10_000;
+
+#[derive(Deserialize, Debug)]
+pub struct HistogramParams {
+    metric: HistogramMetric,
+    buckets: Option<i32>,
+    from_height: Option<Height>,
+    to_height: Option<Height>,
+}
+
+impl Validate for HistogramParams {
+    fn validate(&self) -> Result<(), String> {
+        match self.buckets {
+            Some(buckets) if !(1..=MAXIMUM_HISTOGRAM_BUCKETS).contains(&buckets) =>
+                Err(format!("buckets: must be between 1 and {MAXIMUM_HISTOGRAM_BUCKETS}")),
+            _ => check_range("from_height", self.from_height.as_ref(), "to_height", self.to_height.as_ref()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct HistogramResponse {
+    unit: &'static str,
+    /// Lowest and highest height with a value; null if there are none.
+    from_height: Option<Height>,
+    to_height: Option<Height>,
+    blocks: i64,
+    buckets: Vec<HistogramBucket>,
+}
+
+/// Distribution of block times, transactions per block or the turnout of signatures, counted in
+/// buckets of equal width between the lowest and the highest value, so charts need not fetch
+/// every block. Without a range, the latest 10000 indexed heights are counted.
+pub async fn handler(Params(params): Params<HistogramParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<HistogramResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let histogram = repository::histogram(&conn,
+                                          params.metric,
+                                          params.buckets.unwrap_or(DEFAULT_HISTOGRAM_BUCKETS),
+                                          params.from_height,
+                                          params.to_height,
+                                          DEFAULT_HISTOGRAM_BLOCKS).await?;
+
+    Ok(Json(HistogramResponse {
+        unit: params.metric.unit(),
+        from_height: histogram.first_height,
+        to_height: histogram.last_height,
+        blocks: histogram.blocks,
+        buckets: histogram.buckets,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(query: &str) -> HistogramParams {
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    #[test]
+    fn bucket_counts_are_bounded() {
+        assert!(params("metric=block_time").validate().is_ok());
+        assert!(params("metric=turnout&buckets=200").validate().is_ok());
+        assert!(params("metric=transactions&buckets=0").validate().is_err());
+        assert!(params("metric=transactions&buckets=201").validate().is_err());
+        assert!(params("metric=block_time&from_height=10&to_height=9").validate().is_err());
+    }
+}
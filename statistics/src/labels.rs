@@ -0,0 +1,128 @@
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, ConnectionPool, Error, repository, sql, tags};
+use crate::params::{Limit, Params, Validate};
+use crate::repository::AddressLabel;
+
+/// Longest address and label, as the columns hold them.
+const MAXIMUM_LABEL_LENGTH: usize = 255;
+const DEFAULT_LABELS_LIMIT: i64 = 100;
+const MAXIMUM_LABELS_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct LabelRequest {
+    label: String,
+    /// Such as `exchange`, `bridge` or `team`, in the form of a tag.
+    category: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LabelsParams {
+    category: Option<String>,
+    #[serde(default)]
+    limit: Limit<DEFAULT_LABELS_LIMIT, MAXIMUM_LABELS_LIMIT>,
+    #[serde(default)]
+    offset: i64,
+}
+
+impl Validate for LabelsParams {
+    fn validate(&self) -> Result<(), String> {
+        if self.offset < 0 {
+            return Err("offset: must not be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct LabelsResponse {
+    labels: Vec<AddressLabel>,
+    /// Pass as `offset` to get the next page; null on the last page.
+    next_offset: Option<i64>,
+}
+
+/// Known addresses in alphabetical order, optionally of one category, whether labelled by the
+/// community source the indexer refreshes or by an admin.
+pub async fn handler(Params(params): Params<LabelsParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<LabelsResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let limit = params.limit.value();
+    let labels = repository::address_labels(&conn, params.category.as_deref(), params.offset, limit).await?;
+    let next_offset = (labels.len() as i64 == limit).then_some(params.offset + limit);
+
+    Ok(Json(LabelsResponse { labels, next_offset }))
+}
+
+/// Labels the address, replacing its label from the community source, which no refresh then
+/// overwrites.
+pub async fn save_handler(headers: HeaderMap,
+                          Path(address): Path<String>,
+                          State(state): State<AppState>,
+                          Json(request): Json<LabelRequest>)
+                          -> Result<StatusCode, Error> {
+    sql::authorize(&headers, &state)?;
+
+    if !is_valid(&address, &request) {
+        return Err(Error::InvalidLabel);
+    }
+
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    repository::save_label(&conn, &address, &request.label, request.category.as_deref()).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes the label of the address; a label of the community source comes back with the next
+/// refresh while the source lists the address.
+pub async fn delete_handler(headers: HeaderMap,
+                            Path(address): Path<String>,
+                            State(state): State<AppState>)
+                            -> Result<StatusCode, Error> {
+    sql::authorize(&headers, &state)?;
+
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    if !repository::delete_label(&conn, &address).await? {
+        return Err(Error::LabelNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Addresses are letters and digits, as every encoding of Osmosis addresses is.
+fn is_valid(address: &str, request: &LabelRequest) -> bool {
+    !address.is_empty()
+        && address.len() <= MAXIMUM_LABEL_LENGTH
+        && address.chars().all(|c| c.is_ascii_alphanumeric())
+        && !request.label.trim().is_empty()
+        && request.label.len() <= MAXIMUM_LABEL_LENGTH
+        && !request.label.chars().any(char::is_control)
+        && request.category.as_deref().is_none_or(tags::is_valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(label: &str, category: Option<&str>) -> LabelRequest {
+        LabelRequest { label: label.to_string(), category: category.map(str::to_string) }
+    }
+
+    #[test]
+    fn labels_are_names_of_addresses() {
+        assert!(is_valid("osmo1qyqszqgpqyqszqgpqyqszqgpqyqszqgp", &request("Binance deposit", Some("exchange"))));
+        assert!(is_valid("ABCDEF", &request("Team wallet", None)));
+        assert!(!is_valid("osmo1/../", &request("Binance deposit", None)));
+        assert!(!is_valid("osmo1", &request(" ", None)));
+        assert!(!is_valid("osmo1", &request("Line\nbreak", None)));
+        assert!(!is_valid("osmo1", &request("Bridge", Some("Cross Chain"))));
+    }
+}
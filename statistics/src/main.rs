@@ -1,67 +1,532 @@
+use std::env;
+use std::fmt::Debug;
 use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
     Json,
+    middleware::{self, Next},
     response::IntoResponse,
-    Router, routing::{get},
+    Router, routing::{get, post, put},
 };
-use axum::extract::{Query, State};
-use bb8::Pool;
+use axum::extract::{Extension, FromRef, State};
+use bb8::{ErrorSink, Pool};
 use bb8_postgres::PostgresConnectionManager;
+use chrono::{DateTime, Utc};
+use model::{ConsensusAddress, Height};
+use osmosis_core::{database, Error as CoreError, secrets};
+use params::{check_range, Limit, Params, pinned_upper_bound, Validate};
 use serde::{Deserialize, Serialize};
-use tokio_postgres::NoTls;
+use tokio_postgres_rustls::MakeRustlsConnect;
+use tracing::{error, info, info_span, Instrument, warn};
+
+mod absence;
+mod anomalies;
+mod blocks;
+mod coalesce;
+mod commission;
+mod completeness;
+mod demo;
+mod exports;
+mod feed;
+mod formats;
+mod frequency;
+mod histogram;
+mod labels;
+mod maintenance;
+mod node_versions;
+mod params;
+mod partitions;
+mod performance;
+mod rate_limit;
+mod records;
+mod reports;
+mod repository;
+mod rpc;
+mod schedule;
+mod slo;
+mod sql;
+mod status;
+mod summary;
+mod sync;
+mod tags;
+mod top;
+mod uptime;
+mod validators;
+mod versioning;
+
+const DEFAULT_RPC_URL: &str = "https://rpc.osmosis.zone";
+const DEFAULT_DATABASE_HOST: &str = "db";
+const DEFAULT_DATABASE_USER: &str = "postgres";
+/// Login role for ad-hoc SQL and reports, a member of `statistics_readonly`, which the migrations
+/// create with read access to the indexed tables only.
+const DEFAULT_SQL_DATABASE_USER: &str = "statistics_sql";
+const DATABASE_POOL_SIZE: u32 = 10;
+const SQL_DATABASE_POOL_SIZE: u32 = 2;
+const DATABASE_CONNECTION_TIMEOUT_IN_SECONDS: u64 = 2;
+const DATABASE_RETRY_AFTER_IN_SECONDS: u64 = 10;
+const DEFAULT_STAT_PAGE_SIZE: i64 = 1000;
+const MAXIMUM_STAT_PAGE_SIZE: i64 = 10000;
+/// Blocks fetched from the portal at a time while streaming, which bounds the memory a response takes.
+const STREAMED_BLOCKS_PER_FETCH: i32 = 1000;
+/// Exit codes follow sysexits.h like those of the indexer, so restart policies can tell a
+/// misconfiguration, which no restart fixes, from other failures.
+const EXIT_FAILURE: u8 = 1;
+/// `EX_CONFIG`: a setting or secret is invalid.
+const EXIT_CONFIG_ERROR: u8 = 78;
+
+type ConnectionPool = Pool<PostgresConnectionManager<MakeRustlsConnect>>;
+
+/// Logs why the pool could not open a connection, which checking one out reports as a timeout.
+#[derive(Clone, Copy, Debug)]
+struct LogConnectionErrors;
+
+impl ErrorSink<tokio_postgres::Error> for LogConnectionErrors {
+    fn sink(&self, error: tokio_postgres::Error) {
+        warn!(error = ?error, "Could not connect to the database");
+    }
+
+    fn boxed_clone(&self) -> Box<dyn ErrorSink<tokio_postgres::Error>> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    pool: ConnectionPool,
+    connection_permits: summary::ConnectionPermits,
+    /// Key required by admin endpoints; they are disabled when it is not set.
+    admin_key: Option<String>,
+    /// Connections of the restricted login user under which ad-hoc SQL and reports are executed.
+    sql_pool: ConnectionPool,
+    http_client: reqwest::Client,
+    rpc_url: String,
+    profiles: rpc::Profiles,
+    status_cache: status::StatusCache,
+    slo: slo::Objective,
+    exports: exports::Exports,
+}
+
+impl FromRef<AppState> for ConnectionPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for summary::ConnectionPermits {
+    fn from_ref(state: &AppState) -> Self {
+        state.connection_permits.clone()
+    }
+}
+
+impl FromRef<AppState> for exports::Exports {
+    fn from_ref(state: &AppState) -> Self {
+        state.exports.clone()
+    }
+}
+
+#[derive(Debug)]
+enum Error {
+    CouldNotGetDatabaseConnection,
+    CouldNotQueryDatabase,
+    CouldNotGetResponseFromRpc,
+    RpcUnavailable,
+    CouldNotExportToS3,
+    CouldNotResolveSecret,
+    CouldNotConfigureTls,
+    CouldNotParseDatabaseConfig,
+    CouldNotConvertHeight,
+    CouldNotServe,
+    CouldNotReadConfig,
+    InvalidListenAddress,
+
+    InvalidParameter(String),
+    InvalidBucket,
+    InvalidFormat,
+    InvalidDuration,
+    InvalidTag,
+    InvalidLabel,
+    TooManyHeights,
+    BlockNotFound,
+    LabelNotFound,
+
+    Unauthorized,
+    CouldNotExecuteQuery(String),
+    ReportNotFound,
+    MissingReportParameter,
+}
+
+/// Responses only tell clients what kind of failure it was, so the underlying error is logged.
+impl From<CoreError> for Error {
+    fn from(e: CoreError) -> Self {
+        warn!(error = ?e, "Shared code failed");
+        match e {
+            CoreError::CouldNotParseResponseForBlockAtHeight { .. }
+            | CoreError::CouldNotParseResponseForBlockchain { .. } => Error::CouldNotGetResponseFromRpc,
+            CoreError::CouldNotResolveSecret { .. } => Error::CouldNotResolveSecret,
+            CoreError::CouldNotConfigureTls(_) => Error::CouldNotConfigureTls,
+            CoreError::CouldNotParseDatabaseConfig(_) => Error::CouldNotParseDatabaseConfig,
+            CoreError::DatabaseUnavailable(_) => Error::CouldNotGetDatabaseConnection,
+            CoreError::CouldNotIndexDuplicateHeight(_)
+            | CoreError::CouldNotInsertBatch(_)
+            | CoreError::InsertedIncorrectNumberOfRows { .. }
+            | CoreError::CouldNotInsertSignatures(_) => Error::CouldNotQueryDatabase,
+        }
+    }
+}
+
+/// Failures of the database are reported as one kind each, so their cause is logged where they
+/// are converted.
+impl Error {
+    fn could_not_get_database_connection(e: impl Debug) -> Self {
+        warn!(error = ?e, "Could not get a database connection");
+        Error::CouldNotGetDatabaseConnection
+    }
+
+    fn could_not_query_database(e: impl Debug) -> Self {
+        warn!(error = ?e, "Could not query the database");
+        Error::CouldNotQueryDatabase
+    }
+
+    fn could_not_get_response_from_rpc(e: impl Debug) -> Self {
+        warn!(error = ?e, "Could not get a response from RPC");
+        Error::CouldNotGetResponseFromRpc
+    }
+
+    fn rpc_unavailable(e: impl Debug) -> Self {
+        warn!(error = ?e, "RPC is unavailable");
+        Error::RpcUnavailable
+    }
+
+    fn could_not_read_config(e: impl Debug) -> Self {
+        warn!(error = ?e, "Could not read the config");
+        Error::CouldNotReadConfig
+    }
+
+    fn could_not_serve(e: impl Debug) -> Self {
+        warn!(error = ?e, "Could not serve");
+        Error::CouldNotServe
+    }
+
+    fn exit_code(&self) -> u8 {
+        match self {
+            Error::CouldNotResolveSecret | Error::CouldNotConfigureTls | Error::CouldNotParseDatabaseConfig
+            | Error::CouldNotReadConfig | Error::InvalidListenAddress => EXIT_CONFIG_ERROR,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let status = match &self {
+            Error::CouldNotGetDatabaseConnection | Error::RpcUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Error::CouldNotQueryDatabase | Error::CouldNotResolveSecret | Error::CouldNotConfigureTls
+            | Error::CouldNotParseDatabaseConfig | Error::CouldNotConvertHeight | Error::CouldNotServe
+            | Error::CouldNotReadConfig | Error::InvalidListenAddress => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::CouldNotGetResponseFromRpc | Error::CouldNotExportToS3 => StatusCode::BAD_GATEWAY,
+            Error::InvalidBucket | Error::InvalidFormat | Error::InvalidDuration | Error::InvalidTag
+            | Error::InvalidLabel | Error::TooManyHeights
+            | Error::CouldNotExecuteQuery(_) | Error::InvalidParameter(_) | Error::MissingReportParameter => {
+                StatusCode::BAD_REQUEST
+            }
+            Error::BlockNotFound | Error::ReportNotFound | Error::LabelNotFound => StatusCode::NOT_FOUND,
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+        };
+
+        let mut headers = HeaderMap::new();
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(DATABASE_RETRY_AFTER_IN_SECONDS));
+        }
+
+        let body = match self {
+            Error::CouldNotExecuteQuery(message) | Error::InvalidParameter(message) => message,
+            e => format!("{e:?}"),
+        };
+
+        (status, headers, body).into_response()
+    }
+}
 
 #[derive(Deserialize, Debug)]
-struct Params {
-    validator: String,
+struct StatParams {
+    validator: ConsensusAddress,
+    from_height: Option<Height>,
+    to_height: Option<Height>,
+    from_time: Option<DateTime<Utc>>,
+    to_time: Option<DateTime<Utc>>,
+    limit: Option<Limit<DEFAULT_STAT_PAGE_SIZE, MAXIMUM_STAT_PAGE_SIZE>>,
+    after_height: Option<Height>,
+    as_of_height: Option<Height>,
+    /// `json`, `csv` or `ndjson`; the `Accept` header is used if not given.
+    format: Option<String>,
+    /// `s3` writes the blocks to the export bucket, as NDJSON unless CSV is asked for, and
+    /// answers with a pre-signed URL to them.
+    deliver: Option<exports::Delivery>,
+}
+
+impl Validate for StatParams {
+    fn validate(&self) -> Result<(), String> {
+        check_range("from_height", self.from_height.as_ref(), "to_height", self.to_height.as_ref())?;
+        check_range("from_time", self.from_time.as_ref(), "to_time", self.to_time.as_ref())
+    }
 }
 
 #[derive(Serialize, Debug)]
 struct Response {
-    heights: Vec<i64>,
+    moniker: Option<String>,
+    operator_address: Option<String>,
+    avatar_url: Option<String>,
+    /// Heights of `blocks`, sent only by the deprecated `/stat` without a version prefix.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heights: Option<Vec<Height>>,
+    blocks: Vec<repository::ProposedBlock>,
+    /// Number of blocks on all pages.
+    total: i64,
+    /// Pass as `after_height` together with `as_of_height` to get the next page; null on the last page.
+    next_after_height: Option<Height>,
+    as_of_height: Option<Height>,
+    completeness: completeness::Completeness,
 }
 
 #[tokio::main]
-async fn main() {
-    let manager =
-        PostgresConnectionManager::new_from_stringlike("host=db user=postgres", NoTls)
-            .unwrap();
-    let pool = Pool::builder().build(manager).await.unwrap();
+async fn main() -> ExitCode {
+    logging::init();
 
-    let app = Router::new()
-        .route("/stat", get(handler))
-        .with_state(pool);
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!(error = ?e, "Statistics service stopped");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// The pool is built without connecting, so the service starts and answers 503 while the
+/// database is unavailable instead of failing.
+fn connection_pool(database_config: database::DatabaseConfig, size: u32) -> ConnectionPool {
+    let manager = PostgresConnectionManager::new(database_config.config, database_config.tls);
+    Pool::builder()
+        .max_size(size)
+        .error_sink(Box::new(LogConnectionErrors))
+        .connection_timeout(Duration::from_secs(DATABASE_CONNECTION_TIMEOUT_IN_SECONDS))
+        .build_unchecked(manager)
+}
+
+async fn run() -> Result<(), Error> {
+    let demo = demo::Demo::from_env();
+
+    let mut database_config = database::from_env(DEFAULT_DATABASE_HOST, DEFAULT_DATABASE_USER, None).await?;
+    if demo.is_some() {
+        let timeout = demo::DEMO_STATEMENT_TIMEOUT_IN_MILLISECONDS;
+        database_config.config.options(format!("-c statement_timeout={timeout}"));
+    }
+    // Ad-hoc SQL logs in as a user of its own, since a query can switch to any role its login user
+    // is a member of, e.g. with set_config('role', ...), which a SET ROLE before it cannot prevent.
+    let mut sql_database_config = database_config.clone();
+    sql_database_config.config
+        .user(env::var("SQL_DATABASE_USER").as_deref().unwrap_or(DEFAULT_SQL_DATABASE_USER));
+    if let Some(password) = secrets::resolve("SQL_DATABASE_PASSWORD").await? {
+        sql_database_config.config.password(password);
+    }
+
+    let state = AppState {
+        pool: connection_pool(database_config, DATABASE_POOL_SIZE),
+        connection_permits: summary::ConnectionPermits::new(DATABASE_POOL_SIZE),
+        admin_key: secrets::resolve("ADMIN_KEY").await?,
+        sql_pool: connection_pool(sql_database_config, SQL_DATABASE_POOL_SIZE),
+        http_client: reqwest::Client::new(),
+        rpc_url: env::var("RPC_URL").unwrap_or_else(|_| DEFAULT_RPC_URL.to_string()),
+        profiles: rpc::Profiles::from_env()?,
+        status_cache: status::StatusCache::default(),
+        slo: slo::Objective::from_env(),
+        exports: exports::Exports::from_env(reqwest::Client::new()).await?,
+    };
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-    println!("listening on {}", addr);
+    let default_requests_per_minute = demo.is_some().then_some(demo::DEFAULT_DEMO_REQUESTS_PER_MINUTE);
+    let rate_limit = rate_limit::RateLimit::from_env(default_requests_per_minute);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+    let routes = match demo {
+        Some(demo) => demo::routes().layer(middleware::from_fn_with_state(demo, demo::cache)),
+        None => routes(),
+    };
+    let routes = versioning::versioned(routes);
+    let routes = match rate_limit {
+        Some(rate_limit) => routes.layer(middleware::from_fn_with_state(rate_limit, rate_limit::limit)),
+        None => routes,
+    };
+    let app = routes
+        .layer(middleware::from_fn(log_request))
+        .with_state(state);
+
+    let addr = match env::var("LISTEN_ADDRESS") {
+        Ok(addr) => addr.parse().map_err(|_| Error::InvalidListenAddress)?,
+        Err(_) => SocketAddr::from(([127, 0, 0, 1], 8080)),
+    };
+    info!(%addr, "Listening");
+
+    axum::Server::try_bind(&addr)
+        .map_err(Error::could_not_serve)?
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
-        .unwrap();
+        .map_err(Error::could_not_serve)
 }
 
-async fn handler(Query(params): Query<Params>, State(pool): State<Pool<PostgresConnectionManager<NoTls>>>)
-                 -> impl IntoResponse {
+fn routes() -> Router<AppState> {
+    aggregate_routes()
+        .route("/status", get(status::handler))
+        .route("/metrics", get(slo::metrics_handler))
+        .route("/stat", get(handler))
+        .route("/stat/partitions", get(partitions::handler))
+        .route("/performance", get(performance::handler))
+        .route("/uptime", get(uptime::handler))
+        .route("/validators", get(validators::handler))
+        .route("/labels", get(labels::handler))
+        .route("/validator/:validator/summary", get(summary::handler))
+        .route("/schedule/upcoming", get(schedule::upcoming_handler))
+        .route("/alerts/commission", get(commission::handler))
+        .route("/anomalies", get(anomalies::handler))
+        .route("/network/versions", get(node_versions::handler))
+        .route("/blocks/proposers", post(blocks::proposers_handler))
+        .route("/blocks/:hash", get(blocks::handler))
+        .route("/rpc/block/:height", get(rpc::block_handler))
+        .route("/records/:plugin", get(records::handler))
+        .route("/feed", get(feed::handler))
+        .route("/sync/changes", get(sync::changes_handler))
+        .route("/reports/:name", get(reports::handler))
+        .route("/admin/sql", post(sql::handler))
+        .route("/admin/reports/:name", put(reports::save_handler).delete(reports::delete_handler))
+        .route("/admin/validators/:validator/tags", put(tags::save_handler))
+        .route("/admin/labels/:address", put(labels::save_handler).delete(labels::delete_handler))
+}
+
+/// Endpoints aggregating many blocks, where identical requests arriving together are answered
+/// by a single query.
+fn aggregate_routes() -> Router<AppState> {
+    Router::new()
+        .route("/top", get(top::handler))
+        .route("/absence", get(absence::handler))
+        .route("/frequency", get(frequency::handler))
+        .route("/histogram", get(histogram::handler))
+        .route("/summary", get(summary::counts_handler))
+        .route("/validator/:validator/maintenance-impact", get(maintenance::handler))
+        .route_layer(middleware::from_fn_with_state(coalesce::Coalescer::default(), coalesce::coalesce))
+}
+
+/// Handles every request in a span with its method and path, and logs its status and duration.
+async fn log_request<B>(request: Request<B>, next: Next<B>) -> axum::response::Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request)
+        .instrument(info_span!("request", %method, %path))
+        .await;
+
+    info!(%method,
+          %path,
+          status = %response.status(),
+          duration_in_milliseconds = started.elapsed().as_millis() as u64,
+          "Request");
+    response
+}
+
+/// Pages through the blocks proposed by a validator in ascending height. The first page pins the
+/// highest indexed height as `as_of_height`, and following pages passing it back see the same
+/// blocks even while new ones are indexed, so pages neither shift nor overlap.
+/// As CSV or NDJSON only the blocks are sent, streamed from the database, and all of them
+/// unless `limit` is given. Delivered to S3 they are written the same way.
+async fn handler(Params(params): Params<StatParams>,
+                 headers: HeaderMap,
+                 deprecation: Option<Extension<versioning::Deprecation>>,
+                 State(pool): State<ConnectionPool>,
+                 State(exports): State<exports::Exports>)
+                 -> Result<axum::response::Response, Error> {
     let validator = params.validator;
+    let format = formats::Format::negotiate(params.format.as_deref(), &headers)?;
+    let export_name = format!("stat-{validator}-{}", Utc::now().format("%Y%m%dT%H%M%S%.3fZ"));
 
-    let conn = pool.get().await
-        .unwrap();
+    let conn = pool.get_owned().await
+        .map_err(Error::could_not_get_database_connection)?;
 
-    let rows = conn
-        .query("SELECT height FROM proposer_to_height WHERE proposer = $1", &[&validator])
-        .await
-        .unwrap();
+    if format != formats::Format::Json || params.deliver.is_some() {
+        let blocks = async_stream::try_stream! {
+            let filter = repository::ProposedBlocksFilter {
+                validator: &validator,
+                from_height: params.from_height,
+                to_height: pinned_upper_bound(params.to_height, params.as_of_height),
+                from_time: params.from_time,
+                to_time: params.to_time,
+            };
+            let mut conn = conn;
+            let transaction = conn.transaction().await.map_err(Error::could_not_query_database)?;
+            let limit = params.limit.map(Limit::value);
+            let portal = repository::bind_blocks_proposed_by(&transaction, &filter, params.after_height, limit)
+                .await?;
+
+            loop {
+                let blocks = repository::fetch_proposed_blocks(&transaction, &portal, STREAMED_BLOCKS_PER_FETCH)
+                    .await?;
+                let exhausted = blocks.len() < STREAMED_BLOCKS_PER_FETCH as usize;
+
+                for block in blocks {
+                    yield block;
+                }
+                if exhausted {
+                    break;
+                }
+            }
+        };
+
+        let columns = &["height", "block_time", "num_txs"];
+        if params.deliver == Some(exports::Delivery::S3) {
+            let export = exports.deliver(&export_name, format, formats::lines(format, columns, blocks)).await?;
+            return Ok(Json(export).into_response());
+        }
+        return Ok(formats::stream_rows(format, columns, blocks));
+    }
+
+    let limit = params.limit.unwrap_or_default().value();
+
+    let as_of_height = match params.as_of_height {
+        Some(h) => Some(h),
+        None => repository::indexed_height_range(&conn).await?.1,
+    };
+    let to_height = pinned_upper_bound(params.to_height, as_of_height);
+
+    let filter = repository::ProposedBlocksFilter {
+        validator: &validator,
+        from_height: params.from_height,
+        to_height,
+        from_time: params.from_time,
+        to_time: params.to_time,
+    };
+
+    let blocks = repository::blocks_proposed_by(&conn, &filter, params.after_height, limit).await?;
+    let next_after_height = if blocks.len() as i64 == limit { blocks.last().map(|b| b.height) } else { None };
+
+    let total = repository::count_blocks_proposed_by(&conn, &filter).await?;
+
+    let validator = repository::find_validator(&conn, &validator).await?;
 
-    let heights: Vec<i64> = rows
-        .into_iter()
-        .map(|r| r.get(0))
-        .collect();
+    let completeness =
+        completeness::request_completeness(&conn, params.from_height, to_height).await?;
 
     let response = Response {
-        heights
+        moniker: validator.as_ref().map(|v| v.moniker.clone()),
+        operator_address: validator.as_ref().and_then(|v| v.operator_address.clone()),
+        avatar_url: validator.and_then(|v| v.avatar_url),
+        heights: deprecation.map(|_| blocks.iter().map(|b| b.height).collect()),
+        blocks,
+        total,
+        next_after_height,
+        as_of_height,
+        completeness,
     };
 
-    (StatusCode::OK, Json(response))
+    Ok((StatusCode::OK, Json(response)).into_response())
 }
@@ -0,0 +1,100 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use model::ConsensusAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Params, Validate};
+
+/// Recent blocks the block time and the proposer frequency are estimated from, about a day.
+const RECENT_BLOCKS: i64 = 20000;
+/// Planned downtimes longer than a week are not maintenance.
+const MAXIMUM_DURATION_IN_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Deserialize, Debug)]
+pub struct ImpactParams {
+    /// Length of the downtime like `90s`, `30m`, `2h` or `1d`.
+    duration: String,
+}
+
+impl Validate for ImpactParams {}
+
+#[derive(Serialize, Debug)]
+pub struct ImpactResponse {
+    validator: ConsensusAddress,
+    duration_in_seconds: u64,
+    /// Recent blocks the estimate is based on, and how many of them the validator proposed.
+    recent_blocks: i64,
+    recent_proposals: i64,
+    /// Null, as are the estimates, if no recent block has a known time.
+    seconds_per_block: Option<f64>,
+    /// Blocks produced during the downtime, none of which the validator signs.
+    expected_missed_blocks: Option<f64>,
+    /// Blocks the validator would have proposed during the downtime.
+    expected_missed_proposals: Option<f64>,
+}
+
+/// Estimates what a planned downtime of `duration` costs the validator, assuming blocks keep
+/// coming at the recent pace and the validator keeps its recent share of proposals.
+pub async fn handler(Path(validator): Path<ConsensusAddress>,
+                     Params(params): Params<ImpactParams>,
+                     State(pool): State<ConnectionPool>)
+                     -> Result<Json<ImpactResponse>, Error> {
+    let duration_in_seconds = parse_duration_in_seconds(&params.duration)
+        .filter(|d| (1..=MAXIMUM_DURATION_IN_SECONDS).contains(d))
+        .ok_or(Error::InvalidDuration)?;
+
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let activity = repository::recent_activity(&conn, &validator, RECENT_BLOCKS).await?;
+
+    let expected_missed_blocks = activity.seconds_per_block
+        .filter(|s| *s > 0.0)
+        .map(|s| duration_in_seconds as f64 / s);
+    let proposal_share = match activity.blocks {
+        0 => 0.0,
+        blocks => activity.proposals as f64 / blocks as f64,
+    };
+
+    Ok(Json(ImpactResponse {
+        validator,
+        duration_in_seconds,
+        recent_blocks: activity.blocks,
+        recent_proposals: activity.proposals,
+        seconds_per_block: activity.seconds_per_block,
+        expected_missed_blocks,
+        expected_missed_proposals: expected_missed_blocks.map(|b| b * proposal_share),
+    }))
+}
+
+fn parse_duration_in_seconds(duration: &str) -> Option<u64> {
+    let unit_position = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = duration.split_at(unit_position);
+
+    let unit_in_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    amount.parse::<u64>().ok()?.checked_mul(unit_in_seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_need_a_unit() {
+        assert_eq!(parse_duration_in_seconds("90s"), Some(90));
+        assert_eq!(parse_duration_in_seconds("30m"), Some(1800));
+        assert_eq!(parse_duration_in_seconds("2h"), Some(7200));
+        assert_eq!(parse_duration_in_seconds("1d"), Some(86400));
+        assert_eq!(parse_duration_in_seconds("30"), None);
+        assert_eq!(parse_duration_in_seconds("m"), None);
+        assert_eq!(parse_duration_in_seconds("1h30m"), None);
+    }
+}
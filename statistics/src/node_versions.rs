@@ -0,0 +1,66 @@
+use axum::extract::State;
+use axum::Json;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{check_range, Params, Validate};
+use crate::repository::VersionAdoption;
+
+/// Days returned when no `from_day` is given.
+const DEFAULT_VERSION_DAYS: i32 = 90;
+const MAXIMUM_VERSION_DAYS: i64 = 366;
+
+#[derive(Deserialize, Debug)]
+pub struct VersionsParams {
+    from_day: Option<NaiveDate>,
+    to_day: Option<NaiveDate>,
+}
+
+impl Validate for VersionsParams {
+    fn validate(&self) -> Result<(), String> {
+        check_range("from_day", self.from_day.as_ref(), "to_day", self.to_day.as_ref())?;
+        match (self.from_day, self.to_day) {
+            (Some(from_day), Some(to_day)) if (to_day - from_day).num_days() >= MAXIMUM_VERSION_DAYS =>
+                Err(format!("to_day: at most {MAXIMUM_VERSION_DAYS} days from from_day")),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct VersionsResponse {
+    versions: Vec<VersionAdoption>,
+}
+
+/// Per UTC day and software version, the nodes the indexer's RPC node saw running it, the
+/// validators among them and the blocks they proposed, so the adoption of an upgrade can be
+/// plotted by nodes, validators or share of blocks. Days without observations are left out.
+pub async fn handler(Params(params): Params<VersionsParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<VersionsResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let versions = repository::version_adoption(&conn,
+                                                params.from_day,
+                                                params.to_day,
+                                                DEFAULT_VERSION_DAYS).await?;
+
+    Ok(Json(VersionsResponse { versions }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(from_day: &str, to_day: &str) -> VersionsParams {
+        VersionsParams { from_day: Some(from_day.parse().unwrap()), to_day: Some(to_day.parse().unwrap()) }
+    }
+
+    #[test]
+    fn ranges_span_at_most_the_maximum_days() {
+        assert!(params("2024-01-01", "2024-12-31").validate().is_ok());
+        assert!(params("2024-01-01", "2025-01-01").validate().is_err());
+        assert!(params("2024-01-02", "2024-01-01").validate().is_err());
+    }
+}
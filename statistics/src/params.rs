@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{DeserializeOwned, Error as _};
+
+use crate::Error;
+
+/// Longer than any name in the IANA time zone database.
+const MAXIMUM_TIME_ZONE_LENGTH: usize = 64;
+
+/// Query parameters of an endpoint, each checked by its type as it is deserialized and then all
+/// of them together by `Validate`. Invalid parameters are answered with 400 and a message naming
+/// the parameter and what is wrong with it, such as `limit: must be between 1 and 1000`.
+#[derive(Debug)]
+pub(crate) struct Params<T>(pub T);
+
+/// Checks which involve more than one parameter, such as the bounds of a range.
+pub trait Validate {
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Named parameters of saved reports, which the report itself checks.
+impl Validate for HashMap<String, String> {}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Params<T>
+    where T: DeserializeOwned + Validate,
+          S: Send + Sync {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Error> {
+        parse(parts.uri.query().unwrap_or_default()).map(Params)
+    }
+}
+
+pub(crate) fn parse<T: DeserializeOwned + Validate>(query: &str) -> Result<T, Error> {
+    let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+    let params: T = serde_path_to_error::deserialize(deserializer)
+        .map_err(|e| Error::InvalidParameter(e.to_string()))?;
+
+    params.validate().map_err(Error::InvalidParameter)?;
+    Ok(params)
+}
+
+/// Number of items to return, between 1 and `MAXIMUM`, and `DEFAULT` if not given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limit<const DEFAULT: i64, const MAXIMUM: i64>(i64);
+
+impl<const DEFAULT: i64, const MAXIMUM: i64> Limit<DEFAULT, MAXIMUM> {
+    pub fn value(self) -> i64 {
+        self.0
+    }
+}
+
+impl<const DEFAULT: i64, const MAXIMUM: i64> Default for Limit<DEFAULT, MAXIMUM> {
+    fn default() -> Self {
+        Limit(DEFAULT)
+    }
+}
+
+impl<'de, const DEFAULT: i64, const MAXIMUM: i64> Deserialize<'de> for Limit<DEFAULT, MAXIMUM> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let limit = i64::deserialize(deserializer)?;
+        if !(1..=MAXIMUM).contains(&limit) {
+            return Err(D::Error::custom(format!("must be between 1 and {MAXIMUM}")));
+        }
+
+        Ok(Limit(limit))
+    }
+}
+
+/// IANA time zone, such as `Europe/Zurich`, which days are bucketed in; `UTC` if not given.
+/// Postgres converts times with the offsets of the zone, daylight saving time included, and
+/// endpoints reject names it does not know.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TimeZone(String);
+
+impl TimeZone {
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_utc(&self) -> bool {
+        self.0 == "UTC"
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        TimeZone("UTC".to_string())
+    }
+}
+
+impl Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeZone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let valid = !name.is_empty()
+            && name.len() <= MAXIMUM_TIME_ZONE_LENGTH
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'));
+        if !valid {
+            return Err(D::Error::custom("must be an IANA time zone such as Europe/Zurich"));
+        }
+
+        Ok(TimeZone(name))
+    }
+}
+
+/// Checks that the lower bound of a range, of heights or of times, is not above the upper one.
+pub fn check_range<T: PartialOrd + Display>(from_name: &str,
+                                            from: Option<&T>,
+                                            to_name: &str,
+                                            to: Option<&T>)
+                                            -> Result<(), String> {
+    match (from, to) {
+        (Some(from), Some(to)) if from > to => Err(format!("{from_name}: {from} is above {to_name} {to}")),
+        _ => Ok(()),
+    }
+}
+
+/// Upper bound of a range pinned to `as_of_height`: the lower of the two if both are given.
+/// `Option::min` would take a missing bound for the lowest instead.
+pub fn pinned_upper_bound<T: Ord>(to: Option<T>, as_of_height: Option<T>) -> Option<T> {
+    match (to, as_of_height) {
+        (Some(to), Some(as_of_height)) => Some(to.min(as_of_height)),
+        (to, as_of_height) => to.or(as_of_height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use model::Height;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    struct RangeParams {
+        from: Option<Height>,
+        to: Option<Height>,
+        #[serde(default)]
+        limit: Limit<10, 100>,
+    }
+
+    impl Validate for RangeParams {
+        fn validate(&self) -> Result<(), String> {
+            check_range("from", self.from.as_ref(), "to", self.to.as_ref())
+        }
+    }
+
+    fn message(query: &str) -> String {
+        match parse::<RangeParams>(query) {
+            Err(Error::InvalidParameter(message)) => message,
+            result => panic!("expected invalid parameters, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_parameters_are_named() {
+        assert_eq!(message("limit=0"), "limit: must be between 1 and 100");
+        assert_eq!(message("from=20&to=10"), "from: 20 is above to 10");
+        assert!(message("from=ten").starts_with("from: "));
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct TimeZoneParams {
+        #[serde(default)]
+        tz: TimeZone,
+    }
+
+    impl Validate for TimeZoneParams {}
+
+    #[test]
+    fn time_zones_are_utc_unless_named() {
+        assert!(parse::<TimeZoneParams>("").unwrap().tz.is_utc());
+        assert_eq!(parse::<TimeZoneParams>("tz=America/Argentina/Buenos_Aires").unwrap().tz.name(),
+                   "America/Argentina/Buenos_Aires");
+        assert!(parse::<TimeZoneParams>("tz=Etc/GMT%2B3").is_ok());
+        assert!(parse::<TimeZoneParams>("tz=UTC%27%3B").is_err());
+        assert!(parse::<TimeZoneParams>("tz=").is_err());
+    }
+
+    #[test]
+    fn pinned_upper_bound_is_the_lower_bound_given() {
+        assert_eq!(pinned_upper_bound(Some(100), None), Some(100));
+        assert_eq!(pinned_upper_bound(None, Some(100)), Some(100));
+        assert_eq!(pinned_upper_bound(Some(100), Some(50)), Some(50));
+        assert_eq!(pinned_upper_bound::<u64>(None, None), None);
+    }
+
+    #[test]
+    fn missing_limit_is_the_default() {
+        let params = parse::<RangeParams>("from=10&to=20").unwrap();
+
+        assert_eq!(params.limit.value(), 10);
+        assert_eq!(parse::<RangeParams>("limit=100").unwrap().limit.value(), 100);
+    }
+}
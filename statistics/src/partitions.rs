@@ -0,0 +1,43 @@
+use axum::extract::State;
+use axum::Json;
+use model::ConsensusAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::completeness::{Completeness, request_completeness};
+use crate::params::{Limit, Params, Validate};
+use crate::repository::Partition;
+
+const DEFAULT_NUMBER_OF_PARTITIONS: i64 = 8;
+const MAXIMUM_NUMBER_OF_PARTITIONS: i64 = 64;
+
+#[derive(Deserialize, Debug)]
+pub struct PartitionsParams {
+    validator: ConsensusAddress,
+    #[serde(default)]
+    parts: Limit<DEFAULT_NUMBER_OF_PARTITIONS, MAXIMUM_NUMBER_OF_PARTITIONS>,
+}
+
+impl Validate for PartitionsParams {}
+
+#[derive(Serialize, Debug)]
+pub struct PartitionsResponse {
+    partitions: Vec<Partition>,
+    completeness: Completeness,
+}
+
+/// Splits heights proposed by a validator into at most `parts` inclusive height ranges holding
+/// (almost) the same number of rows. The ranges do not overlap and together cover every row,
+/// so a client can download the whole history with parallel
+/// `/stat?validator=&from_height=&to_height=` requests.
+pub async fn handler(Params(params): Params<PartitionsParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<PartitionsResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let partitions = repository::partitions(&conn, &params.validator, params.parts.value() as i32).await?;
+
+    let completeness = request_completeness(&conn, None, None).await?;
+
+    Ok(Json(PartitionsResponse { partitions, completeness }))
+}
@@ -0,0 +1,79 @@
+use axum::extract::State;
+use axum::Json;
+use model::{ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::completeness::{Completeness, request_completeness};
+use crate::params::{check_range, Params, Validate};
+
+#[derive(Deserialize, Debug)]
+pub struct PerformanceParams {
+    validator: ConsensusAddress,
+    from: Option<Height>,
+    to: Option<Height>,
+}
+
+impl Validate for PerformanceParams {
+    fn validate(&self) -> Result<(), String> {
+        check_range("from", self.from.as_ref(), "to", self.to.as_ref())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PerformanceResponse {
+    validator: ConsensusAddress,
+    /// Blocks in the range at or above the first validator set snapshot, which the figures cover.
+    blocks: i64,
+    expected_proposals: f64,
+    actual_proposals: i64,
+    /// Actual proposals per expected one; null if none were expected.
+    ratio: Option<f64>,
+    /// Standard deviations the actual proposals are above, or below if negative, the expected
+    /// ones; null if the validator had all or none of the voting power.
+    deviation: Option<f64>,
+    completeness: Completeness,
+}
+
+/// Compares the blocks the validator proposed within the inclusive height range `[from, to]`
+/// with the blocks its share of the voting power entitles it to, block by block, according to
+/// the latest validator set snapshot at or below each height.
+pub async fn handler(Params(params): Params<PerformanceParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<PerformanceResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let expectation = repository::proposal_expectation(&conn, &params.validator, params.from, params.to).await?;
+
+    let completeness = request_completeness(&conn, params.from, params.to).await?;
+
+    Ok(Json(PerformanceResponse {
+        validator: params.validator,
+        blocks: expectation.blocks,
+        expected_proposals: expectation.expected_proposals,
+        actual_proposals: expectation.proposals,
+        ratio: (expectation.expected_proposals > 0.0)
+            .then(|| expectation.proposals as f64 / expectation.expected_proposals),
+        deviation: deviation(expectation.proposals, expectation.expected_proposals, expectation.variance),
+        completeness,
+    }))
+}
+
+/// Z-score of the actual proposals, proposals being a sum of independent draws per block.
+fn deviation(actual: i64, expected: f64, variance: f64) -> Option<f64> {
+    (variance > 0.0).then(|| (actual as f64 - expected) / variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deviation_is_in_standard_deviations() {
+        // 10000 blocks at a share of 0.1 make 1000 expected proposals with a variance of 900.
+        assert_eq!(deviation(1000, 1000.0, 900.0), Some(0.0));
+        assert_eq!(deviation(940, 1000.0, 900.0), Some(-2.0));
+        assert_eq!(deviation(1090, 1000.0, 900.0), Some(3.0));
+        assert_eq!(deviation(0, 0.0, 0.0), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode};
+use axum::http::header::RETRY_AFTER;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use tracing::{info, warn};
+
+const X_RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const X_RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const X_RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+/// Most clients remembered; buckets which refilled completely are dropped once it is reached.
+const MAXIMUM_RATE_LIMITED_CLIENTS: usize = 10000;
+
+/// Token bucket per client address, holding up to a minute of requests.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    requests_per_minute: f64,
+    /// Peers whose X-Forwarded-For entries are believed, such as a reverse proxy in front of the service.
+    trusted_proxies: Arc<Vec<IpAddr>>,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// What a request left of the client's bucket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Allowance {
+    allowed: bool,
+    remaining: u64,
+    /// Seconds until the bucket is full again, or until the next request is allowed if it was not.
+    reset_in_seconds: u64,
+}
+
+impl RateLimit {
+    /// Reads RATE_LIMIT_REQUESTS_PER_MINUTE, falling back to `default_requests_per_minute`, and
+    /// the comma separated TRUSTED_PROXIES, which are the loopback addresses if not set.
+    /// Requests are not limited if neither gives a rate.
+    pub fn from_env(default_requests_per_minute: Option<f64>) -> Option<Self> {
+        let requests_per_minute = env::var("RATE_LIMIT_REQUESTS_PER_MINUTE")
+            .ok()
+            .and_then(|r| r.parse::<f64>().ok())
+            .or(default_requests_per_minute)
+            .filter(|r| *r > 0.0 && r.is_finite())?;
+
+        let trusted_proxies = match env::var("TRUSTED_PROXIES") {
+            Ok(proxies) => proxies.split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .filter_map(|p| p.parse().map_err(|_| warn!(proxy = p, "Ignoring invalid trusted proxy")).ok())
+                .collect(),
+            Err(_) => vec![IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)],
+        };
+
+        info!(requests_per_minute, ?trusted_proxies, "Limiting requests per client");
+        Some(RateLimit::new(requests_per_minute, trusted_proxies))
+    }
+
+    fn new(requests_per_minute: f64, trusted_proxies: Vec<IpAddr>) -> Self {
+        RateLimit {
+            requests_per_minute,
+            trusted_proxies: Arc::new(trusted_proxies),
+            buckets: Arc::default(),
+        }
+    }
+
+    fn acquire(&self, client: IpAddr, now: Instant) -> Allowance {
+        let burst = burst(self.requests_per_minute);
+        let requests_per_second = self.requests_per_minute / 60.0;
+        let refilled = |b: &Bucket| {
+            let elapsed = now.saturating_duration_since(b.refilled_at).as_secs_f64();
+            (b.tokens + elapsed * requests_per_second).min(burst)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAXIMUM_RATE_LIMITED_CLIENTS && !buckets.contains_key(&client) {
+            // Buckets which refilled completely are no different from new ones.
+            buckets.retain(|_, b| refilled(b) < burst);
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: burst, refilled_at: now });
+        bucket.tokens = refilled(bucket);
+        bucket.refilled_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let missing = if allowed { burst - bucket.tokens } else { 1.0 - bucket.tokens };
+        Allowance {
+            allowed,
+            remaining: bucket.tokens.floor() as u64,
+            reset_in_seconds: (missing / requests_per_second).ceil() as u64,
+        }
+    }
+
+    /// The connecting address, or, when it is a trusted proxy, the address before the last
+    /// trusted proxy in X-Forwarded-For. Entries further left could be made up by the client.
+    fn client_address(&self, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.ip();
+
+        let mut forwarded = headers.get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .map(|a| a.trim().parse::<IpAddr>())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev();
+
+        while self.trusted_proxies.contains(&client) {
+            match forwarded.next() {
+                Some(Ok(address)) => client = address,
+                _ => break,
+            }
+        }
+
+        client
+    }
+}
+
+/// Rejects clients which ran out of requests with 429 and tells every client its limit in
+/// X-RateLimit-Limit, what is left of it in X-RateLimit-Remaining and the seconds until the
+/// bucket is full again in X-RateLimit-Reset.
+pub async fn limit<B>(State(rate_limit): State<RateLimit>,
+                      ConnectInfo(peer): ConnectInfo<SocketAddr>,
+                      request: Request<B>,
+                      next: Next<B>)
+                      -> axum::response::Response {
+    let client = rate_limit.client_address(peer, request.headers());
+    let allowance = rate_limit.acquire(client, Instant::now());
+
+    let mut response = if allowance.allowed {
+        next.run(request).await
+    } else {
+        warn!(%client, "Client ran out of requests");
+        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+        response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(allowance.reset_in_seconds));
+        response
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(X_RATE_LIMIT_LIMIT, HeaderValue::from(burst(rate_limit.requests_per_minute) as u64));
+    headers.insert(X_RATE_LIMIT_REMAINING, HeaderValue::from(allowance.remaining));
+    headers.insert(X_RATE_LIMIT_RESET, HeaderValue::from(allowance.reset_in_seconds));
+    response
+}
+
+/// Tokens a bucket holds at most: a minute of requests, but always at least one request.
+fn burst(requests_per_minute: f64) -> f64 {
+    requests_per_minute.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn requests_of_a_client_never_exceed_the_rate(
+            requests_per_minute in 1..600u32,
+            steps_in_milliseconds in prop::collection::vec(0..2000u64, 1..300),
+        ) {
+            let rate_limit = RateLimit::new(requests_per_minute as f64, Vec::new());
+            let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+            let started = Instant::now();
+            let mut now = started;
+            let mut granted = 0u32;
+
+            for step in steps_in_milliseconds {
+                now += Duration::from_millis(step);
+                if rate_limit.acquire(client, now).allowed {
+                    granted += 1;
+                }
+            }
+
+            let allowed = burst(requests_per_minute as f64)
+                + now.duration_since(started).as_secs_f64() * requests_per_minute as f64 / 60.0;
+            prop_assert!(granted as f64 <= allowed + 1e-9);
+        }
+    }
+
+    #[test]
+    fn forwarded_address_is_only_trusted_from_trusted_proxies() {
+        let proxy = IpAddr::from([10, 0, 0, 2]);
+        let rate_limit = RateLimit::new(60.0, vec![IpAddr::V4(Ipv4Addr::LOCALHOST), proxy]);
+        let mut headers = HeaderMap::new();
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("203.0.113.9, 192.0.2.7, 10.0.0.2"));
+
+        let local = SocketAddr::from(([127, 0, 0, 1], 40000));
+        assert_eq!(rate_limit.client_address(local, &headers), IpAddr::from([192, 0, 2, 7]));
+
+        let remote = SocketAddr::from(([198, 51, 100, 3], 40000));
+        assert_eq!(rate_limit.client_address(remote, &headers), IpAddr::from([198, 51, 100, 3]));
+    }
+}
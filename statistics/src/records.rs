@@ -0,0 +1,61 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use model::Height;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{check_range, Params, Validate};
+use crate::repository::CustomRecord;
+
+const MAXIMUM_RECORD_HEIGHTS: u64 = 10_000;
+
+#[derive(Deserialize, Debug)]
+pub struct RecordsParams {
+    from_height: Height,
+    to_height: Height,
+}
+
+impl Validate for RecordsParams {
+    fn validate(&self) -> Result<(), String> {
+        check_range("from_height", Some(&self.from_height), "to_height", Some(&self.to_height))?;
+        if self.from_height.count_to(self.to_height) > MAXIMUM_RECORD_HEIGHTS {
+            return Err(format!("to_height: at most {MAXIMUM_RECORD_HEIGHTS} heights from from_height"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecordsResponse {
+    records: Vec<CustomRecord>,
+}
+
+/// Values the plugin stored for the blocks of the inclusive range, whether they are still in
+/// `custom_records` or were moved to the archive because the blocks are old.
+pub async fn handler(Path(plugin): Path<String>,
+                     Params(params): Params<RecordsParams>,
+                     State(pool): State<ConnectionPool>)
+                     -> Result<Json<RecordsResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let records = repository::custom_records(&conn, &plugin, params.from_height, params.to_height).await?;
+
+    Ok(Json(RecordsResponse { records }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(from_height: u64, to_height: u64) -> RecordsParams {
+        RecordsParams { from_height: Height::new(from_height), to_height: Height::new(to_height) }
+    }
+
+    #[test]
+    fn ranges_span_at_most_the_maximum_heights() {
+        assert!(params(1, 10_000).validate().is_ok());
+        assert!(params(1, 10_001).validate().is_err());
+        assert!(params(10, 9).validate().is_err());
+    }
+}
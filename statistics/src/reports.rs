@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Json;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+
+use crate::{AppState, Error, repository, sql};
+use crate::params::Params;
+use crate::repository::Report;
+
+const REPORT_MAXIMUM_ROWS: i64 = 10000;
+
+#[derive(Deserialize, Debug)]
+pub struct ReportDefinition {
+    sql: String,
+    #[serde(default)]
+    parameters: Vec<String>,
+}
+
+impl From<ReportDefinition> for Report {
+    fn from(definition: ReportDefinition) -> Self {
+        Report { sql: definition.sql, parameters: definition.parameters }
+    }
+}
+
+/// Saves a named report. `parameters` lists query string parameters of the report in the
+/// order of their placeholders, so the first one is bound to `$1` and so on.
+pub async fn save_handler(headers: HeaderMap,
+                          Path(name): Path<String>,
+                          State(state): State<AppState>,
+                          Json(definition): Json<ReportDefinition>)
+                          -> Result<StatusCode, Error> {
+    sql::authorize(&headers, &state)?;
+
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    repository::save_report(&conn, &name, &definition.into()).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_handler(headers: HeaderMap,
+                            Path(name): Path<String>,
+                            State(state): State<AppState>)
+                            -> Result<StatusCode, Error> {
+    sql::authorize(&headers, &state)?;
+
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    if !repository::delete_report(&conn, &name).await? {
+        return Err(Error::ReportNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Runs a saved report with parameters taken from the query string. Reports run in the same
+/// read-only sandbox as ad-hoc SQL, but do not require the admin key.
+pub async fn handler(Path(name): Path<String>,
+                     Params(params): Params<HashMap<String, String>>,
+                     State(state): State<AppState>)
+                     -> Result<impl IntoResponse, Error> {
+    let definition = request_report(&state, &name).await?;
+
+    let parameters = definition.parameters
+        .iter()
+        .map(|p| params.get(p).cloned().ok_or(Error::MissingReportParameter))
+        .collect::<Result<Vec<String>, Error>>()?;
+
+    let rows = sql::query_read_only(&state, &definition.sql, &parameters, REPORT_MAXIMUM_ROWS).await?;
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], rows))
+}
+
+async fn request_report(state: &AppState, name: &str) -> Result<Report, Error> {
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    repository::find_report(&conn, name).await?
+        .ok_or(Error::ReportNotFound)
+}
@@ -0,0 +1,1223 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use model::{BlockHash, ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::{Portal, Row, Transaction};
+use tokio_postgres::types::FromSql;
+
+use crate::Error;
+
+/// Upper bound of height ranges given without one.
+const MAXIMUM_HEIGHT: i64 = i64::MAX;
+
+#[derive(Debug)]
+pub struct Block {
+    pub height: Height,
+    pub hash: Option<BlockHash>,
+    pub proposer: ConsensusAddress,
+    pub block_time: Option<DateTime<Utc>>,
+    pub num_txs: Option<i32>,
+}
+
+/// Block of a known proposer. The time and the number of transactions are missing for blocks
+/// indexed before they were recorded.
+#[derive(Serialize, Debug)]
+pub struct ProposedBlock {
+    pub height: Height,
+    pub block_time: Option<DateTime<Utc>>,
+    pub num_txs: Option<i32>,
+}
+
+/// Selects the blocks of a validator. Bounds are inclusive. Blocks without a recorded time are
+/// left out when filtering by time.
+#[derive(Debug)]
+pub struct ProposedBlocksFilter<'a> {
+    pub validator: &'a ConsensusAddress,
+    pub from_height: Option<Height>,
+    pub to_height: Option<Height>,
+    pub from_time: Option<DateTime<Utc>>,
+    pub to_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Partition {
+    pub from_height: Height,
+    pub to_height: Height,
+    pub count: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProposerCount {
+    /// 1 for the validator which proposed the most blocks, counted across all pages.
+    pub rank: i64,
+    pub proposer: ConsensusAddress,
+    pub moniker: Option<String>,
+    pub count: i64,
+}
+
+/// Blocks proposed by the validators with a tag, or by untagged validators if `tag` is null.
+#[derive(Serialize, Debug)]
+pub struct TagCount {
+    pub tag: Option<String>,
+    pub validators: i64,
+    pub count: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncedValidator {
+    pub address: ConsensusAddress,
+    pub operator_address: String,
+    pub moniker: String,
+    pub avatar_url: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct Validator {
+    pub moniker: String,
+    pub operator_address: Option<String>,
+    /// Resolved from the Keybase identity of validators known to the LCD endpoint.
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommissionChange {
+    pub id: i64,
+    pub validator: ConsensusAddress,
+    pub moniker: Option<String>,
+    /// Decimal fractions as given by the LCD endpoint, e.g. `0.050000000000000000`.
+    pub old_rate: String,
+    pub new_rate: String,
+    /// First indexed block at or after the change; null if no block was indexed yet.
+    pub height: Option<Height>,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Anomaly {
+    pub id: i64,
+    pub kind: String,
+    pub validator: ConsensusAddress,
+    pub moniker: Option<String>,
+    pub first_height: Height,
+    pub last_height: Height,
+    /// Standard deviations below the expected proposals for a shortfall, or the number of blocks
+    /// missed in a row for a burst.
+    pub score: f64,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Inclusive range of consecutive heights which a validator did not propose.
+#[derive(Serialize, Debug)]
+pub struct Absence {
+    pub first_height: Height,
+    pub last_height: Height,
+    pub blocks: i64,
+}
+
+/// Nodes seen running a version on a UTC day, the validators among them and the blocks these
+/// validators proposed that day.
+#[derive(Serialize, Debug)]
+pub struct VersionAdoption {
+    pub day: NaiveDate,
+    pub version: String,
+    pub nodes: i64,
+    pub validators: i64,
+    pub proposed_blocks: i64,
+}
+
+/// Blocks a validator proposed in total, from the daily counts, and its rank among the validators
+/// by blocks proposed. Blocks without a time are not counted.
+#[derive(Debug)]
+pub struct ProposalTotals {
+    pub proposals: i64,
+    pub first_proposed_height: Option<Height>,
+    pub last_proposed_height: Option<Height>,
+    /// 1 for the validators which proposed the most blocks; None if it proposed none.
+    pub rank: Option<i64>,
+    /// Validators which proposed at least one block.
+    pub validators: i64,
+}
+
+/// Consecutive heights all proposed by one validator.
+#[derive(Serialize, Debug)]
+pub struct ProposalStreak {
+    pub first_height: Height,
+    pub last_height: Height,
+    pub blocks: i64,
+}
+
+/// Blocks proposed within an inclusive range of heights, or on a UTC day.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum ProposalBucket {
+    Heights { first_height: Height, last_height: Height, blocks: i64 },
+    /// `starts_at` is midnight of the day in the time zone of the buckets.
+    Day { day: NaiveDate, starts_at: DateTime<Utc>, blocks: i64 },
+}
+
+/// Latest blocks indexed, the share of them a validator proposed and their average time.
+#[derive(Debug)]
+pub struct RecentActivity {
+    pub blocks: i64,
+    pub proposals: i64,
+    pub seconds_per_block: Option<f64>,
+}
+
+/// Blocks of a range whose proposer was chosen from a known validator set, how many of them
+/// a validator proposed and how many it was expected to, given its share of the voting power.
+#[derive(Debug)]
+pub struct ProposalExpectation {
+    pub blocks: i64,
+    pub proposals: i64,
+    pub expected_proposals: f64,
+    /// Variance of the proposals if each block is proposed by the validator with the probability
+    /// of its share.
+    pub variance: f64,
+}
+
+/// Signatures of a validator over the blocks of a window whose commit is stored.
+#[derive(Debug)]
+pub struct Signatures {
+    pub first_height: Option<Height>,
+    pub last_height: Option<Height>,
+    /// Blocks of the window whose signatures are stored, whether the validator was in the set.
+    pub blocks: i64,
+    pub signed: i64,
+    /// Blocks the validator was in the set for but did not sign.
+    pub missed: i64,
+}
+
+/// What `/validators` are listed by.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidatorSort {
+    #[default]
+    Blocks,
+    Moniker,
+    Address,
+}
+
+impl ValidatorSort {
+    /// Most blocks first, monikers and addresses in alphabetical order.
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            ValidatorSort::Blocks => SortOrder::Desc,
+            ValidatorSort::Moniker | ValidatorSort::Address => SortOrder::Asc,
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            ValidatorSort::Blocks => "c.blocks",
+            ValidatorSort::Moniker => "v.moniker",
+            ValidatorSort::Address => "c.proposer",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// A validator which proposed at least one indexed block.
+#[derive(Serialize, Debug)]
+pub struct KnownProposer {
+    pub address: ConsensusAddress,
+    /// Null until the indexer resolves the validator's metadata.
+    pub moniker: Option<String>,
+    /// Label of its consensus or operator address in the address directory.
+    pub label: Option<String>,
+    pub blocks: i64,
+}
+
+/// Name of a known address, such as an exchange, a bridge or a team wallet.
+#[derive(Serialize, Debug)]
+pub struct AddressLabel {
+    pub address: String,
+    pub label: String,
+    pub category: Option<String>,
+    /// `community` for labels of the community source, `admin` for labels set through the API.
+    pub source: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Value of each block which histograms are made of.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HistogramMetric {
+    /// Seconds since the previous block, for blocks whose previous block is indexed.
+    BlockTime,
+    Transactions,
+    /// Validators which signed the commit of the block, in percent of the set.
+    Turnout,
+}
+
+impl HistogramMetric {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            HistogramMetric::BlockTime => "seconds",
+            HistogramMetric::Transactions => "transactions",
+            HistogramMetric::Turnout => "percent",
+        }
+    }
+
+    /// Height and value of each block between `first` and `last`, two SQL expressions.
+    fn values(&self, first: &str, last: &str) -> String {
+        match self {
+            HistogramMetric::BlockTime => {
+                format!("SELECT p.height, extract(epoch FROM p.block_time - previous.block_time)::float8 \
+                         FROM proposer_to_height p \
+                         JOIN proposer_to_height previous ON previous.height = p.height - 1 \
+                         WHERE p.height BETWEEN {first} AND {last}")
+            }
+            HistogramMetric::Transactions => {
+                format!("SELECT height, num_txs::float8 FROM proposer_to_height \
+                         WHERE height BETWEEN {first} AND {last} AND num_txs IS NOT NULL")
+            }
+            HistogramMetric::Turnout => {
+                format!("SELECT b.height, (bit_count(b.signed) * 100.0 / cardinality(s.addresses))::float8 \
+                         FROM block_signatures b JOIN signer_sets s ON s.id = b.signer_set_id \
+                         WHERE b.height BETWEEN {first} AND {last}")
+            }
+        }
+    }
+}
+
+/// Values of a metric over a range of blocks, counted in buckets of equal width.
+#[derive(Debug)]
+pub struct Histogram {
+    /// Lowest and highest height with a value; null if there are none.
+    pub first_height: Option<Height>,
+    pub last_height: Option<Height>,
+    pub blocks: i64,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// Blocks whose value is at least `lower` and below `upper`, or up to it for the last bucket.
+#[derive(Serialize, Debug)]
+pub struct HistogramBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub blocks: i64,
+}
+
+/// Value a plugin stored for a block.
+#[derive(Serialize, Debug)]
+pub struct CustomRecord {
+    pub height: Height,
+    pub key: String,
+    pub value: serde_json::Value,
+    /// Read from `custom_records_archive`, where values of old blocks are moved.
+    pub archived: bool,
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub sql: String,
+    pub parameters: Vec<String>,
+}
+
+/// Returns up to `limit` blocks above `after_height` in ascending height.
+pub async fn blocks_proposed_by(conn: &tokio_postgres::Client,
+                                filter: &ProposedBlocksFilter<'_>,
+                                after_height: Option<Height>,
+                                limit: i64)
+                                -> Result<Vec<ProposedBlock>, Error> {
+    let rows = conn
+        .query("SELECT height, block_time, num_txs FROM proposer_to_height WHERE proposer = $1 \
+                AND ($2::bigint IS NULL OR height >= $2) AND ($3::bigint IS NULL OR height <= $3) \
+                AND ($4::timestamptz IS NULL OR block_time >= $4) \
+                AND ($5::timestamptz IS NULL OR block_time <= $5) \
+                AND ($6::bigint IS NULL OR height > $6) \
+                ORDER BY height LIMIT $7",
+               &[&filter.validator, &filter.from_height, &filter.to_height, &filter.from_time, &filter.to_time,
+                 &after_height, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter().map(to_proposed_block).collect()
+}
+
+/// Binds the same blocks as `blocks_proposed_by` to a portal, all of them without a limit, so
+/// they are fetched a chunk at a time with `fetch_proposed_blocks` rather than all at once.
+pub async fn bind_blocks_proposed_by(transaction: &Transaction<'_>,
+                                     filter: &ProposedBlocksFilter<'_>,
+                                     after_height: Option<Height>,
+                                     limit: Option<i64>)
+                                     -> Result<Portal, Error> {
+    transaction
+        .bind("SELECT height, block_time, num_txs FROM proposer_to_height WHERE proposer = $1 \
+               AND ($2::bigint IS NULL OR height >= $2) AND ($3::bigint IS NULL OR height <= $3) \
+               AND ($4::timestamptz IS NULL OR block_time >= $4) \
+               AND ($5::timestamptz IS NULL OR block_time <= $5) \
+               AND ($6::bigint IS NULL OR height > $6) \
+               ORDER BY height LIMIT $7",
+              &[&filter.validator, &filter.from_height, &filter.to_height, &filter.from_time, &filter.to_time,
+                &after_height, &limit])
+        .await
+        .map_err(Error::could_not_query_database)
+}
+
+/// Fetches the next `rows` blocks of the portal; fewer are returned once it is exhausted.
+pub async fn fetch_proposed_blocks(transaction: &Transaction<'_>, portal: &Portal, rows: i32)
+                                   -> Result<Vec<ProposedBlock>, Error> {
+    transaction.query_portal(portal, rows)
+        .await
+        .map_err(Error::could_not_query_database)?
+        .iter()
+        .map(to_proposed_block)
+        .collect()
+}
+
+fn to_proposed_block(row: &Row) -> Result<ProposedBlock, Error> {
+    Ok(ProposedBlock { height: get_height(row, 0)?, block_time: row.get(1), num_txs: row.get(2) })
+}
+
+/// Counts the blocks `blocks_proposed_by` pages through.
+pub async fn count_blocks_proposed_by(conn: &tokio_postgres::Client, filter: &ProposedBlocksFilter<'_>)
+                                      -> Result<i64, Error> {
+    let row = conn
+        .query_one("SELECT count(*) FROM proposer_to_height WHERE proposer = $1 \
+                    AND ($2::bigint IS NULL OR height >= $2) AND ($3::bigint IS NULL OR height <= $3) \
+                    AND ($4::timestamptz IS NULL OR block_time >= $4) \
+                    AND ($5::timestamptz IS NULL OR block_time <= $5)",
+                   &[&filter.validator, &filter.from_height, &filter.to_height, &filter.from_time,
+                     &filter.to_time])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(row.get(0))
+}
+
+/// Returns the lowest and the highest indexed height, from the ranges of indexed heights at
+/// either end, so endpoints which must not read `proposer_to_height` can pin their height.
+pub async fn indexed_height_range(conn: &tokio_postgres::Client)
+                                  -> Result<(Option<Height>, Option<Height>), Error> {
+    let row = conn
+        .query_one("SELECT (SELECT first_height FROM indexed_height_ranges ORDER BY first_height LIMIT 1), \
+                           (SELECT last_height FROM indexed_height_ranges ORDER BY first_height DESC LIMIT 1)",
+                   &[])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok((get_height(&row, 0)?, get_height(&row, 1)?))
+}
+
+/// Seconds between now and the time of the highest indexed block, which grows while indexing
+/// is stalled.
+pub async fn indexing_lag_in_seconds(conn: &tokio_postgres::Client) -> Result<Option<f64>, Error> {
+    let row = conn
+        .query_opt("SELECT EXTRACT(EPOCH FROM now() - block_time)::float8 FROM proposer_to_height \
+                    WHERE height = (SELECT max(height) FROM proposer_to_height)",
+                   &[])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(row.and_then(|r| r.get(0)))
+}
+
+/// Counts, per window of seconds before now, the blocks of the window whose indexing time is
+/// known and how many of them were indexed within `lag_in_seconds` of their block time.
+pub async fn lag_compliance(conn: &tokio_postgres::Client, lag_in_seconds: f64, windows_in_seconds: &[i64])
+                            -> Result<Vec<(i64, i64)>, Error> {
+    let rows = conn
+        .query("SELECT count(p.height), \
+                    count(p.height) FILTER (WHERE p.indexed_at - p.block_time <= make_interval(secs => $1)) \
+                FROM unnest($2::bigint[]) WITH ORDINALITY AS w(seconds, position) \
+                LEFT JOIN proposer_to_height p \
+                    ON p.block_time >= now() - make_interval(secs => w.seconds) AND p.indexed_at IS NOT NULL \
+                GROUP BY w.position ORDER BY w.position",
+               &[&lag_in_seconds, &windows_in_seconds])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// Finds maximal runs of consecutive indexed heights within `[from_height, to_height]`
+/// as inclusive ranges in ascending order. The indexer keeps the runs in `indexed_height_ranges`,
+/// so this reads a row per run rather than every height.
+pub async fn indexed_ranges(conn: &tokio_postgres::Client,
+                            from_height: Option<Height>,
+                            to_height: Option<Height>)
+                            -> Result<Vec<[Height; 2]>, Error> {
+    let rows = conn
+        .query("SELECT greatest(first_height, $1), least(last_height, $2) FROM indexed_height_ranges \
+                WHERE ($1::bigint IS NULL OR last_height >= $1) AND ($2::bigint IS NULL OR first_height <= $2) \
+                ORDER BY first_height",
+               &[&from_height, &to_height])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter().map(|r| Ok([get_height(r, 0)?, get_height(r, 1)?])).collect()
+}
+
+pub async fn partitions(conn: &tokio_postgres::Client, validator: &ConsensusAddress, parts: i32)
+                        -> Result<Vec<Partition>, Error> {
+    let rows = conn
+        .query("SELECT min(height), max(height), count(*) FROM \
+                (SELECT height, ntile($2) OVER (ORDER BY height) AS part \
+                 FROM proposer_to_height WHERE proposer = $1) AS p \
+                GROUP BY part ORDER BY part",
+               &[&validator, &parts])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter()
+        .map(|r| Ok(Partition { from_height: get_height(r, 0)?, to_height: get_height(r, 1)?, count: r.get(2) }))
+        .collect()
+}
+
+/// Bounds are inclusive. Skips the `offset` highest ranked proposers.
+pub async fn top_proposers(conn: &tokio_postgres::Client,
+                           from_height: Option<Height>,
+                           to_height: Option<Height>,
+                           offset: i64,
+                           limit: i64)
+                           -> Result<Vec<ProposerCount>, Error> {
+    let rows = conn
+        .query(&format!("WITH {} \
+                         SELECT p.rank, p.proposer, v.moniker, p.count FROM \
+                         (SELECT proposer, sum(blocks)::bigint AS count, \
+                              row_number() OVER (ORDER BY sum(blocks) DESC, proposer) AS rank \
+                          FROM proposer_counts \
+                          GROUP BY proposer ORDER BY count DESC, proposer OFFSET $3 LIMIT $4) AS p \
+                         LEFT JOIN validators v ON v.address = p.proposer \
+                         ORDER BY p.rank",
+                        proposer_counts("$1", "$2")),
+               &[&from_height, &to_height, &offset, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows.iter()
+        .map(|r| ProposerCount { rank: r.get(0), proposer: r.get(1), moniker: r.get(2), count: r.get(3) })
+        .collect())
+}
+
+/// Every proposer of an indexed block, with the blocks it proposed. Proposers without a moniker
+/// come last when sorted by moniker, and ties are ordered by address.
+pub async fn known_proposers(conn: &tokio_postgres::Client,
+                             sort: ValidatorSort,
+                             order: SortOrder,
+                             offset: i64,
+                             limit: i64)
+                             -> Result<Vec<KnownProposer>, Error> {
+    let rows = conn
+        .query(&format!("WITH {}, \
+                         totals AS ( \
+                             SELECT proposer, sum(blocks)::bigint AS blocks FROM proposer_counts \
+                             GROUP BY proposer) \
+                         SELECT c.proposer, v.moniker, COALESCE(lc.label, lo.label), c.blocks \
+                         FROM totals c LEFT JOIN validators v ON v.address = c.proposer \
+                         LEFT JOIN address_labels lc ON lc.address = c.proposer \
+                         LEFT JOIN address_labels lo ON lo.address = v.operator_address \
+                         ORDER BY {} {} NULLS LAST, c.proposer OFFSET $1 LIMIT $2",
+                        proposer_counts("NULL", "NULL"),
+                        sort.column(),
+                        order.sql()),
+               &[&offset, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows.iter()
+        .map(|r| KnownProposer { address: r.get(0), moniker: r.get(1), label: r.get(2), blocks: r.get(3) })
+        .collect())
+}
+
+/// Bounds are inclusive. The blocks of a validator with several tags count for each of them.
+pub async fn proposals_by_tag(conn: &tokio_postgres::Client,
+                              from_height: Option<Height>,
+                              to_height: Option<Height>)
+                              -> Result<Vec<TagCount>, Error> {
+    let rows = conn
+        .query(&format!("WITH {} \
+                         SELECT t.tag, count(DISTINCT c.proposer), sum(c.blocks)::bigint AS count \
+                         FROM proposer_counts c LEFT JOIN validator_tags t ON t.address = c.proposer \
+                         GROUP BY t.tag ORDER BY count DESC, t.tag",
+                        proposer_counts("$1", "$2")),
+               &[&from_height, &to_height])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows.iter().map(|r| TagCount { tag: r.get(0), validators: r.get(1), count: r.get(2) }).collect())
+}
+
+pub async fn replace_validator_tags(conn: &tokio_postgres::Client, validator: &ConsensusAddress, tags: &[String])
+                                    -> Result<(), Error> {
+    conn
+        .execute("WITH removed AS (DELETE FROM validator_tags WHERE address = $1 AND NOT tag = ANY($2)) \
+                  INSERT INTO validator_tags(address, tag) SELECT $1, unnest($2::varchar[]) \
+                  ON CONFLICT DO NOTHING",
+                 &[&validator, &tags])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(())
+}
+
+pub async fn block_by_hash(conn: &tokio_postgres::Client, hash: &BlockHash) -> Result<Option<Block>, Error> {
+    let row = conn
+        .query_opt("SELECT height, hash, proposer, block_time, num_txs FROM proposer_to_height WHERE hash = $1",
+                   &[&hash])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    row.as_ref().map(block).transpose()
+}
+
+pub async fn block_at_height(conn: &tokio_postgres::Client, height: Height) -> Result<Option<Block>, Error> {
+    let row = conn
+        .query_opt("SELECT height, hash, proposer, block_time, num_txs FROM proposer_to_height WHERE height = $1",
+                   &[&height])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    row.as_ref().map(block).transpose()
+}
+
+/// Returns the indexed blocks among `heights`, in no particular order.
+pub async fn blocks_at_heights(conn: &tokio_postgres::Client, heights: &[Height]) -> Result<Vec<Block>, Error> {
+    let rows = conn
+        .query("SELECT height, hash, proposer, block_time, num_txs FROM proposer_to_height \
+                WHERE height = ANY($1)",
+               &[&heights])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter().map(block).collect()
+}
+
+/// Returns up to `limit` blocks above `after_height` in ascending height, skipping the heights
+/// in `excluded` and, if given, blocks of other proposers.
+pub async fn blocks_after(conn: &tokio_postgres::Client,
+                          after_height: Height,
+                          excluded: &[Height],
+                          proposer: Option<&ConsensusAddress>,
+                          limit: i64)
+                          -> Result<Vec<Block>, Error> {
+    let rows = conn
+        .query("SELECT height, hash, proposer, block_time, num_txs FROM proposer_to_height \
+                WHERE height > $1 AND NOT (height = ANY($2)) \
+                AND ($3::varchar IS NULL OR proposer = $3) \
+                ORDER BY height LIMIT $4",
+               &[&after_height, &excluded, &proposer, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter().map(block).collect()
+}
+
+/// Queues heights for the indexer; heights which are queued already are skipped.
+pub async fn request_heights(conn: &tokio_postgres::Client, heights: &[Height]) -> Result<(), Error> {
+    conn
+        .execute("INSERT INTO height_requests(height) SELECT * FROM UNNEST($1::bigint[]) \
+                  ON CONFLICT DO NOTHING", &[&heights])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(())
+}
+
+/// Prefers the names which the indexer resolves from the LCD endpoint over the ones imported from genesis.
+pub async fn find_validator(conn: &tokio_postgres::Client, address: &ConsensusAddress)
+                            -> Result<Option<Validator>, Error> {
+    let row = conn
+        .query_opt("SELECT moniker, operator_address, avatar_url FROM \
+                    (SELECT moniker, operator_address, avatar_url, 0 AS priority FROM validators \
+                     WHERE address = $1 \
+                     UNION ALL \
+                     SELECT moniker, operator_address, NULL, 1 FROM genesis_validators WHERE address = $1) AS v \
+                    ORDER BY priority LIMIT 1",
+                   &[&address])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(row.map(|r| Validator { moniker: r.get(0), operator_address: r.get(1), avatar_url: r.get(2) }))
+}
+
+/// Label of the consensus address, or else of the operator address.
+pub async fn find_label(conn: &tokio_postgres::Client,
+                        address: &ConsensusAddress,
+                        operator_address: Option<&str>)
+                        -> Result<Option<String>, Error> {
+    let row = conn
+        .query_opt("SELECT label FROM address_labels WHERE address = $1 OR address = $2 \
+                    ORDER BY address = $1 DESC LIMIT 1",
+                   &[&address, &operator_address])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(row.map(|r| r.get(0)))
+}
+
+pub async fn address_labels(conn: &tokio_postgres::Client, category: Option<&str>, offset: i64, limit: i64)
+                            -> Result<Vec<AddressLabel>, Error> {
+    let rows = conn
+        .query("SELECT address, label, category, source, updated_at FROM address_labels \
+                WHERE $1::varchar IS NULL OR category = $1 \
+                ORDER BY address OFFSET $2 LIMIT $3",
+               &[&category, &offset, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows.iter()
+        .map(|r| AddressLabel {
+            address: r.get(0),
+            label: r.get(1),
+            category: r.get(2),
+            source: r.get(3),
+            updated_at: r.get(4),
+        })
+        .collect())
+}
+
+pub async fn save_label(conn: &tokio_postgres::Client, address: &str, label: &str, category: Option<&str>)
+                        -> Result<(), Error> {
+    conn
+        .execute("INSERT INTO address_labels(address, label, category, source) VALUES ($1, $2, $3, 'admin') \
+                  ON CONFLICT (address) DO UPDATE SET label = excluded.label, category = excluded.category, \
+                  source = 'admin', updated_at = now()",
+                 &[&address, &label, &category])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(())
+}
+
+/// Returns false if the address has no label.
+pub async fn delete_label(conn: &tokio_postgres::Client, address: &str) -> Result<bool, Error> {
+    let count_rows_deleted = conn
+        .execute("DELETE FROM address_labels WHERE address = $1", &[&address])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(count_rows_deleted > 0)
+}
+
+pub async fn commission_changes(conn: &tokio_postgres::Client,
+                                validator: Option<&ConsensusAddress>,
+                                after_id: Option<i64>,
+                                limit: i64)
+                                -> Result<Vec<CommissionChange>, Error> {
+    let rows = conn
+        .query("SELECT c.id, c.address, v.moniker, c.old_rate::text, c.new_rate::text, c.height, c.changed_at \
+                FROM commission_changes c LEFT JOIN validators v ON v.address = c.address \
+                WHERE ($1::varchar IS NULL OR c.address = $1) AND ($2::bigint IS NULL OR c.id > $2) \
+                ORDER BY c.id LIMIT $3",
+               &[&validator, &after_id, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter()
+        .map(|r| Ok(CommissionChange {
+            id: r.get(0),
+            validator: r.get(1),
+            moniker: r.get(2),
+            old_rate: r.get(3),
+            new_rate: r.get(4),
+            height: get_height(r, 5)?,
+            changed_at: r.get(6),
+        }))
+        .collect()
+}
+
+pub async fn anomalies(conn: &tokio_postgres::Client,
+                       validator: Option<&ConsensusAddress>,
+                       kind: Option<&str>,
+                       after_id: Option<i64>,
+                       limit: i64)
+                       -> Result<Vec<Anomaly>, Error> {
+    let rows = conn
+        .query("SELECT a.id, a.kind, a.address, v.moniker, a.first_height, a.last_height, a.score, a.detected_at \
+                FROM anomalies a LEFT JOIN validators v ON v.address = a.address \
+                WHERE ($1::varchar IS NULL OR a.address = $1) AND ($2::varchar IS NULL OR a.kind = $2) \
+                AND ($3::bigint IS NULL OR a.id > $3) \
+                ORDER BY a.id LIMIT $4",
+               &[&validator, &kind, &after_id, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter()
+        .map(|r| Ok(Anomaly {
+            id: r.get(0),
+            kind: r.get(1),
+            validator: r.get(2),
+            moniker: r.get(3),
+            first_height: get_height(r, 4)?,
+            last_height: get_height(r, 5)?,
+            score: r.get(6),
+            detected_at: r.get(7),
+        }))
+        .collect()
+}
+
+/// Values of the plugin between the heights, inclusive, in ascending height and key. Archived
+/// chunks overlapping the range are unpacked, which is slower, unless the value was stored again
+/// after it was archived. Values are read as text, since they are JSON of any shape.
+pub async fn custom_records(conn: &tokio_postgres::Client, plugin: &str, from_height: Height, to_height: Height)
+                            -> Result<Vec<CustomRecord>, Error> {
+    let rows = conn
+        .query("SELECT height, key, value::text, false FROM custom_records \
+                WHERE plugin = $1 AND height BETWEEN $2 AND $3 \
+                UNION ALL \
+                SELECT (r->>'height')::bigint, r->>'key', (r->'value')::text, true \
+                FROM custom_records_archive a CROSS JOIN jsonb_array_elements(a.records) r \
+                WHERE a.plugin = $1 AND a.last_height >= $2 AND a.first_height <= $3 \
+                AND (r->>'height')::bigint BETWEEN $2 AND $3 \
+                AND NOT EXISTS (SELECT 1 FROM custom_records c \
+                                WHERE c.plugin = $1 AND c.height = (r->>'height')::bigint AND c.key = r->>'key') \
+                ORDER BY 1, 2",
+               &[&plugin, &from_height, &to_height])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter()
+        .map(|r| Ok(CustomRecord {
+            height: get_height(r, 0)?,
+            key: r.get(1),
+            value: serde_json::from_str(r.get(2)).map_err(Error::could_not_query_database)?,
+            archived: r.get(3),
+        }))
+        .collect()
+}
+
+/// Compares each proposal of the validator with the previous one through `lag()`. The heights
+/// right outside the range stand in for proposals, so the stretches at both ends are found too.
+pub async fn longest_absences(conn: &tokio_postgres::Client,
+                              validator: &ConsensusAddress,
+                              from_height: Option<Height>,
+                              to_height: Option<Height>,
+                              limit: i64)
+                              -> Result<Vec<Absence>, Error> {
+    let rows = conn
+        .query("WITH bounds AS ( \
+                    SELECT COALESCE($2, min(height)) AS lowest, COALESCE($3, max(height)) AS highest \
+                    FROM proposer_to_height), \
+                proposals AS ( \
+                    SELECT height FROM proposer_to_height, bounds \
+                    WHERE proposer = $1 AND height BETWEEN lowest AND highest \
+                    UNION ALL SELECT lowest - 1 FROM bounds \
+                    UNION ALL SELECT highest + 1 FROM bounds) \
+                SELECT previous + 1, height - 1, height - previous - 1 AS blocks FROM \
+                    (SELECT height, lag(height) OVER (ORDER BY height) AS previous FROM proposals) AS p \
+                WHERE height - previous > 1 \
+                ORDER BY blocks DESC, previous LIMIT $4",
+               &[&validator, &from_height, &to_height, &limit])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter()
+        .map(|r| Ok(Absence {
+            first_height: get_height(r, 0)?,
+            last_height: get_height(r, 1)?,
+            blocks: r.get(2),
+        }))
+        .collect()
+}
+
+/// Common table expression `proposer_counts (day, proposer, blocks)` counting the blocks between
+/// the heights, two SQL expressions which may be null for no bound. Days within the range are
+/// read from `proposer_daily_counts`, and only the blocks of the days at its ends and blocks without
+/// a time, whose day is null, are counted in `proposer_to_height`.
+/// The heights of a day are contiguous, since block times increase with the height.
+fn proposer_counts(from_height: &str, to_height: &str) -> String {
+    format!("days AS ( \
+                 SELECT day, min(first_height) AS first_height, max(last_height) AS last_height \
+                 FROM proposer_daily_counts GROUP BY day), \
+             bounds AS ( \
+                 SELECT COALESCE({from_height}::bigint, 0) AS lowest, \
+                     COALESCE({to_height}::bigint, {MAXIMUM_HEIGHT}) AS highest), \
+             covered AS ( \
+                 SELECT min(d.first_height) AS first_height, max(d.last_height) AS last_height \
+                 FROM days d, bounds b WHERE d.first_height >= b.lowest AND d.last_height <= b.highest), \
+             uncovered AS ( \
+                 SELECT p.block_time, p.proposer FROM proposer_to_height p \
+                 WHERE p.height >= (SELECT lowest FROM bounds) \
+                     AND p.height <= COALESCE((SELECT first_height FROM covered) - 1, \
+                                              (SELECT highest FROM bounds)) \
+                 UNION ALL \
+                 SELECT p.block_time, p.proposer FROM proposer_to_height p \
+                 WHERE p.height > (SELECT last_height FROM covered) AND p.height <= (SELECT highest FROM bounds) \
+                 UNION ALL \
+                 SELECT p.block_time, p.proposer FROM proposer_to_height p \
+                 WHERE p.height BETWEEN (SELECT first_height FROM covered) AND (SELECT last_height FROM covered) \
+                     AND p.block_time IS NULL), \
+             proposer_counts AS ( \
+                 SELECT d.day, d.proposer, d.blocks FROM proposer_daily_counts d, covered c \
+                 WHERE d.first_height >= c.first_height AND d.last_height <= c.last_height \
+                 UNION ALL \
+                 SELECT (block_time AT TIME ZONE 'UTC')::date, proposer, count(*) FROM uncovered GROUP BY 1, 2)")
+}
+
+/// Groups by `height / bucket_size`, or by the day of the block time in the time zone without
+/// a bucket size. Blocks whose time is unknown are left out of days. UTC days are read from the
+/// daily counts, while days of other zones count the blocks themselves.
+pub async fn proposals_per_bucket(conn: &tokio_postgres::Client,
+                                  validator: &ConsensusAddress,
+                                  bucket_size: Option<i64>,
+                                  time_zone: &str,
+                                  from_height: Option<Height>,
+                                  to_height: Option<Height>)
+                                  -> Result<Vec<ProposalBucket>, Error> {
+    let filter = "proposer = $1 \
+                  AND ($2::bigint IS NULL OR height >= $2) AND ($3::bigint IS NULL OR height <= $3)";
+
+    let Some(bucket_size) = bucket_size else {
+        let days = if time_zone == "UTC" {
+            format!("WITH {} \
+                     SELECT day, sum(blocks)::bigint AS blocks FROM proposer_counts \
+                     WHERE proposer = $1 AND day IS NOT NULL \
+                     GROUP BY day",
+                    proposer_counts("$2", "$3"))
+        } else {
+            format!("SELECT (block_time AT TIME ZONE $4)::date AS day, count(*) AS blocks \
+                     FROM proposer_to_height WHERE {filter} AND block_time IS NOT NULL \
+                     GROUP BY day")
+        };
+        let rows = conn
+            .query(&format!("SELECT day, day::timestamp AT TIME ZONE $4::text, blocks FROM ({days}) AS d \
+                             ORDER BY day"),
+                   &[&validator, &from_height, &to_height, &time_zone])
+            .await
+            .map_err(Error::could_not_query_database)?;
+
+        return Ok(rows.iter()
+            .map(|r| ProposalBucket::Day { day: r.get(0), starts_at: r.get(1), blocks: r.get(2) })
+            .collect());
+    };
+
+    let rows = conn
+        .query(&format!("SELECT height / $4 * $4 AS first_height, count(*) \
+                         FROM proposer_to_height WHERE {filter} \
+                         GROUP BY first_height ORDER BY first_height"),
+               &[&validator, &from_height, &to_height, &bucket_size])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    rows.iter()
+        .map(|r| {
+            let first_height: Height = get_height(r, 0)?;
+            Ok(ProposalBucket::Heights {
+                first_height,
+                last_height: first_height.saturating_add(bucket_size as u64 - 1),
+                blocks: r.get(1),
+            })
+        })
+        .collect()
+}
+
+/// Nodes are matched to validators by moniker, as peers do not announce their validator keys.
+/// Without `from_day` the range spans the `default_days` days up to its end, today by default.
+pub async fn version_adoption(conn: &tokio_postgres::Client,
+                              from_day: Option<NaiveDate>,
+                              to_day: Option<NaiveDate>,
+                              default_days: i32)
+                              -> Result<Vec<VersionAdoption>, Error> {
+    let rows = conn
+        .query("WITH days AS ( \
+                    SELECT COALESCE($2::date, (now() AT TIME ZONE 'UTC')::date) AS to_day), \
+                observed AS ( \
+                    SELECT n.day, n.version, n.node_id, n.moniker FROM node_versions n, days \
+                    WHERE n.day <= days.to_day AND n.day >= COALESCE($1::date, days.to_day - $3 + 1)), \
+                matched AS ( \
+                    SELECT DISTINCT o.day, o.version, v.address \
+                    FROM observed o JOIN validators v ON v.moniker = o.moniker), \
+                proposals AS ( \
+                    SELECT m.day, m.version, count(*) AS validators, \
+                           COALESCE(sum(c.blocks), 0)::bigint AS proposed_blocks \
+                    FROM matched m \
+                    LEFT JOIN proposer_daily_counts c ON c.day = m.day AND c.proposer = m.address \
+                    GROUP BY m.day, m.version) \
+                SELECT o.day, o.version, count(DISTINCT o.node_id), \
+                       COALESCE(max(p.validators), 0), COALESCE(max(p.proposed_blocks), 0) \
+                FROM observed o LEFT JOIN proposals p ON p.day = o.day AND p.version = o.version \
+                GROUP BY o.day, o.version ORDER BY o.day, o.version",
+               &[&from_day, &to_day, &default_days])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows.iter()
+        .map(|r| VersionAdoption {
+            day: r.get(0),
+            version: r.get(1),
+            nodes: r.get(2),
+            validators: r.get(3),
+            proposed_blocks: r.get(4),
+        })
+        .collect())
+}
+
+pub async fn proposal_totals(conn: &tokio_postgres::Client, validator: &ConsensusAddress)
+                             -> Result<ProposalTotals, Error> {
+    let row = conn
+        .query_one("WITH totals AS ( \
+                        SELECT proposer, sum(blocks)::bigint AS blocks, min(first_height) AS first_height, \
+                               max(last_height) AS last_height \
+                        FROM proposer_daily_counts GROUP BY proposer), \
+                    ranked AS ( \
+                        SELECT *, rank() OVER (ORDER BY blocks DESC) AS rank FROM totals) \
+                    SELECT COALESCE(r.blocks, 0), r.first_height, r.last_height, r.rank, \
+                           (SELECT count(*) FROM totals) \
+                    FROM (SELECT 1) AS one LEFT JOIN ranked r ON r.proposer = $1",
+                   &[&validator])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(ProposalTotals {
+        proposals: row.get(0),
+        first_proposed_height: get_height(&row, 1)?,
+        last_proposed_height: get_height(&row, 2)?,
+        rank: row.get(3),
+        validators: row.get(4),
+    })
+}
+
+/// The lowest of the longest streaks, or None if the validator proposed no block.
+pub async fn longest_proposal_streak(conn: &tokio_postgres::Client, validator: &ConsensusAddress)
+                                     -> Result<Option<ProposalStreak>, Error> {
+    let row = conn
+        .query_opt("SELECT min(height), max(height), count(*) AS blocks FROM \
+                        (SELECT height, height - row_number() OVER (ORDER BY height) AS island \
+                         FROM proposer_to_height WHERE proposer = $1) AS p \
+                    GROUP BY island ORDER BY blocks DESC, island LIMIT 1",
+                   &[&validator])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    Ok(Some(ProposalStreak {
+        first_height: get_height(&row, 0)?,
+        last_height: get_height(&row, 1)?,
+        blocks: row.get(2),
+    }))
+}
+
+/// The average block time spans the first and the last of the blocks whose time is known.
+pub async fn recent_activity(conn: &tokio_postgres::Client, validator: &ConsensusAddress, blocks: i64)
+                             -> Result<RecentActivity, Error> {
+    let row = conn
+        .query_one("WITH recent AS ( \
+                        SELECT height, proposer, block_time FROM proposer_to_height \
+                        ORDER BY height DESC LIMIT $2) \
+                    SELECT count(*), count(*) FILTER (WHERE proposer = $1), \
+                        (EXTRACT(EPOCH FROM max(block_time) - min(block_time)) \
+                        / NULLIF(max(height) FILTER (WHERE block_time IS NOT NULL) \
+                            - min(height) FILTER (WHERE block_time IS NOT NULL), 0))::float8 \
+                    FROM recent",
+                   &[&validator, &blocks])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(RecentActivity { blocks: row.get(0), proposals: row.get(1), seconds_per_block: row.get(2) })
+}
+
+/// Each snapshot of the validator set applies from its height until the next snapshot, so blocks
+/// below the first snapshot are left out.
+pub async fn proposal_expectation(conn: &tokio_postgres::Client,
+                                  validator: &ConsensusAddress,
+                                  from_height: Option<Height>,
+                                  to_height: Option<Height>)
+                                  -> Result<ProposalExpectation, Error> {
+    let row = conn
+        .query_one("WITH shares AS ( \
+                        SELECT height, COALESCE(sum(voting_power) FILTER (WHERE address = $1), 0)::float8 \
+                            / NULLIF(sum(voting_power), 0) AS share \
+                        FROM validator_set_snapshots GROUP BY height), \
+                    windows AS ( \
+                        SELECT height AS first_height, lead(height) OVER (ORDER BY height) AS next_height, \
+                            COALESCE(share, 0) AS share \
+                        FROM shares), \
+                    per_window AS ( \
+                        SELECT w.share, count(*) AS blocks, count(*) FILTER (WHERE p.proposer = $1) AS proposals \
+                        FROM proposer_to_height p JOIN windows w ON p.height >= w.first_height \
+                            AND (w.next_height IS NULL OR p.height < w.next_height) \
+                        WHERE ($2::bigint IS NULL OR p.height >= $2) AND ($3::bigint IS NULL OR p.height <= $3) \
+                        GROUP BY w.first_height, w.share) \
+                    SELECT COALESCE(sum(blocks), 0)::bigint, COALESCE(sum(proposals), 0)::bigint, \
+                        COALESCE(sum(blocks * share), 0), COALESCE(sum(blocks * share * (1 - share)), 0) \
+                    FROM per_window",
+                   &[&validator, &from_height, &to_height])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(ProposalExpectation {
+        blocks: row.get(0),
+        proposals: row.get(1),
+        expected_proposals: row.get(2),
+        variance: row.get(3),
+    })
+}
+
+/// Counts the blocks the validator signed and missed among the `window` heights up to the highest
+/// one with stored signatures. Its bit in each block's bitmap is at its position in the signer set.
+pub async fn signatures_in_window(conn: &tokio_postgres::Client, validator: &ConsensusAddress, window: i64)
+                                  -> Result<Signatures, Error> {
+    let row = conn
+        .query_one("WITH w AS (\
+                        SELECT b.height, b.signed, array_position(s.addresses, $1::varchar) AS position \
+                        FROM block_signatures b JOIN signer_sets s ON s.id = b.signer_set_id \
+                        WHERE b.height > (SELECT max(height) FROM block_signatures) - $2) \
+                    SELECT min(height), max(height), count(*), \
+                        count(*) FILTER (WHERE get_bit(signed, position - 1) = 1), \
+                        count(*) FILTER (WHERE get_bit(signed, position - 1) = 0) \
+                    FROM w",
+                   &[&validator, &window])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(Signatures {
+        first_height: get_height(&row, 0)?,
+        last_height: get_height(&row, 1)?,
+        blocks: row.get(2),
+        signed: row.get(3),
+        missed: row.get(4),
+    })
+}
+
+/// Counts the values of the metric between `from_height` and `to_height` in `buckets` buckets
+/// spanning the lowest to the highest value. Without `to_height` the range ends at the highest
+/// indexed height, and without `from_height` it spans the `default_blocks` heights up to its end.
+/// Every bucket is returned, including empty ones, unless there are no values at all.
+pub async fn histogram(conn: &tokio_postgres::Client,
+                       metric: HistogramMetric,
+                       buckets: i32,
+                       from_height: Option<Height>,
+                       to_height: Option<Height>,
+                       default_blocks: i64)
+                       -> Result<Histogram, Error> {
+    let values = metric.values("(SELECT from_height FROM heights)", "(SELECT to_height FROM heights)");
+    let rows = conn
+        .query(&format!("WITH top AS ( \
+                             SELECT COALESCE($3::bigint, (SELECT max(height) FROM proposer_to_height)) \
+                                 AS to_height), \
+                         heights AS (SELECT COALESCE($2::bigint, to_height - $4 + 1) AS from_height, to_height \
+                                     FROM top), \
+                         v (height, value) AS ({values}), \
+                         s AS (SELECT min(value) AS lowest, max(value) AS highest, \
+                                   min(height) AS first_height, max(height) AS last_height, count(*) AS blocks \
+                               FROM v), \
+                         b AS (SELECT CASE WHEN s.highest = s.lowest THEN 1 \
+                                   ELSE least(width_bucket(v.value, s.lowest, s.highest, $1), $1) END AS bucket, \
+                                   count(*) AS blocks \
+                               FROM v, s GROUP BY bucket) \
+                         SELECT s.lowest + (s.highest - s.lowest) * (i - 1) / $1, \
+                             s.lowest + (s.highest - s.lowest) * i / $1, \
+                             COALESCE(b.blocks, 0), s.first_height, s.last_height, s.blocks \
+                         FROM s CROSS JOIN generate_series(1, $1) i LEFT JOIN b ON b.bucket = i \
+                         WHERE s.blocks > 0 \
+                         ORDER BY i"),
+               &[&buckets, &from_height, &to_height, &default_blocks])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    let Some(first) = rows.first() else {
+        return Ok(Histogram { first_height: None, last_height: None, blocks: 0, buckets: vec![] });
+    };
+
+    Ok(Histogram {
+        first_height: get_height(first, 3)?,
+        last_height: get_height(first, 4)?,
+        blocks: first.get(5),
+        buckets: rows.iter()
+            .map(|r| HistogramBucket { lower: r.get(0), upper: r.get(1), blocks: r.get(2) })
+            .collect(),
+    })
+}
+
+/// Only names of the IANA time zone database are known, not abbreviations or POSIX offsets.
+pub async fn time_zone_exists(conn: &tokio_postgres::Client, name: &str) -> Result<bool, Error> {
+    let row = conn
+        .query_one("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)", &[&name])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(row.get(0))
+}
+
+/// Returns validators updated after the block at `height` was produced, or all of them if the
+/// time of the block is unknown.
+pub async fn validators_updated_after(conn: &tokio_postgres::Client, height: Height)
+                                      -> Result<Vec<SyncedValidator>, Error> {
+    let rows = conn
+        .query("SELECT address, operator_address, moniker, avatar_url, updated_at AT TIME ZONE 'UTC' \
+                FROM validators \
+                WHERE updated_at > COALESCE( \
+                    (SELECT block_time FROM proposer_to_height WHERE height = $1) AT TIME ZONE 'UTC', \
+                    '-infinity') \
+                ORDER BY address",
+               &[&height])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows
+        .iter()
+        .map(|r| SyncedValidator {
+            address: r.get(0),
+            operator_address: r.get(1),
+            moniker: r.get(2),
+            avatar_url: r.get(3),
+            updated_at: r.get(4),
+        })
+        .collect())
+}
+
+pub async fn find_report(conn: &tokio_postgres::Client, name: &str) -> Result<Option<Report>, Error> {
+    let row = conn
+        .query_opt("SELECT sql, parameters FROM reports WHERE name = $1", &[&name])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(row.map(|r| Report { sql: r.get(0), parameters: r.get(1) }))
+}
+
+pub async fn save_report(conn: &tokio_postgres::Client, name: &str, report: &Report) -> Result<(), Error> {
+    conn
+        .execute("INSERT INTO reports(name, sql, parameters) VALUES ($1, $2, $3) \
+                  ON CONFLICT (name) DO UPDATE SET sql = excluded.sql, parameters = excluded.parameters",
+                 &[&name, &report.sql, &report.parameters])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(())
+}
+
+/// Returns false if there is no report with the name.
+pub async fn delete_report(conn: &tokio_postgres::Client, name: &str) -> Result<bool, Error> {
+    let count_rows_deleted = conn
+        .execute("DELETE FROM reports WHERE name = $1", &[&name])
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(count_rows_deleted > 0)
+}
+
+/// Expects the columns height, hash, proposer, block_time and num_txs.
+fn block(row: &Row) -> Result<Block, Error> {
+    Ok(Block {
+        height: get_height(row, 0)?,
+        hash: row.get(1),
+        proposer: row.get(2),
+        block_time: row.get(3),
+        num_txs: row.get(4),
+    })
+}
+
+/// Heights are stored as `bigint`, so reading one can fail where a negative height was stored.
+fn get_height<'a, T: FromSql<'a>>(row: &'a Row, index: usize) -> Result<T, Error> {
+    row.try_get(index).map_err(|_| Error::CouldNotConvertHeight)
+}
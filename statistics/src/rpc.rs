@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use model::{BlockHash, ChainId, ConsensusAddress, Height};
+use osmosis_core::profiles::Profile;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+use crate::{AppState, Error, repository};
+
+/// Errors of CometBFT for heights above the chain tip and below the lowest height the node keeps.
+const MISSING_HEIGHT_ERRORS: [&str; 2] = [
+    "must be less than or equal to the current blockchain height",
+    "is not available, lowest height is",
+];
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Source {
+    Database,
+    Rpc,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProxyBlockResponse {
+    height: Height,
+    hash: Option<BlockHash>,
+    proposer: ConsensusAddress,
+    block_time: Option<DateTime<Utc>>,
+    num_txs: Option<i32>,
+    source: Source,
+}
+
+/// The part of the indexer's configuration file which the proxy shares with the indexer.
+#[derive(Deserialize, Default, Debug)]
+struct IndexerConfig {
+    #[serde(default)]
+    profiles: HashMap<ChainId, Profile>,
+}
+
+/// Parser profiles configured in `[profiles.<chain id>]` of the file named by `INDEXER_CONFIG`,
+/// of which the one of the chain of the RPC endpoint is selected on the first proxied block.
+#[derive(Clone, Default)]
+pub struct Profiles {
+    configured: Arc<HashMap<ChainId, Profile>>,
+    selected: Arc<OnceCell<Profile>>,
+}
+
+impl Profiles {
+    pub fn from_env() -> Result<Self, Error> {
+        let config: IndexerConfig = match env::var("INDEXER_CONFIG") {
+            Ok(path) => {
+                let content = fs::read_to_string(&path).map_err(Error::could_not_read_config)?;
+                toml::from_str(&content).map_err(Error::could_not_read_config)?
+            }
+            Err(_) => IndexerConfig::default(),
+        };
+
+        Ok(Profiles { configured: Arc::new(config.profiles), selected: Arc::default() })
+    }
+
+    async fn select(&self, state: &AppState) -> Result<&Profile, Error> {
+        self.selected
+            .get_or_try_init(|| async {
+                let status = request(state, "status").await?;
+                let chain_id: ChainId = status.pointer("/result/node_info/network")
+                    .and_then(|n| serde_json::from_value(n.clone()).ok())
+                    .ok_or_else(|| Error::could_not_get_response_from_rpc("no chain id in /status"))?;
+                Ok(Profile::select(&self.configured, &chain_id))
+            })
+            .await
+    }
+}
+
+/// Read-through proxy: serves the block from the database if it is indexed, otherwise fetches
+/// it from the RPC endpoint and requests its height from the indexer, which indexes it with its
+/// signatures and plugin records, so API users have a single source for both historical and fresh
+/// blocks.
+pub async fn block_handler(Path(height): Path<Height>, State(state): State<AppState>)
+                           -> Result<Json<ProxyBlockResponse>, Error> {
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    if let Some(block) = repository::block_at_height(&conn, height).await? {
+        return Ok(Json(ProxyBlockResponse {
+            height,
+            hash: block.hash,
+            proposer: block.proposer,
+            block_time: block.block_time,
+            num_txs: block.num_txs,
+            source: Source::Database,
+        }));
+    }
+
+    let profile = state.profiles.select(&state).await?;
+    let response = request(&state, &format!("block?height={height}")).await?;
+    let block = profile.parse_block(&response)?;
+
+    repository::request_heights(&conn, &[block.height]).await?;
+
+    Ok(Json(ProxyBlockResponse {
+        height: block.height,
+        hash: Some(block.hash),
+        proposer: block.proposer,
+        block_time: Some(block.block_time),
+        num_txs: Some(block.num_txs),
+        source: Source::Rpc,
+    }))
+}
+
+/// Only a node reporting the height as missing makes a 404; a node which is rate limiting or
+/// unavailable makes a 503 and any other failure a 502.
+async fn request(state: &AppState, path_and_query: &str) -> Result<Value, Error> {
+    let response = state.http_client
+        .get(format!("{}/{path_and_query}", state.rpc_url))
+        .send()
+        .await
+        .map_err(Error::could_not_get_response_from_rpc)?;
+    let status = response.status();
+    let body = response.text().await.map_err(Error::could_not_get_response_from_rpc)?;
+    let json: Option<Value> = serde_json::from_str(&body).ok();
+
+    if json.as_ref().and_then(|j| j.get("error")).is_some_and(is_missing_height) {
+        return Err(Error::BlockNotFound);
+    }
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+        return Err(Error::rpc_unavailable(status));
+    }
+    json.filter(|j| status.is_success() && j.get("error").is_none())
+        .ok_or_else(|| Error::could_not_get_response_from_rpc(format!("{status}: {body}")))
+}
+
+fn is_missing_height(error: &Value) -> bool {
+    let data = error.get("data").and_then(Value::as_str).unwrap_or_default();
+    MISSING_HEIGHT_ERRORS.iter().any(|e| data.contains(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn heights_above_the_tip_and_below_the_lowest_height_are_missing() {
+        let above = json!({"code": -32603, "message": "Internal error",
+            "data": "height 100 must be less than or equal to the current blockchain height 50"});
+        let below = json!({"code": -32603, "message": "Internal error",
+            "data": "height 1 is not available, lowest height is 2"});
+        let other = json!({"code": -32603, "message": "Internal error", "data": "could not load block"});
+
+        assert!(is_missing_height(&above));
+        assert!(is_missing_height(&below));
+        assert!(!is_missing_height(&other));
+    }
+}
@@ -0,0 +1,223 @@
+use axum::extract::State;
+use axum::Json;
+use model::{ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, Error};
+use crate::params::{Limit, Params, Validate};
+
+const DEFAULT_UPCOMING_BLOCKS: i64 = 100;
+const MAXIMUM_UPCOMING_BLOCKS: i64 = 10000;
+/// Most validators CometBFT returns per page of `/validators`.
+const VALIDATORS_PER_PAGE: u32 = 100;
+/// Priorities are rescaled whenever they spread further than this many times the total power.
+const PRIORITY_WINDOW_SIZE_FACTOR: i128 = 2;
+
+#[derive(Deserialize, Debug)]
+pub struct UpcomingParams {
+    #[serde(default)]
+    blocks: Limit<DEFAULT_UPCOMING_BLOCKS, MAXIMUM_UPCOMING_BLOCKS>,
+    /// Only the heights at which this validator is expected to propose.
+    validator: Option<ConsensusAddress>,
+}
+
+impl Validate for UpcomingParams {}
+
+#[derive(Serialize, Debug)]
+pub struct UpcomingResponse {
+    /// Height of the validator set the schedule is computed from.
+    height: Height,
+    upcoming: Vec<UpcomingProposer>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct UpcomingProposer {
+    height: Height,
+    proposer: ConsensusAddress,
+}
+
+#[derive(Deserialize, Debug)]
+struct ValidatorsResponse {
+    result: ValidatorsResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct ValidatorsResult {
+    block_height: Height,
+    validators: Vec<RpcValidator>,
+    /// Number of validators on all pages.
+    total: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcValidator {
+    address: ConsensusAddress,
+    voting_power: String,
+    proposer_priority: String,
+}
+
+#[derive(Clone, Debug)]
+struct ScheduledValidator {
+    address: ConsensusAddress,
+    voting_power: i64,
+    proposer_priority: i64,
+}
+
+/// Estimates the proposers of the next `blocks` heights by running CometBFT's proposer priority
+/// algorithm on the current validator set of the RPC endpoint. The estimate holds as long as the
+/// set and its voting power do not change; any delegation or jailing shifts it.
+pub async fn upcoming_handler(Params(params): Params<UpcomingParams>, State(state): State<AppState>)
+                              -> Result<Json<UpcomingResponse>, Error> {
+    let blocks = params.blocks.value() as u64;
+
+    let (height, validators) = request_validator_set(&state).await?;
+
+    let upcoming = upcoming_proposers(validators, blocks as usize)
+        .into_iter()
+        .zip(Height::range(height.saturating_add(1), height.saturating_add(blocks + 1)))
+        .map(|(proposer, height)| UpcomingProposer { height, proposer })
+        .filter(|p| params.validator.as_ref().is_none_or(|v| *v == p.proposer))
+        .collect();
+
+    Ok(Json(UpcomingResponse { height, upcoming }))
+}
+
+async fn request_validator_set(state: &AppState) -> Result<(Height, Vec<ScheduledValidator>), Error> {
+    let mut validators = Vec::new();
+    let mut page = 1;
+
+    loop {
+        let response: ValidatorsResponse = state.http_client
+            .get(format!("{}/validators?page={page}&per_page={VALIDATORS_PER_PAGE}", state.rpc_url))
+            .send()
+            .await
+            .map_err(|_| Error::CouldNotGetResponseFromRpc)?
+            .json()
+            .await
+            .map_err(|_| Error::CouldNotGetResponseFromRpc)?;
+
+        let total: usize = response.result.total.parse().map_err(|_| Error::CouldNotGetResponseFromRpc)?;
+        let count_on_page = response.result.validators.len();
+
+        for validator in response.result.validators {
+            validators.push(ScheduledValidator {
+                address: validator.address,
+                voting_power: validator.voting_power.parse().map_err(|_| Error::CouldNotGetResponseFromRpc)?,
+                proposer_priority: validator.proposer_priority
+                    .parse()
+                    .map_err(|_| Error::CouldNotGetResponseFromRpc)?,
+            });
+        }
+
+        if validators.len() >= total || count_on_page == 0 {
+            return Ok((response.result.block_height, validators));
+        }
+        page += 1;
+    }
+}
+
+/// Proposers of the heights after the one whose validator set is given, in order.
+fn upcoming_proposers(mut validators: Vec<ScheduledValidator>, count: usize) -> Vec<ConsensusAddress> {
+    let total_voting_power: i64 = validators.iter().map(|v| v.voting_power).sum();
+
+    (0..count)
+        .map_while(|_| increment_proposer_priority(&mut validators, total_voting_power))
+        .collect()
+}
+
+/// One step of CometBFT's `IncrementProposerPriority`: priorities are rescaled and centered
+/// around zero, every validator gains its voting power, and the one with the highest priority,
+/// or the lowest address among equals, proposes and pays the total voting power.
+fn increment_proposer_priority(validators: &mut [ScheduledValidator], total_voting_power: i64)
+                               -> Option<ConsensusAddress> {
+    rescale_priorities(validators, PRIORITY_WINDOW_SIZE_FACTOR * total_voting_power as i128);
+    shift_by_average_priority(validators);
+
+    for validator in validators.iter_mut() {
+        validator.proposer_priority = validator.proposer_priority.saturating_add(validator.voting_power);
+    }
+
+    let proposer = validators
+        .iter_mut()
+        .max_by(|a, b| a.proposer_priority.cmp(&b.proposer_priority).then_with(|| b.address.cmp(&a.address)))?;
+    proposer.proposer_priority = proposer.proposer_priority.saturating_sub(total_voting_power);
+
+    Some(proposer.address.clone())
+}
+
+fn rescale_priorities(validators: &mut [ScheduledValidator], maximum_difference: i128) {
+    let priorities = validators.iter().map(|v| v.proposer_priority as i128);
+    let (Some(highest), Some(lowest)) = (priorities.clone().max(), priorities.min()) else {
+        return;
+    };
+
+    let difference = highest - lowest;
+    if maximum_difference <= 0 || difference <= maximum_difference {
+        return;
+    }
+
+    let ratio = (difference + maximum_difference - 1) / maximum_difference;
+    for validator in validators.iter_mut() {
+        validator.proposer_priority = (validator.proposer_priority as i128 / ratio) as i64;
+    }
+}
+
+/// The average is rounded like `big.Int.Div` in Go, towards negative infinity for negative sums.
+fn shift_by_average_priority(validators: &mut [ScheduledValidator]) {
+    if validators.is_empty() {
+        return;
+    }
+
+    let sum: i128 = validators.iter().map(|v| v.proposer_priority as i128).sum();
+    let average = sum.div_euclid(validators.len() as i128);
+
+    for validator in validators.iter_mut() {
+        validator.proposer_priority = (validator.proposer_priority as i128 - average) as i64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn validator_set(voting_powers: &[i64]) -> Vec<ScheduledValidator> {
+        voting_powers
+            .iter()
+            .enumerate()
+            .map(|(i, &voting_power)| ScheduledValidator {
+                address: format!("{i:040X}").parse().unwrap(),
+                voting_power,
+                proposer_priority: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn equal_validators_take_turns_in_address_order() {
+        let validators = validator_set(&[10, 10, 10]);
+        let addresses: Vec<ConsensusAddress> = validators.iter().map(|v| v.address.clone()).collect();
+
+        let proposers = upcoming_proposers(validators, 6);
+
+        assert_eq!(proposers, [&addresses[..], &addresses[..]].concat());
+    }
+
+    proptest! {
+        #[test]
+        fn validators_propose_in_proportion_to_voting_power(
+            voting_powers in prop::collection::vec(1..20i64, 1..10),
+        ) {
+            let validators = validator_set(&voting_powers);
+            let total_voting_power: i64 = voting_powers.iter().sum();
+
+            let proposers = upcoming_proposers(validators.clone(), total_voting_power as usize);
+
+            for (validator, &voting_power) in validators.iter().zip(&voting_powers) {
+                let proposed = proposers.iter().filter(|p| **p == validator.address).count() as i64;
+                prop_assert_eq!(proposed, voting_power);
+            }
+        }
+    }
+}
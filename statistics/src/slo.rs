@@ -0,0 +1,155 @@
+use std::env;
+use std::fmt::Write;
+
+use axum::extract::State;
+use axum::http::{header, HeaderValue};
+use axum::response::IntoResponse;
+use serde::Serialize;
+
+use crate::{AppState, Error, repository};
+
+const DEFAULT_LAG_OBJECTIVE_IN_SECONDS: f64 = 30.0;
+const DEFAULT_TARGET: f64 = 0.99;
+/// Rolling windows compliance is tracked over; a short and a long one burning fast together
+/// make a sustained lag rather than a spike.
+const WINDOWS: [(&str, i64); 4] = [("5m", 5 * 60), ("1h", 60 * 60), ("6h", 6 * 60 * 60), ("1d", 24 * 60 * 60)];
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Blocks are to be indexed within `lag_in_seconds` of their block time, for the `target`
+/// fraction of them.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct Objective {
+    pub lag_in_seconds: f64,
+    pub target: f64,
+}
+
+impl Objective {
+    /// Reads `SLO_LAG_IN_SECONDS` and `SLO_TARGET`, keeping the defaults for missing or invalid values.
+    pub fn from_env() -> Self {
+        let lag_in_seconds = env::var("SLO_LAG_IN_SECONDS")
+            .ok()
+            .and_then(|l| l.parse().ok())
+            .filter(|l: &f64| *l > 0.0)
+            .unwrap_or(DEFAULT_LAG_OBJECTIVE_IN_SECONDS);
+        let target = env::var("SLO_TARGET")
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .filter(|t: &f64| *t > 0.0 && *t < 1.0)
+            .unwrap_or(DEFAULT_TARGET);
+
+        Objective { lag_in_seconds, target }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SloReport {
+    objective: Objective,
+    windows: Vec<WindowCompliance>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WindowCompliance {
+    window: &'static str,
+    /// Blocks of the window whose indexing time is known.
+    blocks: i64,
+    /// Fraction of those indexed in time; null without blocks, e.g. while indexing is stalled.
+    compliance: Option<f64>,
+    /// How fast the error budget is spent: 1 spends exactly the budget over the window,
+    /// higher values spend it sooner.
+    burn_rate: Option<f64>,
+}
+
+/// Compliance over every window, for blocks whose time falls into it.
+pub async fn report(conn: &tokio_postgres::Client, objective: Objective) -> Result<SloReport, Error> {
+    let windows_in_seconds: Vec<i64> = WINDOWS.iter().map(|(_, w)| *w).collect();
+    let counts = repository::lag_compliance(conn, objective.lag_in_seconds, &windows_in_seconds).await?;
+
+    let windows = WINDOWS
+        .iter()
+        .zip(counts)
+        .map(|((window, _), (blocks, in_time))| {
+            let compliance = (blocks > 0).then(|| in_time as f64 / blocks as f64);
+            WindowCompliance {
+                window,
+                blocks,
+                compliance,
+                burn_rate: compliance.map(|c| burn_rate(c, objective.target)),
+            }
+        })
+        .collect();
+
+    Ok(SloReport { objective, windows })
+}
+
+/// Serves the lag and the SLO in the Prometheus text format, for alerting on burn rates.
+pub async fn metrics_handler(State(state): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let lag_in_seconds = repository::indexing_lag_in_seconds(&conn).await?;
+    let report = report(&conn, state.slo).await?;
+
+    let content_type = HeaderValue::from_static(METRICS_CONTENT_TYPE);
+    Ok(([(header::CONTENT_TYPE, content_type)], to_metrics(lag_in_seconds, &report)))
+}
+
+fn burn_rate(compliance: f64, target: f64) -> f64 {
+    (1.0 - compliance) / (1.0 - target)
+}
+
+fn to_metrics(lag_in_seconds: Option<f64>, report: &SloReport) -> String {
+    let mut metrics = String::new();
+
+    let _ = writeln!(metrics, "# HELP indexer_lag_seconds Time since the highest indexed block was produced.");
+    let _ = writeln!(metrics, "# TYPE indexer_lag_seconds gauge");
+    if let Some(lag_in_seconds) = lag_in_seconds {
+        let _ = writeln!(metrics, "indexer_lag_seconds {lag_in_seconds}");
+    }
+
+    let _ = writeln!(metrics, "# HELP indexer_lag_slo_objective_seconds Lag to index blocks within.");
+    let _ = writeln!(metrics, "# TYPE indexer_lag_slo_objective_seconds gauge");
+    let _ = writeln!(metrics, "indexer_lag_slo_objective_seconds {}", report.objective.lag_in_seconds);
+
+    write_per_window(&mut metrics,
+                     "indexer_lag_slo_compliance",
+                     "Fraction of blocks indexed within the objective.",
+                     report,
+                     |w| w.compliance);
+    write_per_window(&mut metrics,
+                     "indexer_lag_slo_burn_rate",
+                     "Rate at which the error budget is spent.",
+                     report,
+                     |w| w.burn_rate);
+
+    metrics
+}
+
+fn write_per_window(metrics: &mut String,
+                    name: &str,
+                    help: &str,
+                    report: &SloReport,
+                    value: impl Fn(&WindowCompliance) -> Option<f64>) {
+    let _ = writeln!(metrics, "# HELP {name} {help}");
+    let _ = writeln!(metrics, "# TYPE {name} gauge");
+
+    for window in &report.windows {
+        if let Some(value) = value(window) {
+            let _ = writeln!(metrics, "{name}{{window=\"{}\"}} {value}", window.window);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn meeting_the_target_exactly_burns_at_rate_one(target in 0.5..0.9999f64) {
+            prop_assert!((burn_rate(target, target) - 1.0).abs() < 1e-6);
+            prop_assert_eq!(burn_rate(1.0, target), 0.0);
+        }
+    }
+}
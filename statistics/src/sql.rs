@@ -0,0 +1,106 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::Json;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use tokio_postgres::types::{ToSql, Type};
+
+use crate::{AppState, Error};
+
+const ADMIN_KEY_HEADER: &str = "x-admin-key";
+const SQL_STATEMENT_TIMEOUT_IN_MILLISECONDS: u32 = 5000;
+const SQL_MAXIMUM_ROWS: i64 = 10000;
+
+#[derive(Deserialize, Debug)]
+pub struct SqlRequest {
+    sql: String,
+    limit: Option<i64>,
+}
+
+/// Escape hatch for ad-hoc analytics which bespoke endpoints do not cover yet.
+/// The query runs as `SQL_DATABASE_USER`, `statistics_sql` by default, in a read-only transaction
+/// which is always rolled back, with a statement timeout and at most SQL_MAXIMUM_ROWS rows.
+/// Rows are returned as a JSON array of objects keyed by column name.
+pub async fn handler(headers: HeaderMap,
+                     State(state): State<AppState>,
+                     Json(request): Json<SqlRequest>)
+                     -> Result<impl IntoResponse, Error> {
+    authorize(&headers, &state)?;
+
+    let limit = request.limit.unwrap_or(SQL_MAXIMUM_ROWS);
+    let rows = query_read_only(&state, &request.sql, &[], limit).await?;
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], rows))
+}
+
+/// Runs the query in the sandbox described above and returns the rows as a JSON array.
+/// Parameters are bound as text, so the query has to cast them, e.g. `$1::bigint`. A superuser
+/// could read and change anything, so queries are refused if the user is or can become one.
+pub async fn query_read_only(state: &AppState, sql: &str, parameters: &[String], limit: i64)
+                             -> Result<String, Error> {
+    let sql = sql.trim().trim_end_matches(';');
+    let limit = limit.clamp(0, SQL_MAXIMUM_ROWS);
+
+    let mut conn = state.sql_pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let transaction = conn
+        .build_transaction()
+        .read_only(true)
+        .start()
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    transaction
+        .batch_execute(&format!("SET LOCAL statement_timeout = {SQL_STATEMENT_TIMEOUT_IN_MILLISECONDS}"))
+        .await
+        .map_err(Error::could_not_query_database)?;
+
+    let is_superuser: bool = transaction
+        .query_one("SELECT exists(SELECT FROM pg_roles \
+                    WHERE rolsuper AND pg_has_role(current_user, oid, 'MEMBER'))", &[])
+        .await
+        .map_err(Error::could_not_query_database)?
+        .get(0);
+    if is_superuser {
+        return Err(Error::could_not_query_database("SQL_DATABASE_USER is a superuser"));
+    }
+
+    let statement = transaction
+        .prepare_typed(&format!("SELECT coalesce(json_agg(q), '[]')::text \
+                                 FROM (SELECT * FROM ({sql}) AS q LIMIT {limit}) AS q"),
+                       &vec![Type::TEXT; parameters.len()])
+        .await
+        .map_err(to_execution_error)?;
+
+    let parameters: Vec<&(dyn ToSql + Sync)> = parameters
+        .iter()
+        .map(|p| p as &(dyn ToSql + Sync))
+        .collect();
+
+    let rows: String = transaction
+        .query_one(&statement, &parameters)
+        .await
+        .map_err(to_execution_error)?
+        .get(0);
+
+    transaction.rollback().await
+        .map_err(Error::could_not_query_database)?;
+
+    Ok(rows)
+}
+
+fn to_execution_error(e: tokio_postgres::Error) -> Error {
+    Error::CouldNotExecuteQuery(e.as_db_error().map_or_else(
+        || e.to_string(),
+        |db| db.message().to_string()))
+}
+
+pub fn authorize(headers: &HeaderMap, state: &AppState) -> Result<(), Error> {
+    let expected = state.admin_key.as_deref().ok_or(Error::Unauthorized)?;
+
+    match headers.get(ADMIN_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        Some(key) if key == expected => Ok(()),
+        _ => Err(Error::Unauthorized),
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::{Arc, RwLock};
+
+use axum::extract::State;
+use axum::Json;
+use model::Height;
+use serde::Serialize;
+
+use crate::{AppState, Error, repository};
+use crate::slo::{self, SloReport};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Status {
+    lowest_indexed_height: Option<Height>,
+    indexed_height: Option<Height>,
+    /// Seconds since the highest indexed block was produced.
+    lag_in_seconds: Option<f64>,
+    slo: SloReport,
+    database_available: bool,
+}
+
+/// The last status read from the database, served while the database is unavailable.
+pub type StatusCache = Arc<RwLock<Option<Status>>>;
+
+/// Reports the indexed range, the indexing lag and its compliance with the SLO. While the
+/// database is unavailable the last known status is served with `database_available` set to false.
+pub async fn handler(State(state): State<AppState>) -> Result<Json<Status>, Error> {
+    match request_status(&state).await {
+        Ok(status) => {
+            if let Ok(mut cache) = state.status_cache.write() {
+                *cache = Some(status.clone());
+            }
+            Ok(Json(status))
+        }
+        Err(e) => {
+            let cached = state.status_cache
+                .read()
+                .ok()
+                .and_then(|c| c.clone())
+                .ok_or(e)?;
+
+            Ok(Json(Status { database_available: false, ..cached }))
+        }
+    }
+}
+
+async fn request_status(state: &AppState) -> Result<Status, Error> {
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let (lowest_indexed_height, indexed_height) = repository::indexed_height_range(&conn).await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let lag_in_seconds = repository::indexing_lag_in_seconds(&conn).await
+        .map_err(Error::could_not_get_database_connection)?;
+    let slo = slo::report(&conn, state.slo).await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    Ok(Status {
+        lowest_indexed_height,
+        indexed_height,
+        lag_in_seconds,
+        slo,
+        database_available: true,
+    })
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use bb8::PooledConnection;
+use bb8_postgres::PostgresConnectionManager;
+use futures::future;
+use model::{ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::{ConnectionPool, DATABASE_CONNECTION_TIMEOUT_IN_SECONDS, Error, repository};
+use crate::completeness::{Completeness, request_completeness};
+use crate::params::{Params, Validate};
+use crate::repository::{Absence, ProposalStreak, ProposedBlocksFilter};
+
+#[derive(Serialize, Debug)]
+pub struct SummaryResponse {
+    validator: ConsensusAddress,
+    moniker: Option<String>,
+    /// Highest indexed height when the request arrived, which every other figure is as of.
+    as_of_height: Option<Height>,
+    proposals: i64,
+    longest_absence: Option<Absence>,
+    completeness: Completeness,
+}
+
+/// Everything known about the validator up to the highest indexed height. The queries are
+/// independent, so each runs on its own connection at the same time, all bounded by the height
+/// read first, so blocks indexed meanwhile do not make them disagree.
+pub async fn handler(Path(validator): Path<ConsensusAddress>,
+                     State(pool): State<ConnectionPool>,
+                     State(permits): State<ConnectionPermits>)
+                     -> Result<Json<SummaryResponse>, Error> {
+    let connections = permits.check_out(&pool, 4).await?;
+    let [validator_conn, proposals_conn, absences_conn, completeness_conn] = &connections.connections[..] else {
+        unreachable!("checked out four connections");
+    };
+    let (_, as_of_height) = repository::indexed_height_range(validator_conn).await?;
+
+    let filter = ProposedBlocksFilter {
+        validator: &validator,
+        from_height: None,
+        to_height: as_of_height,
+        from_time: None,
+        to_time: None,
+    };
+
+    let (found, proposals, absences, completeness) = tokio::try_join!(
+        repository::find_validator(validator_conn, &validator),
+        repository::count_blocks_proposed_by(proposals_conn, &filter),
+        repository::longest_absences(absences_conn, &validator, None, as_of_height, 1),
+        request_completeness(completeness_conn, None, as_of_height),
+    )?;
+
+    Ok(Json(SummaryResponse {
+        moniker: found.map(|v| v.moniker),
+        validator,
+        as_of_height,
+        proposals,
+        longest_absence: absences.into_iter().next(),
+        completeness,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CountsParams {
+    validator: ConsensusAddress,
+}
+
+impl Validate for CountsParams {}
+
+#[derive(Serialize, Debug)]
+pub struct CountsResponse {
+    validator: ConsensusAddress,
+    proposals: i64,
+    first_proposed_height: Option<Height>,
+    last_proposed_height: Option<Height>,
+    longest_streak: Option<ProposalStreak>,
+    /// 1 for the validators which proposed the most blocks; null if it proposed none.
+    rank: Option<i64>,
+    /// Validators which proposed at least one block, which the rank is out of.
+    validators: i64,
+}
+
+/// Counts of the blocks the validator proposed in one cheap call, so clients need not download
+/// every height. Totals and the rank are read from the daily counts and so leave out blocks
+/// without a time, while the longest run of consecutive heights it proposed counts every block.
+pub async fn counts_handler(Params(params): Params<CountsParams>,
+                            State(pool): State<ConnectionPool>,
+                            State(permits): State<ConnectionPermits>)
+                            -> Result<Json<CountsResponse>, Error> {
+    let connections = permits.check_out(&pool, 2).await?;
+    let [totals_conn, streak_conn] = &connections.connections[..] else {
+        unreachable!("checked out two connections");
+    };
+    let (totals, longest_streak) = tokio::try_join!(
+        repository::proposal_totals(totals_conn, &params.validator),
+        repository::longest_proposal_streak(streak_conn, &params.validator),
+    )?;
+
+    Ok(Json(CountsResponse {
+        validator: params.validator,
+        proposals: totals.proposals,
+        first_proposed_height: totals.first_proposed_height,
+        last_proposed_height: totals.last_proposed_height,
+        longest_streak,
+        rank: totals.rank,
+        validators: totals.validators,
+    }))
+}
+
+/// Limits the connections held together by handlers which run their queries on several
+/// connections to the size of the pool. A handler holding some of its connections while waiting
+/// for the rest could otherwise wait for others doing the same until they all time out.
+#[derive(Clone)]
+pub struct ConnectionPermits(Arc<Semaphore>);
+
+/// Connections checked out together, which are returned before the permits.
+struct Connections<'a> {
+    connections: Vec<PooledConnection<'a, PostgresConnectionManager<MakeRustlsConnect>>>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl ConnectionPermits {
+    pub fn new(pool_size: u32) -> Self {
+        ConnectionPermits(Arc::new(Semaphore::new(pool_size as usize)))
+    }
+
+    /// Takes permits for all `count` connections before checking out any of them, so a handler
+    /// which gets its permits gets its connections too.
+    async fn check_out<'a>(&'a self, pool: &'a ConnectionPool, count: u32) -> Result<Connections<'a>, Error> {
+        let permit = time::timeout(Duration::from_secs(DATABASE_CONNECTION_TIMEOUT_IN_SECONDS),
+                                   self.0.acquire_many(count))
+            .await
+            .map_err(Error::could_not_get_database_connection)?
+            .map_err(Error::could_not_get_database_connection)?;
+        let connections = future::try_join_all((0..count).map(|_| pool.get()))
+            .await
+            .map_err(Error::could_not_get_database_connection)?;
+
+        Ok(Connections { connections, _permit: permit })
+    }
+}
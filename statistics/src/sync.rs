@@ -0,0 +1,85 @@
+use axum::extract::State;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use model::{BlockHash, ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Params, Validate};
+use crate::repository::SyncedValidator;
+
+/// Bumped whenever the envelope or a row changes incompatibly.
+const SYNC_FORMAT_VERSION: u32 = 1;
+const SYNC_BLOCKS_PER_PAGE: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct ChangesParams {
+    since_height: Height,
+}
+
+impl Validate for ChangesParams {}
+
+#[derive(Serialize, Debug)]
+pub struct ChangesResponse {
+    version: u32,
+    since_height: Height,
+    /// Pass as `since_height` to continue.
+    next_since_height: Height,
+    /// Further changes are available right away above `next_since_height`.
+    has_more: bool,
+    changes: Vec<Change>,
+}
+
+/// Every change is an upsert of the row under the primary key of its table.
+#[derive(Serialize, Debug)]
+#[serde(tag = "table", content = "row", rename_all = "snake_case")]
+enum Change {
+    ProposerToHeight(SyncedBlock),
+    Validators(SyncedValidator),
+}
+
+#[derive(Serialize, Debug)]
+struct SyncedBlock {
+    height: Height,
+    hash: Option<BlockHash>,
+    proposer: ConsensusAddress,
+    block_time: Option<DateTime<Utc>>,
+    num_txs: Option<i32>,
+}
+
+/// Changes for mirrors of the indexed dataset which cannot use logical replication: blocks above
+/// `since_height` in ascending height, up to SYNC_BLOCKS_PER_PAGE per response, followed by
+/// validators updated after the block at `since_height` was produced (all of them if its time is
+/// unknown). Changes are delivered at least once, so a mirror applies them idempotently.
+/// Blocks which are indexed late below a mirror's cursor, e.g. by gap repair, are not resent.
+pub async fn changes_handler(Params(params): Params<ChangesParams>, State(pool): State<ConnectionPool>)
+                             -> Result<Json<ChangesResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let blocks = repository::blocks_after(&conn, params.since_height, &[], None, SYNC_BLOCKS_PER_PAGE).await?;
+    let has_more = blocks.len() as i64 == SYNC_BLOCKS_PER_PAGE;
+    let next_since_height = blocks.last().map_or(params.since_height, |b| b.height);
+
+    let validators = repository::validators_updated_after(&conn, params.since_height).await?;
+
+    let changes = blocks
+        .into_iter()
+        .map(|b| Change::ProposerToHeight(SyncedBlock {
+            height: b.height,
+            hash: b.hash,
+            proposer: b.proposer,
+            block_time: b.block_time,
+            num_txs: b.num_txs,
+        }))
+        .chain(validators.into_iter().map(Change::Validators))
+        .collect();
+
+    Ok(Json(ChangesResponse {
+        version: SYNC_FORMAT_VERSION,
+        since_height: params.since_height,
+        next_since_height,
+        has_more,
+        changes,
+    }))
+}
@@ -0,0 +1,60 @@
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use model::ConsensusAddress;
+use serde::Deserialize;
+
+use crate::{AppState, Error, repository, sql};
+
+/// Longest tag, as the column holds it.
+const MAXIMUM_TAG_LENGTH: usize = 64;
+
+/// Aggregations which endpoints can group validators by.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// Each tag of a validator, such as `exchange` or `community`, and untagged validators as null.
+    Tag,
+}
+
+/// Replaces the tags of a validator with the given ones; an empty list removes them all.
+pub async fn save_handler(headers: HeaderMap,
+                          Path(validator): Path<ConsensusAddress>,
+                          State(state): State<AppState>,
+                          Json(tags): Json<Vec<String>>)
+                          -> Result<StatusCode, Error> {
+    sql::authorize(&headers, &state)?;
+
+    if !tags.iter().all(|t| is_valid(t)) {
+        return Err(Error::InvalidTag);
+    }
+
+    let conn = state.pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    repository::replace_validator_tags(&conn, &validator, &tags).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lowercase letters, digits and dashes, such as `team-run`.
+pub fn is_valid(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= MAXIMUM_TAG_LENGTH
+        && tag.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_lowercase_words_with_dashes() {
+        assert!(is_valid("exchange"));
+        assert!(is_valid("team-run"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("Exchange"));
+        assert!(!is_valid("team run"));
+        assert!(!is_valid(&"a".repeat(MAXIMUM_TAG_LENGTH + 1)));
+    }
+}
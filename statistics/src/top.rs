@@ -0,0 +1,78 @@
+use axum::extract::State;
+use axum::Json;
+use model::Height;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::completeness::{Completeness, request_completeness};
+use crate::params::{check_range, Limit, Params, pinned_upper_bound, Validate};
+use crate::repository::{ProposerCount, TagCount};
+use crate::tags::GroupBy;
+
+const DEFAULT_LEADERBOARD_LIMIT: i64 = 10;
+const MAXIMUM_LEADERBOARD_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct TopParams {
+    #[serde(default)]
+    limit: Limit<DEFAULT_LEADERBOARD_LIMIT, MAXIMUM_LEADERBOARD_LIMIT>,
+    from: Option<Height>,
+    to: Option<Height>,
+    /// Number of higher ranked proposers to skip.
+    #[serde(default)]
+    offset: i64,
+    as_of_height: Option<Height>,
+    group_by: Option<GroupBy>,
+}
+
+impl Validate for TopParams {
+    fn validate(&self) -> Result<(), String> {
+        if self.offset < 0 {
+            return Err("offset: must not be negative".to_string());
+        }
+        check_range("from", self.from.as_ref(), "to", self.to.as_ref())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopResponse {
+    proposers: Vec<ProposerCount>,
+    /// Pass as `offset` together with `as_of_height` to get the next page; null on the last page.
+    next_offset: Option<i64>,
+    as_of_height: Option<Height>,
+    /// Blocks in the whole range by tag of their proposer, with `group_by=tag`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<Vec<TagCount>>,
+    completeness: Completeness,
+}
+
+/// Leaderboard of the `limit` validators which proposed the most blocks within the inclusive
+/// height range `[from, to]`, most blocks first. Ties are ordered by consensus address.
+/// The first page pins the highest indexed height as `as_of_height`, and following pages passing
+/// it back count the same blocks, so ranks do not shift between pages while new blocks arrive.
+/// Apart from the blocks of the days at the ends of the range, everything is read from the tables
+/// the indexer maintains, `proposer_daily_counts` and `indexed_height_ranges`.
+pub async fn handler(Params(params): Params<TopParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<TopResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let as_of_height = match params.as_of_height {
+        Some(h) => Some(h),
+        None => repository::indexed_height_range(&conn).await?.1,
+    };
+    let to = pinned_upper_bound(params.to, as_of_height);
+
+    let limit = params.limit.value();
+    let proposers = repository::top_proposers(&conn, params.from, to, params.offset, limit).await?;
+    let next_offset = (proposers.len() as i64 == limit).then_some(params.offset + limit);
+
+    let tags = match params.group_by {
+        Some(GroupBy::Tag) => Some(repository::proposals_by_tag(&conn, params.from, to).await?),
+        None => None,
+    };
+
+    let completeness = request_completeness(&conn, params.from, to).await?;
+
+    Ok(Json(TopResponse { proposers, next_offset, as_of_height, tags, completeness }))
+}
@@ -0,0 +1,81 @@
+use axum::extract::State;
+use axum::Json;
+use model::{ConsensusAddress, Height};
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Params, Validate};
+
+const DEFAULT_UPTIME_WINDOW: i64 = 10_000;
+const MAXIMUM_UPTIME_WINDOW: i64 = 1_000_000;
+
+#[derive(Deserialize, Debug)]
+pub struct UptimeParams {
+    validator: ConsensusAddress,
+    /// Number of latest blocks to count.
+    window: Option<i64>,
+}
+
+impl Validate for UptimeParams {
+    fn validate(&self) -> Result<(), String> {
+        match self.window {
+            Some(window) if !(1..=MAXIMUM_UPTIME_WINDOW).contains(&window) =>
+                Err(format!("window: must be between 1 and {MAXIMUM_UPTIME_WINDOW}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct UptimeResponse {
+    validator: ConsensusAddress,
+    window: i64,
+    /// Lowest and highest height of the window with stored signatures; null if there are none.
+    from_height: Option<Height>,
+    to_height: Option<Height>,
+    /// Blocks of the window whose signatures are stored, which is fewer than `window` while
+    /// they are being backfilled.
+    blocks_with_signatures: i64,
+    signed: i64,
+    missed: i64,
+    /// Signed blocks per block the validator was in the set for, in percent; null if it never was.
+    uptime_percentage: Option<f64>,
+}
+
+/// Counts the blocks among the latest `window` which the validator signed and missed, from the
+/// commits stored in `block_signatures`. Blocks while it was not in the active set count as neither.
+pub async fn handler(Params(params): Params<UptimeParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<UptimeResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let window = params.window.unwrap_or(DEFAULT_UPTIME_WINDOW);
+    let signatures = repository::signatures_in_window(&conn, &params.validator, window).await?;
+
+    Ok(Json(UptimeResponse {
+        validator: params.validator,
+        window,
+        from_height: signatures.first_height,
+        to_height: signatures.last_height,
+        blocks_with_signatures: signatures.blocks,
+        signed: signatures.signed,
+        missed: signatures.missed,
+        uptime_percentage: uptime_percentage(signatures.signed, signatures.missed),
+    }))
+}
+
+fn uptime_percentage(signed: i64, missed: i64) -> Option<f64> {
+    (signed + missed > 0).then(|| signed as f64 * 100.0 / (signed + missed) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uptime_counts_only_blocks_in_the_set() {
+        assert_eq!(uptime_percentage(9_950, 50), Some(99.5));
+        assert_eq!(uptime_percentage(0, 10), Some(0.0));
+        assert_eq!(uptime_percentage(0, 0), None);
+    }
+}
@@ -0,0 +1,76 @@
+use axum::extract::State;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConnectionPool, Error, repository};
+use crate::params::{Limit, Params, Validate};
+use crate::repository::{KnownProposer, SortOrder, ValidatorSort};
+
+const DEFAULT_VALIDATORS_LIMIT: i64 = 100;
+const MAXIMUM_VALIDATORS_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct ValidatorsParams {
+    #[serde(default)]
+    sort: ValidatorSort,
+    /// `asc` or `desc`; most blocks first, or alphabetical order, if not given.
+    order: Option<SortOrder>,
+    #[serde(default)]
+    limit: Limit<DEFAULT_VALIDATORS_LIMIT, MAXIMUM_VALIDATORS_LIMIT>,
+    #[serde(default)]
+    offset: i64,
+}
+
+impl Validate for ValidatorsParams {
+    fn validate(&self) -> Result<(), String> {
+        if self.offset < 0 {
+            return Err("offset: must not be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ValidatorsResponse {
+    validators: Vec<KnownProposer>,
+    /// Pass as `offset` to get the next page; null on the last page.
+    next_offset: Option<i64>,
+}
+
+/// Every validator which proposed an indexed block, with its moniker and the blocks it proposed,
+/// so UIs can fill a validator picker. Sorted by `blocks`, `moniker` or `address`.
+pub async fn handler(Params(params): Params<ValidatorsParams>, State(pool): State<ConnectionPool>)
+                     -> Result<Json<ValidatorsResponse>, Error> {
+    let conn = pool.get().await
+        .map_err(Error::could_not_get_database_connection)?;
+
+    let limit = params.limit.value();
+    let validators = repository::known_proposers(&conn,
+                                                 params.sort,
+                                                 params.order.unwrap_or(params.sort.default_order()),
+                                                 params.offset,
+                                                 limit).await?;
+    let next_offset = (validators.len() as i64 == limit).then_some(params.offset + limit);
+
+    Ok(Json(ValidatorsResponse { validators, next_offset }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::params::parse;
+
+    use super::*;
+
+    #[test]
+    fn validators_are_sorted_by_blocks_unless_asked_otherwise() {
+        let params = parse::<ValidatorsParams>("").unwrap();
+        assert_eq!((params.sort, params.order, params.limit.value()), (ValidatorSort::Blocks, None, 100));
+
+        let params = parse::<ValidatorsParams>("sort=moniker&order=desc&offset=100").unwrap();
+        assert_eq!((params.sort, params.order, params.offset),
+                   (ValidatorSort::Moniker, Some(SortOrder::Desc), 100));
+
+        assert!(parse::<ValidatorsParams>("sort=commission").is_err());
+        assert!(parse::<ValidatorsParams>("offset=-1").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::env;
+
+use axum::extract::State;
+use axum::http::{header, HeaderName, HeaderValue, Request};
+use axum::middleware::{self, Next};
+use axum::Router;
+use chrono::{DateTime, TimeZone, Utc};
+use tracing::warn;
+
+use crate::AppState;
+
+/// Prefix of the current version of every endpoint.
+pub const CURRENT_VERSION_PREFIX: &str = "/v1";
+/// When the routes without a version prefix were deprecated in favour of `/v1`, 2026-10-16.
+const UNVERSIONED_ROUTES_DEPRECATED_AT_IN_SECONDS: i64 = 1792108800;
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// Routes which still work but have a successor, announced to clients in every response with
+/// the `Deprecation` header (RFC 9745), the `Sunset` header (RFC 8594) once a date to remove
+/// them is set, and a `Link` to the same request in the successor version.
+#[derive(Clone, Debug)]
+pub struct Deprecation {
+    deprecated_at: DateTime<Utc>,
+    sunset: Option<DateTime<Utc>>,
+    successor_prefix: &'static str,
+}
+
+impl Deprecation {
+    /// The routes without a version prefix, which are removed at the RFC 3339 time in
+    /// UNVERSIONED_ROUTES_SUNSET if it is set.
+    pub fn unversioned_from_env() -> Self {
+        let sunset = env::var("UNVERSIONED_ROUTES_SUNSET")
+            .ok()
+            .and_then(|s| {
+                DateTime::parse_from_rfc3339(&s)
+                    .map_err(|_| warn!(sunset = s, "Ignoring invalid sunset of unversioned routes"))
+                    .ok()
+            })
+            .map(|s| s.with_timezone(&Utc));
+
+        Deprecation {
+            deprecated_at: Utc.timestamp_opt(UNVERSIONED_ROUTES_DEPRECATED_AT_IN_SECONDS, 0).unwrap(),
+            sunset,
+            successor_prefix: CURRENT_VERSION_PREFIX,
+        }
+    }
+
+    fn headers(&self, path_and_query: &str) -> Vec<(HeaderName, String)> {
+        let mut headers = vec![
+            (DEPRECATION, format!("@{}", self.deprecated_at.timestamp())),
+            (header::LINK, format!("<{}{path_and_query}>; rel=\"successor-version\"", self.successor_prefix)),
+        ];
+        if let Some(sunset) = self.sunset {
+            headers.push((SUNSET, sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string()));
+        }
+        headers
+    }
+}
+
+/// Serves the routes under the current version prefix, whose responses keep their shape for as
+/// long as the version exists, and also without a prefix, as deprecated aliases for clients
+/// written before versions were introduced. Changing the shape of a response takes a new
+/// version, after which the previous one is deprecated the same way.
+pub fn versioned(routes: Router<AppState>) -> Router<AppState> {
+    let deprecation = Deprecation::unversioned_from_env();
+
+    Router::new()
+        .nest(CURRENT_VERSION_PREFIX, routes.clone())
+        .merge(routes.layer(middleware::from_fn_with_state(deprecation, deprecated)))
+}
+
+/// Adds the deprecation headers to every response of the routes it layers, and the deprecation
+/// to the request, so handlers can keep fields which only the deprecated routes still send.
+pub async fn deprecated<B>(State(deprecation): State<Deprecation>, mut request: Request<B>, next: Next<B>)
+                           -> axum::response::Response {
+    let path_and_query = request.uri()
+        .path_and_query()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    let headers = deprecation.headers(&path_and_query);
+    request.extensions_mut().insert(deprecation);
+
+    let mut response = next.run(request).await;
+    for (name, value) in headers {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deprecated_responses_link_to_the_successor() {
+        let deprecation = Deprecation {
+            deprecated_at: Utc.timestamp_opt(1792108800, 0).unwrap(),
+            sunset: Some(Utc.with_ymd_and_hms(2027, 4, 1, 0, 0, 0).unwrap()),
+            successor_prefix: "/v1",
+        };
+
+        assert_eq!(deprecation.headers("/stat?validator=ABC"), vec![
+            (DEPRECATION, "@1792108800".to_string()),
+            (header::LINK, "</v1/stat?validator=ABC>; rel=\"successor-version\"".to_string()),
+            (SUNSET, "Thu, 01 Apr 2027 00:00:00 GMT".to_string()),
+        ]);
+    }
+}
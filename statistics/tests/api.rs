@@ -0,0 +1,354 @@
+//! Runs the service against PostgreSQL and checks the paging and filters of its queries, its RPC proxy and
+//! the sandbox of ad-hoc SQL, each test in its own copy of the database in `DATABASE_URL`, which
+//! `indexer migrate` must have migrated.
+//! Without `DATABASE_URL` the tests are skipped.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::process::Stdio;
+use std::time::Duration;
+
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, TimeZone, Utc};
+use model::Height;
+use osmosis_core::database::{self, DatabaseConfig};
+use osmosis_core::{repository, Block};
+use serde_json::{json, Value};
+use tokio::process::{Child, Command};
+use tokio::time;
+
+const PROPOSER_A: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+const PROPOSER_B: &str = "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB";
+const PROPOSER_C: &str = "CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC";
+/// 2024-01-01T00:00:00Z, the time of height 0; blocks follow every 6 seconds.
+const GENESIS_TIME_IN_SECONDS: i64 = 1704067200;
+const STARTUP_ATTEMPTS: u32 = 100;
+const ADMIN_KEY: &str = "test-admin-key";
+/// Login user of ad-hoc SQL, created in the cluster by the test of the sandbox.
+const SQL_DATABASE_USER: &str = "statistics_sql_test";
+
+struct TestDatabase {
+    name: String,
+    database_config: DatabaseConfig,
+    client: tokio_postgres::Client,
+}
+
+impl TestDatabase {
+    /// Copies the database without its blocks and validators.
+    async fn create(test: &str) -> Option<Self> {
+        if env::var("DATABASE_URL").is_err() {
+            eprintln!("Skipping {test}, DATABASE_URL is not set");
+            return None;
+        }
+        let database_config = database::from_env("localhost", "postgres", None).await.unwrap();
+        let template = database_config.config.get_dbname().unwrap().to_string();
+        let name = format!("{template}_{test}");
+
+        let maintenance = connect(&database_config, "postgres").await;
+        maintenance.batch_execute(&format!("DROP DATABASE IF EXISTS \"{name}\" WITH (FORCE)")).await.unwrap();
+        maintenance.batch_execute(&format!("CREATE DATABASE \"{name}\" TEMPLATE \"{template}\"")).await.unwrap();
+
+        let client = connect(&database_config, &name).await;
+        client
+            .batch_execute("TRUNCATE proposer_to_height, block_signatures, signer_sets, validators")
+            .await
+            .unwrap();
+        Some(TestDatabase { name, database_config, client })
+    }
+
+    async fn drop(self) {
+        drop(self.client);
+        let maintenance = connect(&self.database_config, "postgres").await;
+        maintenance.batch_execute(&format!("DROP DATABASE \"{}\" WITH (FORCE)", self.name)).await.unwrap();
+    }
+
+    async fn insert(&self, heights: impl IntoIterator<Item=u64>, proposer: &str) {
+        let blocks: Vec<Block> = heights.into_iter().map(|h| block(h, proposer)).collect();
+        repository::insert_batch(&self.client, &blocks).await.unwrap();
+    }
+}
+
+async fn connect(database_config: &DatabaseConfig, name: &str) -> tokio_postgres::Client {
+    let mut config = database_config.config.clone();
+    config.dbname(name);
+    let (client, connection) = config.connect(database_config.tls.clone()).await.unwrap();
+    tokio::spawn(connection);
+    client
+}
+
+fn block_time(height: u64) -> DateTime<Utc> {
+    Utc.timestamp_opt(GENESIS_TIME_IN_SECONDS + 6 * height as i64, 0).unwrap()
+}
+
+fn block(height: u64, proposer: &str) -> Block {
+    Block {
+        proposer: proposer.parse().unwrap(),
+        height: Height::new(height),
+        hash: format!("{height:064X}").parse().unwrap(),
+        last_block_hash: None,
+        block_time: block_time(height),
+        num_txs: 0,
+        last_commit: None,
+    }
+}
+
+/// The service on a free port, stopped when dropped.
+struct Service {
+    _process: Child,
+    url: String,
+}
+
+impl Service {
+    async fn start(database: &TestDatabase) -> Self {
+        Service::start_with(database, &[]).await
+    }
+
+    async fn start_with(database: &TestDatabase, variables: &[(&str, &str)]) -> Self {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let process = Command::new(env!("CARGO_BIN_EXE_statistics"))
+            .envs(variables.iter().copied())
+            .env("DATABASE_NAME", &database.name)
+            .env("LISTEN_ADDRESS", address.to_string())
+            .env_remove("PROFILE")
+            .env_remove("RATE_LIMIT_REQUESTS_PER_MINUTE")
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let service = Service { _process: process, url: format!("http://{address}") };
+
+        for _ in 0..STARTUP_ATTEMPTS {
+            if reqwest::get(format!("{}/v1/validators", service.url)).await.is_ok() {
+                return service;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("statistics service did not start at {address}");
+    }
+
+    async fn get(&self, path_and_query: &str) -> Value {
+        let response = reqwest::get(format!("{}{path_and_query}", self.url)).await.unwrap();
+        assert!(response.status().is_success(), "{path_and_query}: {}", response.status());
+        response.json().await.unwrap()
+    }
+
+    async fn status(&self, path_and_query: &str) -> u16 {
+        reqwest::get(format!("{}{path_and_query}", self.url)).await.unwrap().status().as_u16()
+    }
+
+    /// Runs ad-hoc SQL with the admin key and returns the status and body of the response.
+    async fn sql(&self, sql: &str) -> (u16, String) {
+        let response = reqwest::Client::new()
+            .post(format!("{}/admin/sql", self.url))
+            .header("X-Admin-Key", ADMIN_KEY)
+            .json(&json!({"sql": sql}))
+            .send()
+            .await
+            .unwrap();
+        (response.status().as_u16(), response.text().await.unwrap())
+    }
+}
+
+/// An RPC endpoint of the chain `test-devnet` which moves the block hash and answers `/block` by height:
+/// 5 with a block, 7 as above the chain tip, 8 as rate limited and any other with an internal error.
+async fn start_rpc() -> String {
+    async fn status() -> Json<Value> {
+        Json(json!({"result": {"node_info": {"network": "test-devnet"}}}))
+    }
+
+    async fn block(Query(params): Query<HashMap<String, u64>>) -> (StatusCode, Json<Value>) {
+        let error = |data: &str| json!({"error": {"code": -32603, "message": "Internal error", "data": data}});
+        match params["height"] {
+            5 => (StatusCode::OK, Json(json!({"result": {
+                "block_id": {"hash": ""},
+                "block": {
+                    "header": {"height": "5", "time": block_time(5), "proposer_address": PROPOSER_A,
+                               "last_block_id": {"hash": format!("{:064X}", 5)}},
+                    "data": {"txs": ["tx"]},
+                },
+            }}))),
+            7 => (StatusCode::INTERNAL_SERVER_ERROR,
+                  Json(error("height 7 must be less than or equal to the current blockchain height 6"))),
+            8 => (StatusCode::TOO_MANY_REQUESTS, Json(json!({}))),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(error("could not load block"))),
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let app = Router::new().route("/status", get(status)).route("/block", get(block));
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+    url
+}
+
+fn heights(response: &Value) -> Vec<u64> {
+    response["blocks"].as_array().unwrap().iter().map(|b| b["height"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn stat_pages_stay_pinned_to_the_height_of_the_first_page() {
+    let Some(database) = TestDatabase::create("stat_pages").await else {
+        return;
+    };
+    database.insert(1..=5, PROPOSER_A).await;
+    database.insert([6], PROPOSER_B).await;
+    let service = Service::start(&database).await;
+
+    let first_page = service.get(&format!("/v1/stat?validator={PROPOSER_A}&limit=2")).await;
+    assert_eq!(heights(&first_page), vec![1, 2]);
+    assert_eq!(first_page["total"], 5);
+    assert_eq!(first_page["next_after_height"], 2);
+    assert_eq!(first_page["as_of_height"], 6);
+    assert!(first_page.get("heights").is_none());
+
+    database.insert(7..=8, PROPOSER_A).await;
+
+    let next_page = format!("/v1/stat?validator={PROPOSER_A}&limit=2&as_of_height=6&after_height=");
+    let second_page = service.get(&format!("{next_page}2")).await;
+    assert_eq!(heights(&second_page), vec![3, 4]);
+    assert_eq!(second_page["total"], 5);
+    let last_page = service.get(&format!("{next_page}4")).await;
+    assert_eq!(heights(&last_page), vec![5]);
+    assert_eq!(last_page["next_after_height"], Value::Null);
+
+    let unpinned = service.get(&format!("/stat?validator={PROPOSER_A}")).await;
+    assert_eq!(heights(&unpinned), vec![1, 2, 3, 4, 5, 7, 8]);
+    assert_eq!(unpinned["heights"], json!([1, 2, 3, 4, 5, 7, 8]));
+
+    drop(service);
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn stat_is_narrowed_by_heights_and_times_and_reports_gaps() {
+    let Some(database) = TestDatabase::create("stat_filters").await else {
+        return;
+    };
+    database.insert((1..=4).chain(6..=10), PROPOSER_A).await;
+    let service = Service::start(&database).await;
+
+    let by_heights = service.get(&format!("/v1/stat?validator={PROPOSER_A}&from_height=3&to_height=7")).await;
+    assert_eq!(heights(&by_heights), vec![3, 4, 6, 7]);
+    assert_eq!(by_heights["completeness"], json!({"indexed_ranges": [[3, 4], [6, 7]], "complete": false}));
+
+    let (from_time, to_time) = (block_time(7).to_rfc3339(), block_time(9).to_rfc3339());
+    let query: String = form_urlencoded::Serializer::new(String::new())
+        .append_pair("validator", PROPOSER_A)
+        .append_pair("from_time", &from_time)
+        .append_pair("to_time", &to_time)
+        .finish();
+    let by_times = service.get(&format!("/v1/stat?{query}")).await;
+    assert_eq!(heights(&by_times), vec![7, 8, 9]);
+    assert_eq!(by_times["total"], 3);
+
+    drop(service);
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn top_pages_through_the_proposers_by_offset() {
+    let Some(database) = TestDatabase::create("top_pages").await else {
+        return;
+    };
+    database.insert(1..=3, PROPOSER_A).await;
+    database.insert(4..=5, PROPOSER_B).await;
+    database.insert([6], PROPOSER_C).await;
+    let service = Service::start(&database).await;
+
+    let first_page = service.get("/v1/top?limit=2").await;
+    let ranked: Vec<(Value, Value, Value)> = first_page["proposers"].as_array().unwrap().iter()
+        .map(|p| (p["rank"].clone(), p["proposer"].clone(), p["count"].clone()))
+        .collect();
+    assert_eq!(ranked, vec![(json!(1), json!(PROPOSER_A), json!(3)), (json!(2), json!(PROPOSER_B), json!(2))]);
+    assert_eq!(first_page["next_offset"], 2);
+    assert_eq!(first_page["as_of_height"], 6);
+
+    database.insert([7], PROPOSER_C).await;
+
+    let last_page = service.get("/v1/top?limit=2&offset=2&as_of_height=6").await;
+    assert_eq!(last_page["proposers"], json!([{"rank": 3, "proposer": PROPOSER_C, "moniker": null, "count": 1}]));
+    assert_eq!(last_page["next_offset"], Value::Null);
+
+    drop(service);
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn blocks_missing_from_the_database_are_read_from_rpc_and_queued_for_the_indexer() {
+    let Some(database) = TestDatabase::create("rpc_block").await else {
+        return;
+    };
+    database.insert([4], PROPOSER_B).await;
+    let config = env::temp_dir().join("statistics_rpc_block.toml");
+    fs::write(&config, "[profiles.test-devnet]\nhash = \"/result/block/header/last_block_id/hash\"\n").unwrap();
+    let rpc_url = start_rpc().await;
+    let service = Service::start_with(&database, &[
+        ("RPC_URL", &rpc_url),
+        ("INDEXER_CONFIG", config.to_str().unwrap()),
+    ]).await;
+
+    let indexed = service.get("/rpc/block/4").await;
+    assert_eq!((&indexed["proposer"], &indexed["source"]), (&json!(PROPOSER_B), &json!("database")));
+    let fetched = service.get("/rpc/block/5").await;
+    assert_eq!(fetched["hash"], format!("{:064X}", 5));
+    assert_eq!((&fetched["num_txs"], &fetched["source"]), (&json!(1), &json!("rpc")));
+    assert_eq!(service.status("/rpc/block/7").await, 404);
+    assert_eq!(service.status("/rpc/block/8").await, 503);
+    assert_eq!(service.status("/rpc/block/9").await, 502);
+
+    let requested: Vec<i64> = database.client.query("SELECT height FROM height_requests", &[]).await.unwrap()
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    assert_eq!(requested, vec![5]);
+    let indexed = database.client.query_one("SELECT count(*) FROM proposer_to_height", &[]).await.unwrap();
+    assert_eq!(indexed.get::<_, i64>(0), 1);
+
+    drop(service);
+    fs::remove_file(config).unwrap();
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn ad_hoc_sql_cannot_switch_to_the_role_of_the_service_or_read_the_catalog() {
+    let Some(database) = TestDatabase::create("sql_sandbox").await else {
+        return;
+    };
+    database.insert([1], PROPOSER_A).await;
+    database.client
+        .batch_execute(&format!("DO $$ BEGIN \
+                                 IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = '{SQL_DATABASE_USER}') THEN \
+                                 CREATE ROLE {SQL_DATABASE_USER} LOGIN IN ROLE statistics_readonly; \
+                                 END IF; END $$"))
+        .await
+        .unwrap();
+    let service = Service::start_with(&database, &[
+        ("ADMIN_KEY", ADMIN_KEY),
+        ("SQL_DATABASE_USER", SQL_DATABASE_USER),
+    ]).await;
+
+    let (status, rows) = service.sql("select proposer from proposer_to_height").await;
+    assert_eq!((status, rows), (200, format!("[{{\"proposer\":\"{PROPOSER_A}\"}}]")));
+
+    let (status, message) = service.sql("select * from (select set_config('role', 'postgres', true)) as s").await;
+    assert_eq!((status, message.as_str()), (400, "permission denied to set role \"postgres\""));
+
+    let catalog = "select query_to_xml('select rolname from pg_authid', true, true, '')";
+    let (status, message) = service.sql(catalog).await;
+    assert_eq!((status, message.as_str()), (400, "permission denied for table pg_authid"));
+
+    let superuser_service = Service::start_with(&database, &[
+        ("ADMIN_KEY", ADMIN_KEY),
+        ("SQL_DATABASE_USER", "postgres"),
+    ]).await;
+    assert_eq!(superuser_service.sql(catalog).await.0, 500);
+
+    drop(superuser_service);
+    drop(service);
+    database.drop().await;
+}